@@ -0,0 +1,164 @@
+// Optimized scoring: SIMD argmax per digit and rayon across banks, plus a
+// sampled cross-check against the scalar path so the fast answer can be trusted.
+use super::{BankTooNarrow, BatteryBank, JoltageOverflow, NarrowPolicy, ScoreError};
+use aoc_utils::{Xorshift, argmax_u8};
+use log::{info, warn};
+use rayon::prelude::*;
+
+// Fraction of banks re-scored with the scalar path when cross-checking.
+pub const SAMPLE_FRACTION: f64 = 0.01;
+
+// A bank the two paths disagree on, in its selection or in whether it
+// could be scored at all.
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    pub bankno: u32,
+    pub fast: Result<Vec<usize>, BankTooNarrow>,
+    pub scalar: Result<Vec<usize>, BankTooNarrow>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CrossCheck {
+    pub sampled: u32,
+    pub divergences: Vec<Divergence>,
+}
+
+// `n` distinct banks of `nbanks`, by a partial Fisher-Yates shuffle: the
+// first n slots end up a random pick.
+fn sample_banks(nbanks: u32, n: u32, seed: u64) -> Vec<u32> {
+    let mut rng = Xorshift::new(seed);
+    let mut banks: Vec<u32> = (0..nbanks).collect();
+    for i in 0..n as usize {
+        let j = i + (rng.next_u64() % (banks.len() - i) as u64) as usize;
+        banks.swap(i, j);
+    }
+    banks.truncate(n as usize);
+    banks
+}
+
+impl BatteryBank {
+    pub fn bank_max_joltage_fast(&self, bankno: u32, digits: u32) -> Result<u64, ScoreError> {
+        let sel = self.bank_selection_with(bankno, digits, argmax_u8)?;
+        Ok(self.selection_joltage(bankno, &sel)?)
+    }
+
+    pub fn sum_max_joltages_fast(
+        &self,
+        digits: u32,
+        policy: NarrowPolicy,
+    ) -> Result<u64, ScoreError> {
+        (0..self.nbanks)
+            .into_par_iter()
+            .map(|b| self.bank_max_joltage_fast(b, digits))
+            .filter_map(|r| match r {
+                // Logged like sum_max_joltages does, though not in bank order.
+                Err(ScoreError::TooNarrow(e)) if policy == NarrowPolicy::Skip => {
+                    warn!("{}, skipping", e);
+                    None
+                }
+                r => Some(r),
+            })
+            .try_reduce(
+                || 0,
+                |a, b| {
+                    a.checked_add(b)
+                        .ok_or(JoltageOverflow { bankno: None }.into())
+                },
+            )
+    }
+
+    // Re-score a random ~1% of banks (at least one), each at most once, on
+    // both paths and report every bank where the results disagree.
+    pub fn cross_check_fast(&self, digits: u32, seed: u64) -> CrossCheck {
+        let nsamples = ((self.nbanks as f64 * SAMPLE_FRACTION).ceil() as u32).min(self.nbanks);
+        let mut divergences = Vec::new();
+        let mut sampled = 0;
+        for b in sample_banks(self.nbanks, nsamples, seed) {
+            let fast = self.bank_selection_with(b, digits, argmax_u8);
+            let scalar = self.bank_selection(b, digits);
+            sampled += 1;
+            if fast != scalar {
+                warn!(
+                    "Bank # {} fast selection {:?} differs from scalar {:?}",
+                    b, fast, scalar
+                );
+                divergences.push(Divergence {
+                    bankno: b,
+                    fast,
+                    scalar,
+                });
+            }
+        }
+        info!(
+            "Cross-checked {} of {} banks (seed {}), {} divergent",
+            sampled,
+            self.nbanks,
+            seed,
+            divergences.len()
+        );
+        CrossCheck {
+            sampled,
+            divergences,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fast_matches_scalar() {
+        let b = BatteryBank::new("test.txt", 10).unwrap();
+        for d in 1..=12 {
+            assert_eq!(
+                b.sum_max_joltages_fast(d, NarrowPolicy::Error),
+                b.sum_max_joltages(d, NarrowPolicy::Error)
+            );
+        }
+        assert_eq!(
+            b.sum_max_joltages_fast(12, NarrowPolicy::Error),
+            Ok(3121910778619)
+        );
+        assert_eq!(b.sum_max_joltages_fast(16, NarrowPolicy::Skip), Ok(0));
+        assert!(b.sum_max_joltages_fast(16, NarrowPolicy::Error).is_err());
+
+        // Overflows aren't skipped, in a bank or in the sum.
+        let big = BatteryBank {
+            bank_width: 14,
+            nbanks: 4,
+            base: 36,
+            banks: vec![35; 4 * 14],
+            running: None,
+        };
+        for d in [12, 14] {
+            assert_eq!(
+                big.sum_max_joltages_fast(d, NarrowPolicy::Skip).is_err(),
+                big.sum_max_joltages(d, NarrowPolicy::Skip).is_err(),
+            );
+        }
+        assert_eq!(
+            big.sum_max_joltages_fast(12, NarrowPolicy::Skip),
+            Err(ScoreError::Overflow(JoltageOverflow { bankno: None }))
+        );
+    }
+
+    #[test]
+    fn test_cross_check() {
+        let b = BatteryBank::new("input.txt", 10).unwrap();
+        let check = b.cross_check_fast(12, 42);
+        assert_eq!(check.sampled, 2);
+        assert!(check.divergences.is_empty());
+
+        for seed in 0..20 {
+            let mut picked = sample_banks(50, 20, seed);
+            assert_eq!(picked.len(), 20);
+            picked.sort_unstable();
+            picked.dedup();
+            assert_eq!(picked.len(), 20, "seed {}", seed);
+            let mut all = sample_banks(50, 50, seed);
+            all.sort_unstable();
+            assert_eq!(all, (0..50).collect::<Vec<u32>>());
+        }
+    }
+}
@@ -1,12 +1,16 @@
-use log::info;
+use aoc_utils::argmax;
+use log::{info, warn};
 use simple_logger::SimpleLogger;
 use std::env;
 use std::error::Error;
-use std::fs;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::iter::Peekable;
 use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod fast;
 
 fn read_lines<P>(filename: P) -> io::Result<Peekable<io::Split<io::BufReader<File>>>>
 where
@@ -16,77 +20,360 @@ where
     Ok(io::BufReader::new(file).split(b'\n').peekable())
 }
 
-struct BatteryBank {
-    nbanks: u32,
-    bank_width: u32,
-    banks: Vec<u8>,
+const DEFAULT_BASE: u32 = 10;
+const MAX_BASE: u32 = 36;
+
+const VIZ_CHOSEN: &str = "\x1b[1;32m";
+const VIZ_OTHER: &str = "\x1b[2m";
+const VIZ_RESET: &str = "\x1b[0m";
+
+// Value of an ascii digit in the given base; accepts 0-9 then a-z/A-Z.
+fn digit_value(c: u8, base: u32) -> Option<u8> {
+    let val = match c {
+        b'0'..=b'9' => c - b'0',
+        b'a'..=b'z' => c - b'a' + 10,
+        b'A'..=b'Z' => c - b'A' + 10,
+        _ => return None,
+    };
+    if (val as u32) < base { Some(val) } else { None }
+}
+
+#[derive(Debug, PartialEq)]
+struct BankTooNarrow {
+    bankno: u32,
+    width: u32,
+    digits: u32,
+}
+
+impl fmt::Display for BankTooNarrow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Bank # {} has width {}, too narrow to select {} digits",
+            self.bankno, self.width, self.digits
+        )
+    }
+}
+
+impl Error for BankTooNarrow {}
+
+// A joltage too big for a u64: one bank's, or with no bank number, the sum's.
+#[derive(Debug, PartialEq)]
+struct JoltageOverflow {
+    bankno: Option<u32>,
+}
+
+impl fmt::Display for JoltageOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.bankno {
+            Some(b) => write!(f, "Bank # {} has a max joltage too big for a u64", b),
+            None => write!(f, "Sum of max joltages is too big for a u64"),
+        }
+    }
+}
+
+impl Error for JoltageOverflow {}
+
+// Why a bank, or a sum over banks, has no joltage. Only TooNarrow banks can
+// be skipped; an overflow would make any total wrong.
+#[derive(Debug, PartialEq)]
+enum ScoreError {
+    TooNarrow(BankTooNarrow),
+    Overflow(JoltageOverflow),
+}
+
+impl fmt::Display for ScoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScoreError::TooNarrow(e) => e.fmt(f),
+            ScoreError::Overflow(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for ScoreError {}
+
+impl From<BankTooNarrow> for ScoreError {
+    fn from(e: BankTooNarrow) -> Self {
+        ScoreError::TooNarrow(e)
+    }
+}
+
+impl From<JoltageOverflow> for ScoreError {
+    fn from(e: JoltageOverflow) -> Self {
+        ScoreError::Overflow(e)
+    }
+}
+
+// Add a bank's joltage to a total, failing rather than wrapping.
+fn add_joltage(sum: u64, joltage: u64) -> Result<u64, JoltageOverflow> {
+    sum.checked_add(joltage)
+        .ok_or(JoltageOverflow { bankno: None })
+}
+
+// What to do with a bank that has fewer digits than we want to select.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NarrowPolicy {
+    Skip,
+    Error,
+}
+
+#[derive(Debug, PartialEq)]
+enum BankLineError {
+    InvalidDigit(u8),
+    MismatchedWidth { width: u32, expected: u32 },
+    TooNarrow(BankTooNarrow),
+    Overflow(JoltageOverflow),
+}
+
+impl fmt::Display for BankLineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BankLineError::InvalidDigit(c) => write!(f, "Unexpected digit {}", *c as char),
+            BankLineError::MismatchedWidth { width, expected } => {
+                write!(f, "Mismatched line width {}, expected {}", width, expected)
+            }
+            BankLineError::TooNarrow(e) => e.fmt(f),
+            BankLineError::Overflow(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for BankLineError {}
+
+// Declared dimensions from a `# width=W banks=N` line at the top of a bank file.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct BankHeader {
+    width: Option<u32>,
+    banks: Option<u32>,
+}
+
+#[derive(Debug, PartialEq)]
+struct InvalidHeader(String);
+
+impl fmt::Display for InvalidHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid header field {}", self.0)
+    }
+}
+
+impl Error for InvalidHeader {}
+
+// A header that doesn't parse, or that the banks after it don't match.
+#[derive(Debug, PartialEq)]
+enum HeaderError {
+    Invalid(InvalidHeader),
+    Width { declared: u32, got: u32 },
+    Banks { declared: u32, got: u32 },
 }
 
-fn argmax(slice: &[u8]) -> usize {
-    if slice.len() == 0 {
-        panic!("Empty slice");
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeaderError::Invalid(e) => e.fmt(f),
+            HeaderError::Width { declared, got } => {
+                write!(f, "Header declared width {}, got {}", declared, got)
+            }
+            HeaderError::Banks { declared, got } => {
+                write!(f, "Header declared {} banks, got {}", declared, got)
+            }
+        }
     }
+}
+
+impl Error for HeaderError {}
+
+fn is_comment(line: &[u8]) -> bool {
+    line.first() == Some(&b'#')
+}
 
-    let mut argmax = 0;
-    for (i, v) in slice.into_iter().enumerate() {
-        if *v > slice[argmax] {
-            argmax = i;
+// A comment is only a header if it has at least one width=/banks= field;
+// anything else after the '#' is ignored.
+fn parse_header(line: &[u8]) -> Result<Option<BankHeader>, InvalidHeader> {
+    let text = match std::str::from_utf8(&line[1..]) {
+        Ok(t) => t,
+        Err(_) => return Ok(None),
+    };
+    let mut header = BankHeader {
+        width: None,
+        banks: None,
+    };
+    for field in text.split_whitespace() {
+        let (key, val) = match field.split_once('=') {
+            Some(kv) => kv,
+            None => continue,
+        };
+        let slot = match key {
+            "width" => &mut header.width,
+            "banks" => &mut header.banks,
+            _ => continue,
+        };
+        match val.parse::<u32>() {
+            Ok(v) => *slot = Some(v),
+            Err(_) => return Err(InvalidHeader(field.to_string())),
         }
     }
-    argmax
+    if header.width.is_none() && header.banks.is_none() {
+        Ok(None)
+    } else {
+        Ok(Some(header))
+    }
+}
+
+// Sum of max joltages over every bank so far, kept up to date by push_bank_line.
+struct RunningTotal {
+    digits: u32,
+    sum: u64,
+}
+
+struct BatteryBank {
+    nbanks: u32,
+    bank_width: u32,
+    base: u32,
+    banks: Vec<u8>,
+    running: Option<RunningTotal>,
 }
 
 impl BatteryBank {
-    fn new<P>(filename: P) -> BatteryBank
+    fn new<P>(filename: P, base: u32) -> Result<BatteryBank, Box<dyn Error>>
     where
         P: AsRef<Path>,
     {
-        let mut banks = Vec::<u8>::new();
-        let mut nlines = 0;
-
-        let mut process_line = |line: &[u8], expected_width: Option<u32>| -> u32 {
-            let mut w = 0;
-            for c in line {
-                if *c <= b'0' || *c > b'9' {
-                    panic!("Expected digit in [1-9], got {}", *c);
-                }
-                banks.push(*c - b'0');
-                w += 1;
-                if let Some(ewidth) = expected_width {
-                    if w > ewidth {
-                        panic!("Line exceeded expected width {}", ewidth);
+        let mut bank = BatteryBank::empty(base, None);
+        let mut header = None;
+
+        let mut line_iter = read_lines(filename)?;
+        while let Some(line) = line_iter.next() {
+            let line = line?;
+            if is_comment(&line) {
+                // Any comment ahead of the first bank can be the header; the
+                // first that parses as one is, and later ones are just notes.
+                if bank.nbanks == 0 && header.is_none() {
+                    header = parse_header(&line).map_err(HeaderError::Invalid)?;
+                    if let Some(BankHeader {
+                        width: Some(w),
+                        banks: Some(n),
+                    }) = header
+                    {
+                        bank.banks.reserve(w as usize * n as usize);
                     }
                 }
+                continue;
             }
-            w
-        };
-
-        let mut line_iter = read_lines(filename).unwrap();
-        let first = match line_iter.next() {
-            Some(f) => f.unwrap(),
-            None => panic!("No lines to read!"),
-        };
-        let width = process_line(&first, None);
-        nlines += 1;
-
-        while let Some(line) = line_iter.next() {
-            let w = process_line(&line.unwrap(), Some(width));
-            if w == 0 && line_iter.peek().is_none() {
+            if line.is_empty() && line_iter.peek().is_none() {
                 break;
             }
-            if w != width {
-                panic!("Mismatched line width {}, expected {}", w, width);
+            bank.push_bank_line(&line)?;
+        }
+        if bank.nbanks == 0 {
+            return Err(From::from("No lines to read!"));
+        }
+
+        if let Some(h) = header {
+            if let Some(w) = h.width
+                && w != bank.bank_width
+            {
+                return Err(From::from(HeaderError::Width {
+                    declared: w,
+                    got: bank.bank_width,
+                }));
+            }
+            if let Some(n) = h.banks
+                && n != bank.nbanks
+            {
+                return Err(From::from(HeaderError::Banks {
+                    declared: n,
+                    got: bank.nbanks,
+                }));
             }
-            nlines += 1;
         }
+        Ok(bank)
+    }
 
+    fn empty(base: u32, running: Option<RunningTotal>) -> BatteryBank {
+        if !(2..=MAX_BASE).contains(&base) {
+            panic!("Base must be in [2, {}], got {}", MAX_BASE, base);
+        }
         BatteryBank {
-            nbanks: nlines,
-            bank_width: width,
-            banks: banks,
+            nbanks: 0,
+            bank_width: 0,
+            base,
+            banks: Vec::new(),
+            running,
         }
     }
 
+    // An empty bank fed one line at a time through push_bank_line, which
+    // scores each new bank with `digits` selected into a running total.
+    fn new_streaming(base: u32, digits: u32) -> BatteryBank {
+        if digits < 1 {
+            panic!("Digits must be > 0!");
+        }
+        BatteryBank::empty(base, Some(RunningTotal { digits, sum: 0 }))
+    }
+
+    // Parse and append one bank, returning its max joltage if we keep a running
+    // total. The first line pushed fixes the bank width. Rejected lines (bad
+    // digits, wrong width, too narrow to score, overflowing the total) leave
+    // the bank untouched.
+    fn push_bank_line(&mut self, line: &[u8]) -> Result<Option<u64>, BankLineError> {
+        if self.nbanks > 0 && line.len() != self.bank_width as usize {
+            return Err(BankLineError::MismatchedWidth {
+                width: line.len() as u32,
+                expected: self.bank_width,
+            });
+        }
+        if let Some(running) = &self.running
+            && line.len() < running.digits as usize
+        {
+            return Err(BankLineError::TooNarrow(BankTooNarrow {
+                bankno: self.nbanks,
+                width: line.len() as u32,
+                digits: running.digits,
+            }));
+        }
+        let start = self.banks.len();
+        for c in line {
+            match digit_value(*c, self.base) {
+                Some(v) => self.banks.push(v),
+                None => {
+                    self.banks.truncate(start);
+                    return Err(BankLineError::InvalidDigit(*c));
+                }
+            }
+        }
+        self.bank_width = line.len() as u32;
+        self.nbanks += 1;
+
+        let digits = match &self.running {
+            Some(running) => running.digits,
+            None => return Ok(None),
+        };
+        let scored = self
+            .bank_max_joltage(self.nbanks - 1, digits)
+            .and_then(|j| Ok((j, add_joltage(self.running_total().unwrap(), j)?)));
+        let (joltage, sum) = match scored {
+            Ok(js) => js,
+            Err(e) => {
+                self.banks.truncate(start);
+                self.nbanks -= 1;
+                return Err(match e {
+                    ScoreError::TooNarrow(e) => BankLineError::TooNarrow(e),
+                    ScoreError::Overflow(e) => BankLineError::Overflow(e),
+                });
+            }
+        };
+        if let Some(running) = &mut self.running {
+            running.sum = sum;
+        }
+        Ok(Some(joltage))
+    }
+
+    fn running_total(&self) -> Option<u64> {
+        self.running.as_ref().map(|r| r.sum)
+    }
+
+    #[allow(dead_code)]
     fn bank_offset_val(&self, bankno: u32, offset: u32) -> u8 {
         if bankno > self.nbanks {
             panic!(
@@ -114,48 +401,307 @@ impl BatteryBank {
         &self.banks[base..(base + self.bank_width as usize)]
     }
 
-    fn bank_max_joltage(&self, bankno: u32, digits: u32) -> u64 {
+    // Positions within the bank of the digits chosen for the max joltage, in order.
+    fn bank_selection(&self, bankno: u32, digits: u32) -> Result<Vec<usize>, BankTooNarrow> {
+        self.bank_selection_with(bankno, digits, argmax)
+    }
+
+    // bank_selection with a pluggable argmax, so the fast path can swap in SIMD.
+    fn bank_selection_with(
+        &self,
+        bankno: u32,
+        digits: u32,
+        argmax: fn(&[u8]) -> usize,
+    ) -> Result<Vec<usize>, BankTooNarrow> {
         if digits < 1 {
             panic!("Digits must be > 0!");
         }
         let joltages = self.bank(bankno);
+        if joltages.len() < digits as usize {
+            return Err(BankTooNarrow {
+                bankno,
+                width: joltages.len() as u32,
+                digits,
+            });
+        }
 
-        let mut res = 0;
+        let mut sel = Vec::with_capacity(digits as usize);
         let mut last_pos = 0;
         for d in 0..digits {
             let digits_rem = digits - 1 - d;
             let pos = argmax(&joltages[last_pos..joltages.len() - digits_rem as usize]) + last_pos;
             // info!(
-            //     "digit {} rem {} pos {} val {} res {} ",
-            //     d, digits_rem, pos, joltages[pos], res
+            //     "digit {} rem {} pos {} val {} ",
+            //     d, digits_rem, pos, joltages[pos]
             // );
-            res += 10_u64.pow(digits_rem as u32) * joltages[pos] as u64;
+            sel.push(pos);
             last_pos = pos + 1;
         }
-        res
+        Ok(sel)
+    }
+
+    // High bases and long selections overflow a u64 quickly: 14 digits in
+    // base 36 already do.
+    fn selection_joltage(&self, bankno: u32, selection: &[usize]) -> Result<u64, JoltageOverflow> {
+        let joltages = self.bank(bankno);
+        let mut res: u64 = 0;
+        for pos in selection {
+            res = res
+                .checked_mul(self.base as u64)
+                .and_then(|r| r.checked_add(joltages[*pos] as u64))
+                .ok_or(JoltageOverflow {
+                    bankno: Some(bankno),
+                })?;
+        }
+        Ok(res)
     }
 
-    fn sum_max_joltages(&self, digits: u32) -> u64 {
+    fn bank_max_joltage(&self, bankno: u32, digits: u32) -> Result<u64, ScoreError> {
+        let sel = self.bank_selection(bankno, digits)?;
+        Ok(self.selection_joltage(bankno, &sel)?)
+    }
+
+    fn sum_max_joltages(&self, digits: u32, policy: NarrowPolicy) -> Result<u64, ScoreError> {
         let mut sum = 0;
         for b in 0..self.nbanks {
-            sum += self.bank_max_joltage(b, digits) as u64;
+            match self.bank_max_joltage(b, digits) {
+                Ok(j) => sum = add_joltage(sum, j)?,
+                Err(ScoreError::TooNarrow(e)) if policy == NarrowPolicy::Skip => {
+                    warn!("{}, skipping", e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(sum)
+    }
+
+    // One bank's digits with the selected ones highlighted for a terminal.
+    fn render_selection(&self, bankno: u32, selection: &[usize]) -> String {
+        let mut out = String::new();
+        let mut chosen = selection.iter().peekable();
+        for (i, v) in self.bank(bankno).iter().enumerate() {
+            let c = char::from_digit(*v as u32, self.base).unwrap();
+            if chosen.peek() == Some(&&i) {
+                chosen.next();
+                out.push_str(&format!("{}{}{}", VIZ_CHOSEN, c, VIZ_RESET));
+            } else {
+                out.push_str(&format!("{}{}{}", VIZ_OTHER, c, VIZ_RESET));
+            }
+        }
+        out
+    }
+
+    fn print_viz(&self, digits: u32, policy: NarrowPolicy) -> Result<(), ScoreError> {
+        for b in 0..self.nbanks {
+            match self.bank_selection(b, digits) {
+                Ok(sel) => println!(
+                    "{} {}",
+                    self.render_selection(b, &sel),
+                    self.selection_joltage(b, &sel)?
+                ),
+                Err(e) => match policy {
+                    NarrowPolicy::Skip => {
+                        println!("{} (skipped)", self.render_selection(b, &[]));
+                    }
+                    NarrowPolicy::Error => return Err(e.into()),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn selections_json(&self, digits: u32, policy: NarrowPolicy) -> Result<String, ScoreError> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+        let mut skipped = 0;
+        for b in 0..self.nbanks {
+            let sel = match self.bank_selection(b, digits) {
+                Ok(sel) => sel,
+                Err(e) => match policy {
+                    NarrowPolicy::Skip => {
+                        skipped += 1;
+                        continue;
+                    }
+                    NarrowPolicy::Error => return Err(e.into()),
+                },
+            };
+            let joltage = self.selection_joltage(b, &sel)?;
+            total = add_joltage(total, joltage)?;
+            let indices: Vec<String> = sel.iter().map(|p| p.to_string()).collect();
+            let values: Vec<String> = sel.iter().map(|p| self.bank(b)[*p].to_string()).collect();
+            entries.push(format!(
+                "{{\"bank\":{},\"indices\":[{}],\"values\":[{}],\"joltage\":{}}}",
+                b,
+                indices.join(","),
+                values.join(","),
+                joltage
+            ));
+        }
+        Ok(format!(
+            "{{\"banks\":[{}],\"base\":{},\"digits\":{},\"skipped\":{},\"total\":{}}}",
+            entries.join(","),
+            self.base,
+            digits,
+            skipped,
+            total
+        ))
+    }
+}
+
+struct Options {
+    file: String,
+    base: u32,
+    json: bool,
+    viz: bool,
+    fast: bool,
+    cross_check: bool,
+    seed: Option<u64>,
+    narrow: NarrowPolicy,
+}
+
+fn parse_args<I>(mut args: I) -> Result<Options, Box<dyn Error>>
+where
+    I: Iterator<Item = String>,
+{
+    let mut file = None;
+    let mut base = DEFAULT_BASE;
+    let mut json = false;
+    let mut viz = false;
+    let mut fast = false;
+    let mut cross_check = false;
+    let mut seed = None;
+    let mut narrow = NarrowPolicy::Error;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--base" => {
+                let val = match args.next() {
+                    Some(val) => val,
+                    None => return Err(From::from("--base needs a value!")),
+                };
+                base = match val.parse::<u32>() {
+                    Ok(b) if (2..=MAX_BASE).contains(&b) => b,
+                    _ => {
+                        return Err(From::from(format!(
+                            "--base must be an integer in [2, {}], got {}",
+                            MAX_BASE, val
+                        )));
+                    }
+                };
+            }
+            "--json" => {
+                json = true;
+            }
+            "--viz" => {
+                viz = true;
+            }
+            "--fast" => {
+                fast = true;
+            }
+            "--cross-check" => {
+                cross_check = true;
+            }
+            "--seed" => {
+                seed = match args.next().map(|v| v.parse::<u64>()) {
+                    Some(Ok(s)) => Some(s),
+                    _ => return Err(From::from("--seed needs an integer value")),
+                };
+            }
+            "--narrow" => {
+                narrow = match args.next().as_deref() {
+                    Some("skip") => NarrowPolicy::Skip,
+                    Some("error") => NarrowPolicy::Error,
+                    _ => return Err(From::from("--narrow needs one of skip|error")),
+                };
+            }
+            _ => {
+                if file.is_some() {
+                    return Err(From::from(format!("Unexpected argument {}", arg)));
+                }
+                file = Some(arg);
+            }
         }
-        sum
+    }
+    // --json and --viz always score on the scalar path.
+    if fast && (json || viz) {
+        return Err(From::from("--fast can't go with --json or --viz"));
+    }
+    match file {
+        Some(file) => Ok(Options {
+            file,
+            base,
+            json,
+            viz,
+            fast,
+            cross_check,
+            seed,
+            narrow,
+        }),
+        None => Err(From::from("Need a file argument!")),
     }
 }
 
+// Score banks as they arrive on stdin, keeping only the running total current.
+fn stream_stdin(opts: &Options) -> Result<u64, Box<dyn Error>> {
+    let mut bank = BatteryBank::new_streaming(opts.base, 12);
+    for line in io::stdin().lock().split(b'\n') {
+        let line = line?;
+        if line.is_empty() || is_comment(&line) {
+            continue;
+        }
+        match bank.push_bank_line(&line) {
+            Ok(j) => info!("bank {} -> {:?}", bank.nbanks - 1, j),
+            Err(BankLineError::TooNarrow(e)) if opts.narrow == NarrowPolicy::Skip => {
+                warn!("{}, skipping", e);
+            }
+            Err(e) => return Err(From::from(e)),
+        }
+    }
+    Ok(bank.running_total().unwrap())
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     SimpleLogger::new().init().unwrap();
-    let mut args = env::args().skip(1).peekable();
-    let file = match args.next() {
-        Some(file) => file,
-        None => {
-            return Err(From::from("Need a file argument!"));
-        }
-    };
-    let bank = BatteryBank::new(file);
+    let opts = parse_args(env::args().skip(1))?;
+    if opts.file == "-" {
+        println!("Max joltage is {}", stream_stdin(&opts)?);
+        return Ok(());
+    }
+    let bank =
+        BatteryBank::new(&opts.file, opts.base).map_err(|e| format!("{}: {}", opts.file, e))?;
+
+    if opts.json {
+        println!("{}", bank.selections_json(12, opts.narrow)?);
+    } else if opts.viz {
+        bank.print_viz(12, opts.narrow)?;
+        println!("Max joltage is {}", bank.sum_max_joltages(12, opts.narrow)?);
+    } else if opts.fast {
+        println!(
+            "Max joltage is {}",
+            bank.sum_max_joltages_fast(12, opts.narrow)?
+        );
+    } else {
+        println!("Max joltage is {}", bank.sum_max_joltages(12, opts.narrow)?);
+    }
 
-    println!("Max joltage is {}", bank.sum_max_joltages(12));
+    if opts.cross_check {
+        let seed = match opts.seed {
+            Some(s) => s,
+            None => SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64,
+        };
+        let check = bank.cross_check_fast(12, seed);
+        println!(
+            "Cross-checked {} banks (seed {}): {} divergent",
+            check.sampled,
+            seed,
+            check.divergences.len()
+        );
+        for d in &check.divergences {
+            println!(
+                "  bank {}: fast {:?} scalar {:?}",
+                d.bankno, d.fast, d.scalar
+            );
+        }
+    }
 
     Ok(())
 }
@@ -169,17 +715,209 @@ mod tests {
         let b = BatteryBank {
             bank_width: 4,
             nbanks: 2,
+            base: 10,
             banks: [1, 3, 3, 9, 2, 4, 1, 6].to_vec(),
+            running: None,
+        };
+        assert_eq!(b.bank_max_joltage(0, 2), Ok(39));
+        assert_eq!(b.bank_max_joltage(1, 2), Ok(46));
+    }
+
+    #[test]
+    fn test_hex_bank() {
+        let b = BatteryBank {
+            bank_width: 4,
+            nbanks: 1,
+            base: 16,
+            banks: [0xa, 0x3, 0xf, 0x1].to_vec(),
+            running: None,
+        };
+        assert_eq!(b.bank_max_joltage(0, 2), Ok(0xf1));
+        assert_eq!(b.bank_max_joltage(0, 3), Ok(0xaf1));
+    }
+
+    #[test]
+    fn test_selection() {
+        let b = BatteryBank {
+            bank_width: 4,
+            nbanks: 2,
+            base: 10,
+            banks: [1, 3, 3, 9, 2, 4, 1, 6].to_vec(),
+            running: None,
+        };
+        assert_eq!(b.bank_selection(0, 2), Ok(vec![1, 3]));
+        assert_eq!(b.bank_selection(1, 3), Ok(vec![1, 2, 3]));
+        assert_eq!(
+            b.selections_json(2, NarrowPolicy::Error).unwrap(),
+            "{\"banks\":[{\"bank\":0,\"indices\":[1,3],\"values\":[3,9],\"joltage\":39},\
+             {\"bank\":1,\"indices\":[1,3],\"values\":[4,6],\"joltage\":46}],\
+             \"base\":10,\"digits\":2,\"skipped\":0,\"total\":85}"
+        );
+    }
+
+    #[test]
+    fn test_narrow_bank() {
+        let b = BatteryBank {
+            bank_width: 1,
+            nbanks: 2,
+            base: 10,
+            banks: [7, 4].to_vec(),
+            running: None,
+        };
+        assert_eq!(b.bank_max_joltage(0, 1), Ok(7));
+        assert_eq!(
+            b.bank_max_joltage(1, 2),
+            Err(ScoreError::TooNarrow(BankTooNarrow {
+                bankno: 1,
+                width: 1,
+                digits: 2
+            }))
+        );
+        assert_eq!(b.sum_max_joltages(1, NarrowPolicy::Error), Ok(11));
+        assert_eq!(b.sum_max_joltages(2, NarrowPolicy::Skip), Ok(0));
+        assert!(b.sum_max_joltages(2, NarrowPolicy::Error).is_err());
+        assert_eq!(
+            b.selections_json(2, NarrowPolicy::Skip).unwrap(),
+            "{\"banks\":[],\"base\":10,\"digits\":2,\"skipped\":2,\"total\":0}"
+        );
+    }
+
+    #[test]
+    fn test_joltage_overflow() {
+        // 12 base 36 digits fit a u64, 14 don't, and four 12-digit maxima
+        // add up to more than one holds.
+        let b = BatteryBank {
+            bank_width: 14,
+            nbanks: 4,
+            base: 36,
+            banks: vec![35; 4 * 14],
+            running: None,
+        };
+        let bank_overflow = ScoreError::Overflow(JoltageOverflow { bankno: Some(0) });
+        let sum_overflow = ScoreError::Overflow(JoltageOverflow { bankno: None });
+        assert_eq!(b.bank_max_joltage(0, 12), Ok(36u64.pow(12) - 1));
+        assert_eq!(b.bank_max_joltage(0, 14), Err(bank_overflow));
+        assert_eq!(
+            b.sum_max_joltages(12, NarrowPolicy::Skip),
+            Err(sum_overflow)
+        );
+        assert!(b.sum_max_joltages(14, NarrowPolicy::Skip).is_err());
+        assert!(b.selections_json(12, NarrowPolicy::Error).is_err());
+
+        let mut s = BatteryBank::new_streaming(36, 12);
+        for _ in 0..3 {
+            assert!(s.push_bank_line(b"zzzzzzzzzzzz").is_ok());
+        }
+        assert_eq!(
+            s.push_bank_line(b"zzzzzzzzzzzz"),
+            Err(BankLineError::Overflow(JoltageOverflow { bankno: None }))
+        );
+        assert_eq!(s.nbanks, 3);
+        assert_eq!(s.banks.len(), 36);
+        assert_eq!(s.running_total(), Some(3 * (36u64.pow(12) - 1)));
+    }
+
+    #[test]
+    fn test_push_bank_line() {
+        let mut b = BatteryBank::new_streaming(10, 2);
+        assert_eq!(b.push_bank_line(b"1339"), Ok(Some(39)));
+        assert_eq!(b.push_bank_line(b"2416"), Ok(Some(46)));
+        assert_eq!(b.running_total(), Some(85));
+        assert_eq!(
+            b.push_bank_line(b"241"),
+            Err(BankLineError::MismatchedWidth {
+                width: 3,
+                expected: 4
+            })
+        );
+        assert_eq!(
+            b.push_bank_line(b"24x1"),
+            Err(BankLineError::InvalidDigit(b'x'))
+        );
+        assert_eq!(b.nbanks, 2);
+        assert_eq!(b.banks.len(), 8);
+        assert_eq!(b.running_total(), Some(85));
+
+        let mut narrow = BatteryBank::new_streaming(10, 2);
+        assert!(matches!(
+            narrow.push_bank_line(b"7"),
+            Err(BankLineError::TooNarrow(_))
+        ));
+        assert_eq!(narrow.nbanks, 0);
+    }
+
+    #[test]
+    fn test_parse_header() {
+        assert_eq!(
+            parse_header(b"# width=10 banks=200"),
+            Ok(Some(BankHeader {
+                width: Some(10),
+                banks: Some(200)
+            }))
+        );
+        assert_eq!(
+            parse_header(b"#banks=3"),
+            Ok(Some(BankHeader {
+                width: None,
+                banks: Some(3)
+            }))
+        );
+        assert_eq!(parse_header(b"# just a note"), Ok(None));
+        assert_eq!(
+            parse_header(b"# width=ten"),
+            Err(InvalidHeader("width=ten".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_new_with_header() {
+        let b = BatteryBank::new("test.txt", 10).unwrap();
+        assert_eq!(b.nbanks, 4);
+        assert_eq!(b.bank_width, 15);
+
+        let path = env::temp_dir().join("joltage_header_test.txt");
+        std::fs::write(&path, b"# width=4 banks=2\n1339\n# between banks\n2416\n").unwrap();
+        let b = BatteryBank::new(&path, 10).unwrap();
+        assert_eq!(b.nbanks, 2);
+        assert_eq!(b.sum_max_joltages(2, NarrowPolicy::Error), Ok(85));
+    }
+
+    #[test]
+    fn test_header_mismatch() {
+        let path = env::temp_dir().join("joltage_header_mismatch.txt");
+        std::fs::write(&path, b"# width=4 banks=3\n1339\n2416\n").unwrap();
+        let err = BatteryBank::new(&path, 10).err().unwrap();
+        assert_eq!(err.to_string(), "Header declared 3 banks, got 2");
+        std::fs::write(&path, b"# note\n# width=5\n1339\n2416\n").unwrap();
+        let err = BatteryBank::new(&path, 10).err().unwrap();
+        assert_eq!(err.to_string(), "Header declared width 5, got 4");
+        std::fs::write(&path, b"# width=ten\n1339\n").unwrap();
+        let err = BatteryBank::new(&path, 10).err().unwrap();
+        assert_eq!(err.to_string(), "Invalid header field width=ten");
+    }
+
+    #[test]
+    fn test_render_selection() {
+        let b = BatteryBank {
+            bank_width: 3,
+            nbanks: 1,
+            base: 16,
+            banks: [0xa, 0x3, 0xf].to_vec(),
+            running: None,
         };
-        assert_eq!(b.bank_max_joltage(0, 2), 39);
-        assert_eq!(b.bank_max_joltage(1, 2), 46);
+        assert_eq!(
+            b.render_selection(0, &[0, 2]),
+            "\x1b[1;32ma\x1b[0m\x1b[2m3\x1b[0m\x1b[1;32mf\x1b[0m"
+        );
     }
 
     #[test]
-    fn test_argmax() {
-        let v = [1, 3, 3, 9, 2, 4, 1, 6].to_vec();
-        assert_eq!(argmax(&v), 3);
-        assert_eq!(argmax(&v[..3]), 1);
-        assert_eq!(4 + argmax(&v[4..]), 7);
+    fn test_digit_value() {
+        assert_eq!(digit_value(b'7', 10), Some(7));
+        assert_eq!(digit_value(b'a', 10), None);
+        assert_eq!(digit_value(b'a', 16), Some(10));
+        assert_eq!(digit_value(b'F', 16), Some(15));
+        assert_eq!(digit_value(b'g', 16), None);
+        assert_eq!(digit_value(b'2', 2), None);
     }
 }
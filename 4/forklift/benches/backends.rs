@@ -0,0 +1,84 @@
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use forklift::sparse::SparseFloorMap;
+use forklift::{FloorMap, UpdateMode};
+use std::hint::black_box;
+
+// The same generated maps through each backend: bitset cell by cell, the
+// bitset word kernel, and the sparse set. Regenerate a map with
+// `forklift gen-map W H --density D --seed 42` to compare against a run.
+fn bench_backends(c: &mut Criterion) {
+    let mut group = c.benchmark_group("backends");
+    group.sample_size(10);
+    for (n, density) in [(500, 0.7), (2000, 0.005)] {
+        let dense = FloorMap::random(n, n, density, 42);
+        let simultaneous = dense.clone().with_update_mode(UpdateMode::Simultaneous);
+        let sparse = SparseFloorMap::from_dense(&dense);
+        let name = |what: &str| format!("{} {}@{}", what, n, density);
+
+        group.bench_function(name("count_free per-cell"), |b| {
+            b.iter(|| black_box(&dense).count_free_cells(4))
+        });
+        group.bench_function(name("count_free kernel"), |b| {
+            b.iter(|| black_box(&dense).count_free(4))
+        });
+        group.bench_function(name("count_free sparse"), |b| {
+            b.iter(|| black_box(&sparse).count_free(4))
+        });
+
+        // In-place passes scan cell by cell; simultaneous ones use the kernel.
+        group.bench_function(name("pass in-place"), |b| {
+            b.iter_batched(
+                || dense.clone(),
+                |mut m| m.mark_free(4),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(name("pass simultaneous"), |b| {
+            b.iter_batched(
+                || simultaneous.clone(),
+                |mut m| m.mark_free(4),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(name("pass sparse"), |b| {
+            b.iter_batched(
+                || sparse.clone(),
+                |mut m| m.mark_free(4),
+                BatchSize::LargeInput,
+            )
+        });
+
+        group.bench_function(name("exhaust in-place"), |b| {
+            b.iter_batched(
+                || dense.clone(),
+                |mut m| m.count_and_mark_exhaust(4),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(name("exhaust simultaneous"), |b| {
+            b.iter_batched(
+                || simultaneous.clone(),
+                |mut m| m.count_and_mark_exhaust(4),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(name("exhaust incremental"), |b| {
+            b.iter_batched(
+                || dense.clone(),
+                |mut m| m.exhaust_incremental(4),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(name("exhaust sparse"), |b| {
+            b.iter_batched(
+                || sparse.clone(),
+                |mut m| m.count_and_mark_exhaust(4),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_backends);
+criterion_main!(benches);
@@ -0,0 +1,36 @@
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use forklift::{FloorMap, UpdateMode};
+
+// Full rescans against the incremental work queue on the same generated map.
+fn bench_exhaust(c: &mut Criterion) {
+    let mut group = c.benchmark_group("exhaust");
+    group.sample_size(10);
+    for n in [100, 1000] {
+        let map = FloorMap::random(n, n, 0.7, 42);
+        group.bench_function(format!("scan in-place {}", n), |b| {
+            b.iter_batched(
+                || map.clone(),
+                |mut m| m.count_and_mark_exhaust_passes(4),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(format!("scan simultaneous {}", n), |b| {
+            b.iter_batched(
+                || map.clone().with_update_mode(UpdateMode::Simultaneous),
+                |mut m| m.count_and_mark_exhaust_passes(4),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(format!("incremental {}", n), |b| {
+            b.iter_batched(
+                || map.clone(),
+                |mut m| m.exhaust_incremental(4),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_exhaust);
+criterion_main!(benches);
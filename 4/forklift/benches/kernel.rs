@@ -0,0 +1,22 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use forklift::FloorMap;
+use std::hint::black_box;
+
+// Word-at-a-time neighbor counting against the per-cell span popcounts.
+fn bench_kernel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kernel");
+    group.sample_size(10);
+    for n in [1000, 4000] {
+        let map = FloorMap::random(n, n, 0.7, 42);
+        group.bench_function(format!("count_free kernel {}", n), |b| {
+            b.iter(|| black_box(&map).count_free(4))
+        });
+        group.bench_function(format!("count_free per-cell {}", n), |b| {
+            b.iter(|| black_box(&map).count_free_cells(4))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_kernel);
+criterion_main!(benches);
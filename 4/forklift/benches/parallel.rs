@@ -0,0 +1,63 @@
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use forklift::{FloorMap, UpdateMode};
+use std::hint::black_box;
+
+// Serial against rayon scans on a 10k x 10k generated map, then whole exhausts
+// on a smaller one.
+fn bench_parallel(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parallel");
+    group.sample_size(10);
+    let map = FloorMap::random(10_000, 10_000, 0.7, 42).with_update_mode(UpdateMode::Simultaneous);
+    group.bench_function("count_free serial", |b| {
+        b.iter(|| black_box(&map).count_free(4))
+    });
+    group.bench_function("count_free rayon", |b| {
+        b.iter(|| black_box(&map).count_free_par(4))
+    });
+    group.bench_function("pass serial", |b| {
+        b.iter_batched(
+            || map.clone(),
+            |mut m| m.mark_free(4),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("pass rayon", |b| {
+        b.iter_batched(
+            || map.clone(),
+            |mut m| m.mark_free_par(4),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+
+    // Whole exhausts, where the tiles skip the parts of the map that have
+    // stopped changing.
+    let mut group = c.benchmark_group("exhaust");
+    group.sample_size(10);
+    let map = FloorMap::random(3_000, 3_000, 0.7, 42).with_update_mode(UpdateMode::Simultaneous);
+    group.bench_function("serial kernel", |b| {
+        b.iter_batched(
+            || map.clone(),
+            |mut m| m.count_and_mark_exhaust_passes(4),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("rayon", |b| {
+        b.iter_batched(
+            || map.clone(),
+            |mut m| m.exhaust_parallel(4),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("tiled", |b| {
+        b.iter_batched(
+            || map.clone(),
+            |mut m| m.exhaust_tiled(4),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_parallel);
+criterion_main!(benches);
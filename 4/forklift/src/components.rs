@@ -0,0 +1,199 @@
+// Connected-component labeling and flood fill, 8-connected (diagonals join
+// clusters) whatever neighborhood the removal rule uses.
+use super::FloorMap;
+use aoc_utils::Grid;
+
+const ADJACENT: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+pub struct Components {
+    // 0 for empty cells, otherwise the 1-based label of the cell's cluster.
+    pub labels: Grid<u32>,
+    // sizes[l - 1] is the number of pallets carrying label l.
+    pub sizes: Vec<u32>,
+}
+
+impl Components {
+    pub fn count(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn size_of(&self, label: u32) -> u32 {
+        self.sizes[label as usize - 1]
+    }
+
+    // Label and size of the biggest cluster, the lowest label on ties.
+    pub fn largest(&self) -> Option<(u32, u32)> {
+        aoc_utils::try_argmax(&self.sizes).map(|i| (i as u32 + 1, self.sizes[i]))
+    }
+}
+
+impl FloorMap {
+    // Labels are handed out in row-major order of each cluster's first cell.
+    // Clusters join across the edges of a wrapped map.
+    pub fn components(&self) -> Components {
+        let mut labels = Grid::new(self.width() as usize, self.height() as usize, 0u32);
+        let mut sizes = Vec::new();
+        for (x, y) in self.occupied() {
+            if labels[(x as usize, y as usize)] != 0 {
+                continue;
+            }
+            let label = sizes.len() as u32 + 1;
+            let mut size = 0;
+            self.flood(x, y, |cx, cy| {
+                let l = &mut labels[(cx as usize, cy as usize)];
+                if *l != 0 {
+                    return false;
+                }
+                *l = label;
+                size += 1;
+                true
+            });
+            sizes.push(size);
+        }
+        Components { labels, sizes }
+    }
+
+    // Every cell reachable from (x, y) through cells of the same kind: pallets
+    // from a pallet, empty floor from empty floor (walls block it). The seed
+    // comes first.
+    pub fn flood_fill(&self, x: i32, y: i32) -> Vec<(i32, i32)> {
+        if !self.grid.in_bounds(x as isize, y as isize) {
+            panic!(
+                "({}, {}) outside of {}x{} FloorMap!",
+                x,
+                y,
+                self.width(),
+                self.height()
+            );
+        }
+        let mut seen = Grid::new(self.width() as usize, self.height() as usize, false);
+        let mut cells = Vec::new();
+        self.flood(x, y, |cx, cy| {
+            let s = &mut seen[(cx as usize, cy as usize)];
+            if *s {
+                return false;
+            }
+            *s = true;
+            cells.push((cx, cy));
+            true
+        });
+        cells
+    }
+
+    // Depth-first walk over the 8-connected cells matching (x, y)'s kind.
+    // `claim` sees each candidate and returns false for ones already visited.
+    fn flood<F>(&self, x: i32, y: i32, mut claim: F)
+    where
+        F: FnMut(i32, i32) -> bool,
+    {
+        let state = self.cell(x, y);
+        if !claim(x, y) {
+            return;
+        }
+        let mut stack = vec![(x, y)];
+        while let Some((cx, cy)) = stack.pop() {
+            for (xoff, yoff) in ADJACENT {
+                if let Some((nx, ny)) = self.resolve(cx + xoff, cy + yoff)
+                    && self.cell(nx, ny) == state
+                    && claim(nx, ny)
+                {
+                    stack.push((nx, ny));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Boundary;
+    use super::*;
+
+    #[test]
+    fn test_components() {
+        let map = b"@@..@
+@...@
+..@..
+....@";
+        let m = FloorMap::new_from_lines(map.split(|&v| v == b'\n'));
+        let c = m.components();
+        // (2, 2) is two columns from (0, 1), so it stands alone.
+        assert_eq!(c.count(), 4);
+        assert_eq!(c.sizes, vec![3, 2, 1, 1]);
+        assert_eq!(c.labels[(1, 0)], 1);
+        assert_eq!(c.labels[(4, 1)], 2);
+        assert_eq!(c.labels[(2, 2)], 3);
+        assert_eq!(c.labels[(2, 0)], 0);
+        assert_eq!(c.largest(), Some((1, 3)));
+        assert_eq!(c.size_of(4), 1);
+
+        // Diagonal contact is enough.
+        let diag = FloorMap::new_from_lines([b"@.".as_slice(), b".@"]);
+        assert_eq!(diag.components().sizes, vec![2]);
+
+        // On a torus the left and right columns touch.
+        let edges = FloorMap::new_from_lines([b"@..@".as_slice()]);
+        assert_eq!(edges.components().count(), 2);
+        assert_eq!(
+            edges
+                .clone()
+                .with_boundary(Boundary::Wrap)
+                .components()
+                .sizes,
+            vec![2]
+        );
+
+        let empty = FloorMap::new_from_lines([b"...".as_slice()]);
+        assert_eq!(empty.components().largest(), None);
+    }
+
+    #[test]
+    fn test_flood_fill() {
+        let map = b"@@.@
+..@.
+@...";
+        let m = FloorMap::new_from_lines(map.split(|&v| v == b'\n'));
+        let pallets = m.flood_fill(0, 0);
+        assert_eq!(pallets[0], (0, 0));
+        assert_eq!(pallets.len(), 4);
+        assert!(!pallets.contains(&(0, 2)));
+        assert_eq!(m.flood_fill(0, 2), vec![(0, 2)]);
+        // Empty floor: (2, 0) reaches the rest of the floor diagonally.
+        assert_eq!(m.flood_fill(2, 0).len(), 7);
+        assert_eq!(m.flood_fill(3, 2).len(), 7);
+    }
+
+    #[test]
+    fn test_flood_fill_walls() {
+        let map = b"..#..
+..#..
+..#..";
+        let m = FloorMap::new_from_lines(map.split(|&v| v == b'\n'));
+        assert_eq!(m.flood_fill(0, 0).len(), 6);
+        assert_eq!(m.flood_fill(2, 1).len(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_flood_fill_off_map() {
+        FloorMap::new_from_lines([b"@@".as_slice()]).flood_fill(2, 0);
+    }
+
+    #[test]
+    fn test_components_after_exhaust() {
+        let mut m = FloorMap::new_from_file("test.txt");
+        m.count_and_mark_exhaust(4);
+        let c = m.components();
+        assert_eq!(c.sizes.iter().sum::<u32>(), 71 - 43);
+        assert_eq!(c.largest(), Some((1, 28)));
+    }
+}
@@ -0,0 +1,133 @@
+// Cell-by-cell comparison of two maps of the same size, e.g. to find where two
+// implementations of the removal rule part ways.
+use super::viz::render_marked;
+use super::{Cell, FloorMap};
+use std::error::Error;
+use std::fmt;
+
+// A pallet only the first map has, one only the second has, and any other
+// disagreement (a wall on one side).
+const REMOVED: &str = "\x1b[1;31m-\x1b[0m";
+const ADDED: &str = "\x1b[1;32m+\x1b[0m";
+const CHANGED: &str = "\x1b[1;33m~\x1b[0m";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellDiff {
+    pub x: i32,
+    pub y: i32,
+    pub left: Cell,
+    pub right: Cell,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SizeMismatch {
+    pub left: (i32, i32),
+    pub right: (i32, i32),
+}
+
+impl fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Can't diff a {}x{} map against a {}x{} one",
+            self.left.0, self.left.1, self.right.0, self.right.1
+        )
+    }
+}
+
+impl Error for SizeMismatch {}
+
+impl FloorMap {
+    // Every cell where the two maps disagree, in row-major order.
+    pub fn diff(&self, other: &FloorMap) -> Result<Vec<CellDiff>, SizeMismatch> {
+        if (self.width(), self.height()) != (other.width(), other.height()) {
+            return Err(SizeMismatch {
+                left: (self.width(), self.height()),
+                right: (other.width(), other.height()),
+            });
+        }
+        let mut diffs = Vec::new();
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let (left, right) = (self.cell(x, y), other.cell(x, y));
+                if left != right {
+                    diffs.push(CellDiff { x, y, left, right });
+                }
+            }
+        }
+        Ok(diffs)
+    }
+}
+
+// ANSI rendering of the first map with its differences from the second marked.
+pub fn render(map: &FloorMap, diffs: &[CellDiff]) -> String {
+    let diffs = diffs.iter().map(|d| ((d.x, d.y), (d.left, d.right)));
+    render_marked(map, diffs, |_, diff| {
+        diff.map(|sides| match sides {
+            (Cell::Pallet, Cell::Empty) => REMOVED,
+            (Cell::Empty, Cell::Pallet) => ADDED,
+            _ => CHANGED,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let a = FloorMap::new_from_lines([b"@@.".as_slice(), b".#."]);
+        let b = FloorMap::new_from_lines([b"@.@".as_slice(), b"..."]);
+        let diffs = a.diff(&b).unwrap();
+        assert_eq!(
+            diffs,
+            vec![
+                CellDiff {
+                    x: 1,
+                    y: 0,
+                    left: Cell::Pallet,
+                    right: Cell::Empty
+                },
+                CellDiff {
+                    x: 2,
+                    y: 0,
+                    left: Cell::Empty,
+                    right: Cell::Pallet
+                },
+                CellDiff {
+                    x: 1,
+                    y: 1,
+                    left: Cell::Wall,
+                    right: Cell::Empty
+                },
+            ]
+        );
+        assert!(a.diff(&a).unwrap().is_empty());
+        assert_eq!(
+            render(&a, &diffs),
+            format!("@{REMOVED}{ADDED}\n.{CHANGED}.\n")
+        );
+
+        let narrow = FloorMap::new_from_lines([b"@@".as_slice()]);
+        assert_eq!(
+            a.diff(&narrow).unwrap_err(),
+            SizeMismatch {
+                left: (3, 2),
+                right: (2, 1)
+            }
+        );
+    }
+
+    #[test]
+    fn test_diff_exhaust_modes() {
+        // In-place and simultaneous passes reach the same exhausted map.
+        let mut a = FloorMap::new_from_file("test.txt");
+        let mut b = a
+            .clone()
+            .with_update_mode(super::super::UpdateMode::Simultaneous);
+        a.count_and_mark_exhaust(4);
+        b.count_and_mark_exhaust(4);
+        assert!(a.diff(&b).unwrap().is_empty());
+    }
+}
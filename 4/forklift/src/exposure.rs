@@ -0,0 +1,68 @@
+// How exposed the pallets are: the edges they share with empty floor, a way of
+// scoring a layout on its own rather than by how much an exhaust removes.
+// Edges are the four orthogonal sides whatever neighborhood the removal rule
+// uses; walls cover a side, and off-map sides follow the boundary.
+use super::{Cell, FloorMap};
+
+const SIDES: [(i32, i32); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+impl FloorMap {
+    // Sides of (x, y) facing empty floor, 0 to 4.
+    pub fn exposure(&self, x: i32, y: i32) -> u8 {
+        SIDES
+            .iter()
+            .filter(|&&(xoff, yoff)| self.cell(x + xoff, y + yoff) == Cell::Empty)
+            .count() as u8
+    }
+
+    // Total length of pallet edges facing empty floor.
+    pub fn perimeter(&self) -> u64 {
+        self.occupied()
+            .map(|(x, y)| self.exposure(x, y) as u64)
+            .sum()
+    }
+
+    // Pallet counts by exposure: [fully enclosed, one side open, .., all four].
+    pub fn exposure_histogram(&self) -> [u64; 5] {
+        let mut counts = [0; 5];
+        for (x, y) in self.occupied() {
+            counts[self.exposure(x, y) as usize] += 1;
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Boundary;
+    use super::*;
+
+    #[test]
+    fn test_exposure() {
+        let map = b"@@.
+@@#
+..@";
+        let m = FloorMap::new_from_lines(map.split(|&v| v == b'\n'));
+        // Off-map sides read as empty under the default boundary.
+        assert_eq!(m.exposure(0, 0), 2);
+        assert_eq!(m.exposure(1, 0), 2);
+        assert_eq!(m.exposure(1, 1), 1);
+        assert_eq!(m.exposure(2, 2), 3);
+        assert_eq!(m.perimeter(), 2 + 2 + 2 + 1 + 3);
+        assert_eq!(m.exposure_histogram(), [0, 1, 3, 1, 0]);
+
+        // Walled in, only the inside edges count.
+        let filled = m.clone().with_boundary(Boundary::Filled);
+        assert_eq!(filled.perimeter(), 4);
+
+        let mut exhausted = FloorMap::new_from_file("test.txt");
+        let before = exhausted.perimeter();
+        exhausted.count_and_mark_exhaust(4);
+        // What's left after an exhaust is the compact core.
+        assert!(exhausted.perimeter() < before);
+        assert_eq!(
+            exhausted.exposure_histogram().iter().sum::<u64>(),
+            exhausted.pallet_count()
+        );
+    }
+}
@@ -0,0 +1,336 @@
+// Several floors of the same size stacked on top of each other, written as
+// grids separated by blank lines with the ground floor first. A pallet counts
+// its neighbors on its own floor as usual plus, on the floors directly above
+// and below, the cell it sits under or on and that cell's neighborhood: the
+// full 26-cell cube for the default Moore neighborhood.
+use super::neighborhood::Neighborhood;
+use super::{Boundary, Cell, FloorMap, MapParseError, Rule, UpdateMode, parse_file};
+use std::error::Error;
+use std::path::Path;
+
+#[derive(Clone)]
+pub struct FloorStack {
+    // Ground floor first. Only the floors' boundaries and neighborhoods are
+    // used; the stack's own update mode applies across all of them.
+    floors: Vec<FloorMap>,
+    update: UpdateMode,
+}
+
+// Move a parse error found within one floor to its line in the whole file.
+fn shift_line(e: MapParseError, by: usize) -> MapParseError {
+    match e {
+        MapParseError::UnexpectedChar {
+            line,
+            column,
+            found,
+        } => MapParseError::UnexpectedChar {
+            line: line + by,
+            column,
+            found,
+        },
+        MapParseError::MismatchedWidth {
+            line,
+            width,
+            expected,
+        } => MapParseError::MismatchedWidth {
+            line: line + by,
+            width,
+            expected,
+        },
+        e => e,
+    }
+}
+
+impl FloorStack {
+    pub fn new_from_file<P>(filename: P) -> FloorStack
+    where
+        P: AsRef<Path>,
+    {
+        Self::try_from_file(filename).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn new_from_lines<I, S, T>(line_iter: T) -> FloorStack
+    where
+        I: Iterator<Item = S>,
+        S: AsRef<[u8]>,
+        T: IntoIterator<IntoIter = I, Item = S>,
+    {
+        Self::try_from_lines(line_iter).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_from_file<P>(filename: P) -> Result<FloorStack, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        parse_file(filename, |lines| Self::try_from_lines(lines))
+    }
+
+    // Any run of blank lines separates two floors; blank lines before the
+    // first floor or after the last are ignored.
+    pub fn try_from_lines<I, S, T>(line_iter: T) -> Result<FloorStack, MapParseError>
+    where
+        I: Iterator<Item = S>,
+        S: AsRef<[u8]>,
+        T: IntoIterator<IntoIter = I, Item = S>,
+    {
+        let mut floors: Vec<FloorMap> = Vec::new();
+        let mut section: Vec<Vec<u8>> = Vec::new();
+        let mut start = 0;
+        let mut add_floor = |section: &mut Vec<Vec<u8>>, start: usize| {
+            let floor =
+                FloorMap::try_from_lines(section.drain(..)).map_err(|e| shift_line(e, start))?;
+            if let Some(first) = floors.first() {
+                let (size, expected) = (
+                    (floor.width() as usize, floor.height() as usize),
+                    (first.width() as usize, first.height() as usize),
+                );
+                if size != expected {
+                    return Err(MapParseError::MismatchedFloor {
+                        line: start + 1,
+                        size,
+                        expected,
+                    });
+                }
+            }
+            floors.push(floor);
+            Ok(())
+        };
+        for (lineno, line) in line_iter.into_iter().enumerate() {
+            let line = line.as_ref();
+            if line.is_empty() {
+                if !section.is_empty() {
+                    add_floor(&mut section, start)?;
+                }
+                continue;
+            }
+            if section.is_empty() {
+                start = lineno;
+            }
+            section.push(line.to_vec());
+        }
+        if !section.is_empty() {
+            add_floor(&mut section, start)?;
+        }
+        if floors.is_empty() {
+            return Err(MapParseError::Empty);
+        }
+        Ok(FloorStack {
+            floors,
+            update: UpdateMode::InPlace,
+        })
+    }
+
+    pub fn from_floors(floors: Vec<FloorMap>) -> FloorStack {
+        FloorStack {
+            floors,
+            update: UpdateMode::InPlace,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.floors[0].width()
+    }
+
+    pub fn height(&self) -> i32 {
+        self.floors[0].height()
+    }
+
+    pub fn depth(&self) -> i32 {
+        self.floors.len() as i32
+    }
+
+    pub fn floor(&self, z: i32) -> &FloorMap {
+        &self.floors[z as usize]
+    }
+
+    // Applied to every floor.
+    pub fn with_neighborhood(mut self, neighborhood: Neighborhood) -> Self {
+        self.floors = self
+            .floors
+            .into_iter()
+            .map(|f| f.with_neighborhood(neighborhood))
+            .collect();
+        self
+    }
+
+    pub fn with_update_mode(mut self, update: UpdateMode) -> Self {
+        self.update = update;
+        self
+    }
+
+    // Applied to every floor; nothing lies above the top floor or below the
+    // ground floor.
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.floors = self
+            .floors
+            .into_iter()
+            .map(|f| f.with_boundary(boundary))
+            .collect();
+        self
+    }
+
+    pub fn pallet_count(&self) -> u64 {
+        self.floors.iter().map(|f| f.pallet_count()).sum()
+    }
+
+    pub fn cell(&self, x: i32, y: i32, z: i32) -> Cell {
+        self.floor(z).cell(x, y)
+    }
+
+    pub fn map_val(&self, x: i32, y: i32, z: i32) -> bool {
+        z >= 0 && z < self.depth() && self.floor(z).map_val(x, y)
+    }
+
+    // Saturates at u8::MAX, which a Rule judges the same as any larger count
+    // since thresholds are u8 too.
+    pub fn count_neighbors(&self, x: i32, y: i32, z: i32) -> u8 {
+        let mut sum = self.floor(z).count_neighbors(x, y) as u32;
+        for dz in [-1, 1] {
+            if z + dz >= 0 && z + dz < self.depth() {
+                let floor = self.floor(z + dz);
+                sum += floor.count_neighbors(x, y) as u32 + floor.occupied_val(x, y) as u32;
+            }
+        }
+        sum.min(u8::MAX as u32) as u8
+    }
+
+    // Every pallet, floor by floor in each floor's row-major order.
+    pub fn occupied(&self) -> impl Iterator<Item = (i32, i32, i32)> + '_ {
+        self.floors
+            .iter()
+            .zip(0..)
+            .flat_map(|(f, z)| f.occupied().map(move |(x, y)| (x, y, z)))
+    }
+
+    pub fn count_free(&self, rule: impl Into<Rule>) -> u32 {
+        let rule = rule.into();
+        self.occupied()
+            .filter(|&(x, y, z)| rule.removes(self.count_neighbors(x, y, z)))
+            .count() as u32
+    }
+
+    pub fn count_and_mark_free(&mut self, rule: impl Into<Rule>) -> u32 {
+        self.mark_free(rule).len() as u32
+    }
+
+    // One pass over every floor, ground floor first; within a floor the scan
+    // runs column by column like FloorMap::mark_free.
+    pub fn mark_free(&mut self, rule: impl Into<Rule>) -> Vec<(i32, i32, i32)> {
+        let rule = rule.into();
+        let mut freed = Vec::new();
+        for z in 0..self.depth() {
+            for x in 0..self.width() {
+                for y in 0..self.height() {
+                    if self.floor(z).map_val(x, y) && rule.removes(self.count_neighbors(x, y, z)) {
+                        freed.push((x, y, z));
+                        if self.update == UpdateMode::InPlace {
+                            self.floors[z as usize].clear_val(x, y);
+                        }
+                    }
+                }
+            }
+        }
+        if self.update == UpdateMode::Simultaneous {
+            for &(x, y, z) in &freed {
+                self.floors[z as usize].clear_val(x, y);
+            }
+        }
+        freed
+    }
+
+    pub fn count_and_mark_exhaust_passes(&mut self, rule: impl Into<Rule>) -> Vec<u32> {
+        self.exhaust_with(rule, |_, _| {})
+    }
+
+    pub fn exhaust_with<F>(&mut self, rule: impl Into<Rule>, mut on_pass: F) -> Vec<u32>
+    where
+        F: FnMut(&FloorStack, &[(i32, i32, i32)]),
+    {
+        let rule = rule.into();
+        let mut passes = Vec::new();
+        loop {
+            let freed = self.mark_free(rule);
+            if freed.is_empty() {
+                break;
+            }
+            on_pass(self, &freed);
+            passes.push(freed.len() as u32);
+        }
+        passes
+    }
+
+    pub fn count_and_mark_exhaust(&mut self, rule: impl Into<Rule>) -> u32 {
+        self.count_and_mark_exhaust_passes(rule).iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_floor_stack() {
+        let cube = b"@@@
+@@@
+@@@
+
+@@@
+@@@
+@@@
+
+
+@@@
+@@@
+@@@
+";
+        let s = FloorStack::new_from_lines(cube.split(|&v| v == b'\n'));
+        assert_eq!((s.width(), s.height(), s.depth()), (3, 3, 3));
+        assert_eq!(s.pallet_count(), 27);
+        assert_eq!(s.count_neighbors(1, 1, 1), 26);
+        assert_eq!(s.count_neighbors(0, 0, 0), 7);
+        assert_eq!(s.count_neighbors(1, 1, 0), 17);
+        // Only the eight corners of the cube have fewer than 8 neighbors.
+        assert_eq!(s.count_free(8), 8);
+
+        // One floor behaves exactly like the FloorMap.
+        let map = FloorMap::new_from_file("test.txt");
+        let mut one = FloorStack::from_floors(vec![map.clone()]);
+        assert_eq!(one.count_free(4), map.count_free(4));
+        assert_eq!(
+            one.count_and_mark_exhaust_passes(4),
+            map.clone().count_and_mark_exhaust_passes(4)
+        );
+
+        // With an empty floor between them, two copies don't touch.
+        let blank = FloorMap::new_from_lines(vec![vec![b'.'; 10]; 10]);
+        let mut apart = FloorStack::from_floors(vec![map.clone(), blank, map.clone()])
+            .with_update_mode(UpdateMode::Simultaneous);
+        assert_eq!(apart.count_and_mark_exhaust(4), 2 * 43);
+        // Stacked directly, each shields the other.
+        let mut together = FloorStack::from_floors(vec![map.clone(), map]);
+        assert!(together.count_and_mark_exhaust(4) < 2 * 43);
+    }
+
+    #[test]
+    fn test_floor_parse_errors() {
+        let parse = |s: &[u8]| FloorStack::try_from_lines(s.split(|&v| v == b'\n')).err();
+        assert_eq!(
+            parse(b"@@\n@@\n\n@@@\n@@@"),
+            Some(MapParseError::MismatchedFloor {
+                line: 4,
+                size: (3, 2),
+                expected: (2, 2)
+            })
+        );
+        assert_eq!(
+            parse(b"@@\n\n@@\n@x"),
+            Some(MapParseError::UnexpectedChar {
+                line: 4,
+                column: 2,
+                found: b'x'
+            })
+        );
+        assert_eq!(parse(b"\n\n"), Some(MapParseError::Empty));
+        assert!(parse(b"\n@@\n\n\n@@\n\n").is_none());
+    }
+}
@@ -0,0 +1,204 @@
+// Maps from images, e.g. drawings or screenshots turned into test maps: one
+// cell per pixel, a pallet wherever the pixel is darker than the threshold.
+// Reads PNG and the binary and plain PGM graymaps (and binary PBM, so the
+// bitmaps render::write_pbm exports come back in); the format is taken from
+// the file's magic bytes, not its name.
+use super::FloorMap;
+use aoc_utils::BitGrid;
+use std::error::Error;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+// Intensities below this read as pallets by default: anything darker than
+// mid-gray.
+pub const DEFAULT_IMAGE_THRESHOLD: u8 = 128;
+
+impl FloorMap {
+    // A width x height map from row-major 8-bit intensities.
+    pub fn from_gray(width: usize, height: usize, pixels: &[u8], threshold: u8) -> FloorMap {
+        let mut grid = BitGrid::new(width, height);
+        for (i, &v) in pixels.iter().enumerate().take(width * height) {
+            grid.set(i % width, i / width, v < threshold);
+        }
+        FloorMap::from_grid(grid)
+    }
+
+    pub fn try_from_image_file<P>(filename: P, threshold: u8) -> Result<FloorMap, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        Self::try_from_image_bytes(&fs::read(filename)?, threshold)
+    }
+
+    pub fn try_from_image_bytes(bytes: &[u8], threshold: u8) -> Result<FloorMap, Box<dyn Error>> {
+        let (width, height, pixels) = match bytes {
+            [0x89, b'P', b'N', b'G', ..] => decode_png(bytes)?,
+            [b'P', b'2' | b'4' | b'5', ..] => decode_netpbm(bytes)?,
+            _ => return Err(From::from("Not a PNG, PGM or PBM image")),
+        };
+        Ok(FloorMap::from_gray(width, height, &pixels, threshold))
+    }
+}
+
+// Width, height and one intensity per pixel. Color is reduced to luma, and
+// transparency blends toward white so that clear areas read as empty floor.
+fn decode_png(bytes: &[u8]) -> Result<(usize, usize, Vec<u8>), Box<dyn Error>> {
+    let mut decoder = png::Decoder::new(Cursor::new(bytes));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size().ok_or("PNG is too large")?];
+    let info = reader.next_frame(&mut buf)?;
+    let (width, height) = (info.width as usize, info.height as usize);
+    let channels = info.color_type.samples();
+    let luma = |px: &[u8]| -> u8 {
+        let (v, alpha) = match px {
+            [g] => (*g as u32, 255),
+            [g, a] => (*g as u32, *a as u32),
+            [r, g, b] => (
+                (299 * *r as u32 + 587 * *g as u32 + 114 * *b as u32) / 1000,
+                255,
+            ),
+            [r, g, b, a, ..] => (
+                (299 * *r as u32 + 587 * *g as u32 + 114 * *b as u32) / 1000,
+                *a as u32,
+            ),
+            [] => (255, 255),
+        };
+        ((v * alpha + 255 * (255 - alpha)) / 255) as u8
+    };
+    let pixels = buf
+        .chunks(info.line_size)
+        .take(height)
+        .flat_map(|row| row.chunks(channels).take(width).map(luma))
+        .collect();
+    Ok((width, height, pixels))
+}
+
+// The P2 (plain) and P5 (binary) graymaps and the P4 bitmap. Samples are
+// scaled from the header's maxval to 0-255.
+fn decode_netpbm(bytes: &[u8]) -> Result<(usize, usize, Vec<u8>), Box<dyn Error>> {
+    let magic = bytes[1];
+    let mut pos = 2;
+    // Whitespace-separated decimal fields, with # comments to the end of the
+    // line allowed between them.
+    let field = |pos: &mut usize| -> Result<usize, String> {
+        loop {
+            match bytes.get(*pos) {
+                Some(c) if c.is_ascii_whitespace() => *pos += 1,
+                Some(b'#') => {
+                    while bytes.get(*pos).is_some_and(|&c| c != b'\n') {
+                        *pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+        let start = *pos;
+        while bytes.get(*pos).is_some_and(|c| c.is_ascii_digit()) {
+            *pos += 1;
+        }
+        std::str::from_utf8(&bytes[start..*pos])
+            .unwrap()
+            .parse()
+            .map_err(|_| format!("Bad netpbm field at byte {}", start))
+    };
+    let width = field(&mut pos)?;
+    let height = field(&mut pos)?;
+    let maxval = match magic {
+        b'4' => 1,
+        _ => field(&mut pos)?,
+    };
+    if maxval == 0 || maxval > u16::MAX as usize {
+        return Err(From::from(format!(
+            "netpbm maxval {} is out of range",
+            maxval
+        )));
+    }
+    let scale = |v: usize| (v.min(maxval) * 255 / maxval) as u8;
+    let pixels: Vec<u8> = match magic {
+        b'2' => (0..width * height)
+            .map(|_| field(&mut pos).map(scale))
+            .collect::<Result<_, _>>()?,
+        // A single whitespace byte separates the header from the raster.
+        b'5' => {
+            let raster = bytes.get(pos + 1..).unwrap_or(&[]);
+            if maxval < 256 {
+                raster.iter().map(|&v| scale(v as usize)).collect()
+            } else {
+                raster
+                    .chunks_exact(2)
+                    .map(|v| scale(u16::from_be_bytes([v[0], v[1]]) as usize))
+                    .collect()
+            }
+        }
+        // Rows padded to whole bytes, set bits black.
+        _ => {
+            let raster = bytes.get(pos + 1..).unwrap_or(&[]);
+            let row_bytes = width.div_ceil(8);
+            raster
+                .chunks(row_bytes.max(1))
+                .take(height)
+                .flat_map(|row| {
+                    (0..width).map(move |x| match row.get(x / 8) {
+                        Some(&b) if b & (0x80 >> (x % 8)) != 0 => 0,
+                        _ => 255,
+                    })
+                })
+                .collect()
+        }
+    };
+    if pixels.len() < width * height {
+        return Err(From::from(format!(
+            "{}x{} image holds only {} pixels",
+            width,
+            height,
+            pixels.len()
+        )));
+    }
+    Ok((width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::render;
+    use super::*;
+
+    #[test]
+    fn test_from_image() {
+        let map = FloorMap::new_from_file("test.txt");
+        // What export writes comes back as the same pallets, walls aside.
+        let mut pbm = Vec::new();
+        render::write_pbm(&map, &mut pbm).unwrap();
+        let back = FloorMap::try_from_image_bytes(&pbm, DEFAULT_IMAGE_THRESHOLD).unwrap();
+        assert_eq!(back.grid, map.grid);
+        let mut pgm = Vec::new();
+        render::write_pgm(&map, &[], &mut pgm).unwrap();
+        let back = FloorMap::try_from_image_bytes(&pgm, DEFAULT_IMAGE_THRESHOLD).unwrap();
+        assert_eq!(back.grid, map.grid);
+
+        // Plain PGM with a comment and a 16-level maxval, thresholded.
+        let plain = b"P2\n# drawn by hand\n3 2\n15\n0 7 15\n8 3 12\n";
+        let m = FloorMap::try_from_image_bytes(plain, 128).unwrap();
+        assert_eq!((m.width(), m.height()), (3, 2));
+        assert_eq!(m.pallet_count(), 3);
+        assert!(m.map_val(0, 0) && m.map_val(1, 0) && m.map_val(1, 1));
+        let strict = FloorMap::try_from_image_bytes(plain, 20).unwrap();
+        assert_eq!(strict.pallet_count(), 1);
+
+        // RGBA through the png encoder: black, white, red, and clear black.
+        let mut png_bytes = Vec::new();
+        let mut enc = png::Encoder::new(&mut png_bytes, 2, 2);
+        enc.set_color(png::ColorType::Rgba);
+        enc.set_depth(png::BitDepth::Eight);
+        let rgba = [0, 0, 0, 255, 255, 255, 255, 255, 200, 0, 0, 255, 0, 0, 0, 0];
+        enc.write_header().unwrap().write_image_data(&rgba).unwrap();
+        let m = FloorMap::try_from_image_bytes(&png_bytes, 128).unwrap();
+        assert_eq!((m.width(), m.height()), (2, 2));
+        assert!(m.map_val(0, 0) && !m.map_val(1, 0) && m.map_val(0, 1) && !m.map_val(1, 1));
+
+        assert!(FloorMap::try_from_image_bytes(b"@@.\n", 128).is_err());
+        assert!(FloorMap::try_from_image_bytes(b"P5\n4 4\n255\n\0\0", 128).is_err());
+        assert!(FloorMap::try_from_image_bytes(b"P2\nx 4\n", 128).is_err());
+    }
+}
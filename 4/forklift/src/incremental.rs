@@ -0,0 +1,124 @@
+// Exhaust without rescanning the whole map: keep every pallet's neighbor count
+// and only revisit the neighbors of cells that were just removed, so a pass
+// costs time proportional to what it frees rather than to the map size.
+use super::{Cell, FloorMap, Rule};
+use aoc_utils::Grid;
+
+impl FloorMap {
+    // Same passes as count_and_mark_exhaust_passes in UpdateMode::Simultaneous,
+    // whatever update mode the map is set to.
+    pub fn exhaust_incremental(&mut self, rule: impl Into<Rule>) -> Vec<u32> {
+        self.exhaust_incremental_with(rule, |_, _| {})
+    }
+
+    pub fn exhaust_incremental_with<F>(&mut self, rule: impl Into<Rule>, mut on_pass: F) -> Vec<u32>
+    where
+        F: FnMut(&FloorMap, &[(i32, i32)]),
+    {
+        let rule = rule.into();
+        let mut passes = Vec::new();
+        let mut counts = Grid::new(self.grid.width(), self.grid.height(), 0u8);
+        let mut queue = Vec::new();
+        for (x, y) in self.occupied() {
+            let n = self.count_neighbors(x, y);
+            counts[(x as usize, y as usize)] = n;
+            if rule.removes(n) {
+                queue.push((x, y));
+            }
+        }
+
+        while !queue.is_empty() {
+            // Free the whole pass first so the decrements below only see
+            // pallets that are still standing.
+            self.clear_cells(&queue);
+            self.record_pass(&queue);
+            on_pass(self, &queue);
+            passes.push(queue.len() as u32);
+
+            let mut next = Vec::new();
+            for &(x, y) in &queue {
+                for ((nx, ny), cell) in self.neighbors(x, y) {
+                    if cell != Cell::Pallet {
+                        continue;
+                    }
+                    let n = &mut counts[(nx as usize, ny as usize)];
+                    *n -= 1;
+                    // Everything still standing was spared at its old count,
+                    // so each cell crosses into the rule at most once. Counts
+                    // only fall, so an Over rule never pulls in a new cell.
+                    if !rule.removes(*n + 1) && rule.removes(*n) {
+                        next.push((nx, ny));
+                    }
+                }
+            }
+            queue = next;
+        }
+        passes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::UpdateMode;
+    use super::super::neighborhood::{Neighborhood, Shape};
+    use super::*;
+
+    #[test]
+    fn test_incremental_example() {
+        let mut b = FloorMap::new_from_file("test.txt");
+        assert_eq!(b.exhaust_incremental(4), vec![13, 12, 7, 5, 2, 1, 1, 1, 1]);
+        assert!(b.exhaust_incremental(4).is_empty());
+        assert_eq!(
+            FloorMap::new_from_file("test.txt").exhaust_incremental(0),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_incremental_walls() {
+        let map = b"#@@.
+#@@@
+####";
+        let lines = || map.split(|&v| v == b'\n');
+        let mut scan = FloorMap::new_from_lines(lines()).with_update_mode(UpdateMode::Simultaneous);
+        let mut inc = FloorMap::new_from_lines(lines());
+        assert_eq!(
+            inc.exhaust_incremental(5),
+            scan.count_and_mark_exhaust_passes(5)
+        );
+        assert_eq!(inc.grid, scan.grid);
+        assert_eq!(inc.wall_count(), 6);
+    }
+
+    #[test]
+    fn test_incremental_matches_simultaneous() {
+        let neighborhoods = [
+            Neighborhood::moore(),
+            Neighborhood::new(Shape::VonNeumann, 1).unwrap(),
+            Neighborhood::new(Shape::Moore, 2).unwrap(),
+            Neighborhood::new(Shape::Hex, 1).unwrap(),
+        ];
+        for (seed, n) in neighborhoods.iter().enumerate() {
+            let map = FloorMap::random(40, 30, 0.7, seed as u64 + 1).with_neighborhood(*n);
+            for t in [1, 3, 4, 6] {
+                let mut scan = map.clone().with_update_mode(UpdateMode::Simultaneous);
+                let mut inc = map.clone();
+                let mut freed = Vec::new();
+                assert_eq!(
+                    inc.exhaust_incremental_with(t, |_, f| freed.extend_from_slice(f)),
+                    scan.count_and_mark_exhaust_passes(t)
+                );
+                assert_eq!(inc.grid, scan.grid);
+                assert!(freed.iter().all(|(x, y)| !inc.map_val(*x, *y)));
+
+                let mut scan = map.clone().with_update_mode(UpdateMode::Simultaneous);
+                let mut inc = map.clone();
+                assert_eq!(
+                    inc.exhaust_incremental(Rule::Over(t)),
+                    scan.count_and_mark_exhaust_passes(Rule::Over(t))
+                );
+                assert_eq!(inc.grid, scan.grid);
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// Whole-word neighbor counting: instead of popcounting a 3x3 window per cell,
+// add up the shifted occupancy words of the rows around a word with a
+// bit-sliced adder, so one pass over a word counts all 64 of its cells at
+// once. std::simd is nightly-only, so the lanes here are the bits of plain
+// u64 words; the row loops are simple enough for the compiler to widen.
+use super::{Boundary, FloorMap, Rule};
+use aoc_utils::bitgrid::WORD_BITS;
+
+// Word i of a packed row, shifted so bit x holds cell x + xoff. Cells past
+// either end of the row read as empty; |xoff| is at most the largest radius.
+fn shifted(row: &[u64], i: usize, xoff: i32) -> u64 {
+    let s = xoff.unsigned_abs() as usize;
+    if s == 0 {
+        row[i]
+    } else if xoff > 0 {
+        let carry = row.get(i + 1).map_or(0, |w| w << (WORD_BITS - s));
+        (row[i] >> s) | carry
+    } else {
+        let carry = if i > 0 {
+            row[i - 1] >> (WORD_BITS - s)
+        } else {
+            0
+        };
+        (row[i] << s) | carry
+    }
+}
+
+// Add a one-bit-per-lane input into the bit planes of a per-lane counter.
+fn add(planes: &mut [u64], mut carry: u64) {
+    for p in planes.iter_mut() {
+        let sum = *p ^ carry;
+        carry &= *p;
+        *p = sum;
+        if carry == 0 {
+            break;
+        }
+    }
+}
+
+// Lanes whose counter is below `t`, comparing from the top plane down.
+fn less_than(planes: &[u64], t: u32) -> u64 {
+    // Past what the planes can hold, every lane is below it.
+    if t >> planes.len() != 0 {
+        return u64::MAX;
+    }
+    let mut lt = 0;
+    let mut eq = u64::MAX;
+    for (b, p) in planes.iter().enumerate().rev() {
+        if t >> b & 1 == 1 {
+            lt |= eq & !p;
+            eq &= p;
+        } else {
+            eq &= !p;
+        }
+    }
+    lt
+}
+
+impl FloorMap {
+    // The kernel treats everything past the edges as empty; a wrapped map
+    // goes through count_neighbors instead.
+    pub(crate) fn kernel_applies(&self) -> bool {
+        matches!(self.boundary, Boundary::Empty)
+    }
+
+    // The grid's words with walls or'd in: walls count as neighbors exactly
+    // like pallets do.
+    pub(crate) fn occupied_words(&self) -> Vec<u64> {
+        let pallets = self.grid.words();
+        match &self.walls {
+            Some(walls) => pallets
+                .iter()
+                .zip(walls.words())
+                .map(|(p, w)| p | w)
+                .collect(),
+            None => pallets.to_vec(),
+        }
+    }
+
+    // Pallets `rule` removes against the current map, packed like the grid's
+    // words.
+    pub(crate) fn free_mask(&self, rule: Rule) -> Vec<u64> {
+        self.free_mask_rows(rule, &self.occupied_words(), 0, self.height())
+    }
+
+    // free_mask's words for rows y0..y1 alone, counted against `occupied`
+    // from occupied_words, so bands of rows can be worked out apart.
+    pub(crate) fn free_mask_rows(
+        &self,
+        rule: Rule,
+        occupied: &[u64],
+        y0: i32,
+        y1: i32,
+    ) -> Vec<u64> {
+        let stride = self.grid.stride();
+        let pallets = self.grid.words();
+        let nplanes = (u32::BITS - self.neighborhood.size().leading_zeros()) as usize;
+        let mut planes = vec![0u64; nplanes];
+        let mut mask = vec![0u64; (y1 - y0) as usize * stride];
+        for y in y0..y1 {
+            for i in 0..stride {
+                let start = y as usize * stride;
+                if pallets[start + i] == 0 {
+                    continue;
+                }
+                planes.fill(0);
+                for yoff in self.neighborhood.row_offsets() {
+                    let ny = y + yoff;
+                    if ny < 0 || ny >= self.height() {
+                        continue;
+                    }
+                    let row = &occupied[ny as usize * stride..(ny as usize + 1) * stride];
+                    let (lo, hi) = self.neighborhood.row_span(yoff);
+                    for xoff in lo..=hi {
+                        if (xoff, yoff) != (0, 0) {
+                            add(&mut planes, shifted(row, i, xoff));
+                        }
+                    }
+                }
+                let lt = less_than(&planes, rule.threshold() as u32);
+                mask[(y - y0) as usize * stride + i] = pallets[start + i]
+                    & match rule {
+                        Rule::Under(_) => lt,
+                        Rule::Over(_) => !lt,
+                    };
+            }
+        }
+        mask
+    }
+
+    // Coordinates of the set bits of a mask from free_mask, in mark_free's
+    // column-major scan order.
+    pub(crate) fn mask_cells(&self, mask: &[u64]) -> Vec<(i32, i32)> {
+        let mut cells = self.mask_cells_rows(mask, 0);
+        cells.sort_unstable();
+        cells
+    }
+
+    // The same for a mask from free_mask_rows starting at row y0, in
+    // row-major order.
+    pub(crate) fn mask_cells_rows(&self, mask: &[u64], y0: i32) -> Vec<(i32, i32)> {
+        let stride = self.grid.stride();
+        let mut cells = Vec::new();
+        for (i, &word) in mask.iter().enumerate() {
+            let (y, x0) = (y0 + (i / stride) as i32, ((i % stride) * WORD_BITS) as i32);
+            let mut bits = word;
+            while bits != 0 {
+                cells.push((x0 + bits.trailing_zeros() as i32, y));
+                bits &= bits - 1;
+            }
+        }
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::neighborhood::{Neighborhood, Shape};
+    use super::*;
+
+    #[test]
+    fn test_adder() {
+        let mut planes = [0u64; 4];
+        for n in 0..9u64 {
+            // Lane n gets n ones added.
+            let lanes = (0..9).filter(|&l| l > n).fold(0, |acc, l| acc | 1 << l);
+            add(&mut planes, lanes);
+        }
+        for t in 0..=9 {
+            let expected = (0..9).filter(|&l| l < t).fold(0, |acc, l| acc | 1 << l);
+            assert_eq!(less_than(&planes, t as u32) & 0x1ff, expected, "t = {}", t);
+        }
+        let row = [0b1011, 1 << 63 | 1];
+        assert_eq!(shifted(&row, 0, 1), 0b101 | 1 << 63);
+        assert_eq!(shifted(&row, 1, -1), 0b10);
+        assert_eq!(shifted(&row, 1, 1), 1 << 62);
+        assert_eq!(shifted(&row, 0, -2), 0b101100);
+    }
+
+    #[test]
+    fn test_kernel_matches_per_cell() {
+        let neighborhoods = [
+            Neighborhood::moore(),
+            Neighborhood::new(Shape::VonNeumann, 1).unwrap(),
+            Neighborhood::new(Shape::Moore, 2).unwrap(),
+            Neighborhood::new(Shape::VonNeumann, 3).unwrap(),
+            Neighborhood::new(Shape::Hex, 2).unwrap(),
+        ];
+        let walled = FloorMap::new_from_lines([b"#@@.@#".as_slice(), b"@#@@.@", b"..@#@@"]);
+        for (seed, n) in neighborhoods.iter().enumerate() {
+            // Wide enough that neighborhoods straddle word boundaries.
+            for map in [
+                FloorMap::random(150, 20, 0.6, seed as u64 + 7),
+                walled.clone(),
+            ] {
+                let map = map.with_neighborhood(*n);
+                for t in [0, 1, 3, 4, 8] {
+                    for rule in [Rule::Under(t), Rule::Over(t)] {
+                        let expected: Vec<(i32, i32)> = (0..map.width())
+                            .flat_map(|x| (0..map.height()).map(move |y| (x, y)))
+                            .filter(|&(x, y)| {
+                                map.map_val(x, y) && rule.removes(map.count_neighbors(x, y))
+                            })
+                            .collect();
+                        assert_eq!(
+                            map.mask_cells(&map.free_mask(rule)),
+                            expected,
+                            "{:?} {:?}",
+                            n,
+                            rule
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
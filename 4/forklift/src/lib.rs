@@ -0,0 +1,1245 @@
+use aoc_utils::bitgrid::WORD_BITS;
+use aoc_utils::{BitGrid, Xorshift};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::iter;
+use std::path::Path;
+
+pub mod components;
+pub mod diff;
+mod exposure;
+pub mod floors;
+pub mod image;
+mod incremental;
+mod kernel;
+pub mod neighborhood;
+mod parallel;
+pub mod path;
+mod reach;
+pub mod render;
+pub mod replay;
+mod rle;
+mod save;
+pub mod sparse;
+pub mod stats;
+pub mod stream;
+mod tiled;
+mod transform;
+pub mod tui;
+mod undo;
+pub mod viz;
+use neighborhood::Neighborhood;
+#[cfg(test)]
+use neighborhood::Shape;
+
+fn read_lines<P>(filename: P) -> io::Result<io::Split<io::BufReader<File>>>
+where
+    P: AsRef<Path>,
+{
+    let file = File::open(filename)?;
+    Ok(io::BufReader::new(file).split(b'\n'))
+}
+
+// Feed a file's lines to `parse`, surfacing a read error part way through
+// rather than parsing a truncated map.
+fn parse_file<P, F, M>(filename: P, parse: F) -> Result<M, Box<dyn Error>>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut dyn Iterator<Item = Vec<u8>>) -> Result<M, MapParseError>,
+{
+    let mut io_err = None;
+    let mut lines = read_lines(filename)?.map_while(|res| res.map_err(|e| io_err = Some(e)).ok());
+    let map = parse(&mut lines);
+    drop(lines);
+    if let Some(e) = io_err {
+        return Err(From::from(e));
+    }
+    Ok(map?)
+}
+
+// One line of the @/./# grid format into `cells`, which it replaces.
+fn parse_row(line: &[u8], lineno: usize, cells: &mut Vec<Cell>) -> Result<(), MapParseError> {
+    cells.clear();
+    for (col, &c) in line.iter().enumerate() {
+        match Cell::from_glyph(c) {
+            Some(cell) => cells.push(cell),
+            None => {
+                return Err(MapParseError::UnexpectedChar {
+                    line: lineno + 1,
+                    column: col + 1,
+                    found: c,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+// Lines and columns are 1-based, as an editor would show them.
+#[derive(Debug, PartialEq)]
+pub enum MapParseError {
+    Empty,
+    UnexpectedChar {
+        line: usize,
+        column: usize,
+        found: u8,
+    },
+    MismatchedWidth {
+        line: usize,
+        width: usize,
+        expected: usize,
+    },
+    // RLE input whose first line isn't `x = W, y = H`.
+    BadRleHeader {
+        line: usize,
+    },
+    // An RLE run that spills past the width or height from the header.
+    RleOverflow {
+        line: usize,
+        column: usize,
+    },
+    // A floor of a multi-floor map, starting at `line`, whose (width,
+    // height) differs from the ground floor's.
+    MismatchedFloor {
+        line: usize,
+        size: (usize, usize),
+        expected: (usize, usize),
+    },
+}
+
+impl fmt::Display for MapParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapParseError::Empty => write!(f, "No lines to read!"),
+            MapParseError::UnexpectedChar {
+                line,
+                column,
+                found,
+            } => {
+                write!(
+                    f,
+                    "Unexpected '{}' at line {}, column {}",
+                    found.escape_ascii(),
+                    line,
+                    column
+                )?;
+                if *found == b'\r' {
+                    write!(f, " (the file has CRLF line endings; convert it to LF)")?;
+                }
+                Ok(())
+            }
+            MapParseError::MismatchedWidth {
+                line,
+                width,
+                expected,
+            } => write!(f, "Line {} is {} wide, expected {}", line, width, expected),
+            MapParseError::BadRleHeader { line } => {
+                write!(f, "Line {} should be an RLE header like x = 3, y = 2", line)
+            }
+            MapParseError::RleOverflow { line, column } => write!(
+                f,
+                "RLE run at line {}, column {} runs off the map",
+                line, column
+            ),
+            MapParseError::MismatchedFloor {
+                line,
+                size,
+                expected,
+            } => write!(
+                f,
+                "Floor at line {} is {}x{}, expected {}x{}",
+                line, size.0, size.1, expected.0, expected.1
+            ),
+        }
+    }
+}
+
+impl Error for MapParseError {}
+
+// A cell that isn't on the map, with the map's size.
+#[derive(Debug, PartialEq)]
+pub struct OffMap {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl fmt::Display for OffMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({}, {}) outside of {}x{} FloorMap!",
+            self.x, self.y, self.width, self.height
+        )
+    }
+}
+
+impl Error for OffMap {}
+
+// What a map cell holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cell {
+    Empty,
+    Pallet,
+    // A fixed obstacle: never removed, and it counts toward its neighbors'
+    // totals just like a pallet does.
+    Wall,
+}
+
+impl Cell {
+    pub fn from_glyph(c: u8) -> Option<Cell> {
+        match c {
+            b'.' => Some(Cell::Empty),
+            b'@' => Some(Cell::Pallet),
+            b'#' => Some(Cell::Wall),
+            _ => None,
+        }
+    }
+
+    pub fn glyph(self) -> u8 {
+        match self {
+            Cell::Empty => b'.',
+            Cell::Pallet => b'@',
+            Cell::Wall => b'#',
+        }
+    }
+}
+
+// How a pass applies its removals.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpdateMode {
+    // Free cells as the scan reaches them, so later cells in the same pass
+    // already see the earlier removals.
+    InPlace,
+    // Every cell in a pass is judged against the map as it stood when the
+    // pass began; removals land together at the end.
+    Simultaneous,
+}
+
+// The on-map cell a Custom boundary sends an off-map (x, y, width, height)
+// lookup to, or None for an empty one.
+pub type BoundaryFn = fn(i32, i32, i32, i32) -> Option<(i32, i32)>;
+
+// Which pallets a pass removes, by neighbor count. Every scan, exhaust and
+// backend takes one; a bare u8 threshold means Under.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rule {
+    // Fewer than N neighbors: the puzzle's rule, freeing exposed pallets.
+    Under(u8),
+    // N or more neighbors: thinning out the crowded middle instead.
+    Over(u8),
+}
+
+impl Rule {
+    pub fn removes(self, neighbors: u8) -> bool {
+        match self {
+            Rule::Under(t) => neighbors < t,
+            Rule::Over(t) => neighbors >= t,
+        }
+    }
+
+    pub fn threshold(self) -> u8 {
+        match self {
+            Rule::Under(t) | Rule::Over(t) => t,
+        }
+    }
+}
+
+impl From<u8> for Rule {
+    fn from(t: u8) -> Rule {
+        Rule::Under(t)
+    }
+}
+
+// What a neighbor lookup past the edge of the map sees. Not PartialEq, since
+// Custom's function pointers can't be compared reliably; use matches!.
+#[derive(Debug, Clone, Copy)]
+pub enum Boundary {
+    // Off-map cells are always empty.
+    Empty,
+    // Off-map cells are always occupied, as if the map were walled in.
+    Filled,
+    // The map is a torus: stepping off one edge comes back on the opposite one.
+    Wrap,
+    // The map is reflected across each edge, edge cells included, so the
+    // cell just off the left edge is the leftmost cell itself.
+    Mirror,
+    // Any other mapping.
+    Custom(BoundaryFn),
+}
+
+// Fold a coordinate back into 0..n by reflecting it across the edges.
+fn mirror(v: i32, n: i32) -> i32 {
+    let m = v.rem_euclid(2 * n);
+    if m < n { m } else { 2 * n - 1 - m }
+}
+
+impl Boundary {
+    // The on-map cell a lookup at (x, y) lands on for a width x height map,
+    // or None if it reads as off-map.
+    pub fn resolve(self, x: i32, y: i32, width: i32, height: i32) -> Option<(i32, i32)> {
+        let on_map = |x: i32, y: i32| x >= 0 && y >= 0 && x < width && y < height;
+        if on_map(x, y) {
+            return Some((x, y));
+        }
+        if width == 0 || height == 0 {
+            return None;
+        }
+        match self {
+            Boundary::Empty | Boundary::Filled => None,
+            Boundary::Wrap => Some((x.rem_euclid(width), y.rem_euclid(height))),
+            Boundary::Mirror => Some((mirror(x, width), mirror(y, height))),
+            Boundary::Custom(to) => to(x, y, width, height).filter(|&(x, y)| on_map(x, y)),
+        }
+    }
+}
+
+// One pass of an exhaust: how many pallets it removed, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassResult {
+    pub removed: u32,
+    // In the order the pass freed them.
+    pub freed: Vec<(i32, i32)>,
+}
+
+#[derive(Clone)]
+pub struct FloorMap {
+    // Pallets.
+    grid: BitGrid,
+    // Same shape as `grid`; None until a map actually has walls, so maps
+    // without them pay nothing extra to count.
+    walls: Option<BitGrid>,
+    neighborhood: Neighborhood,
+    update: UpdateMode,
+    boundary: Boundary,
+    // Cells freed by each pass, newest last, once with_undo turns it on.
+    undo: Option<Vec<Vec<(i32, i32)>>>,
+}
+
+impl FloorMap {
+    pub fn new_from_file<P>(filename: P) -> FloorMap
+    where
+        P: AsRef<Path>,
+    {
+        Self::try_from_file(filename).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn new_from_lines<I, S, T>(line_iter: T) -> FloorMap
+    where
+        I: Iterator<Item = S>,
+        S: AsRef<[u8]>,
+        T: IntoIterator<IntoIter = I, Item = S>,
+    {
+        Self::try_from_lines(line_iter).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn try_from_file<P>(filename: P) -> Result<FloorMap, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        parse_file(filename, |lines| Self::try_from_lines(lines))
+    }
+
+    pub fn try_from_lines<I, S, T>(line_iter: T) -> Result<FloorMap, MapParseError>
+    where
+        I: Iterator<Item = S>,
+        S: AsRef<[u8]>,
+        T: IntoIterator<IntoIter = I, Item = S>,
+    {
+        // One row at a time, so the whole map never sits in memory unpacked.
+        let mut grid: Option<BitGrid> = None;
+        let mut walls: Option<BitGrid> = None;
+        let mut cells = Vec::new();
+        let mut row = Vec::<bool>::new();
+        let mut wall_row = Vec::<bool>::new();
+        let mut line_iter = line_iter.into_iter().enumerate().peekable();
+
+        while let Some((lineno, line)) = line_iter.next() {
+            let line = line.as_ref();
+            // A trailing newline leaves one empty line at the end.
+            if line.is_empty() && grid.is_some() && line_iter.peek().is_none() {
+                break;
+            }
+            parse_row(line, lineno, &mut cells)?;
+            row.clear();
+            row.extend(cells.iter().map(|&c| c == Cell::Pallet));
+            wall_row.clear();
+            wall_row.extend(cells.iter().map(|&c| c == Cell::Wall));
+            let grid = grid.get_or_insert_with(|| BitGrid::new(row.len(), 0));
+            if row.len() != grid.width() {
+                return Err(MapParseError::MismatchedWidth {
+                    line: lineno + 1,
+                    width: row.len(),
+                    expected: grid.width(),
+                });
+            }
+            if walls.is_none() && wall_row.contains(&true) {
+                walls = Some(BitGrid::new(grid.width(), grid.height()));
+            }
+            if let Some(walls) = &mut walls {
+                walls.push_row(&wall_row);
+            }
+            grid.push_row(&row);
+        }
+
+        match grid {
+            Some(grid) => Ok(FloorMap {
+                walls,
+                ..FloorMap::from_grid(grid)
+            }),
+            None => Err(MapParseError::Empty),
+        }
+    }
+
+    // A width x height map where each cell holds a pallet with probability
+    // `density`, deterministic for a given seed.
+    pub fn random(width: usize, height: usize, density: f64, seed: u64) -> FloorMap {
+        let mut rng = Xorshift::new(seed);
+        let mut next = || rng.next_u64();
+        let cutoff = (density.clamp(0.0, 1.0) * u64::MAX as f64) as u64;
+        let mut grid = BitGrid::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                grid.set(x, y, next() < cutoff);
+            }
+        }
+        FloorMap::from_grid(grid)
+    }
+
+    // The plain @/./# format try_from_lines reads back.
+    pub fn write_grid<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut row = Vec::with_capacity(self.grid.width() + 1);
+        for y in 0..self.height() {
+            row.clear();
+            row.extend((0..self.width()).map(|x| self.cell(x, y).glyph()));
+            row.push(b'\n');
+            out.write_all(&row)?;
+        }
+        Ok(())
+    }
+
+    fn from_grid(grid: BitGrid) -> FloorMap {
+        FloorMap {
+            grid,
+            walls: None,
+            neighborhood: Neighborhood::moore(),
+            update: UpdateMode::InPlace,
+            boundary: Boundary::Empty,
+            undo: None,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.grid.width() as i32
+    }
+
+    pub fn height(&self) -> i32 {
+        self.grid.height() as i32
+    }
+
+    pub fn with_neighborhood(mut self, neighborhood: Neighborhood) -> Self {
+        self.neighborhood = neighborhood;
+        self
+    }
+
+    pub fn with_update_mode(mut self, update: UpdateMode) -> Self {
+        self.update = update;
+        self
+    }
+
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    // The on-map cell a lookup at (x, y) lands on under the boundary policy;
+    // every map read goes through here.
+    fn resolve(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        self.boundary.resolve(x, y, self.width(), self.height())
+    }
+
+    pub fn pallet_count(&self) -> u64 {
+        self.grid.count_ones()
+    }
+
+    pub fn wall_count(&self) -> u64 {
+        self.walls.as_ref().map_or(0, |w| w.count_ones())
+    }
+
+    // Off-map lookups read whatever the boundary maps them to; a Filled
+    // boundary reads as wall.
+    pub fn cell(&self, x: i32, y: i32) -> Cell {
+        match self.resolve(x, y) {
+            Some((x, y)) if self.is_wall(x, y) => Cell::Wall,
+            Some((x, y)) if self.grid.get(x as isize, y as isize) => Cell::Pallet,
+            None if matches!(self.boundary, Boundary::Filled) => Cell::Wall,
+            _ => Cell::Empty,
+        }
+    }
+
+    // For on-map coordinates only.
+    fn is_wall(&self, x: i32, y: i32) -> bool {
+        self.walls
+            .as_ref()
+            .is_some_and(|w| w.get(x as isize, y as isize))
+    }
+
+    // Coordinates of every pallet in row-major order, read a word at a time.
+    pub fn occupied(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        let stride = self.grid.stride();
+        self.grid
+            .words()
+            .iter()
+            .enumerate()
+            .flat_map(move |(i, &word)| {
+                let (y, x0) = ((i / stride) as i32, ((i % stride) * WORD_BITS) as i32);
+                let mut bits = word;
+                iter::from_fn(move || {
+                    if bits == 0 {
+                        return None;
+                    }
+                    let bit = bits.trailing_zeros() as i32;
+                    bits &= bits - 1;
+                    Some((x0 + bit, y))
+                })
+            })
+    }
+
+    // Coordinates of every empty cell in row-major order; walls are neither
+    // occupied nor free.
+    pub fn free_cells(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        (0..self.height())
+            .flat_map(move |y| (0..self.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.cell(x, y) == Cell::Empty)
+    }
+
+    pub fn map_val(&self, x: i32, y: i32) -> bool {
+        self.resolve(x, y)
+            .is_some_and(|(x, y)| self.grid.get(x as isize, y as isize))
+    }
+
+    // Pallet or wall: anything that counts as a neighbor.
+    fn occupied_val(&self, x: i32, y: i32) -> bool {
+        self.cell(x, y) != Cell::Empty
+    }
+
+    fn check_on_map(&self, x: i32, y: i32) -> Result<(), OffMap> {
+        if self.grid.in_bounds(x as isize, y as isize) {
+            Ok(())
+        } else {
+            Err(OffMap {
+                x,
+                y,
+                width: self.width(),
+                height: self.height(),
+            })
+        }
+    }
+
+    // Clear (x, y), which an empty cell or a wall shrugs off; only a cell off
+    // the map is an error.
+    pub fn free_val(&mut self, x: i32, y: i32) -> Result<(), OffMap> {
+        self.check_on_map(x, y)?;
+        self.clear_val(x, y);
+        Ok(())
+    }
+
+    // free_val over a batch, checked up front so that a batch with a cell
+    // off the map changes nothing.
+    pub fn free_all(&mut self, cells: &[(i32, i32)]) -> Result<(), OffMap> {
+        for &(x, y) in cells {
+            self.check_on_map(x, y)?;
+        }
+        self.clear_cells(cells);
+        Ok(())
+    }
+
+    // For the passes, whose cells come off the map's own grid.
+    pub(crate) fn clear_val(&mut self, x: i32, y: i32) {
+        debug_assert!(self.grid.in_bounds(x as isize, y as isize));
+        self.grid.set(x as usize, y as usize, false);
+    }
+
+    pub(crate) fn clear_cells(&mut self, cells: &[(i32, i32)]) {
+        for &(x, y) in cells {
+            self.clear_val(x, y);
+        }
+    }
+
+    // Put a pallet back, e.g. when undoing a pass.
+    pub fn place_val(&mut self, x: i32, y: i32) {
+        if !self.grid.in_bounds(x as isize, y as isize) {
+            panic!("out of bounds!");
+        }
+        if self.is_wall(x, y) {
+            panic!("({}, {}) is a wall", x, y);
+        }
+        self.grid.set(x as usize, y as usize, true);
+    }
+
+    // The neighborhood of (x, y) as the boundary resolves it: every neighbor
+    // that lands on the map, with its on-map coordinates and what it holds,
+    // in the neighborhood's offset order. A neighbor the boundary sends
+    // nowhere is skipped; one it sends to the same cell as another (a narrow
+    // wrapped map, a mirror) comes up once per offset.
+    pub fn neighbors(&self, x: i32, y: i32) -> impl Iterator<Item = ((i32, i32), Cell)> + '_ {
+        self.neighborhood
+            .offsets()
+            .filter_map(move |(xoff, yoff)| self.resolve(x + xoff, y + yoff))
+            .map(|(nx, ny)| ((nx, ny), self.cell(nx, ny)))
+    }
+
+    pub fn count_neighbors(&self, x: i32, y: i32) -> u8 {
+        if !self.grid.in_bounds(x as isize, y as isize) {
+            panic!(
+                "({}, {}) outside of {}x{} FloorMap!",
+                x,
+                y,
+                self.width(),
+                self.height()
+            );
+        }
+        // The span popcounts below only know how to clip or wrap a row; the
+        // other policies (and spans wider than a wrapped map, which would land
+        // on themselves) count one offset at a time through cell().
+        let spans_fit = match self.boundary {
+            Boundary::Empty => true,
+            Boundary::Wrap => 2 * self.neighborhood.row_reach(0) < self.width(),
+            _ => false,
+        };
+        if !spans_fit {
+            let on_map = self
+                .neighbors(x, y)
+                .filter(|&(_, cell)| cell != Cell::Empty)
+                .count() as u32;
+            // Every neighbor a Filled boundary sends nowhere is a wall.
+            let off_map = match self.boundary {
+                Boundary::Filled => self.neighborhood.size() - self.neighbors(x, y).count() as u32,
+                _ => 0,
+            };
+            return (on_map + off_map) as u8;
+        }
+        // Each neighborhood row is a contiguous span, so popcount it straight
+        // out of the packed words and drop the cell itself afterwards.
+        let mut sum = 0;
+        for yoff in self.neighborhood.row_offsets() {
+            let (lo, hi) = self.neighborhood.row_span(yoff);
+            sum += self.count_row_span(&self.grid, y + yoff, x + lo, x + hi);
+            if let Some(walls) = &self.walls {
+                sum += self.count_row_span(walls, y + yoff, x + lo, x + hi);
+            }
+        }
+        (sum - self.occupied_val(x, y) as u32) as u8
+    }
+
+    // Set bits of `bits` (the pallets or the walls) in row y from x0 to x1
+    // inclusive, splitting the span where it crosses an edge of a wrapped map.
+    fn count_row_span(&self, bits: &BitGrid, y: i32, x0: i32, x1: i32) -> u32 {
+        let span = |y: i32, x0: i32, x1: i32| bits.count_span(y as isize, x0 as isize, x1 as isize);
+        if matches!(self.boundary, Boundary::Empty) {
+            return span(y, x0, x1);
+        }
+        let (w, y) = (self.width(), y.rem_euclid(self.height()));
+        if x0 < 0 {
+            span(y, x0 + w, w - 1) + span(y, 0, x1)
+        } else if x1 >= w {
+            span(y, x0, w - 1) + span(y, 0, x1 - w)
+        } else {
+            span(y, x0, x1)
+        }
+    }
+
+    pub fn count_free(&self, rule: impl Into<Rule>) -> u32 {
+        let rule = rule.into();
+        if self.kernel_applies() {
+            return self.free_mask(rule).iter().map(|w| w.count_ones()).sum();
+        }
+        self.count_free_cells(rule)
+    }
+
+    // count_free one cell at a time through count_neighbors, whatever the
+    // boundary; the reference the word kernel is measured against.
+    pub fn count_free_cells(&self, rule: impl Into<Rule>) -> u32 {
+        let rule = rule.into();
+        self.occupied()
+            .filter(|&(x, y)| rule.removes(self.count_neighbors(x, y)))
+            .count() as u32
+    }
+
+    pub fn count_and_mark_free(&mut self, rule: impl Into<Rule>) -> u32 {
+        self.mark_free(rule).len() as u32
+    }
+
+    // One in-place pass, returning the coordinates freed in scan order.
+    pub fn mark_free(&mut self, rule: impl Into<Rule>) -> Vec<(i32, i32)> {
+        self.mark_free_within(rule.into(), None)
+    }
+
+    // mark_free, only touching pallets set in `allowed` when there is one.
+    pub(crate) fn mark_free_within(
+        &mut self,
+        rule: Rule,
+        allowed: Option<&BitGrid>,
+    ) -> Vec<(i32, i32)> {
+        if self.update == UpdateMode::Simultaneous && self.kernel_applies() {
+            let mut mask = self.free_mask(rule);
+            if let Some(allowed) = allowed {
+                for (m, a) in mask.iter_mut().zip(allowed.words()) {
+                    *m &= a;
+                }
+            }
+            let freed = self.mask_cells(&mask);
+            self.clear_cells(&freed);
+            self.record_pass(&freed);
+            return freed;
+        }
+        let mut freed = Vec::new();
+        for x in 0..self.width() {
+            for y in 0..self.height() {
+                if self.map_val(x, y)
+                    && allowed.is_none_or(|a| a.get(x as isize, y as isize))
+                    && rule.removes(self.count_neighbors(x, y))
+                {
+                    freed.push((x, y));
+                    if self.update == UpdateMode::InPlace {
+                        self.clear_val(x, y);
+                    }
+                }
+            }
+        }
+        // The scan above only read the map, so it saw the frozen pre-pass state.
+        if self.update == UpdateMode::Simultaneous {
+            self.clear_cells(&freed);
+        }
+        self.record_pass(&freed);
+        freed
+    }
+
+    // Run passes until one frees nothing, returning how many each pass freed
+    // (the final empty pass is not included).
+    pub fn count_and_mark_exhaust_passes(&mut self, rule: impl Into<Rule>) -> Vec<u32> {
+        self.exhaust_with(rule, |_, _| {})
+    }
+
+    // count_and_mark_exhaust_passes, calling `on_pass` with the map and the
+    // cells it freed after every pass that freed something.
+    pub fn exhaust_with<F>(&mut self, rule: impl Into<Rule>, mut on_pass: F) -> Vec<u32>
+    where
+        F: FnMut(&FloorMap, &[(i32, i32)]),
+    {
+        let rule = rule.into();
+        let mut passes = Vec::new();
+        loop {
+            let freed = self.mark_free(rule);
+            if freed.is_empty() {
+                break;
+            }
+            on_pass(self, &freed);
+            passes.push(freed.len() as u32);
+        }
+        passes
+    }
+
+    // count_and_mark_exhaust_passes, keeping each pass's freed cells too.
+    pub fn count_and_mark_exhaust_history(&mut self, rule: impl Into<Rule>) -> Vec<PassResult> {
+        let mut history = Vec::new();
+        self.exhaust_with(rule, |_, freed| {
+            history.push(PassResult {
+                removed: freed.len() as u32,
+                freed: freed.to_vec(),
+            })
+        });
+        history
+    }
+
+    pub fn count_and_mark_exhaust(&mut self, rule: impl Into<Rule>) -> u32 {
+        self.count_and_mark_exhaust_passes(rule).iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_map() {
+        let b = FloorMap {
+            walls: None,
+            #[rustfmt::skip]
+            grid: BitGrid::from_vec(
+                4,
+                4,
+                [
+                    false, false, true,  false,
+                    true,  false, false, true,
+                    true,  false, true,  true,
+                    true,  false, true,  true,
+                ]
+                .to_vec(),
+            )
+            .unwrap(),
+            neighborhood: Neighborhood::moore(),
+            update: UpdateMode::InPlace,
+            boundary: Boundary::Empty,
+            undo: None,
+        };
+        assert!(b.map_val(2, 0));
+        assert!(!b.map_val(4, 0));
+        assert_eq!(b.count_neighbors(0, 1), 1);
+    }
+    #[test]
+    fn test_new_map_file() {
+        let b = FloorMap::new_from_file("test.txt");
+        assert_eq!(b.width(), 10);
+        assert_eq!(b.height(), 10);
+    }
+
+    #[test]
+    fn test_new_from_str() {
+        let map = b"..@@.@@@@.
+@@@.@.@.@@
+@@@@@.@.@@
+@.@@@@..@.
+@@.@@@@.@@
+.@@@@@@@.@
+.@.@.@.@@@
+@.@@@.@@@@
+.@@@@@@@@.
+@.@.@@@.@.";
+        let mut b = FloorMap::new_from_lines(map.split(|&v| v == b'\n'));
+        assert_eq!(b.count_neighbors(0, 0), 2);
+        assert_eq!(b.count_free(4), 13);
+        assert_eq!(b.count_and_mark_exhaust(4), 43);
+    }
+
+    #[test]
+    fn test_update_modes() {
+        let mut in_place = FloorMap::new_from_file("test.txt");
+        let mut simultaneous =
+            FloorMap::new_from_file("test.txt").with_update_mode(UpdateMode::Simultaneous);
+        // Same first-pass verdicts as the read-only count, unlike in-place.
+        assert_eq!(simultaneous.count_and_mark_free(4), 13);
+        assert_eq!(in_place.count_and_mark_free(4), 32);
+
+        let mut simultaneous =
+            FloorMap::new_from_file("test.txt").with_update_mode(UpdateMode::Simultaneous);
+        assert_eq!(
+            simultaneous.count_and_mark_exhaust_passes(4),
+            vec![13, 12, 7, 5, 2, 1, 1, 1, 1]
+        );
+
+        // A row of three: in place the left end frees first, which then frees
+        // its neighbor, but simultaneously only the two ends go.
+        let line = || [b"@@@".as_slice()];
+        let mut a = FloorMap::new_from_lines(line());
+        assert_eq!(a.mark_free(2), vec![(0, 0), (1, 0), (2, 0)]);
+        let mut b = FloorMap::new_from_lines(line()).with_update_mode(UpdateMode::Simultaneous);
+        assert_eq!(b.mark_free(2), vec![(0, 0), (2, 0)]);
+        assert_eq!(b.mark_free(2), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn test_neighborhood_shapes() {
+        let map = b"@@@
+@@@
+@@@";
+        let lines = || map.split(|&v| v == b'\n');
+        let moore = FloorMap::new_from_lines(lines());
+        assert_eq!(moore.count_neighbors(1, 1), 8);
+        assert_eq!(moore.count_neighbors(0, 0), 3);
+        assert_eq!(moore.count_free(4), 4);
+
+        let vn = FloorMap::new_from_lines(lines())
+            .with_neighborhood(Neighborhood::new(Shape::VonNeumann, 1).unwrap());
+        assert_eq!(vn.count_neighbors(1, 1), 4);
+        assert_eq!(vn.count_neighbors(0, 0), 2);
+        assert_eq!(vn.count_neighbors(1, 0), 3);
+        assert_eq!(vn.count_free(3), 4);
+
+        let mut moore2 = FloorMap::new_from_lines(lines())
+            .with_neighborhood(Neighborhood::new(Shape::Moore, 2).unwrap());
+        assert_eq!(moore2.count_neighbors(0, 0), 8);
+        assert_eq!(moore2.count_free(9), 9);
+        assert_eq!(moore2.count_and_mark_exhaust(8), 0);
+        assert_eq!(moore2.count_and_mark_exhaust(9), 9);
+
+        let vn2 = FloorMap::new_from_lines(lines())
+            .with_neighborhood(Neighborhood::new(Shape::VonNeumann, 2).unwrap());
+        assert_eq!(vn2.count_neighbors(0, 0), 5);
+        assert_eq!(vn2.count_neighbors(1, 1), 8);
+
+        // Axial hexes: the top-left and bottom-right corners miss out on
+        // their diagonal, the other two corners keep theirs.
+        let hex = FloorMap::new_from_lines(lines())
+            .with_neighborhood(Neighborhood::new(Shape::Hex, 1).unwrap());
+        assert_eq!(hex.count_neighbors(1, 1), 6);
+        assert_eq!(hex.count_neighbors(0, 0), 2);
+        assert_eq!(hex.count_neighbors(2, 0), 3);
+        assert_eq!(hex.count_neighbors(1, 0), 4);
+        assert_eq!(hex.count_free(3), 2);
+    }
+
+    #[test]
+    fn test_popcount_matches_offsets() {
+        // Wider than a word so spans straddle word boundaries.
+        let map = FloorMap::random(150, 9, 0.6, 3);
+        for n in [
+            Neighborhood::moore(),
+            Neighborhood::new(Shape::VonNeumann, 3).unwrap(),
+            Neighborhood::new(Shape::Moore, 4).unwrap(),
+            Neighborhood::new(Shape::Hex, 1).unwrap(),
+            Neighborhood::new(Shape::Hex, 3).unwrap(),
+        ] {
+            let m = map.clone().with_neighborhood(n);
+            for y in 0..m.height() {
+                for x in 0..m.width() {
+                    let by_offset: u8 = n
+                        .offsets()
+                        .map(|(xoff, yoff)| m.map_val(x + xoff, y + yoff) as u8)
+                        .sum();
+                    assert_eq!(m.count_neighbors(x, y), by_offset, "({}, {})", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrap() {
+        let map = b"@@@@
+@@@@
+@@@@";
+        let lines = || map.split(|&v| v == b'\n');
+        let mut flat = FloorMap::new_from_lines(lines());
+        let mut torus = FloorMap::new_from_lines(lines()).with_boundary(Boundary::Wrap);
+        assert_eq!(flat.count_neighbors(0, 0), 3);
+        assert_eq!(torus.count_neighbors(0, 0), 8);
+        assert!(torus.map_val(-1, -1));
+        assert!(torus.map_val(4, 3));
+        assert!(!flat.map_val(4, 3));
+        assert_eq!(flat.count_and_mark_exhaust(4), 12);
+        assert_eq!(torus.count_and_mark_exhaust(4), 0);
+
+        // Spans split across the seam, and radius 2 on a 4-wide map falls back
+        // to counting offsets (which may land on the same cell twice).
+        let map = FloorMap::random(70, 6, 0.5, 11).with_boundary(Boundary::Wrap);
+        let narrow = FloorMap::random(4, 5, 0.5, 11).with_boundary(Boundary::Wrap);
+        for m in [map, narrow] {
+            for n in [
+                Neighborhood::moore(),
+                Neighborhood::new(Shape::VonNeumann, 2).unwrap(),
+                Neighborhood::new(Shape::Moore, 2).unwrap(),
+            ] {
+                let m = m.clone().with_neighborhood(n);
+                for y in 0..m.height() {
+                    for x in 0..m.width() {
+                        let by_offset: u8 = n
+                            .offsets()
+                            .map(|(xoff, yoff)| m.map_val(x + xoff, y + yoff) as u8)
+                            .sum();
+                        assert_eq!(m.count_neighbors(x, y), by_offset, "({}, {})", x, y);
+                    }
+                }
+                let mut scan = m.clone().with_update_mode(UpdateMode::Simultaneous);
+                let mut inc = m.clone();
+                assert_eq!(
+                    inc.exhaust_incremental(4),
+                    scan.count_and_mark_exhaust_passes(4)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_boundaries() {
+        let lines = || [b"@@.".as_slice(), b"@.."];
+        let m = |b| FloorMap::new_from_lines(lines()).with_boundary(b);
+        // (0, 0) sees (1, 0) and (0, 1) on the map, plus whatever the five
+        // cells off its top and left edges hold.
+        assert_eq!(m(Boundary::Empty).count_neighbors(0, 0), 2);
+        assert_eq!(m(Boundary::Filled).count_neighbors(0, 0), 7);
+        assert_eq!(m(Boundary::Filled).cell(-1, 0), Cell::Wall);
+        // Mirrored, (-1, -1), (0, -1) and (-1, 0) are (0, 0) itself, and
+        // (1, -1) and (-1, 1) are (1, 0) and (0, 1).
+        assert_eq!(m(Boundary::Mirror).count_neighbors(0, 0), 7);
+        assert_eq!(mirror(-1, 3), 0);
+        assert_eq!(mirror(4, 3), 1);
+        assert_eq!(mirror(-7, 3), 0);
+
+        // A cylinder: wrap left to right only.
+        let cylinder = |x: i32, y: i32, w: i32, _h: i32| Some((x.rem_euclid(w), y));
+        let c = m(Boundary::Custom(cylinder));
+        assert_eq!(c.count_neighbors(2, 0), 3);
+        assert!(c.map_val(3, 0));
+        assert!(!c.map_val(0, -1));
+
+        // Every boundary counts the same as looking up each offset itself.
+        let map = FloorMap::random(20, 7, 0.5, 3);
+        for b in [
+            Boundary::Filled,
+            Boundary::Mirror,
+            Boundary::Custom(cylinder),
+        ] {
+            let m = map.clone().with_boundary(b);
+            for y in 0..m.height() {
+                for x in 0..m.width() {
+                    let by_offset: u8 = Neighborhood::moore()
+                        .offsets()
+                        .map(|(xoff, yoff)| (m.cell(x + xoff, y + yoff) != Cell::Empty) as u8)
+                        .sum();
+                    assert_eq!(m.count_neighbors(x, y), by_offset);
+                }
+            }
+        }
+        // Every cell of this map touches the edge, so walled in none come free.
+        assert_eq!(m(Boundary::Empty).count_and_mark_exhaust(4), 3);
+        assert_eq!(m(Boundary::Filled).count_and_mark_exhaust(4), 0);
+    }
+
+    #[test]
+    fn test_write_grid() {
+        let map = FloorMap::new_from_lines([b"@.#".as_slice(), b"..@"]);
+        let mut out = Vec::new();
+        map.write_grid(&mut out).unwrap();
+        assert_eq!(out, b"@.#\n..@\n");
+        let back = FloorMap::new_from_lines(out.split(|&v| v == b'\n').filter(|l| !l.is_empty()));
+        assert_eq!(back.grid, map.grid);
+        assert_eq!(back.walls, map.walls);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parse = |s: &[u8]| FloorMap::try_from_lines(s.split(|&v| v == b'\n')).err();
+        assert_eq!(parse(b"@.@\n.@.\n"), None);
+        assert_eq!(
+            parse(b"@.@\n.x.\n"),
+            Some(MapParseError::UnexpectedChar {
+                line: 2,
+                column: 2,
+                found: b'x'
+            })
+        );
+        assert_eq!(
+            parse(b"@.@\n.@\n"),
+            Some(MapParseError::MismatchedWidth {
+                line: 2,
+                width: 2,
+                expected: 3
+            })
+        );
+        assert_eq!(
+            parse(b"@.@\n.@..\n"),
+            Some(MapParseError::MismatchedWidth {
+                line: 2,
+                width: 4,
+                expected: 3
+            })
+        );
+        assert_eq!(
+            FloorMap::try_from_lines(Vec::<&[u8]>::new()).err(),
+            Some(MapParseError::Empty)
+        );
+
+        let crlf = parse(b"@.@\r\n.@.\r\n").unwrap();
+        assert_eq!(
+            crlf,
+            MapParseError::UnexpectedChar {
+                line: 1,
+                column: 4,
+                found: b'\r'
+            }
+        );
+        assert_eq!(
+            crlf.to_string(),
+            "Unexpected '\\r' at line 1, column 4 (the file has CRLF line endings; convert it to LF)"
+        );
+        assert!(FloorMap::try_from_file("missing.txt").is_err());
+    }
+
+    #[test]
+    fn test_walls() {
+        let map = b"#@@
+#@.
+###";
+        let mut m = FloorMap::new_from_lines(map.split(|&v| v == b'\n'));
+        assert_eq!(m.wall_count(), 5);
+        assert_eq!(m.pallet_count(), 3);
+        assert_eq!(m.cell(0, 0), Cell::Wall);
+        assert_eq!(m.cell(1, 0), Cell::Pallet);
+        assert_eq!(m.cell(2, 1), Cell::Empty);
+        assert_eq!(m.cell(3, 0), Cell::Empty);
+        assert!(!m.map_val(0, 0));
+        // Walls count as neighbors, so they shore the pallets up.
+        assert_eq!(m.count_neighbors(1, 1), 7);
+        assert_eq!(m.count_neighbors(2, 0), 2);
+        // (2, 0) goes first, leaving (1, 0) with only three.
+        assert_eq!(m.count_and_mark_exhaust_passes(4), vec![1, 1]);
+        assert_eq!(m.count_and_mark_exhaust(8), 1);
+        assert_eq!(m.wall_count(), 5);
+        assert_eq!(m.pallet_count(), 0);
+
+        // Wall-free maps never allocate the second layer.
+        assert!(FloorMap::new_from_file("test.txt").walls.is_none());
+        // Walls on a later row still line up with the rows before them.
+        let late = FloorMap::new_from_lines([b"@.".as_slice(), b".#"]);
+        assert_eq!(late.cell(1, 1), Cell::Wall);
+        assert_eq!(late.cell(1, 0), Cell::Empty);
+        assert_eq!(late.count_neighbors(0, 0), 1);
+    }
+
+    #[test]
+    fn test_occupied() {
+        let map = b"@.#
+.@@";
+        let m = FloorMap::new_from_lines(map.split(|&v| v == b'\n'));
+        assert_eq!(
+            m.occupied().collect::<Vec<_>>(),
+            vec![(0, 0), (1, 1), (2, 1)]
+        );
+        assert_eq!(m.free_cells().collect::<Vec<_>>(), vec![(1, 0), (0, 1)]);
+
+        // Pallets past the first word of a row.
+        let wide = FloorMap::random(130, 5, 0.3, 9);
+        assert_eq!(wide.occupied().count() as u64, wide.pallet_count());
+        assert!(wide.occupied().all(|(x, y)| wide.map_val(x, y)));
+        assert_eq!(
+            wide.free_cells().count() as u64,
+            130 * 5 - wide.pallet_count()
+        );
+    }
+
+    #[test]
+    fn test_exhaust_passes() {
+        let mut b = FloorMap::new_from_file("test.txt");
+        let passes = b.count_and_mark_exhaust_passes(4);
+        assert_eq!(passes.iter().sum::<u32>(), 43);
+        assert_eq!(passes, vec![32, 9, 2]);
+        assert!(b.count_and_mark_exhaust_passes(4).is_empty());
+    }
+
+    #[test]
+    fn test_exhaust_history() {
+        let start = FloorMap::new_from_file("test.txt");
+        let mut b = start.clone();
+        let history = b.count_and_mark_exhaust_history(4);
+        let removed: Vec<u32> = history.iter().map(|p| p.removed).collect();
+        assert_eq!(removed, vec![32, 9, 2]);
+        assert!(history.iter().all(|p| p.freed.len() == p.removed as usize));
+        // The coordinates are exactly the pallets that went missing.
+        for p in &history {
+            for &(x, y) in &p.freed {
+                assert!(start.map_val(x, y) && !b.map_val(x, y));
+            }
+        }
+        assert!(b.count_and_mark_exhaust_history(4).is_empty());
+    }
+
+    #[test]
+    fn test_rule_over() {
+        assert!(Rule::Under(4).removes(3) && !Rule::Under(4).removes(4));
+        assert!(Rule::Over(4).removes(4) && !Rule::Over(4).removes(3));
+        assert_eq!(Rule::from(4), Rule::Under(4));
+
+        let start = FloorMap::new_from_lines([b"@@@".as_slice(), b"@@@", b"@@@"]);
+        // Only the middle has all eight neighbors.
+        assert_eq!(start.count_free(Rule::Over(8)), 1);
+        assert_eq!(start.count_free_cells(Rule::Over(5)), 5);
+        for update in [UpdateMode::InPlace, UpdateMode::Simultaneous] {
+            let mut b = start.clone().with_update_mode(update);
+            // Removals only lower counts, so nothing new gets crowded after
+            // the first pass.
+            let passes = b.count_and_mark_exhaust_passes(Rule::Over(5));
+            assert_eq!(passes.len(), 1);
+            assert_eq!(b.count_free(Rule::Over(5)), 0);
+        }
+        // In place, the left midpoint and the middle go first and thin out
+        // the other edge midpoints before the scan reaches them.
+        let mut b = start.clone();
+        assert_eq!(b.mark_free(Rule::Over(5)), vec![(0, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn test_free_val() {
+        let mut m = FloorMap::new_from_lines([b"@@#".as_slice(), b".@@"]);
+        assert_eq!(m.free_val(0, 0), Ok(()));
+        assert!(!m.map_val(0, 0));
+        // Freeing a wall or an empty cell is allowed and changes nothing.
+        assert_eq!(m.free_val(2, 0), Ok(()));
+        assert_eq!(m.cell(2, 0), Cell::Wall);
+        assert_eq!(m.free_val(0, 1), Ok(()));
+        let off = OffMap {
+            x: 3,
+            y: 0,
+            width: 3,
+            height: 2,
+        };
+        assert_eq!(m.free_val(3, 0), Err(off));
+        assert_eq!(
+            m.free_val(-1, 1).unwrap_err().to_string(),
+            "(-1, 1) outside of 3x2 FloorMap!"
+        );
+
+        // A batch with a bad cell in it is turned away whole.
+        assert!(m.free_all(&[(1, 0), (1, 2)]).is_err());
+        assert_eq!(m.pallet_count(), 3);
+        assert_eq!(m.free_all(&[(1, 0), (1, 1), (2, 1)]), Ok(()));
+        assert_eq!(m.pallet_count(), 0);
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let lines = || [b"@@#".as_slice(), b".@.", b"@.@"];
+        let m = |b| FloorMap::new_from_lines(lines()).with_boundary(b);
+        let around = |m: &FloorMap, x, y| m.neighbors(x, y).collect::<Vec<_>>();
+        let flat = m(Boundary::Empty);
+        // Corners see three cells, edges five, in offset order.
+        assert_eq!(
+            around(&flat, 0, 0),
+            vec![
+                ((1, 0), Cell::Pallet),
+                ((0, 1), Cell::Empty),
+                ((1, 1), Cell::Pallet)
+            ]
+        );
+        assert_eq!(around(&flat, 2, 2).len(), 3);
+        assert_eq!(around(&flat, 1, 0).len(), 5);
+        assert_eq!(around(&flat, 0, 1).len(), 5);
+        assert_eq!(around(&flat, 1, 1).len(), 8);
+        // Off the map works too, seeing only what's on it.
+        assert_eq!(around(&flat, -1, -1), vec![((0, 0), Cell::Pallet)]);
+        assert_eq!(around(&flat, 5, 5), vec![]);
+
+        // Filled yields the same on-map cells but still counts the rest.
+        let filled = m(Boundary::Filled);
+        assert_eq!(around(&filled, 0, 0), around(&flat, 0, 0));
+        assert_eq!(filled.count_neighbors(0, 0), 2 + 5);
+        // Wrapped, the corner's eight neighbors all land on the map.
+        let torus = m(Boundary::Wrap);
+        let corner = around(&torus, 0, 0);
+        assert_eq!(corner.len(), 8);
+        assert_eq!(corner[0], ((2, 2), Cell::Pallet));
+        assert_eq!(
+            corner.iter().filter(|(_, c)| *c != Cell::Empty).count() as u8,
+            torus.count_neighbors(0, 0)
+        );
+
+        // count_neighbors is the non-empty neighbors, whatever the boundary.
+        let map = FloorMap::random(9, 6, 0.5, 5);
+        for b in [Boundary::Empty, Boundary::Wrap, Boundary::Mirror] {
+            for n in [
+                Neighborhood::moore(),
+                Neighborhood::new(Shape::Hex, 2).unwrap(),
+            ] {
+                let m = map.clone().with_boundary(b).with_neighborhood(n);
+                for y in 0..m.height() {
+                    for x in 0..m.width() {
+                        let occupied = m.neighbors(x, y).filter(|(_, c)| *c != Cell::Empty).count();
+                        assert_eq!(m.count_neighbors(x, y), occupied as u8);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,200 +1,1002 @@
-use log::info;
+use forklift::floors::FloorStack;
+use forklift::image::DEFAULT_IMAGE_THRESHOLD;
+use forklift::neighborhood::{Neighborhood, Shape};
+use forklift::path::{self, Connectivity};
+use forklift::sparse::{self, SparseFloorMap};
+use forklift::stats::{PassStats, StatsFormat};
+use forklift::{
+    Boundary, Cell, FloorMap, Rule, UpdateMode, diff, render, replay, stream, tui, viz,
+};
 use simple_logger::SimpleLogger;
-use std::borrow::Cow;
 use std::env;
 use std::error::Error;
-use std::fs;
 use std::fs::File;
-use std::io::{self, BufRead};
-use std::iter::Peekable;
+use std::io::{self, BufWriter, Write};
 use std::path::Path;
+use std::process;
+use std::thread;
+use std::time::Duration;
 
-fn read_lines<P>(filename: P) -> io::Result<io::Split<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).split(b'\n'))
+const DEFAULT_THRESHOLD: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Grid,
+    Rle,
+    // A saved FloorMap::save checkpoint.
+    Snapshot,
+    // A PNG, PGM or PBM image, thresholded into pallets and empty floor.
+    Image,
 }
 
-struct FloorMap {
-    height: i32,
-    width: i32,
-    map: Vec<bool>,
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Storage {
+    // FloorMap's bitset.
+    Dense,
+    // SparseFloorMap's set of occupied cells.
+    Sparse,
 }
 
-impl FloorMap {
-    fn new_from_file<P>(filename: P) -> FloorMap
-    where
-        P: AsRef<Path>,
-    {
-        let mut line_iter = read_lines(filename).unwrap().map(|res| res.unwrap());
-        return Self::new_from_lines(&mut line_iter);
+#[derive(Debug, PartialEq)]
+enum Command {
+    // Run part 1 or the part 2 exhaust.
+    Exhaust,
+    // `fill X Y`: report what's reachable from one cell.
+    Fill { x: i32, y: i32 },
+    // `diff A B`: list the cells where map B differs from map A (the file).
+    Diff { other: String },
+    // `path X0 Y0 X1 Y1`: shortest route across the empty floor.
+    Path { from: (i32, i32), to: (i32, i32) },
+    // `replay LOG`: apply a --log to the map, then check it ends where this
+    // run's own exhaust does.
+    Replay { log: String },
+    // `gen-map W H`: write a random map to stdout instead of reading one.
+    GenMap { width: usize, height: usize },
+}
+
+struct Options {
+    command: Command,
+    file: String,
+    // None picks by extension: .rle is RLE, .bin a snapshot, .png, .pgm or
+    // .pbm an image, anything else the @/. grid.
+    format: Option<Format>,
+    // Image pixels darker than this become pallets.
+    image_threshold: u8,
+    // None picks sparse storage for mostly-empty or huge maps, as long as the
+    // run needs nothing only the dense map has.
+    storage: Option<Storage>,
+    // Read blank-line-separated floors and count neighbors in 3D.
+    floors: bool,
+    // Count part 1 while reading, never holding more than a few rows.
+    stream: bool,
+    part: u32,
+    threshold: u8,
+    // Remove crowded cells (at or above the threshold) instead of exposed ones.
+    over: bool,
+    neighborhood: Neighborhood,
+    update: UpdateMode,
+    boundary: Boundary,
+    incremental: bool,
+    parallel: bool,
+    // Run part 2 over ghost-bordered tiles instead of whole-map scans.
+    tiled: bool,
+    // Only remove pallets a forklift can reach from the edge of the map.
+    from_edge: bool,
+    components: bool,
+    // Score the layout by pallet edges facing empty floor.
+    exposure: bool,
+    tui: bool,
+    viz: bool,
+    delay: Option<Duration>,
+    render: Option<String>,
+    scale: u32,
+    // Write the map as a PBM (or PGM, by extension) at the end of the run.
+    export: Option<String>,
+    // Also export the start and every pass, numbered like a PNG sequence.
+    export_passes: bool,
+    // Write every removal to this JSON lines file.
+    log: Option<String>,
+    save: Option<String>,
+    stats: Option<StatsFormat>,
+    // Also save every this many passes, not just at the end.
+    checkpoint: Option<u32>,
+    // Which moves the path subcommand and --from-edge's forklift may take.
+    connectivity: Connectivity,
+    // Fraction of cells and generator seed for gen-map.
+    density: f64,
+    seed: u64,
+}
+
+impl Options {
+    fn rule(&self) -> Rule {
+        if self.over {
+            Rule::Over(self.threshold)
+        } else {
+            Rule::Under(self.threshold)
+        }
     }
 
-    fn new_from_lines<I, S, T>(line_iter: T) -> FloorMap
-    where
-        I: Iterator<Item = S>,
-        S: AsRef<[u8]>,
-        T: IntoIterator<IntoIter = I, Item = S>,
-    {
-        let mut map = Vec::<bool>::new();
-        let mut nlines = 0;
-
-        let mut process_line = |line: &[u8], expected_width: Option<i32>| -> i32 {
-            let mut w = 0;
-            for c in line {
-                map.push(match *c {
-                    b'@' => true,
-                    b'.' => false,
-                    _ => {
-                        panic!("Unexpected input {}", *c);
-                    }
-                });
-                w += 1;
-                if let Some(ewidth) = expected_width {
-                    if w > ewidth {
-                        panic!("Line exceeded expected width {}", ewidth);
+    // Only plain part 1 and part 2 runs (with or without --stats) work on a
+    // SparseFloorMap.
+    fn needs_dense(&self) -> bool {
+        self.command != Command::Exhaust
+            || self.incremental
+            || self.from_edge
+            || self.parallel
+            || self.tiled
+            || self.components
+            || self.exposure
+            || self.tui
+            || self.viz
+            || self.render.is_some()
+            || self.export.is_some()
+            || self.log.is_some()
+            || self.save.is_some()
+    }
+}
+
+fn parse_args<I>(mut args: I) -> Result<Options, Box<dyn Error>>
+where
+    I: Iterator<Item = String>,
+{
+    let mut command = Command::Exhaust;
+    let mut file = None;
+    let mut format = None;
+    let mut image_threshold = DEFAULT_IMAGE_THRESHOLD;
+    let mut storage = None;
+    let mut floors = false;
+    let mut stream = false;
+    let mut part = 2;
+    let mut threshold = DEFAULT_THRESHOLD;
+    let mut over = false;
+    let mut shape = Shape::Moore;
+    let mut radius = 1;
+    let mut update = UpdateMode::InPlace;
+    let mut boundary = Boundary::Empty;
+    let mut incremental = false;
+    let mut from_edge = false;
+    let mut parallel = false;
+    let mut tiled = false;
+    let mut components = false;
+    let mut exposure = false;
+    let mut tui = false;
+    let mut viz = false;
+    let mut delay = None;
+    let mut render = None;
+    let mut scale = 4;
+    let mut export = None;
+    let mut export_passes = false;
+    let mut log = None;
+    let mut save = None;
+    let mut stats = None;
+    let mut checkpoint = None;
+    let mut connectivity = Connectivity::Four;
+    let mut density = 0.5;
+    let mut seed = 1;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "fill" if file.is_none() && command == Command::Exhaust => {
+                let mut coord = || match args.next().map(|v| v.parse::<i32>()) {
+                    Some(Ok(v)) => Ok(v),
+                    _ => Err("fill needs X and Y coordinates"),
+                };
+                command = Command::Fill {
+                    x: coord()?,
+                    y: coord()?,
+                };
+            }
+            "path" if file.is_none() && command == Command::Exhaust => {
+                let mut coord = || match args.next().map(|v| v.parse::<i32>()) {
+                    Some(Ok(v)) => Ok(v),
+                    _ => Err("path needs X0 Y0 X1 Y1 coordinates"),
+                };
+                command = Command::Path {
+                    from: (coord()?, coord()?),
+                    to: (coord()?, coord()?),
+                };
+            }
+            "diff" if file.is_none() && command == Command::Exhaust => {
+                match (args.next(), args.next()) {
+                    (Some(a), Some(b)) => {
+                        file = Some(a);
+                        command = Command::Diff { other: b };
                     }
+                    _ => return Err(From::from("diff needs two map files")),
                 }
             }
-            w
-        };
-        let mut line_iter = line_iter.into_iter().peekable();
-
-        let first = match line_iter.next() {
-            Some(f) => f,
-            None => panic!("No lines to read!"),
-        };
-        let width = process_line(first.as_ref(), None);
-        nlines += 1;
-
-        while let Some(line) = line_iter.next() {
-            let w = process_line(line.as_ref(), Some(width));
-            if w == 0 && line_iter.peek().is_none() {
-                break;
+            "replay" if file.is_none() && command == Command::Exhaust => {
+                command = match args.next() {
+                    Some(log) => Command::Replay { log },
+                    None => return Err(From::from("replay needs a log file")),
+                };
+            }
+            "gen-map" if file.is_none() && command == Command::Exhaust => {
+                let mut size = || match args.next().map(|v| v.parse::<usize>()) {
+                    Some(Ok(v)) => Ok(v),
+                    _ => Err("gen-map needs a width and height"),
+                };
+                command = Command::GenMap {
+                    width: size()?,
+                    height: size()?,
+                };
+            }
+            "--density" => {
+                density = match args.next().map(|v| v.parse::<f64>()) {
+                    Some(Ok(d)) if (0.0..=1.0).contains(&d) => d,
+                    _ => return Err(From::from("--density needs a value in [0, 1]")),
+                };
+            }
+            "--seed" => {
+                seed = match args.next().map(|v| v.parse::<u64>()) {
+                    Some(Ok(s)) => s,
+                    _ => return Err(From::from("--seed needs a non-negative integer")),
+                };
+            }
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("grid") => Some(Format::Grid),
+                    Some("rle") => Some(Format::Rle),
+                    Some("snapshot") => Some(Format::Snapshot),
+                    Some("image") => Some(Format::Image),
+                    _ => return Err(From::from("--format needs grid|rle|snapshot|image")),
+                };
+            }
+            "--image-threshold" => {
+                image_threshold = match args.next().map(|v| v.parse::<u8>()) {
+                    Some(Ok(t)) => t,
+                    _ => return Err(From::from("--image-threshold needs a value in [0, 255]")),
+                };
+            }
+            "--storage" => {
+                storage = match args.next().as_deref() {
+                    Some("auto") => None,
+                    Some("dense") => Some(Storage::Dense),
+                    Some("sparse") => Some(Storage::Sparse),
+                    _ => return Err(From::from("--storage needs auto|dense|sparse")),
+                };
+            }
+            "--floors" => {
+                floors = true;
+            }
+            "--stream" => {
+                stream = true;
+            }
+            "--part" => {
+                part = match args.next().as_deref() {
+                    Some("1") => 1,
+                    Some("2") => 2,
+                    _ => return Err(From::from("--part needs 1 or 2")),
+                };
             }
-            if w != width {
-                panic!("Mismatched line width {}, expected {}", w, width);
+            "--threshold" => {
+                let val = match args.next() {
+                    Some(val) => val,
+                    None => return Err(From::from("--threshold needs a value!")),
+                };
+                threshold = match val.parse::<u8>() {
+                    Ok(t) => t,
+                    Err(_) => {
+                        return Err(From::from(format!(
+                            "--threshold must be a non-negative integer, got {}",
+                            val
+                        )));
+                    }
+                };
+            }
+            "--rule" => {
+                over = match args.next().as_deref() {
+                    Some("under") => false,
+                    Some("over") => true,
+                    _ => return Err(From::from("--rule needs under|over and a threshold")),
+                };
+                threshold = match args.next().map(|v| v.parse::<u8>()) {
+                    Some(Ok(t)) => t,
+                    _ => return Err(From::from("--rule needs under|over and a threshold")),
+                };
+            }
+            "--connect" => {
+                connectivity = match args.next().as_deref() {
+                    Some("4") => Connectivity::Four,
+                    Some("8") => Connectivity::Eight,
+                    _ => return Err(From::from("--connect needs 4 or 8")),
+                };
+            }
+            "--neighborhood" => {
+                shape = match args.next().as_deref() {
+                    Some("moore") => Shape::Moore,
+                    Some("vonneumann") => Shape::VonNeumann,
+                    Some("hex") => Shape::Hex,
+                    _ => return Err(From::from("--neighborhood needs moore|vonneumann|hex")),
+                };
+            }
+            "--update" => {
+                update = match args.next().as_deref() {
+                    Some("inplace") => UpdateMode::InPlace,
+                    Some("simultaneous") => UpdateMode::Simultaneous,
+                    _ => return Err(From::from("--update needs inplace|simultaneous")),
+                };
+            }
+            "--wrap" => {
+                boundary = Boundary::Wrap;
+            }
+            "--boundary" => {
+                boundary = match args.next().as_deref() {
+                    Some("empty") => Boundary::Empty,
+                    Some("filled") => Boundary::Filled,
+                    Some("wrap") => Boundary::Wrap,
+                    Some("mirror") => Boundary::Mirror,
+                    _ => return Err(From::from("--boundary needs empty|filled|wrap|mirror")),
+                };
+            }
+            "--from-edge" => {
+                from_edge = true;
+            }
+            "--incremental" => {
+                incremental = true;
+            }
+            "--parallel" => {
+                parallel = true;
+            }
+            "--tiled" => {
+                tiled = true;
+            }
+            "--components" => {
+                components = true;
+            }
+            "--exposure" => {
+                exposure = true;
+            }
+            "--tui" => {
+                tui = true;
+            }
+            "--viz" => {
+                viz = true;
+            }
+            "--delay" => {
+                delay = match args.next().map(|v| v.parse::<u64>()) {
+                    Some(Ok(ms)) => Some(Duration::from_millis(ms)),
+                    _ => return Err(From::from("--delay needs a value in milliseconds")),
+                };
+            }
+            "--render" => {
+                render = match args.next() {
+                    Some(path) => Some(path),
+                    None => return Err(From::from("--render needs an output path")),
+                };
+            }
+            "--export-pbm" => {
+                export = match args.next() {
+                    Some(path) => Some(path),
+                    None => return Err(From::from("--export-pbm needs an output path")),
+                };
+            }
+            "--log" => {
+                log = match args.next() {
+                    Some(path) => Some(path),
+                    None => return Err(From::from("--log needs an output path")),
+                };
+            }
+            "--export-passes" => {
+                export_passes = true;
+            }
+            "--scale" => {
+                scale = match args.next().map(|v| v.parse::<u32>()) {
+                    Some(Ok(s)) if s > 0 => s,
+                    _ => return Err(From::from("--scale needs a positive integer")),
+                };
+            }
+            "--stats" => {
+                stats = match args.next().as_deref() {
+                    Some("csv") => Some(StatsFormat::Csv),
+                    Some("json") => Some(StatsFormat::JsonLines),
+                    _ => return Err(From::from("--stats needs csv|json")),
+                };
+            }
+            "--save" => {
+                save = match args.next() {
+                    Some(path) => Some(path),
+                    None => return Err(From::from("--save needs an output path")),
+                };
+            }
+            "--checkpoint" => {
+                checkpoint = match args.next().map(|v| v.parse::<u32>()) {
+                    Some(Ok(n)) if n > 0 => Some(n),
+                    _ => return Err(From::from("--checkpoint needs a positive pass count")),
+                };
+            }
+            "--radius" => {
+                radius = match args.next().map(|v| v.parse::<i32>()) {
+                    Some(Ok(r)) => r,
+                    _ => return Err(From::from("--radius needs an integer value")),
+                };
+            }
+            _ => {
+                if file.is_some() {
+                    return Err(From::from(format!("Unexpected argument {}", arg)));
+                }
+                file = Some(arg);
             }
-            nlines += 1;
         }
-
-        FloorMap {
-            height: nlines,
-            width: width,
-            map: map,
+    }
+    let neighborhood = match Neighborhood::new(shape, radius) {
+        Ok(n) => n,
+        Err(_) => {
+            return Err(From::from(format!(
+                "Radius {} is out of range for {:?}",
+                radius, shape
+            )));
+        }
+    };
+    // A cell can't have more neighbors than the neighborhood holds, or on a
+    // stack of floors, than it and the two cells above and below it hold.
+    let most = match floors {
+        true => (3 * neighborhood.size() + 2).min(u8::MAX as u32),
+        false => neighborhood.size(),
+    };
+    if threshold as u32 > most {
+        return Err(From::from(format!(
+            "--threshold must be in [0, {}] for this neighborhood, got {}",
+            most, threshold
+        )));
+    }
+    if [incremental, parallel, tiled, from_edge]
+        .iter()
+        .filter(|&&f| f)
+        .count()
+        > 1
+    {
+        return Err(From::from(
+            "Pick at most one of --incremental, --parallel, --tiled and --from-edge",
+        ));
+    }
+    // These engines judge every cell against the map as the pass began, so
+    // an in-place run would quietly get simultaneous passes instead.
+    for (set, flag) in [
+        (incremental, "--incremental"),
+        (parallel, "--parallel"),
+        (tiled, "--tiled"),
+    ] {
+        if set && update != UpdateMode::Simultaneous {
+            return Err(From::from(format!(
+                "{} only runs simultaneous passes; give --update simultaneous too",
+                flag
+            )));
         }
     }
+    if export_passes && export.is_none() {
+        return Err(From::from(
+            "--export-passes needs --export-pbm for the path to number",
+        ));
+    }
+    if checkpoint.is_some() && save.is_none() {
+        return Err(From::from(
+            "--checkpoint needs --save for the path to write",
+        ));
+    }
+    // gen-map makes its map rather than reading one.
+    let file = match (file, &command) {
+        (None, Command::GenMap { .. }) => Some(String::new()),
+        (file, _) => file,
+    };
+    let opts = match file {
+        Some(file) => Options {
+            command,
+            file,
+            format,
+            image_threshold,
+            storage,
+            floors,
+            stream,
+            part,
+            threshold,
+            over,
+            neighborhood,
+            update,
+            boundary,
+            incremental,
+            from_edge,
+            parallel,
+            tiled,
+            components,
+            exposure,
+            tui,
+            viz,
+            delay,
+            render,
+            scale,
+            export,
+            export_passes,
+            log,
+            save,
+            stats,
+            checkpoint,
+            connectivity,
+            density,
+            seed,
+        },
+        None => return Err(From::from("Need a file argument!")),
+    };
+    if opts.storage == Some(Storage::Sparse) && opts.needs_dense() {
+        return Err(From::from(
+            "--storage sparse only runs plain part 1 or 2 (optionally with --stats)",
+        ));
+    }
+    if opts.floors
+        && (opts.needs_dense()
+            || opts.storage.is_some()
+            || file_format(&opts.file, opts.format) != Format::Grid)
+    {
+        return Err(From::from(
+            "--floors only runs plain part 1 or 2 (optionally with --stats) on a grid file",
+        ));
+    }
+    if opts.stream
+        && (opts.part != 1
+            || opts.needs_dense()
+            || opts.floors
+            || opts.storage.is_some()
+            || opts.stats.is_some()
+            || !matches!(opts.boundary, Boundary::Empty | Boundary::Filled)
+            || file_format(&opts.file, opts.format) != Format::Grid)
+    {
+        return Err(From::from(
+            "--stream only runs a plain part 1 on a grid file with an empty or filled boundary",
+        ));
+    }
+    Ok(opts)
+}
 
-    fn map_val(&self, x: i32, y: i32) -> bool {
-        if x < 0 || x >= self.width || y < 0 || y >= self.height {
-            false
-        } else {
-            self.map[y as usize * self.width as usize + x as usize]
+// Exits on a parse error rather than returning it, so the line/column (and
+// any hint) print plainly with Display rather than as a Debug dump.
+fn or_exit<M>(file: &str, parsed: Result<M, Box<dyn Error>>) -> M {
+    match parsed {
+        Ok(map) => map,
+        Err(e) => {
+            eprintln!("{}: {}", file, e);
+            process::exit(1);
         }
     }
+}
 
-    fn free_val(&mut self, x: i32, y: i32) {
-        if x < 0 || x >= self.width || y < 0 || y >= self.height {
-            panic!("out of bounds!");
+fn file_format(file: &str, format: Option<Format>) -> Format {
+    format.unwrap_or_else(|| {
+        let ext = Path::new(file).extension();
+        if ext.is_some_and(|e| e.eq_ignore_ascii_case("rle")) {
+            Format::Rle
+        } else if ext.is_some_and(|e| e.eq_ignore_ascii_case("bin")) {
+            Format::Snapshot
+        } else if ext.is_some_and(|e| {
+            ["png", "pgm", "pbm"]
+                .iter()
+                .any(|i| e.eq_ignore_ascii_case(i))
+        }) {
+            Format::Image
         } else {
-            self.map[y as usize * self.width as usize + x as usize] = false;
+            Format::Grid
         }
+    })
+}
+
+fn load_map(file: &str, format: Option<Format>, image_threshold: u8) -> FloorMap {
+    let parsed = match file_format(file, format) {
+        Format::Grid => FloorMap::try_from_file(file),
+        Format::Rle => FloorMap::try_from_rle_file(file),
+        Format::Snapshot => FloorMap::load(file),
+        Format::Image => FloorMap::try_from_image_file(file, image_threshold),
+    };
+    let map = or_exit(file, parsed);
+    // Nothing in an image says how big the map was meant to be.
+    if file_format(file, format) == Format::Image {
+        eprintln!("{}: {}x{} map", file, map.width(), map.height());
     }
+    map
+}
 
-    fn count_neighbors(&self, x: i32, y: i32) -> u8 {
-        if x < 0 {
-            panic!("width {} below 0!", x);
-        }
-        if y < 0 {
-            panic!("height {} below 0!", y);
-        }
-        if x >= self.width {
-            panic!(
-                "width {} exceeded width of map in FloorMap! {}",
-                x, self.width
-            );
-        }
-        if y >= self.height {
-            panic!(
-                "height {} exceeded # height of FloorMap! {}",
-                y, self.height
-            );
+enum Loaded {
+    Dense(FloorMap),
+    Sparse(SparseFloorMap),
+}
+
+fn load(opts: &Options) -> Loaded {
+    let sparse_ok = opts.storage != Some(Storage::Dense) && !opts.needs_dense();
+    let pick_sparse = |width: i32, height: i32, occupied: u64| {
+        opts.storage == Some(Storage::Sparse)
+            || sparse::prefers_sparse(width as usize, height as usize, occupied)
+    };
+    // An RLE header can promise far more cells than a bitset could hold, so
+    // read it sparsely first and only then decide.
+    if sparse_ok && file_format(&opts.file, opts.format) == Format::Rle {
+        let map = or_exit(&opts.file, SparseFloorMap::try_from_rle_file(&opts.file));
+        if pick_sparse(map.width(), map.height(), map.pallet_count()) {
+            return Loaded::Sparse(map);
         }
-        let mut sum = 0;
-        for xoff in -1..2 {
-            for yoff in -1..2 {
-                sum += if xoff == 0 && yoff == 0 {
-                    0
-                } else {
-                    // info!(
-                    //     "x {} y {} eval {} {} -> {}",
-                    //     x,
-                    //     y,
-                    //     x + xoff,
-                    //     y + yoff,
-                    //     self.map_val(x + xoff, y + yoff) as u8
-                    // );
-                    self.map_val(x + xoff, y + yoff) as u8
-                };
-            }
-        }
-        //info!("({}, {}) -> {}", x, y, sum);
-        sum
-    }
-
-    fn count_free(&self, free_threshold: u8) -> u32 {
-        let mut sum = 0;
-        for x in 0..self.width {
-            for y in 0..self.height {
-                if self.map_val(x, y) {
-                    sum += (self.count_neighbors(x, y) < free_threshold) as u32;
-                }
-            }
+        return Loaded::Dense(map.to_dense());
+    }
+    let map = load_map(&opts.file, opts.format, opts.image_threshold);
+    if sparse_ok
+        && pick_sparse(
+            map.width(),
+            map.height(),
+            map.pallet_count() + map.wall_count(),
+        )
+    {
+        return Loaded::Sparse(SparseFloorMap::from_dense(&map));
+    }
+    Loaded::Dense(map)
+}
+
+// Perimeter, then how many pallets have 0 through 4 open sides.
+fn print_exposure(map: &FloorMap) {
+    let h = map.exposure_histogram();
+    println!(
+        "perimeter: {} exposure: {} {} {} {} {}",
+        map.perimeter(),
+        h[0],
+        h[1],
+        h[2],
+        h[3],
+        h[4]
+    );
+}
+
+// Part 2 with whichever algorithm the options pick. parse_args only lets the
+// incremental, parallel and tiled paths run with --update simultaneous, the
+// only passes they can run.
+fn exhaust<F>(map: &mut FloorMap, opts: &Options, on_pass: F) -> Vec<u32>
+where
+    F: FnMut(&FloorMap, &[(i32, i32)]),
+{
+    if opts.incremental {
+        map.exhaust_incremental_with(opts.rule(), on_pass)
+    } else if opts.parallel {
+        map.exhaust_parallel_with(opts.rule(), on_pass)
+    } else if opts.tiled {
+        map.exhaust_tiled_with(opts.rule(), on_pass)
+    } else if opts.from_edge {
+        map.exhaust_from_edge_with(opts.rule(), opts.connectivity, on_pass)
+    } else {
+        map.exhaust_with(opts.rule(), on_pass)
+    }
+}
+
+fn print_summary(passes: &[u32], stats: bool) {
+    let summary = format!(
+        "{}\npasses: {} {:?}",
+        passes.iter().sum::<u32>(),
+        passes.len(),
+        passes
+    );
+    // Keep stdout to the stats rows alone so it can be piped to a plotter.
+    if stats {
+        eprintln!("{}", summary);
+    } else {
+        println!("{}", summary);
+    }
+}
+
+fn run_sparse(opts: &Options, map: SparseFloorMap) {
+    let mut map = map
+        .with_neighborhood(opts.neighborhood)
+        .with_update_mode(opts.update)
+        .with_boundary(opts.boundary);
+    if opts.part == 1 {
+        println!("{}", map.count_free(opts.rule()));
+        return;
+    }
+    let area = map.width() as u64 * map.height() as u64;
+    if let Some(format) = opts.stats {
+        if let Some(header) = PassStats::header(format) {
+            println!("{}", header);
         }
-        sum
+        let start = PassStats::new(0, opts.threshold, 0, map.pallet_count(), area);
+        println!("{}", start.line(format));
     }
+    let mut pass = 0;
+    let passes = map.exhaust_with(opts.rule(), |map, freed| {
+        pass += 1;
+        if let Some(format) = opts.stats {
+            let removed = freed.len() as u32;
+            let stats = PassStats::new(pass, opts.threshold, removed, map.pallet_count(), area);
+            println!("{}", stats.line(format));
+        }
+    });
+    print_summary(&passes, opts.stats.is_some());
+}
 
-    fn count_and_mark_free(&mut self, free_threshold: u8) -> u32 {
-        let mut sum = 0;
-        for x in 0..self.width {
-            for y in 0..self.height {
-                if self.map_val(x, y) {
-                    if self.count_neighbors(x, y) < free_threshold {
-                        sum += 1;
-                        self.free_val(x, y);
-                    }
-                }
-            }
+fn run_floors(opts: &Options, stack: FloorStack) {
+    let mut stack = stack
+        .with_neighborhood(opts.neighborhood)
+        .with_update_mode(opts.update)
+        .with_boundary(opts.boundary);
+    if opts.part == 1 {
+        println!("{}", stack.count_free(opts.rule()));
+        return;
+    }
+    let area = stack.width() as u64 * stack.height() as u64 * stack.depth() as u64;
+    if let Some(format) = opts.stats {
+        if let Some(header) = PassStats::header(format) {
+            println!("{}", header);
         }
-        sum
+        let start = PassStats::new(0, opts.threshold, 0, stack.pallet_count(), area);
+        println!("{}", start.line(format));
     }
+    let mut pass = 0;
+    let passes = stack.exhaust_with(opts.rule(), |stack, freed| {
+        pass += 1;
+        if let Some(format) = opts.stats {
+            let removed = freed.len() as u32;
+            let stats = PassStats::new(pass, opts.threshold, removed, stack.pallet_count(), area);
+            println!("{}", stats.line(format));
+        }
+    });
+    print_summary(&passes, opts.stats.is_some());
+}
 
-    fn count_and_mark_exhaust(&mut self, free_threshold: u8) -> u32 {
-        let mut sum = 0;
-        loop {
-            let pass_sum = self.count_and_mark_free(free_threshold);
-            if pass_sum == 0 {
-                break;
-            }
-            sum += pass_sum;
+// Sparse generation past the dense limit or below the sparse density, so the
+// same command scales from test maps up to ones no bitset could hold. A seed
+// gives a different map from each generator.
+fn gen_map(width: usize, height: usize, opts: &Options) -> Result<(), Box<dyn Error>> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    let format = opts.format.unwrap_or(Format::Grid);
+    let cells = width as u64 * height as u64;
+    let sparse = cells > sparse::DENSE_CELL_LIMIT || opts.density < sparse::SPARSE_DENSITY;
+    match format {
+        Format::Snapshot | Format::Image => return Err(From::from("gen-map writes grid or rle")),
+        Format::Rle if sparse => {
+            SparseFloorMap::random(width, height, opts.density, opts.seed).write_rle(&mut out)?
+        }
+        _ if cells > sparse::DENSE_CELL_LIMIT => {
+            return Err(From::from(format!(
+                "{}x{} is too big for a grid file; use --format rle",
+                width, height
+            )));
+        }
+        Format::Grid if sparse => SparseFloorMap::random(width, height, opts.density, opts.seed)
+            .to_dense()
+            .write_grid(&mut out)?,
+        Format::Grid => {
+            FloorMap::random(width, height, opts.density, opts.seed).write_grid(&mut out)?
+        }
+        Format::Rle => {
+            FloorMap::random(width, height, opts.density, opts.seed).write_rle(&mut out)?
         }
-        sum
     }
+    out.flush()?;
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     SimpleLogger::new().init().unwrap();
-    let mut args = env::args().skip(1).peekable();
-    let file = match args.next() {
-        Some(file) => file,
-        None => {
-            return Err(From::from("Need a file argument!"));
+    let opts = parse_args(env::args().skip(1))?;
+    if let Command::GenMap { width, height } = opts.command {
+        return gen_map(width, height, &opts);
+    }
+    if opts.stream {
+        let freed =
+            stream::count_free_file(&opts.file, opts.rule(), opts.neighborhood, opts.boundary);
+        println!("{}", or_exit(&opts.file, freed));
+        return Ok(());
+    }
+    if opts.floors {
+        run_floors(
+            &opts,
+            or_exit(&opts.file, FloorStack::try_from_file(&opts.file)),
+        );
+        return Ok(());
+    }
+    let map = match load(&opts) {
+        Loaded::Dense(map) => map,
+        Loaded::Sparse(map) => {
+            run_sparse(&opts, map);
+            return Ok(());
         }
     };
-    let mut map = FloorMap::new_from_file(file);
-    println!("{}", map.count_and_mark_exhaust(4));
+    let mut map = map
+        .with_neighborhood(opts.neighborhood)
+        .with_update_mode(opts.update)
+        .with_boundary(opts.boundary);
+    if let Command::Diff { other } = &opts.command {
+        let diffs = match map.diff(&load_map(other, opts.format, opts.image_threshold)) {
+            Ok(diffs) => diffs,
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        };
+        println!("{} cells differ", diffs.len());
+        if opts.viz {
+            print!("{}", diff::render(&map, &diffs));
+        } else {
+            for d in &diffs {
+                println!(
+                    "({}, {}): {} vs {}",
+                    d.x,
+                    d.y,
+                    d.left.glyph() as char,
+                    d.right.glyph() as char
+                );
+            }
+        }
+    } else if let Command::Replay { log } = &opts.command {
+        let removals = or_exit(log, replay::read_log(log));
+        let mut replayed = map.clone();
+        let replayed_passes = match replayed.replay(&removals) {
+            Ok(passes) => passes,
+            Err(e) => {
+                eprintln!("{}: {}", log, e);
+                process::exit(1);
+            }
+        };
+        println!(
+            "replayed {} removals in {} passes",
+            removals.len(),
+            replayed_passes.len()
+        );
+        let passes = exhaust(&mut map, &opts, |_, _| {});
+        if passes != replayed_passes {
+            println!(
+                "passes differ: {:?} here, {:?} logged",
+                passes, replayed_passes
+            );
+        }
+        // Sizes match, since both started from the same map.
+        let diffs = replayed.diff(&map)?;
+        if !diffs.is_empty() {
+            println!("final state differs in {} cells", diffs.len());
+            if opts.viz {
+                print!("{}", diff::render(&replayed, &diffs));
+            }
+            process::exit(1);
+        }
+        println!("final state matches");
+    } else if let Command::Fill { x, y } = opts.command {
+        if x < 0 || y < 0 || x >= map.width() || y >= map.height() {
+            return Err(From::from(format!(
+                "({}, {}) is outside the {}x{} map",
+                x,
+                y,
+                map.width(),
+                map.height()
+            )));
+        }
+        let kind = match map.cell(x, y) {
+            Cell::Pallet => "pallets",
+            Cell::Wall => "walls",
+            Cell::Empty => "empty cells",
+        };
+        let cells = map.flood_fill(x, y);
+        println!("{} {} reachable from ({}, {})", cells.len(), kind, x, y);
+    } else if let Command::Path { from, to } = opts.command {
+        for (x, y) in [from, to] {
+            if x < 0 || y < 0 || x >= map.width() || y >= map.height() {
+                return Err(From::from(format!(
+                    "({}, {}) is outside the {}x{} map",
+                    x,
+                    y,
+                    map.width(),
+                    map.height()
+                )));
+            }
+        }
+        match map.path(from, to, opts.connectivity) {
+            Some(cells) => {
+                println!("path length {}", cells.len() - 1);
+                if opts.viz {
+                    print!("{}", path::render(&map, &cells));
+                }
+            }
+            None => println!("no path from {:?} to {:?}", from, to),
+        }
+    } else if opts.tui {
+        tui::run(map, opts.rule())?;
+    } else if opts.part == 1 {
+        if opts.from_edge {
+            println!(
+                "{}",
+                map.count_free_from_edge(opts.rule(), opts.connectivity)
+            );
+        } else if opts.parallel {
+            println!("{}", map.count_free_par(opts.rule()));
+        } else {
+            println!("{}", map.count_free(opts.rule()));
+        }
+        if opts.exposure {
+            print_exposure(&map);
+        }
+        if let Some(path) = &opts.export {
+            render::export(path, &map, &[])?;
+        }
+    } else {
+        let mut renderer = match &opts.render {
+            Some(path) => {
+                let delay_ms = opts.delay.map_or(100, |d| d.as_millis() as u64);
+                let mut r = render::Renderer::create(path, &map, opts.scale, delay_ms)?;
+                r.capture(&map, &[])?;
+                Some(r)
+            }
+            None => None,
+        };
+        if opts.viz {
+            print!("{}", viz::render(&map, &[]));
+        }
+        if let Some(path) = &opts.export
+            && opts.export_passes
+        {
+            render::export(render::numbered(path, 0), &map, &[])?;
+        }
+        if let Some(format) = opts.stats {
+            if let Some(header) = PassStats::header(format) {
+                println!("{}", header);
+            }
+            println!("{}", PassStats::of(&map, 0, opts.threshold, 0).line(format));
+        }
+        let mut log = match &opts.log {
+            Some(path) => Some(BufWriter::new(File::create(path)?)),
+            None => None,
+        };
+        let mut pass = 0;
+        // First failure from a render, export, log or checkpoint write; later
+        // ones are skipped.
+        let mut hook_err: Option<Box<dyn Error>> = None;
+        let on_pass = |map: &FloorMap, freed: &[(i32, i32)]| {
+            pass += 1;
+            if opts.viz {
+                if let Some(d) = opts.delay {
+                    thread::sleep(d);
+                }
+                println!("pass {}: freed {}", pass, freed.len());
+                print!("{}", viz::render(map, freed));
+            }
+            if let Some(format) = opts.stats {
+                let stats = PassStats::of(map, pass, opts.threshold, freed.len() as u32);
+                println!("{}", stats.line(format));
+            }
+            if let Some(r) = &mut renderer
+                && hook_err.is_none()
+            {
+                hook_err = r.capture(map, freed).err();
+            }
+            if let Some(path) = &opts.export
+                && opts.export_passes
+                && hook_err.is_none()
+            {
+                hook_err = render::export(render::numbered(path, pass), map, freed).err();
+            }
+            if let Some(out) = &mut log
+                && hook_err.is_none()
+            {
+                hook_err = replay::write_pass(out, pass, freed).err().map(From::from);
+            }
+            if let (Some(path), Some(every)) = (&opts.save, opts.checkpoint)
+                && pass % every == 0
+                && hook_err.is_none()
+            {
+                hook_err = map.save(path).err();
+            }
+        };
+        let passes = exhaust(&mut map, &opts, on_pass);
+        if let Some(out) = &mut log {
+            out.flush()?;
+        }
+        if let Some(e) = hook_err {
+            return Err(e);
+        }
+        if let Some(path) = &opts.save {
+            map.save(path)?;
+        }
+        if let Some(path) = &opts.export {
+            render::export(path, &map, &[])?;
+        }
+        print_summary(&passes, opts.stats.is_some());
+        if opts.components {
+            let c = map.components();
+            let largest = c.largest().map_or(0, |(_, size)| size);
+            println!("components: {} largest: {}", c.count(), largest);
+        }
+        if opts.exposure {
+            print_exposure(&map);
+        }
+    }
     Ok(())
 }
 
@@ -203,40 +1005,242 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_new_map() {
-        let b = FloorMap {
-            width: 4,
-            height: 3,
-            #[rustfmt::skip]
-            map: [
-                false, false, true, false,
-                true, false, false, true,
-                true, false, true, true,
-                true, false, true, true,
-            ]
-            .to_vec(),
-        };
+    fn test_parse_threshold() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        assert_eq!(args(&["f.txt"]).unwrap().threshold, 4);
+        assert_eq!(args(&["--threshold", "8", "f.txt"]).unwrap().threshold, 8);
+        assert_eq!(args(&["f.txt", "--threshold", "0"]).unwrap().threshold, 0);
+        assert!(args(&["--threshold", "9", "f.txt"]).is_err());
+        assert!(args(&["--threshold", "-1", "f.txt"]).is_err());
+        assert!(args(&["f.txt", "--threshold"]).is_err());
+        assert!(args(&["--neighborhood", "vonneumann", "--threshold", "5", "f.txt"]).is_err());
+        assert!(args(&["--neighborhood", "hex", "--threshold", "6", "f.txt"]).is_ok());
+        assert!(args(&["--neighborhood", "hex", "--threshold", "7", "f.txt"]).is_err());
+        assert!(args(&["--radius", "2", "--threshold", "24", "f.txt"]).is_ok());
+        assert!(args(&["--radius", "0", "f.txt"]).is_err());
+
+        let opts = args(&["--rule", "over", "6", "f.txt"]).unwrap();
+        assert_eq!(opts.rule(), Rule::Over(6));
+        assert_eq!(
+            args(&["--rule", "under", "3", "f.txt"]).unwrap().rule(),
+            Rule::Under(3)
+        );
+        assert_eq!(args(&["f.txt"]).unwrap().rule(), Rule::Under(4));
+        assert!(args(&["--rule", "over", "9", "f.txt"]).is_err());
+        assert!(args(&["--rule", "sideways", "3", "f.txt"]).is_err());
+        assert!(args(&["--rule", "over", "f.txt"]).is_err());
     }
+
     #[test]
-    fn test_new_map_file() {
-        let b = FloorMap::new_from_file("test.txt");
+    fn test_parse_save() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        let opts = args(&["--save", "s.bin", "--checkpoint", "5", "f.txt"]).unwrap();
+        assert_eq!(opts.save.as_deref(), Some("s.bin"));
+        assert_eq!(opts.checkpoint, Some(5));
+        assert!(args(&["--checkpoint", "5", "f.txt"]).is_err());
+        assert!(args(&["--save", "s.bin", "--checkpoint", "0", "f.txt"]).is_err());
+        let opts = args(&["--export-pbm", "m.pgm", "--export-passes", "f.txt"]).unwrap();
+        assert_eq!(opts.export.as_deref(), Some("m.pgm"));
+        assert!(opts.export_passes && opts.needs_dense());
+        assert!(args(&["--export-passes", "f.txt"]).is_err());
+        assert!(args(&["--export-pbm"]).is_err());
+        assert_eq!(
+            args(&["--format", "snapshot", "f.txt"]).unwrap().format,
+            Some(Format::Snapshot)
+        );
     }
 
     #[test]
-    fn test_new_from_str() {
-        let map = b"..@@.@@@@.
-@@@.@.@.@@
-@@@@@.@.@@
-@.@@@@..@.
-@@.@@@@.@@
-.@@@@@@@.@
-.@.@.@.@@@
-@.@@@.@@@@
-.@@@@@@@@.
-@.@.@@@.@.";
-        let mut b = FloorMap::new_from_lines(map.split(|&v| v == b'\n'));
-        assert_eq!(b.count_neighbors(0, 0), 2);
-        assert_eq!(b.count_free(4), 13);
-        assert_eq!(b.count_and_mark_exhaust(4), 43);
+    fn test_parse_image() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        let opts = args(&["drawing.PNG"]).unwrap();
+        assert_eq!(file_format(&opts.file, opts.format), Format::Image);
+        assert_eq!(opts.image_threshold, DEFAULT_IMAGE_THRESHOLD);
+        let opts = args(&["--format", "image", "--image-threshold", "40", "f"]).unwrap();
+        assert_eq!(
+            (opts.format, opts.image_threshold),
+            (Some(Format::Image), 40)
+        );
+        assert!(args(&["--image-threshold", "256", "f.pgm"]).is_err());
+        // Images hold no floors.
+        assert!(args(&["--floors", "f.pgm"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_boundary() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        let boundary = |v: &[&str]| args(v).unwrap().boundary;
+        assert!(matches!(boundary(&["f.txt"]), Boundary::Empty));
+        assert!(matches!(boundary(&["--wrap", "f.txt"]), Boundary::Wrap));
+        assert!(matches!(
+            boundary(&["--boundary", "mirror", "f.txt"]),
+            Boundary::Mirror
+        ));
+        assert!(args(&["--boundary", "custom", "f.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_storage() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        assert_eq!(args(&["f.rle"]).unwrap().storage, None);
+        let opts = args(&["--storage", "sparse", "--stats", "csv", "f.rle"]).unwrap();
+        assert_eq!(opts.storage, Some(Storage::Sparse));
+        assert!(!opts.needs_dense());
+        assert!(args(&["--storage", "sparse", "--viz", "f.rle"]).is_err());
+        assert!(args(&["--viz", "f.rle"]).unwrap().needs_dense());
+        assert!(args(&["--exposure", "f.rle"]).unwrap().needs_dense());
+        assert!(args(&["--storage", "sparse", "--exposure", "f.rle"]).is_err());
+        assert!(args(&["--storage", "bits", "f.rle"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_gen_map() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        let opts = args(&["gen-map", "100", "50", "--density", "0.3", "--seed", "7"]).unwrap();
+        assert_eq!(
+            opts.command,
+            Command::GenMap {
+                width: 100,
+                height: 50
+            }
+        );
+        assert_eq!((opts.density, opts.seed), (0.3, 7));
+        assert!(args(&["gen-map", "100"]).is_err());
+        assert!(args(&["gen-map", "1", "1", "--density", "2"]).is_err());
+        assert!(args(&["--density", "0.1"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_fill() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        assert_eq!(
+            args(&["fill", "3", "4", "f.txt"]).unwrap().command,
+            Command::Fill { x: 3, y: 4 }
+        );
+        assert_eq!(
+            args(&["--wrap", "fill", "0", "0", "f.txt"])
+                .unwrap()
+                .command,
+            Command::Fill { x: 0, y: 0 }
+        );
+        assert_eq!(args(&["f.txt"]).unwrap().command, Command::Exhaust);
+        assert!(args(&["fill", "3", "f.txt"]).is_err());
+        assert!(args(&["fill", "f.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_floors() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        assert!(
+            args(&["--floors", "--stats", "csv", "f.txt"])
+                .unwrap()
+                .floors
+        );
+        assert!(!args(&["f.txt"]).unwrap().floors);
+        // Up to 26 neighbors in 3D, but still only 8 on one floor.
+        assert!(args(&["--floors", "--threshold", "26", "f.txt"]).is_ok());
+        assert!(args(&["--floors", "--threshold", "27", "f.txt"]).is_err());
+        assert!(args(&["--threshold", "26", "f.txt"]).is_err());
+        assert!(args(&["--floors", "--tui", "f.txt"]).is_err());
+        assert!(args(&["--floors", "--storage", "sparse", "f.txt"]).is_err());
+        assert!(args(&["--floors", "f.rle"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_stream() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        assert!(args(&["--stream", "--part", "1", "f.txt"]).unwrap().stream);
+        let ok = ["--stream", "--part", "1", "--boundary", "filled", "f.txt"];
+        assert!(args(&ok).is_ok());
+        assert!(args(&["--stream", "f.txt"]).is_err());
+        assert!(args(&["--stream", "--part", "1", "--parallel", "f.txt"]).is_err());
+        assert!(args(&["--stream", "--part", "1", "--boundary", "wrap", "f.txt"]).is_err());
+        assert!(args(&["--stream", "--part", "1", "f.rle"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_update() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        let opts = args(&["--incremental", "--update", "simultaneous", "f.txt"]).unwrap();
+        assert!(opts.incremental);
+        assert_eq!(opts.update, UpdateMode::Simultaneous);
+        assert!(args(&["--incremental", "f.txt"]).is_err());
+        assert!(args(&["--incremental", "--update", "inplace", "f.txt"]).is_err());
+        let ok = [
+            "--parallel",
+            "--update",
+            "simultaneous",
+            "--part",
+            "1",
+            "f.txt",
+        ];
+        assert!(args(&ok).unwrap().parallel);
+        assert!(args(&["--parallel", "f.txt"]).is_err());
+        let ok = ["--tiled", "--update", "simultaneous", "f.txt"];
+        assert!(args(&ok).unwrap().tiled);
+        assert!(args(&["--tiled", "f.txt"]).is_err());
+        assert!(args(&["--tiled", "--update", "inplace", "f.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_replay() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        let opts = args(&[
+            "replay",
+            "r.jsonl",
+            "f.txt",
+            "--incremental",
+            "--update",
+            "simultaneous",
+        ])
+        .unwrap();
+        assert_eq!(
+            opts.command,
+            Command::Replay {
+                log: "r.jsonl".to_string()
+            }
+        );
+        assert_eq!(opts.file, "f.txt");
+        assert!(args(&["replay"]).is_err());
+        let opts = args(&["--log", "r.jsonl", "f.txt"]).unwrap();
+        assert_eq!(opts.log.as_deref(), Some("r.jsonl"));
+        assert!(opts.needs_dense());
+        assert!(args(&["--log"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_path() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        let opts = args(&["path", "0", "1", "5", "6", "f.txt", "--connect", "8"]).unwrap();
+        assert_eq!(
+            opts.command,
+            Command::Path {
+                from: (0, 1),
+                to: (5, 6)
+            }
+        );
+        assert_eq!(opts.connectivity, Connectivity::Eight);
+        assert_eq!(args(&["f.txt"]).unwrap().connectivity, Connectivity::Four);
+        assert!(args(&["path", "0", "1", "5", "f.txt"]).is_err());
+        assert!(args(&["--connect", "6", "f.txt"]).is_err());
+        assert!(args(&["--from-edge", "f.txt"]).unwrap().from_edge);
+        assert!(args(&["--from-edge", "--parallel", "f.txt"]).is_err());
+        assert!(args(&["--tiled", "--incremental", "f.txt"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_diff() {
+        let args = |v: &[&str]| parse_args(v.iter().map(|s| s.to_string()));
+        let opts = args(&["diff", "a.txt", "b.txt", "--viz"]).unwrap();
+        assert_eq!(opts.file, "a.txt");
+        assert_eq!(
+            opts.command,
+            Command::Diff {
+                other: "b.txt".to_string()
+            }
+        );
+        assert!(opts.viz);
+        assert!(args(&["diff", "a.txt"]).is_err());
+        assert!(args(&["a.txt", "diff", "b.txt", "c.txt"]).is_err());
     }
 }
@@ -0,0 +1,169 @@
+// Which cells around (x, y) count as its neighbors.
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    // Every cell within the (2r+1) square, diagonals included.
+    Moore,
+    // Cells within Manhattan distance r, i.e. orthogonal steps only.
+    VonNeumann,
+    // Cells within r steps on a hex grid in axial coordinates: each row of
+    // the map is a row of hexes, shifted half a cell right of the row above,
+    // so (x, y) touches (x + 1, y - 1) and (x - 1, y + 1) but not the other
+    // two diagonals. Radius 1 gives the six surrounding hexes.
+    Hex,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct InvalidNeighborhood;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighborhood {
+    shape: Shape,
+    radius: i32,
+}
+
+impl Neighborhood {
+    // Neighbor counts are u8, so the neighborhood must hold at most 255 cells.
+    pub fn new(shape: Shape, radius: i32) -> Result<Self, InvalidNeighborhood> {
+        let n = Neighborhood { shape, radius };
+        if !(1..=10).contains(&radius) || n.size() > u8::MAX as u32 {
+            Err(InvalidNeighborhood)
+        } else {
+            Ok(n)
+        }
+    }
+
+    pub fn moore() -> Self {
+        Neighborhood {
+            shape: Shape::Moore,
+            radius: 1,
+        }
+    }
+
+    pub fn contains(&self, xoff: i32, yoff: i32) -> bool {
+        if xoff == 0 && yoff == 0 {
+            return false;
+        }
+        match self.shape {
+            Shape::Moore => xoff.abs() <= self.radius && yoff.abs() <= self.radius,
+            Shape::VonNeumann => xoff.abs() + yoff.abs() <= self.radius,
+            Shape::Hex => {
+                xoff.abs() <= self.radius
+                    && yoff.abs() <= self.radius
+                    && (xoff + yoff).abs() <= self.radius
+            }
+        }
+    }
+
+    // Number of neighbor cells, i.e. the largest possible neighbor count.
+    pub fn size(&self) -> u32 {
+        let r = self.radius as u32;
+        match self.shape {
+            Shape::Moore => (2 * r + 1) * (2 * r + 1) - 1,
+            Shape::VonNeumann => 2 * r * (r + 1),
+            Shape::Hex => 3 * r * (r + 1),
+        }
+    }
+
+    pub fn row_offsets(&self) -> RangeInclusive<i32> {
+        -self.radius..=self.radius
+    }
+
+    // How far row yoff reaches to either side of the cell's column at most.
+    pub fn row_reach(&self, yoff: i32) -> i32 {
+        match self.shape {
+            Shape::Moore | Shape::Hex => self.radius,
+            Shape::VonNeumann => self.radius - yoff.abs(),
+        }
+    }
+
+    // Every row of every shape is one contiguous span of column offsets
+    // (including (0, 0) on row 0); centred for the square shapes, leaning
+    // left below the cell and right above it for Hex.
+    pub fn row_span(&self, yoff: i32) -> (i32, i32) {
+        match self.shape {
+            Shape::Moore | Shape::VonNeumann => {
+                let reach = self.row_reach(yoff);
+                (-reach, reach)
+            }
+            Shape::Hex => (
+                (-self.radius).max(-self.radius - yoff),
+                self.radius.min(self.radius - yoff),
+            ),
+        }
+    }
+
+    pub fn offsets(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        let r = self.radius;
+        (-r..=r)
+            .flat_map(move |yoff| (-r..=r).map(move |xoff| (xoff, yoff)))
+            .filter(|(xoff, yoff)| self.contains(*xoff, *yoff))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moore() {
+        let n = Neighborhood::moore();
+        assert_eq!(n.size(), 8);
+        assert_eq!(n.offsets().count(), 8);
+        assert!(n.contains(1, 1));
+        assert!(!n.contains(0, 0));
+        assert!(!n.contains(2, 0));
+
+        let n2 = Neighborhood::new(Shape::Moore, 2).unwrap();
+        assert_eq!(n2.size(), 24);
+        assert_eq!(n2.offsets().count(), 24);
+        assert!(n2.contains(-2, 2));
+    }
+
+    #[test]
+    fn test_von_neumann() {
+        let n = Neighborhood::new(Shape::VonNeumann, 1).unwrap();
+        assert_eq!(n.size(), 4);
+        assert_eq!(
+            n.offsets().collect::<Vec<_>>(),
+            vec![(0, -1), (-1, 0), (1, 0), (0, 1)]
+        );
+        assert!(!n.contains(1, 1));
+
+        let n2 = Neighborhood::new(Shape::VonNeumann, 2).unwrap();
+        assert_eq!(n2.size(), 12);
+        assert_eq!(n2.offsets().count(), 12);
+        assert!(n2.contains(1, 1));
+        assert!(!n2.contains(2, 1));
+        let reaches: Vec<i32> = n2.row_offsets().map(|y| n2.row_reach(y)).collect();
+        assert_eq!(reaches, vec![0, 1, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_hex() {
+        let n = Neighborhood::new(Shape::Hex, 1).unwrap();
+        assert_eq!(n.size(), 6);
+        assert_eq!(
+            n.offsets().collect::<Vec<_>>(),
+            vec![(0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)]
+        );
+        assert!(!n.contains(1, 1) && !n.contains(-1, -1));
+
+        let n2 = Neighborhood::new(Shape::Hex, 2).unwrap();
+        assert_eq!(n2.size(), 18);
+        assert_eq!(n2.offsets().count(), 18);
+        let spans: Vec<(i32, i32)> = n2.row_offsets().map(|y| n2.row_span(y)).collect();
+        assert_eq!(spans, vec![(0, 2), (-1, 2), (-2, 2), (-2, 1), (-2, 0)]);
+        assert!(Neighborhood::new(Shape::Hex, 8).is_ok());
+        assert_eq!(Neighborhood::new(Shape::Hex, 9), Err(InvalidNeighborhood));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(Neighborhood::new(Shape::Moore, 0), Err(InvalidNeighborhood));
+        assert!(Neighborhood::new(Shape::Moore, 7).is_ok());
+        assert_eq!(Neighborhood::new(Shape::Moore, 8), Err(InvalidNeighborhood));
+        assert!(Neighborhood::new(Shape::VonNeumann, 10).is_ok());
+    }
+}
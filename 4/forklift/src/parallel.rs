@@ -0,0 +1,142 @@
+// Rayon versions of the full-map scans, split across bands of rows. A scan
+// only reads the map, so in-place removal can't be parallelized without
+// changing its answer; the parallel passes always run simultaneously. Each
+// band runs the whole-word kernel where it applies.
+use super::{FloorMap, Rule};
+use rayon::prelude::*;
+
+// Rows per rayon task: enough work per band to amortize the split on wide maps.
+const BAND_ROWS: i32 = 16;
+
+impl FloorMap {
+    fn bands(&self) -> Vec<(i32, i32)> {
+        (0..self.height())
+            .step_by(BAND_ROWS as usize)
+            .map(|y0| (y0, (y0 + BAND_ROWS).min(self.height())))
+            .collect()
+    }
+
+    // Cells that would be freed against the current map, in row-major order.
+    fn scan_free_par(&self, rule: Rule) -> Vec<(i32, i32)> {
+        if self.kernel_applies() {
+            let occupied = self.occupied_words();
+            return self
+                .bands()
+                .into_par_iter()
+                .flat_map_iter(|(y0, y1)| {
+                    let mask = self.free_mask_rows(rule, &occupied, y0, y1);
+                    self.mask_cells_rows(&mask, y0)
+                })
+                .collect();
+        }
+        self.bands()
+            .into_par_iter()
+            .flat_map_iter(|(y0, y1)| {
+                (y0..y1).flat_map(move |y| {
+                    (0..self.width()).filter_map(move |x| {
+                        (self.map_val(x, y) && rule.removes(self.count_neighbors(x, y)))
+                            .then_some((x, y))
+                    })
+                })
+            })
+            .collect()
+    }
+
+    pub fn count_free_par(&self, rule: impl Into<Rule>) -> u32 {
+        let rule = rule.into();
+        if self.kernel_applies() {
+            let occupied = self.occupied_words();
+            return self
+                .bands()
+                .into_par_iter()
+                .map(|(y0, y1)| {
+                    self.free_mask_rows(rule, &occupied, y0, y1)
+                        .iter()
+                        .map(|w| w.count_ones())
+                        .sum::<u32>()
+                })
+                .sum();
+        }
+        self.bands()
+            .into_par_iter()
+            .map(|(y0, y1)| {
+                let mut sum = 0;
+                for y in y0..y1 {
+                    for x in 0..self.width() {
+                        if self.map_val(x, y) {
+                            sum += rule.removes(self.count_neighbors(x, y)) as u32;
+                        }
+                    }
+                }
+                sum
+            })
+            .sum()
+    }
+
+    // One simultaneous pass; the freed cells come back in row-major order
+    // rather than mark_free's column-major scan order.
+    pub fn mark_free_par(&mut self, rule: impl Into<Rule>) -> Vec<(i32, i32)> {
+        let freed = self.scan_free_par(rule.into());
+        self.clear_cells(&freed);
+        self.record_pass(&freed);
+        freed
+    }
+
+    pub fn exhaust_parallel_with<F>(&mut self, rule: impl Into<Rule>, mut on_pass: F) -> Vec<u32>
+    where
+        F: FnMut(&FloorMap, &[(i32, i32)]),
+    {
+        let rule = rule.into();
+        let mut passes = Vec::new();
+        loop {
+            let freed = self.mark_free_par(rule);
+            if freed.is_empty() {
+                break;
+            }
+            on_pass(self, &freed);
+            passes.push(freed.len() as u32);
+        }
+        passes
+    }
+
+    pub fn exhaust_parallel(&mut self, rule: impl Into<Rule>) -> Vec<u32> {
+        self.exhaust_parallel_with(rule, |_, _| {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Boundary, UpdateMode};
+    use super::*;
+
+    #[test]
+    fn test_parallel_matches_serial() {
+        let b = FloorMap::new_from_file("test.txt");
+        assert_eq!(b.count_free_par(4), 13);
+        let mut par = b.clone();
+        assert_eq!(par.exhaust_parallel(4), vec![13, 12, 7, 5, 2, 1, 1, 1, 1]);
+
+        // Tall enough to span several bands.
+        let map = FloorMap::random(37, 100, 0.7, 7);
+        for t in [2, 4, 6] {
+            assert_eq!(map.count_free_par(t), map.count_free(t));
+            let mut serial = map.clone().with_update_mode(UpdateMode::Simultaneous);
+            let mut par = map.clone();
+            let mut first = serial.clone();
+            let mut expected = first.mark_free(t);
+            expected.sort_by_key(|&(x, y)| (y, x));
+            assert_eq!(par.clone().mark_free_par(t), expected);
+            assert_eq!(
+                par.exhaust_parallel(t),
+                serial.count_and_mark_exhaust_passes(t)
+            );
+            assert_eq!(par.grid, serial.grid);
+        }
+
+        // The per-cell scan for boundaries the kernel can't handle.
+        let wrapped = map.with_boundary(Boundary::Wrap);
+        for t in [2, 4, 6] {
+            assert_eq!(wrapped.count_free_par(t), wrapped.count_free(t));
+        }
+    }
+}
@@ -0,0 +1,194 @@
+// Shortest routes across the empty floor, for asking how a forklift gets from
+// one spot to another once pallets have been cleared. Pallets and walls both
+// block; the map's boundary decides what lies past the edges.
+use super::viz::render_marked;
+use super::{Boundary, Cell, FloorMap};
+use aoc_utils::Grid;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+const STEP: &str = "\x1b[1;36mo\x1b[0m";
+
+// Which moves count as one step. Diagonal steps cost the same as straight ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connectivity {
+    Four,
+    Eight,
+}
+
+impl Connectivity {
+    pub(crate) fn offsets(self) -> &'static [(i32, i32)] {
+        match self {
+            Connectivity::Four => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Connectivity::Eight => &[
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ],
+        }
+    }
+}
+
+impl FloorMap {
+    // Lower bound on the steps between two cells. A custom boundary can jump
+    // anywhere, so it gets none and the search degrades to Dijkstra.
+    fn path_estimate(&self, (x0, y0): (i32, i32), (x1, y1): (i32, i32), c: Connectivity) -> u32 {
+        let (mut dx, mut dy) = ((x1 - x0).abs(), (y1 - y0).abs());
+        match self.boundary {
+            Boundary::Custom(_) => return 0,
+            Boundary::Wrap => {
+                dx = dx.min(self.width() - dx);
+                dy = dy.min(self.height() - dy);
+            }
+            _ => {}
+        }
+        match c {
+            Connectivity::Four => (dx + dy) as u32,
+            Connectivity::Eight => dx.max(dy) as u32,
+        }
+    }
+
+    // A* over the empty cells. Returns every cell of a shortest path, both ends
+    // included, or None when either end isn't empty floor or no route exists.
+    pub fn path(
+        &self,
+        from: (i32, i32),
+        to: (i32, i32),
+        c: Connectivity,
+    ) -> Option<Vec<(i32, i32)>> {
+        for (x, y) in [from, to] {
+            if !self.grid.in_bounds(x as isize, y as isize) {
+                panic!(
+                    "({}, {}) outside of {}x{} FloorMap!",
+                    x,
+                    y,
+                    self.width(),
+                    self.height()
+                );
+            }
+        }
+        if self.cell(from.0, from.1) != Cell::Empty || self.cell(to.0, to.1) != Cell::Empty {
+            return None;
+        }
+        let (w, h) = (self.width() as usize, self.height() as usize);
+        let mut steps = Grid::new(w, h, u32::MAX);
+        let mut came_from = Grid::new(w, h, None);
+        let mut open = BinaryHeap::new();
+        steps[(from.0 as usize, from.1 as usize)] = 0;
+        // Ties on the estimate go to the cell furthest along, which keeps the
+        // search from fanning out across open floor.
+        open.push(Reverse((self.path_estimate(from, to, c), Reverse(0), from)));
+        while let Some(Reverse((_, Reverse(g), (x, y)))) = open.pop() {
+            if (x, y) == to {
+                break;
+            }
+            if g > steps[(x as usize, y as usize)] {
+                continue;
+            }
+            for (xoff, yoff) in c.offsets() {
+                let (nx, ny) = match self.resolve(x + xoff, y + yoff) {
+                    Some((nx, ny)) if self.cell(nx, ny) == Cell::Empty => (nx, ny),
+                    _ => continue,
+                };
+                let s = &mut steps[(nx as usize, ny as usize)];
+                if g + 1 < *s {
+                    *s = g + 1;
+                    came_from[(nx as usize, ny as usize)] = Some((x, y));
+                    let f = g + 1 + self.path_estimate((nx, ny), to, c);
+                    open.push(Reverse((f, Reverse(g + 1), (nx, ny))));
+                }
+            }
+        }
+        if steps[(to.0 as usize, to.1 as usize)] == u32::MAX {
+            return None;
+        }
+        let mut cells = vec![to];
+        let mut at = to;
+        while let Some(prev) = came_from[(at.0 as usize, at.1 as usize)] {
+            cells.push(prev);
+            at = prev;
+        }
+        cells.reverse();
+        Some(cells)
+    }
+}
+
+// ANSI rendering of the map with a path's cells marked.
+pub fn render(map: &FloorMap, path: &[(i32, i32)]) -> String {
+    let path = path.iter().map(|&at| (at, ()));
+    render_marked(map, path, |_, step| step.map(|()| STEP))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path() {
+        let map = b"..@..
+.@@@.
+.....";
+        let m = FloorMap::new_from_lines(map.split(|&v| v == b'\n'));
+        let four = m.path((0, 0), (4, 0), Connectivity::Four).unwrap();
+        // Down and around the pallets: 4 across plus 2 down and 2 back up.
+        assert_eq!(four.len() - 1, 8);
+        assert_eq!((four[0], four[8]), ((0, 0), (4, 0)));
+        // Every step moves to an adjacent empty cell.
+        for pair in four.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            assert_eq!((x1 - x0).abs() + (y1 - y0).abs(), 1);
+            assert_eq!(m.cell(x1, y1), Cell::Empty);
+        }
+        let eight = m.path((0, 0), (4, 0), Connectivity::Eight).unwrap();
+        assert_eq!(eight.len() - 1, 6);
+        assert_eq!(m.path((1, 1), (4, 0), Connectivity::Four), None);
+        assert_eq!(
+            m.path((0, 0), (0, 0), Connectivity::Four),
+            Some(vec![(0, 0)])
+        );
+
+        // A wall across the map cuts it in two, unless the map wraps.
+        let walled = FloorMap::new_from_lines([b".#.".as_slice(), b".#."]);
+        assert_eq!(walled.path((0, 0), (2, 1), Connectivity::Eight), None);
+        let wrapped = walled.with_boundary(Boundary::Wrap);
+        assert_eq!(
+            wrapped.path((0, 0), (2, 1), Connectivity::Eight),
+            Some(vec![(0, 0), (2, 1)])
+        );
+
+        assert_eq!(
+            render(&m, &four[..2]),
+            format!("{STEP}.@..\n{STEP}@@@.\n.....\n")
+        );
+    }
+
+    #[test]
+    fn test_path_matches_flood_fill() {
+        // Every empty cell the 8-connected flood reaches has a path, and no
+        // other cell does.
+        let mut m = FloorMap::random(30, 20, 0.45, 9);
+        m.count_and_mark_exhaust(4);
+        let start = (0..m.width())
+            .flat_map(|x| (0..m.height()).map(move |y| (x, y)))
+            .find(|&(x, y)| m.cell(x, y) == Cell::Empty)
+            .unwrap();
+        let reached = m.flood_fill(start.0, start.1);
+        for y in 0..m.height() {
+            for x in 0..m.width() {
+                let path = m.path(start, (x, y), Connectivity::Eight);
+                assert_eq!(path.is_some(), reached.contains(&(x, y)), "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_path_off_map() {
+        FloorMap::new_from_lines([b"..".as_slice()]).path((0, 0), (2, 0), Connectivity::Four);
+    }
+}
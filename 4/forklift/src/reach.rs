@@ -0,0 +1,159 @@
+// Removal restricted to pallets a forklift can actually get at: it drives in
+// from outside the map across empty floor, so a pallet sealed inside a ring of
+// pallets or walls stays put until the ring is opened. The map's boundary
+// still decides neighbor counts, but the forklift always starts off-map.
+use super::path::Connectivity;
+use super::{Cell, FloorMap, Rule};
+use aoc_utils::{BitGrid, Grid};
+
+impl FloorMap {
+    // Pallets a forklift moving by `c` can reach: those on the edge of the map
+    // and those next to empty floor connected to the edge.
+    pub fn reachable_pallets(&self, c: Connectivity) -> BitGrid {
+        let (w, h) = (self.width() as usize, self.height() as usize);
+        let mut reachable = BitGrid::new(w, h);
+        let mut seen = Grid::new(w, h, false);
+        let mut stack = Vec::new();
+        if w == 0 || h == 0 {
+            return reachable;
+        }
+        let edge = (0..self.width())
+            .flat_map(|x| [(x, 0), (x, self.height() - 1)])
+            .chain((0..self.height()).flat_map(|y| [(0, y), (self.width() - 1, y)]));
+        for (x, y) in edge {
+            match self.cell(x, y) {
+                Cell::Pallet => reachable.set(x as usize, y as usize, true),
+                Cell::Empty if !seen[(x as usize, y as usize)] => {
+                    seen[(x as usize, y as usize)] = true;
+                    stack.push((x, y));
+                }
+                _ => {}
+            }
+        }
+        while let Some((x, y)) = stack.pop() {
+            for (xoff, yoff) in c.offsets() {
+                let (nx, ny) = (x + xoff, y + yoff);
+                if !self.grid.in_bounds(nx as isize, ny as isize) {
+                    continue;
+                }
+                match self.cell(nx, ny) {
+                    Cell::Pallet => reachable.set(nx as usize, ny as usize, true),
+                    Cell::Empty if !seen[(nx as usize, ny as usize)] => {
+                        seen[(nx as usize, ny as usize)] = true;
+                        stack.push((nx, ny));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        reachable
+    }
+
+    // count_free, only counting pallets reachable from the edge.
+    pub fn count_free_from_edge(&self, rule: impl Into<Rule>, c: Connectivity) -> u32 {
+        let rule = rule.into();
+        let reachable = self.reachable_pallets(c);
+        self.occupied()
+            .filter(|&(x, y)| {
+                reachable.get(x as isize, y as isize) && rule.removes(self.count_neighbors(x, y))
+            })
+            .count() as u32
+    }
+
+    // One pass of mark_free over the pallets reachable when it starts. Cells an
+    // in-place pass frees don't open up new pallets until the next pass.
+    pub fn mark_free_from_edge(
+        &mut self,
+        rule: impl Into<Rule>,
+        c: Connectivity,
+    ) -> Vec<(i32, i32)> {
+        let reachable = self.reachable_pallets(c);
+        self.mark_free_within(rule.into(), Some(&reachable))
+    }
+
+    // exhaust_with, redoing the reachability flood before every pass.
+    pub fn exhaust_from_edge_with<F>(
+        &mut self,
+        rule: impl Into<Rule>,
+        c: Connectivity,
+        mut on_pass: F,
+    ) -> Vec<u32>
+    where
+        F: FnMut(&FloorMap, &[(i32, i32)]),
+    {
+        let rule = rule.into();
+        let mut passes = Vec::new();
+        loop {
+            let freed = self.mark_free_from_edge(rule, c);
+            if freed.is_empty() {
+                break;
+            }
+            on_pass(self, &freed);
+            passes.push(freed.len() as u32);
+        }
+        passes
+    }
+
+    pub fn exhaust_from_edge(&mut self, rule: impl Into<Rule>, c: Connectivity) -> Vec<u32> {
+        self.exhaust_from_edge_with(rule, c, |_, _| {})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::UpdateMode;
+    use super::*;
+
+    #[test]
+    fn test_reachable_pallets() {
+        let map = b"@@@@@
+@...@
+@.@.@
+@...@
+@@@@@";
+        let m = FloorMap::new_from_lines(map.split(|&v| v == b'\n'));
+        let r = m.reachable_pallets(Connectivity::Four);
+        // The ring is on the edge; the middle pallet is sealed inside it.
+        assert_eq!(r.count_ones(), 16);
+        assert!(!r.get(2, 2));
+        // Under the plain rule the middle pallet goes first.
+        assert!(m.clone().mark_free(4).contains(&(2, 2)));
+        assert_eq!(m.count_free_from_edge(4, Connectivity::Four), 16);
+
+        // A gap in the ring lets the forklift in.
+        let open = b"@@.@@
+@...@
+@.@.@
+@...@
+@@@@@";
+        let m = FloorMap::new_from_lines(open.split(|&v| v == b'\n'));
+        assert!(m.reachable_pallets(Connectivity::Four).get(2, 2));
+
+        // Diagonal gaps only count when the forklift can move diagonally.
+        let walled = FloorMap::new_from_lines([b".#@".as_slice(), b"#@#", b"@#."]);
+        assert!(!walled.reachable_pallets(Connectivity::Four).get(1, 1));
+        assert!(walled.reachable_pallets(Connectivity::Eight).get(1, 1));
+    }
+
+    #[test]
+    fn test_exhaust_from_edge() {
+        // Peeling from the edge is a restricted order of the same removals,
+        // so it never frees more than the unconstrained exhaust.
+        let start = FloorMap::new_from_file("test.txt");
+        for update in [UpdateMode::InPlace, UpdateMode::Simultaneous] {
+            let mut free = start.clone().with_update_mode(update);
+            let mut edge = free.clone();
+            let total = free.count_and_mark_exhaust(4);
+            let mut freed = Vec::new();
+            let passes = edge.exhaust_from_edge_with(4, Connectivity::Eight, |m, f| {
+                freed.extend_from_slice(f);
+                assert!(f.iter().all(|&(x, y)| !m.map_val(x, y)));
+            });
+            assert!(passes.iter().sum::<u32>() <= total);
+            assert_eq!(freed.len() as u32, passes.iter().sum::<u32>());
+        }
+        let mut ring = FloorMap::new_from_lines([b"@@@".as_slice(), b"@@@", b"@@@"]);
+        // The corners go first, then the edges, then the middle.
+        assert_eq!(ring.exhaust_from_edge(4, Connectivity::Four), vec![4, 4, 1]);
+    }
+}
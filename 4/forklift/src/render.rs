@@ -0,0 +1,294 @@
+// Image output for the exhaust process: one frame per pass, either as an
+// animated GIF or as a numbered PNG sequence. For maps too big for either,
+// single snapshots as a netpbm bitmap or graymap, a cell per pixel.
+use super::{Cell, FloorMap};
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const EMPTY: u8 = 0;
+const PALLET: u8 = 1;
+const FREED: u8 = 2;
+const WALL: u8 = 3;
+
+// RGB for each pixel index above.
+const PALETTE: [u8; 12] = [
+    0xf4, 0xf1, 0xe8, 0x3b, 0x2f, 0x1e, 0xd6, 0x2d, 0x20, 0x7a, 0x7f, 0x87,
+];
+
+enum Output {
+    Gif(gif::Encoder<BufWriter<File>>),
+    PngSequence { prefix: PathBuf, next: u32 },
+}
+
+pub struct Renderer {
+    scale: u32,
+    width: u32,
+    height: u32,
+    delay_cs: u16,
+    output: Output,
+}
+
+// Indexed pixels for the map at `scale` pixels per cell, marking cells freed
+// by the latest pass.
+pub fn frame_pixels(map: &FloorMap, freed: &[(i32, i32)], scale: u32) -> Vec<u8> {
+    let w = map.width() as usize * scale as usize;
+    let h = map.height() as usize * scale as usize;
+    let mut pixels = vec![EMPTY; w * h];
+    let mut paint = |x: i32, y: i32, val: u8| {
+        for py in 0..scale as usize {
+            let row = (y as usize * scale as usize + py) * w;
+            let start = row + x as usize * scale as usize;
+            pixels[start..start + scale as usize].fill(val);
+        }
+    };
+    for y in 0..map.height() {
+        for x in 0..map.width() {
+            match map.cell(x, y) {
+                Cell::Pallet => paint(x, y, PALLET),
+                Cell::Wall => paint(x, y, WALL),
+                Cell::Empty => {}
+            }
+        }
+    }
+    for (x, y) in freed {
+        paint(*x, *y, FREED);
+    }
+    pixels
+}
+
+impl Renderer {
+    // Paths ending in .gif get an animation; anything else is used as the
+    // prefix for `<prefix>-0000.png`, `<prefix>-0001.png`, ...
+    pub fn create<P>(
+        path: P,
+        map: &FloorMap,
+        scale: u32,
+        delay_ms: u64,
+    ) -> Result<Renderer, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        if scale == 0 {
+            return Err(From::from("Render scale must be > 0"));
+        }
+        let width = map.width() as u32 * scale;
+        let height = map.height() as u32 * scale;
+        let is_gif = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("gif"));
+        let output = if is_gif {
+            if width > u16::MAX as u32 || height > u16::MAX as u32 {
+                return Err(From::from(format!(
+                    "{}x{} is too large for a GIF frame",
+                    width, height
+                )));
+            }
+            let file = BufWriter::new(File::create(path)?);
+            let mut enc = gif::Encoder::new(file, width as u16, height as u16, &PALETTE)?;
+            enc.set_repeat(gif::Repeat::Infinite)?;
+            Output::Gif(enc)
+        } else {
+            Output::PngSequence {
+                prefix: path.with_extension(""),
+                next: 0,
+            }
+        };
+        Ok(Renderer {
+            scale,
+            width,
+            height,
+            delay_cs: (delay_ms / 10).min(u16::MAX as u64) as u16,
+            output,
+        })
+    }
+
+    pub fn capture(&mut self, map: &FloorMap, freed: &[(i32, i32)]) -> Result<(), Box<dyn Error>> {
+        let pixels = frame_pixels(map, freed, self.scale);
+        match &mut self.output {
+            Output::Gif(enc) => {
+                let mut frame = gif::Frame::from_indexed_pixels(
+                    self.width as u16,
+                    self.height as u16,
+                    pixels,
+                    None,
+                );
+                frame.delay = self.delay_cs;
+                enc.write_frame(&frame)?;
+            }
+            Output::PngSequence { prefix, next } => {
+                let mut name = prefix.as_os_str().to_owned();
+                name.push(format!("-{:04}.png", next));
+                let file = BufWriter::new(File::create(PathBuf::from(name))?);
+                let mut enc = png::Encoder::new(file, self.width, self.height);
+                enc.set_color(png::ColorType::Indexed);
+                enc.set_depth(png::BitDepth::Eight);
+                enc.set_palette(&PALETTE[..]);
+                enc.write_header()?.write_image_data(&pixels)?;
+                *next += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Binary PBM (P4): pallets and walls black, a row at a time so the whole
+// image never has to be built.
+pub fn write_pbm<W: Write>(map: &FloorMap, out: &mut W) -> io::Result<()> {
+    write!(out, "P4\n{} {}\n", map.width(), map.height())?;
+    let mut row = vec![0u8; (map.width() as usize).div_ceil(8)];
+    for y in 0..map.height() {
+        row.fill(0);
+        for x in 0..map.width() {
+            if map.cell(x, y) != Cell::Empty {
+                row[x as usize / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        out.write_all(&row)?;
+    }
+    Ok(())
+}
+
+// Gray for each pixel index above, for PGM.
+const GRAYS: [u8; 4] = [255, 0, 170, 85];
+
+// Binary PGM (P5), which unlike PBM tells walls apart and marks the cells
+// freed by the latest pass.
+pub fn write_pgm<W: Write>(map: &FloorMap, freed: &[(i32, i32)], out: &mut W) -> io::Result<()> {
+    write!(out, "P5\n{} {}\n255\n", map.width(), map.height())?;
+    let mut freed = freed.to_vec();
+    freed.sort_unstable_by_key(|&(x, y)| (y, x));
+    let mut freed = freed.into_iter().peekable();
+    let mut row = vec![0u8; map.width() as usize];
+    for y in 0..map.height() {
+        for x in 0..map.width() {
+            let val = match map.cell(x, y) {
+                Cell::Pallet => PALLET,
+                Cell::Wall => WALL,
+                Cell::Empty => EMPTY,
+            };
+            row[x as usize] = GRAYS[val as usize];
+        }
+        while let Some((x, _)) = freed.next_if(|&(_, fy)| fy == y) {
+            row[x as usize] = GRAYS[FREED as usize];
+        }
+        out.write_all(&row)?;
+    }
+    Ok(())
+}
+
+// PGM for paths ending in .pgm, PBM for anything else.
+pub fn export<P>(path: P, map: &FloorMap, freed: &[(i32, i32)]) -> Result<(), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let mut out = BufWriter::new(File::create(path)?);
+    if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("pgm"))
+    {
+        write_pgm(map, freed, &mut out)?;
+    } else {
+        write_pbm(map, &mut out)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+// `<stem>-0003.pbm` for pass 3 of an export to `<stem>.pbm`, like the PNG
+// sequence's frame names.
+pub fn numbered<P: AsRef<Path>>(path: P, pass: u32) -> PathBuf {
+    let path = path.as_ref();
+    let mut name = path.with_extension("").into_os_string();
+    name.push(format!("-{:04}", pass));
+    let name = PathBuf::from(name);
+    match path.extension() {
+        Some(ext) => name.with_extension(ext),
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_frame_pixels() {
+        let map = FloorMap::new_from_lines(b"@.\n.@".split(|&v| v == b'\n'));
+        assert_eq!(frame_pixels(&map, &[], 1), vec![1, 0, 0, 1]);
+        let walled = FloorMap::new_from_lines([b"#@".as_slice()]);
+        assert_eq!(frame_pixels(&walled, &[], 1), vec![3, 1]);
+        #[rustfmt::skip]
+        assert_eq!(
+            frame_pixels(&map, &[(1, 0)], 2),
+            vec![
+                1, 1, 2, 2,
+                1, 1, 2, 2,
+                0, 0, 1, 1,
+                0, 0, 1, 1,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_gif() {
+        let path = env::temp_dir().join("forklift_render_test.gif");
+        let mut map = FloorMap::new_from_file("test.txt");
+        let mut r = Renderer::create(&path, &map, 3, 100).unwrap();
+        r.capture(&map, &[]).unwrap();
+        map.exhaust_with(4, |m, freed| r.capture(m, freed).unwrap());
+        drop(r);
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(&bytes[..6], b"GIF89a");
+        assert_eq!(u16::from_le_bytes([bytes[6], bytes[7]]), 30);
+    }
+
+    #[test]
+    fn test_render_png_sequence() {
+        let dir = env::temp_dir().join("forklift_render_pngs");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let map = FloorMap::new_from_file("test.txt");
+        let mut r = Renderer::create(dir.join("frame"), &map, 1, 100).unwrap();
+        r.capture(&map, &[]).unwrap();
+        r.capture(&map, &[]).unwrap();
+        assert!(dir.join("frame-0000.png").exists());
+        assert!(dir.join("frame-0001.png").exists());
+        let bytes = fs::read(dir.join("frame-0001.png")).unwrap();
+        assert_eq!(&bytes[1..4], b"PNG");
+    }
+
+    #[test]
+    fn test_netpbm() {
+        let map = FloorMap::new_from_lines([b"@.#.@.@.@".as_slice(), b".........", b"@@@@@@@@@"]);
+        let mut pbm = Vec::new();
+        write_pbm(&map, &mut pbm).unwrap();
+        // Nine pixels pad each row out to two bytes.
+        let header = b"P4\n9 3\n";
+        assert_eq!(&pbm[..header.len()], header);
+        assert_eq!(&pbm[header.len()..], &[0xaa, 0x80, 0, 0, 0xff, 0x80]);
+
+        let mut pgm = Vec::new();
+        write_pgm(&map, &[(1, 2), (1, 0)], &mut pgm).unwrap();
+        let header = b"P5\n9 3\n255\n";
+        assert_eq!(&pgm[..header.len()], header);
+        let pixels = &pgm[header.len()..];
+        assert_eq!(pixels.len(), 27);
+        assert_eq!(&pixels[..4], &[0, 170, 85, 255]);
+        assert_eq!(&pixels[18..21], &[0, 170, 0]);
+
+        assert_eq!(
+            numbered("out/map.pbm", 3),
+            PathBuf::from("out/map-0003.pbm")
+        );
+        assert_eq!(numbered("map", 12), PathBuf::from("map-0012"));
+        let path = env::temp_dir().join("forklift_export_test.pgm");
+        export(&path, &map, &[]).unwrap();
+        assert_eq!(&fs::read(&path).unwrap()[..2], b"P5");
+    }
+}
@@ -0,0 +1,240 @@
+// A log of every removal an exhaust makes, one JSON object per line, and
+// replaying one onto the starting map. Since Under's end state doesn't depend
+// on the order pallets go in, replaying a log written by one algorithm (in
+// place, simultaneous, incremental, ...) and comparing with another's result
+// cross-checks them.
+use super::{Cell, FloorMap, OffMap};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Removal {
+    // 1 is the first pass, as in the --stats rows.
+    pub pass: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Removal {
+    pub fn line(&self) -> String {
+        format!(
+            "{{\"pass\":{},\"x\":{},\"y\":{}}}",
+            self.pass, self.x, self.y
+        )
+    }
+
+    // Reads back what line writes, with the keys in any order and any spacing.
+    pub fn from_line(line: &str) -> Option<Removal> {
+        let body = line.trim().strip_prefix('{')?.strip_suffix('}')?;
+        let (mut pass, mut x, mut y) = (None, None, None);
+        for field in body.split(',') {
+            let (key, val) = field.split_once(':')?;
+            let val = val.trim();
+            match key.trim() {
+                "\"pass\"" => pass = Some(val.parse().ok()?),
+                "\"x\"" => x = Some(val.parse().ok()?),
+                "\"y\"" => y = Some(val.parse().ok()?),
+                _ => return None,
+            }
+        }
+        Some(Removal {
+            pass: pass?,
+            x: x?,
+            y: y?,
+        })
+    }
+}
+
+// The cells one pass freed, as log lines.
+pub fn write_pass<W: Write>(out: &mut W, pass: u32, freed: &[(i32, i32)]) -> io::Result<()> {
+    for &(x, y) in freed {
+        writeln!(out, "{}", Removal { pass, x, y }.line())?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LogParseError {
+    // 1-based.
+    pub line: usize,
+}
+
+impl fmt::Display for LogParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Line {} is not a removal", self.line)
+    }
+}
+
+impl Error for LogParseError {}
+
+// Blank lines are skipped.
+pub fn read_log<P>(filename: P) -> Result<Vec<Removal>, Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    let mut log = Vec::new();
+    for (lineno, line) in super::read_lines(filename)?.enumerate() {
+        let line = String::from_utf8_lossy(&line?).into_owned();
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Removal::from_line(&line) {
+            Some(r) => log.push(r),
+            None => return Err(From::from(LogParseError { line: lineno + 1 })),
+        }
+    }
+    Ok(log)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReplayError {
+    // A pass that comes after a later one.
+    OutOfOrder { pass: u32, after: u32 },
+    // A removal of a cell that holds no pallet at that point.
+    NotAPallet { pass: u32, x: i32, y: i32 },
+    // A cell logged twice in the same pass.
+    Duplicate { pass: u32, x: i32, y: i32 },
+    OffMap { pass: u32, cell: OffMap },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::OutOfOrder { pass, after } => {
+                write!(f, "Pass {} is logged after pass {}", pass, after)
+            }
+            ReplayError::NotAPallet { pass, x, y } => {
+                write!(
+                    f,
+                    "Pass {} frees ({}, {}), which holds no pallet",
+                    pass, x, y
+                )
+            }
+            ReplayError::Duplicate { pass, x, y } => {
+                write!(f, "Pass {} frees ({}, {}) more than once", pass, x, y)
+            }
+            ReplayError::OffMap { pass, cell } => write!(f, "Pass {}: {}", pass, cell),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl FloorMap {
+    // Apply a log pass by pass, returning how many each freed. A pass is
+    // checked whole before it's applied, so on an error the map holds the
+    // passes before the bad one.
+    pub fn replay(&mut self, log: &[Removal]) -> Result<Vec<u32>, ReplayError> {
+        let mut passes = Vec::new();
+        let mut last = None;
+        for chunk in log.chunk_by(|a, b| a.pass == b.pass) {
+            let pass = chunk[0].pass;
+            if let Some(after) = last
+                && pass < after
+            {
+                return Err(ReplayError::OutOfOrder { pass, after });
+            }
+            last = Some(pass);
+            let cells: Vec<(i32, i32)> = chunk.iter().map(|r| (r.x, r.y)).collect();
+            let mut seen = HashSet::new();
+            if let Some(&(x, y)) = cells.iter().find(|&&at| !seen.insert(at)) {
+                return Err(ReplayError::Duplicate { pass, x, y });
+            }
+            // Off-map cells are left for free_all to report.
+            if let Some(&(x, y)) = cells.iter().find(|&&(x, y)| {
+                self.grid.in_bounds(x as isize, y as isize) && self.cell(x, y) != Cell::Pallet
+            }) {
+                return Err(ReplayError::NotAPallet { pass, x, y });
+            }
+            self.free_all(&cells)
+                .map_err(|cell| ReplayError::OffMap { pass, cell })?;
+            self.record_pass(&cells);
+            passes.push(cells.len() as u32);
+        }
+        Ok(passes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::UpdateMode;
+    use super::*;
+
+    #[test]
+    fn test_removal_lines() {
+        let r = Removal {
+            pass: 2,
+            x: -1,
+            y: 7,
+        };
+        assert_eq!(r.line(), "{\"pass\":2,\"x\":-1,\"y\":7}");
+        assert_eq!(Removal::from_line(&r.line()), Some(r));
+        assert_eq!(
+            Removal::from_line(" { \"y\": 7, \"x\": -1, \"pass\": 2 } "),
+            Some(r)
+        );
+        assert_eq!(Removal::from_line("{\"pass\":2,\"x\":1}"), None);
+        assert_eq!(Removal::from_line("{\"pass\":2,\"x\":1,\"z\":3}"), None);
+        assert_eq!(Removal::from_line("pass 2"), None);
+    }
+
+    #[test]
+    fn test_replay() {
+        let start = FloorMap::new_from_file("test.txt");
+        let mut logged = Vec::new();
+        let mut pass = 0;
+        let mut run = start.clone();
+        let passes = run.exhaust_with(4, |_, freed| {
+            pass += 1;
+            write_pass(&mut logged, pass, freed).unwrap();
+        });
+        let log: Vec<Removal> = String::from_utf8(logged)
+            .unwrap()
+            .lines()
+            .map(|l| Removal::from_line(l).unwrap())
+            .collect();
+        let mut replayed = start.clone();
+        assert_eq!(replayed.replay(&log), Ok(passes));
+        assert_eq!(replayed.grid, run.grid);
+
+        // Every variant reaches the same end state.
+        let mut simultaneous = start.clone().with_update_mode(UpdateMode::Simultaneous);
+        simultaneous.exhaust_incremental(4);
+        assert_eq!(simultaneous.grid, replayed.grid);
+
+        let r = |pass, x, y| Removal { pass, x, y };
+        let mut m = start.clone();
+        assert_eq!(
+            m.replay(&[r(1, 2, 0), r(1, 0, 0)]),
+            Err(ReplayError::NotAPallet {
+                pass: 1,
+                x: 0,
+                y: 0
+            })
+        );
+        assert_eq!(m.grid, start.grid);
+        // Counting a cell twice would inflate the pass's total.
+        assert_eq!(
+            m.replay(&[r(1, 2, 0), r(1, 3, 0), r(1, 2, 0)]),
+            Err(ReplayError::Duplicate {
+                pass: 1,
+                x: 2,
+                y: 0
+            })
+        );
+        assert_eq!(m.grid, start.grid);
+        assert_eq!(
+            m.replay(&[r(2, 2, 0), r(1, 3, 0)]),
+            Err(ReplayError::OutOfOrder { pass: 1, after: 2 })
+        );
+        let mut m = start.clone();
+        assert!(matches!(
+            m.replay(&[r(1, 2, 0), r(2, 10, 0)]),
+            Err(ReplayError::OffMap { pass: 2, .. })
+        ));
+        assert!(!m.map_val(2, 0));
+    }
+}
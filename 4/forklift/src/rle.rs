@@ -0,0 +1,290 @@
+// Run-length encoded maps, after the Life RLE format: an `x = W, y = H`
+// header, then runs like `3o2b$` where `o`/`@` are pallets, `b`/`.` empty
+// floor, `$` ends a row and `!` ends the map. Rows may stop short (the rest is
+// empty), counts may prefix `$` to skip blank rows, and `#` lines are comments.
+use super::sparse::SparseFloorMap;
+use super::{FloorMap, MapParseError, parse_file};
+use aoc_utils::BitGrid;
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+
+// Life RLE writers keep lines to this many characters.
+const LINE_LIMIT: usize = 70;
+
+fn parse_header(line: &[u8], lineno: usize) -> Result<(usize, usize), MapParseError> {
+    let bad = MapParseError::BadRleHeader { line: lineno };
+    let text =
+        std::str::from_utf8(line).map_err(|_| MapParseError::BadRleHeader { line: lineno })?;
+    let (mut width, mut height) = (None, None);
+    for field in text.split(',') {
+        let (key, val) = match field.split_once('=') {
+            Some(kv) => kv,
+            None => return Err(bad),
+        };
+        let val = val.trim();
+        match key.trim() {
+            "x" => width = val.parse::<usize>().ok(),
+            "y" => height = val.parse::<usize>().ok(),
+            // e.g. Life's `rule = B3/S23`; nothing here uses it.
+            _ => {}
+        }
+    }
+    match (width, height) {
+        (Some(w), Some(h)) => Ok((w, h)),
+        _ => Err(bad),
+    }
+}
+
+// Parse into whatever storage `new` makes from the header's width and height;
+// `fill` gets each run of pallets as (map, y, x0, x1 inclusive).
+pub(crate) fn parse_rle<I, S, T, G, N, F>(
+    line_iter: T,
+    new: N,
+    mut fill: F,
+) -> Result<G, MapParseError>
+where
+    I: Iterator<Item = S>,
+    S: AsRef<[u8]>,
+    T: IntoIterator<IntoIter = I, Item = S>,
+    N: Fn(usize, usize) -> G,
+    F: FnMut(&mut G, usize, usize, usize),
+{
+    let mut map = None;
+    let (mut width, mut height) = (0, 0);
+    let (mut x, mut y) = (0, 0);
+    'lines: for (lineno, line) in line_iter.into_iter().enumerate() {
+        let (line, lineno) = (line.as_ref(), lineno + 1);
+        if line.first() == Some(&b'#') || line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let map = match &mut map {
+            Some(map) => map,
+            None => {
+                (width, height) = parse_header(line, lineno)?;
+                map = Some(new(width, height));
+                continue;
+            }
+        };
+        let mut count: Option<usize> = None;
+        for (col, c) in line.iter().enumerate() {
+            let overflow = MapParseError::RleOverflow {
+                line: lineno,
+                column: col + 1,
+            };
+            match *c {
+                b'0'..=b'9' => {
+                    count = count
+                        .unwrap_or(0)
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add((c - b'0') as usize));
+                    if count.is_none() {
+                        return Err(overflow);
+                    }
+                }
+                b'o' | b'@' | b'b' | b'.' => {
+                    let n = count.take().unwrap_or(1);
+                    if n == 0 {
+                        continue;
+                    }
+                    // x never passes the width, so this can't overflow.
+                    if y >= height || n > width - x {
+                        return Err(overflow);
+                    }
+                    if matches!(*c, b'o' | b'@') {
+                        fill(map, y, x, x + n - 1);
+                    }
+                    x += n;
+                }
+                b'$' => {
+                    y = y.saturating_add(count.take().unwrap_or(1));
+                    x = 0;
+                }
+                b'!' => break 'lines,
+                b' ' | b'\t' | b'\r' => {}
+                _ => {
+                    return Err(MapParseError::UnexpectedChar {
+                        line: lineno,
+                        column: col + 1,
+                        found: *c,
+                    });
+                }
+            }
+        }
+    }
+    // Tolerate a missing `!`, but not a missing header.
+    map.ok_or(MapParseError::Empty)
+}
+
+// Write a width x height map whose pallets come in row-major order. Walls have
+// no RLE glyph, so they're written as empty floor.
+fn write_rle<W, P>(out: &mut W, width: i32, height: i32, pallets: P) -> io::Result<()>
+where
+    W: Write,
+    P: Iterator<Item = (i32, i32)>,
+{
+    // Merge horizontally adjacent pallets into (row, first, last) runs.
+    let mut runs: Vec<(i32, i32, i32)> = Vec::new();
+    for (x, y) in pallets {
+        match runs.last_mut() {
+            Some((ry, _, last)) if *ry == y && *last + 1 == x => *last = x,
+            _ => runs.push((y, x, x)),
+        }
+    }
+    let item = |n: i32, tag: char| {
+        if n == 1 {
+            tag.to_string()
+        } else {
+            format!("{}{}", n, tag)
+        }
+    };
+    let mut items = Vec::new();
+    let (mut y, mut x) = (0, 0);
+    for (ry, first, last) in runs {
+        if ry > y {
+            items.push(item(ry - y, '$'));
+            (y, x) = (ry, 0);
+        }
+        if first > x {
+            items.push(item(first - x, 'b'));
+        }
+        items.push(item(last - first + 1, 'o'));
+        x = last + 1;
+    }
+    items.push("!".to_string());
+
+    writeln!(out, "x = {}, y = {}", width, height)?;
+    let mut line = String::new();
+    for it in items {
+        if !line.is_empty() && line.len() + it.len() > LINE_LIMIT {
+            writeln!(out, "{}", line)?;
+            line.clear();
+        }
+        line.push_str(&it);
+    }
+    writeln!(out, "{}", line)
+}
+
+impl FloorMap {
+    pub fn try_from_rle_file<P>(filename: P) -> Result<FloorMap, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        parse_file(filename, |lines| Self::try_from_rle_lines(lines))
+    }
+
+    pub fn try_from_rle_lines<I, S, T>(line_iter: T) -> Result<FloorMap, MapParseError>
+    where
+        I: Iterator<Item = S>,
+        S: AsRef<[u8]>,
+        T: IntoIterator<IntoIter = I, Item = S>,
+    {
+        let grid = parse_rle(line_iter, BitGrid::new, |grid, y, x0, x1| {
+            grid.fill_span(y, x0, x1, true)
+        })?;
+        Ok(FloorMap::from_grid(grid))
+    }
+
+    pub fn write_rle<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write_rle(out, self.width(), self.height(), self.occupied())
+    }
+}
+
+impl SparseFloorMap {
+    pub fn write_rle<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut pallets: Vec<(i32, i32)> = self.occupied().collect();
+        pallets.sort_unstable_by_key(|&(x, y)| (y, x));
+        write_rle(out, self.width(), self.height(), pallets.into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &[u8]) -> Result<FloorMap, MapParseError> {
+        FloorMap::try_from_rle_lines(s.split(|&v| v == b'\n'))
+    }
+
+    #[test]
+    fn test_rle_matches_grid() {
+        let rle = b"#C the example from test.txt
+x = 10, y = 10
+2b2ob4ob$3obobob2o$5obob2o$ob4o2bob$2ob4ob2o$b7obo$bobobob3o$ob3ob4o$
+b8ob$obob3obo!";
+        let grid = FloorMap::new_from_file("test.txt");
+        let map = parse(rle).unwrap();
+        assert_eq!(map.grid, grid.grid);
+        // Glyphs from the plain format work too, and short rows pad with empty.
+        let alt = parse(b"x = 3, y = 3\n@.@$$2@!").unwrap();
+        assert_eq!(
+            alt.grid,
+            FloorMap::new_from_lines([b"@.@".as_slice(), b"...", b"@@."]).grid
+        );
+    }
+
+    #[test]
+    fn test_write_rle() {
+        let map = FloorMap::new_from_file("test.txt");
+        let mut out = Vec::new();
+        map.write_rle(&mut out).unwrap();
+        assert!(out.split(|&c| c == b'\n').all(|l| l.len() <= LINE_LIMIT));
+        assert_eq!(parse(&out).unwrap().grid, map.grid);
+
+        let mut out = Vec::new();
+        let small = FloorMap::new_from_lines([b"..".as_slice(), b".@", b"..", b"@@"]);
+        small.write_rle(&mut out).unwrap();
+        assert_eq!(out, b"x = 2, y = 4\n$bo2$2o!\n");
+
+        let sparse = SparseFloorMap::random(5000, 3000, 1e-4, 2);
+        let mut out = Vec::new();
+        sparse.write_rle(&mut out).unwrap();
+        let back = SparseFloorMap::try_from_rle_lines(out.split(|&c| c == b'\n')).unwrap();
+        assert_eq!(back.pallet_count(), sparse.pallet_count());
+        assert!(sparse.occupied().all(|(x, y)| back.map_val(x, y)));
+    }
+
+    #[test]
+    fn test_rle_errors() {
+        assert_eq!(
+            parse(b"3o$").err(),
+            Some(MapParseError::BadRleHeader { line: 1 })
+        );
+        assert_eq!(
+            parse(b"x = 2, y = 1\n3o!").err(),
+            Some(MapParseError::RleOverflow { line: 2, column: 2 })
+        );
+        assert_eq!(
+            parse(b"x = 2, y = 1\no$o!").err(),
+            Some(MapParseError::RleOverflow { line: 2, column: 3 })
+        );
+        // Run counts that fit a usize but would overflow added to x or y.
+        let huge = format!("x = 2, y = 1\no{}o!", usize::MAX);
+        assert_eq!(
+            parse(huge.as_bytes()).err(),
+            Some(MapParseError::RleOverflow {
+                line: 2,
+                column: 22
+            })
+        );
+        let huge = format!("x = 2, y = 1\n${}$o!", usize::MAX);
+        assert_eq!(
+            parse(huge.as_bytes()).err(),
+            Some(MapParseError::RleOverflow {
+                line: 2,
+                column: 23
+            })
+        );
+        assert_eq!(
+            parse(b"x = 2, y = 1\noz!").err(),
+            Some(MapParseError::UnexpectedChar {
+                line: 2,
+                column: 2,
+                found: b'z'
+            })
+        );
+        assert_eq!(parse(b"# only a comment").err(), Some(MapParseError::Empty));
+        // Anything after `!` is ignored.
+        assert!(parse(b"x = 1, y = 1\no!\nzzz").is_ok());
+    }
+}
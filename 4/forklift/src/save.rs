@@ -0,0 +1,127 @@
+// Binary snapshots of a map's cells, so a long exhaust over a huge map can be
+// checkpointed and resumed, or a pass inspected later. Only the dimensions and
+// cells are kept; neighborhood, boundary and update mode come from the run.
+use super::FloorMap;
+use aoc_utils::{BitGrid, write_atomic};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// Bumped whenever Snapshot changes shape.
+const SNAPSHOT_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    width: u64,
+    height: u64,
+    // BitGrid's packed rows, each padded out to whole words.
+    words: Vec<u64>,
+    // Laid out like `words`, when the map has walls.
+    walls: Option<Vec<u64>>,
+}
+
+fn config() -> impl bincode::config::Config {
+    // Packed words are close to random, so varints would only grow them.
+    bincode::config::standard().with_fixed_int_encoding()
+}
+
+impl FloorMap {
+    // Goes through a temporary file so a crash mid-write never clobbers the
+    // previous checkpoint.
+    pub fn save<P>(&self, path: P) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION,
+            width: self.grid.width() as u64,
+            height: self.grid.height() as u64,
+            words: self.grid.words().to_vec(),
+            walls: self.walls.as_ref().map(|w| w.words().to_vec()),
+        };
+        write_atomic(path, |out| {
+            bincode::serde::encode_into_std_write(&snapshot, out, config())?;
+            Ok(())
+        })
+    }
+
+    pub fn load<P>(path: P) -> Result<FloorMap, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let mut input = BufReader::new(File::open(path)?);
+        let snapshot: Snapshot = bincode::serde::decode_from_std_read(&mut input, config())?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(From::from(format!(
+                "Snapshot version {} is not supported (expected {})",
+                snapshot.version, SNAPSHOT_VERSION
+            )));
+        }
+        let (width, height) = (snapshot.width as usize, snapshot.height as usize);
+        let unpack = |words| {
+            BitGrid::from_words(width, height, words).map_err(|e| {
+                format!(
+                    "Snapshot holds {} words, expected {} for its size",
+                    e.found, e.expected
+                )
+            })
+        };
+        let grid = unpack(snapshot.words)?;
+        let walls = snapshot.walls.map(unpack).transpose()?;
+        Ok(FloorMap {
+            walls,
+            ..FloorMap::from_grid(grid)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_save_load() {
+        let path = env::temp_dir().join("forklift_save_test.bin");
+        let mut map = FloorMap::new_from_file("test.txt");
+        map.mark_free(4);
+        map.save(&path).unwrap();
+        let mut loaded = FloorMap::load(&path).unwrap();
+        assert_eq!(loaded.grid, map.grid);
+        // Resuming picks up where the saved run left off.
+        assert_eq!(loaded.count_and_mark_exhaust_passes(4), vec![9, 2]);
+
+        let wide = FloorMap::random(130, 7, 0.5, 5);
+        wide.save(&path).unwrap();
+        assert_eq!(FloorMap::load(&path).unwrap().grid, wide.grid);
+
+        let walled = FloorMap::new_from_lines([b"#@.".as_slice(), b"@##"]);
+        walled.save(&path).unwrap();
+        let loaded = FloorMap::load(&path).unwrap();
+        assert_eq!(loaded.walls, walled.walls);
+        assert_eq!(loaded.wall_count(), 3);
+    }
+
+    #[test]
+    fn test_load_bad_files() {
+        let path = env::temp_dir().join("forklift_bad_snapshot.bin");
+        fs::write(&path, b"not a snapshot").unwrap();
+        assert!(FloorMap::load(&path).is_err());
+
+        let wrong = Snapshot {
+            version: SNAPSHOT_VERSION + 1,
+            width: 1,
+            height: 1,
+            words: vec![1],
+            walls: None,
+        };
+        let bytes = bincode::serde::encode_to_vec(&wrong, config()).unwrap();
+        fs::write(&path, bytes).unwrap();
+        assert!(FloorMap::load(&path).is_err());
+        assert!(FloorMap::load(env::temp_dir().join("forklift_no_such.bin")).is_err());
+    }
+}
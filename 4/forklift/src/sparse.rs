@@ -0,0 +1,393 @@
+// A FloorMap alternative that keeps only the coordinates of occupied cells,
+// for maps far too big to hold as a bitset but with few pallets on them. It
+// offers the same counting and exhaust calls; after the first pass an exhaust
+// only revisits pallets next to something the previous pass freed.
+use super::neighborhood::Neighborhood;
+use super::rle::parse_rle;
+use super::{Boundary, Cell, FloorMap, MapParseError, Rule, UpdateMode, parse_file};
+use aoc_utils::{BitGrid, Xorshift};
+use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
+use std::path::Path;
+
+// A hash set entry costs around 16 bytes against one bit per cell densely, so
+// below about 1 in 128 cells occupied the sparse map is the smaller one.
+pub const SPARSE_DENSITY: f64 = 1.0 / 128.0;
+
+// Dense maps with more cells than this (a 1 GiB bitset) aren't worth trying.
+pub const DENSE_CELL_LIMIT: u64 = 1 << 33;
+
+// Whether a width x height map with `occupied` pallets and walls is better
+// stored sparsely.
+pub fn prefers_sparse(width: usize, height: usize, occupied: u64) -> bool {
+    let cells = width as u64 * height as u64;
+    cells > DENSE_CELL_LIMIT || (occupied as f64) < cells as f64 * SPARSE_DENSITY
+}
+
+#[derive(Clone)]
+pub struct SparseFloorMap {
+    width: i32,
+    height: i32,
+    pallets: HashSet<(i32, i32)>,
+    walls: HashSet<(i32, i32)>,
+    neighborhood: Neighborhood,
+    update: UpdateMode,
+    boundary: Boundary,
+}
+
+impl SparseFloorMap {
+    pub fn new(width: usize, height: usize) -> SparseFloorMap {
+        SparseFloorMap {
+            width: width as i32,
+            height: height as i32,
+            pallets: HashSet::new(),
+            walls: HashSet::new(),
+            neighborhood: Neighborhood::moore(),
+            update: UpdateMode::InPlace,
+            boundary: Boundary::Empty,
+        }
+    }
+
+    pub fn try_from_rle_file<P>(filename: P) -> Result<SparseFloorMap, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        parse_file(filename, |lines| Self::try_from_rle_lines(lines))
+    }
+
+    // Runs go straight into the set, so memory follows the pallet count
+    // rather than the area in the header.
+    pub fn try_from_rle_lines<I, S, T>(line_iter: T) -> Result<SparseFloorMap, MapParseError>
+    where
+        I: Iterator<Item = S>,
+        S: AsRef<[u8]>,
+        T: IntoIterator<IntoIter = I, Item = S>,
+    {
+        parse_rle(line_iter, SparseFloorMap::new, |map, y, x0, x1| {
+            for x in x0..=x1 {
+                map.pallets.insert((x as i32, y as i32));
+            }
+        })
+    }
+
+    // About density * width * height pallets at distinct random cells; meant
+    // for the low densities a sparse map is for.
+    pub fn random(width: usize, height: usize, density: f64, seed: u64) -> SparseFloorMap {
+        let mut rng = Xorshift::new(seed);
+        let mut next = || rng.next_u64();
+        let mut map = SparseFloorMap::new(width, height);
+        let cells = width as u64 * height as u64;
+        let target = (density.clamp(0.0, 1.0) * cells as f64) as usize;
+        while map.pallets.len() < target {
+            let x = (next() % width as u64) as i32;
+            let y = (next() % height as u64) as i32;
+            map.pallets.insert((x, y));
+        }
+        map
+    }
+
+    // Same cells and settings as a dense map.
+    pub fn from_dense(map: &FloorMap) -> SparseFloorMap {
+        let walls = (0..map.height())
+            .flat_map(|y| (0..map.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| map.is_wall(x, y));
+        SparseFloorMap {
+            width: map.width(),
+            height: map.height(),
+            pallets: map.occupied().collect(),
+            walls: walls.collect(),
+            neighborhood: map.neighborhood,
+            update: map.update,
+            boundary: map.boundary,
+        }
+    }
+
+    // Panics past DENSE_CELL_LIMIT rather than attempting the allocation.
+    pub fn to_dense(&self) -> FloorMap {
+        let cells = self.width as u64 * self.height as u64;
+        if cells > DENSE_CELL_LIMIT {
+            panic!(
+                "{}x{} map is too big to store densely",
+                self.width, self.height
+            );
+        }
+        let bits = |cells: &HashSet<(i32, i32)>| {
+            let mut grid = BitGrid::new(self.width as usize, self.height as usize);
+            for &(x, y) in cells {
+                grid.set(x as usize, y as usize, true);
+            }
+            grid
+        };
+        FloorMap {
+            grid: bits(&self.pallets),
+            walls: (!self.walls.is_empty()).then(|| bits(&self.walls)),
+            neighborhood: self.neighborhood,
+            update: self.update,
+            boundary: self.boundary,
+            undo: None,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn with_neighborhood(mut self, neighborhood: Neighborhood) -> Self {
+        self.neighborhood = neighborhood;
+        self
+    }
+
+    pub fn with_update_mode(mut self, update: UpdateMode) -> Self {
+        self.update = update;
+        self
+    }
+
+    pub fn with_boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+
+    fn resolve(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        self.boundary.resolve(x, y, self.width, self.height)
+    }
+
+    pub fn pallet_count(&self) -> u64 {
+        self.pallets.len() as u64
+    }
+
+    pub fn wall_count(&self) -> u64 {
+        self.walls.len() as u64
+    }
+
+    pub fn cell(&self, x: i32, y: i32) -> Cell {
+        match self.resolve(x, y) {
+            Some(p) if self.walls.contains(&p) => Cell::Wall,
+            Some(p) if self.pallets.contains(&p) => Cell::Pallet,
+            None if matches!(self.boundary, Boundary::Filled) => Cell::Wall,
+            _ => Cell::Empty,
+        }
+    }
+
+    pub fn map_val(&self, x: i32, y: i32) -> bool {
+        self.resolve(x, y)
+            .is_some_and(|p| self.pallets.contains(&p))
+    }
+
+    // Pallet coordinates in no particular order.
+    pub fn occupied(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.pallets.iter().copied()
+    }
+
+    fn check_on_map(&self, x: i32, y: i32) {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            panic!(
+                "({}, {}) outside of {}x{} SparseFloorMap!",
+                x, y, self.width, self.height
+            );
+        }
+    }
+
+    pub fn free_val(&mut self, x: i32, y: i32) {
+        self.check_on_map(x, y);
+        self.pallets.remove(&(x, y));
+    }
+
+    pub fn place_val(&mut self, x: i32, y: i32) {
+        self.check_on_map(x, y);
+        if self.walls.contains(&(x, y)) {
+            panic!("({}, {}) is a wall", x, y);
+        }
+        self.pallets.insert((x, y));
+    }
+
+    pub fn count_neighbors(&self, x: i32, y: i32) -> u8 {
+        self.check_on_map(x, y);
+        self.neighborhood
+            .offsets()
+            .map(|(xoff, yoff)| (self.cell(x + xoff, y + yoff) != Cell::Empty) as u8)
+            .sum()
+    }
+
+    // The on-map pallets whose counts a change at (x, y) touches. Every
+    // built-in boundary lands a lookup no further away than its offset, so
+    // those are within the neighborhood of (x, y); a Custom one could send a
+    // lookup anywhere, so every pallet might be touched.
+    fn pallet_neighbors(&self, x: i32, y: i32) -> Box<dyn Iterator<Item = (i32, i32)> + '_> {
+        if matches!(self.boundary, Boundary::Custom(_)) {
+            return Box::new(self.occupied());
+        }
+        Box::new(
+            self.neighborhood
+                .offsets()
+                .filter_map(move |(xoff, yoff)| self.resolve(x + xoff, y + yoff))
+                .filter(|p| self.pallets.contains(p)),
+        )
+    }
+
+    pub fn count_free(&self, rule: impl Into<Rule>) -> u32 {
+        let rule = rule.into();
+        self.occupied()
+            .filter(|&(x, y)| rule.removes(self.count_neighbors(x, y)))
+            .count() as u32
+    }
+
+    pub fn count_and_mark_free(&mut self, rule: impl Into<Rule>) -> u32 {
+        self.mark_free(rule).len() as u32
+    }
+
+    // One pass over every pallet, freed cells in FloorMap::mark_free's
+    // column-major order.
+    pub fn mark_free(&mut self, rule: impl Into<Rule>) -> Vec<(i32, i32)> {
+        let all = self.pallets.iter().copied().collect();
+        self.pass(rule.into(), all)
+    }
+
+    // One pass that only judges `candidates`, which must hold every pallet
+    // whose neighbor count changed since it was last judged.
+    fn pass(&mut self, rule: Rule, mut candidates: BTreeSet<(i32, i32)>) -> Vec<(i32, i32)> {
+        let mut freed = Vec::new();
+        while let Some((x, y)) = candidates.pop_first() {
+            if !self.pallets.contains(&(x, y)) || !rule.removes(self.count_neighbors(x, y)) {
+                continue;
+            }
+            freed.push((x, y));
+            if self.update == UpdateMode::InPlace {
+                self.pallets.remove(&(x, y));
+                // Later cells in this pass already see the removal.
+                let later: Vec<_> = self
+                    .pallet_neighbors(x, y)
+                    .filter(|&p| p > (x, y))
+                    .collect();
+                candidates.extend(later);
+            }
+        }
+        if self.update == UpdateMode::Simultaneous {
+            for p in &freed {
+                self.pallets.remove(p);
+            }
+        }
+        freed
+    }
+
+    pub fn count_and_mark_exhaust_passes(&mut self, rule: impl Into<Rule>) -> Vec<u32> {
+        self.exhaust_with(rule, |_, _| {})
+    }
+
+    pub fn exhaust_with<F>(&mut self, rule: impl Into<Rule>, mut on_pass: F) -> Vec<u32>
+    where
+        F: FnMut(&SparseFloorMap, &[(i32, i32)]),
+    {
+        let rule = rule.into();
+        let mut passes = Vec::new();
+        let mut candidates: BTreeSet<_> = self.pallets.iter().copied().collect();
+        loop {
+            let freed = self.pass(rule, candidates);
+            if freed.is_empty() {
+                break;
+            }
+            on_pass(self, &freed);
+            passes.push(freed.len() as u32);
+            candidates = freed
+                .iter()
+                .flat_map(|&(x, y)| self.pallet_neighbors(x, y))
+                .collect();
+        }
+        passes
+    }
+
+    pub fn count_and_mark_exhaust(&mut self, rule: impl Into<Rule>) -> u32 {
+        self.count_and_mark_exhaust_passes(rule).iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::neighborhood::Shape;
+    use super::*;
+
+    #[test]
+    fn test_sparse_matches_dense() {
+        let dense = FloorMap::new_from_file("test.txt");
+        let sparse = SparseFloorMap::from_dense(&dense);
+        assert_eq!(sparse.pallet_count(), 71);
+        assert_eq!(sparse.count_neighbors(0, 0), dense.count_neighbors(0, 0));
+        assert_eq!(sparse.count_free(4), 13);
+        assert_eq!(
+            sparse.clone().count_and_mark_exhaust_passes(4),
+            vec![32, 9, 2]
+        );
+        let mut sim = sparse.clone().with_update_mode(UpdateMode::Simultaneous);
+        assert_eq!(
+            sim.count_and_mark_exhaust_passes(4),
+            vec![13, 12, 7, 5, 2, 1, 1, 1, 1]
+        );
+
+        let walled = FloorMap::new_from_lines([b"#@@.@".as_slice(), b"@@#@@", b".@@@#"]);
+        for n in [
+            Neighborhood::moore(),
+            Neighborhood::new(Shape::VonNeumann, 2).unwrap(),
+        ] {
+            let flip =
+                |x: i32, y: i32, w: i32, h: i32| Some((w - 1 - x.rem_euclid(w), y.rem_euclid(h)));
+            for b in [
+                Boundary::Empty,
+                Boundary::Wrap,
+                Boundary::Filled,
+                Boundary::Mirror,
+                Boundary::Custom(flip),
+            ] {
+                for update in [UpdateMode::InPlace, UpdateMode::Simultaneous] {
+                    for map in [FloorMap::random(30, 20, 0.6, 5), walled.clone()] {
+                        let mut d = map
+                            .with_neighborhood(n)
+                            .with_boundary(b)
+                            .with_update_mode(update);
+                        let mut s = SparseFloorMap::from_dense(&d);
+                        let mut freed = Vec::new();
+                        let passes = s.exhaust_with(3, |_, f| freed.push(f.to_vec()));
+                        let mut dense_freed = Vec::new();
+                        let dense_passes = d.exhaust_with(3, |_, f| dense_freed.push(f.to_vec()));
+                        assert_eq!(passes, dense_passes);
+                        assert_eq!(freed, dense_freed);
+                        assert_eq!(s.to_dense().grid, d.grid);
+
+                        // What's left is crowded enough for the opposite rule.
+                        assert_eq!(
+                            s.count_and_mark_exhaust_passes(Rule::Over(2)),
+                            d.count_and_mark_exhaust_passes(Rule::Over(2))
+                        );
+                        assert_eq!(s.to_dense().grid, d.grid);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sparse_huge() {
+        // A million by a million is far past anything a bitset could hold.
+        let mut m = SparseFloorMap::random(1_000_000, 1_000_000, 1e-8, 3);
+        assert_eq!(m.pallet_count(), 10_000);
+        assert!(prefers_sparse(1_000_000, 1_000_000, m.pallet_count()));
+        // Scattered that thinly, nothing has enough neighbors to stay.
+        assert_eq!(m.count_and_mark_exhaust(1), 10_000);
+
+        let rle = SparseFloorMap::try_from_rle_lines([
+            "x = 1000000, y = 1000000".as_bytes(),
+            b"3o$3o$3o!",
+        ])
+        .unwrap();
+        assert_eq!(rle.pallet_count(), 9);
+        assert_eq!(rle.count_neighbors(1, 1), 8);
+        let block = FloorMap::new_from_lines([b"@@@".as_slice(); 3]);
+        assert_eq!(
+            rle.clone().count_and_mark_exhaust_passes(4),
+            block.clone().count_and_mark_exhaust_passes(4)
+        );
+        assert!(!prefers_sparse(10, 10, 9));
+    }
+}
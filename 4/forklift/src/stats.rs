@@ -0,0 +1,83 @@
+// Per-pass statistics for plotting how the pallet count decays.
+use super::FloorMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatsFormat {
+    Csv,
+    // One JSON object per line.
+    JsonLines,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PassStats {
+    // 0 is the map before any pass.
+    pub pass: u32,
+    pub threshold: u8,
+    pub removed: u32,
+    pub remaining: u64,
+    // Remaining pallets over the map's area.
+    pub density: f64,
+}
+
+impl PassStats {
+    pub fn of(map: &FloorMap, pass: u32, threshold: u8, removed: u32) -> PassStats {
+        let area = map.width() as u64 * map.height() as u64;
+        PassStats::new(pass, threshold, removed, map.pallet_count(), area)
+    }
+
+    // For maps that aren't a FloorMap, e.g. a SparseFloorMap.
+    pub fn new(pass: u32, threshold: u8, removed: u32, remaining: u64, area: u64) -> PassStats {
+        PassStats {
+            pass,
+            threshold,
+            removed,
+            remaining,
+            density: if area == 0 {
+                0.0
+            } else {
+                remaining as f64 / area as f64
+            },
+        }
+    }
+
+    pub fn header(format: StatsFormat) -> Option<&'static str> {
+        match format {
+            StatsFormat::Csv => Some("pass,threshold,removed,remaining,density"),
+            StatsFormat::JsonLines => None,
+        }
+    }
+
+    pub fn line(&self, format: StatsFormat) -> String {
+        match format {
+            StatsFormat::Csv => format!(
+                "{},{},{},{},{:.6}",
+                self.pass, self.threshold, self.removed, self.remaining, self.density
+            ),
+            StatsFormat::JsonLines => format!(
+                "{{\"pass\":{},\"threshold\":{},\"removed\":{},\"remaining\":{},\"density\":{:.6}}}",
+                self.pass, self.threshold, self.removed, self.remaining, self.density
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pass_stats() {
+        let mut map = FloorMap::new_from_file("test.txt");
+        let start = PassStats::of(&map, 0, 4, 0);
+        assert_eq!(start.remaining, 71);
+        assert_eq!(start.line(StatsFormat::Csv), "0,4,0,71,0.710000");
+        let freed = map.mark_free(4).len() as u32;
+        let first = PassStats::of(&map, 1, 4, freed);
+        assert_eq!(first.remaining, 39);
+        assert_eq!(
+            first.line(StatsFormat::JsonLines),
+            "{\"pass\":1,\"threshold\":4,\"removed\":32,\"remaining\":39,\"density\":0.390000}"
+        );
+        assert!(PassStats::header(StatsFormat::JsonLines).is_none());
+    }
+}
@@ -0,0 +1,265 @@
+// Part 1 straight off the input for maps too big to load: read the grid a line
+// at a time and count each row as soon as the rows its neighborhood reaches
+// are in, keeping only that window (three rows for the default Moore
+// neighborhood) in memory. Counts match FloorMap::count_free on the loaded
+// map. Only the Empty and Filled boundaries work this way; the others look at
+// rows on the far side of the map.
+use super::neighborhood::Neighborhood;
+use super::{Boundary, Cell, MapParseError, Rule, parse_file, parse_row};
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+// Why a map couldn't be streamed: a boundary that looks past the window, or
+// the input itself.
+#[derive(Debug)]
+pub enum StreamError {
+    Boundary(Boundary),
+    Parse(MapParseError),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamError::Boundary(b) => {
+                write!(f, "Can't stream a map with the {:?} boundary", b)
+            }
+            StreamError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for StreamError {}
+
+impl From<MapParseError> for StreamError {
+    fn from(e: MapParseError) -> Self {
+        StreamError::Parse(e)
+    }
+}
+
+// Whether off-map cells are walls, for the boundaries that can be streamed.
+fn filled(boundary: Boundary) -> Result<bool, StreamError> {
+    match boundary {
+        Boundary::Empty => Ok(false),
+        Boundary::Filled => Ok(true),
+        b => Err(StreamError::Boundary(b)),
+    }
+}
+
+struct Window {
+    // Rows base.. of the map, as many as the neighborhood spans.
+    rows: VecDeque<Vec<Cell>>,
+    base: i32,
+    width: i32,
+    offsets: Vec<(i32, i32)>,
+    // Whether off-map cells are walls.
+    filled: bool,
+}
+
+impl Window {
+    // Whether (x, y) counts as a neighbor; rows at or past `height`, once the
+    // end of the map is known, are off it.
+    fn occupied(&self, x: i32, y: i32, height: Option<i32>) -> bool {
+        if x < 0 || y < 0 || x >= self.width || height.is_some_and(|h| y >= h) {
+            return self.filled;
+        }
+        self.rows[(y - self.base) as usize][x as usize] != Cell::Empty
+    }
+
+    // Pallets in row y that the rule frees.
+    fn count_row(&self, y: i32, height: Option<i32>, rule: Rule) -> u32 {
+        let row = &self.rows[(y - self.base) as usize];
+        (0..self.width)
+            .filter(|&x| row[x as usize] == Cell::Pallet)
+            .filter(|&x| {
+                let n = self
+                    .offsets
+                    .iter()
+                    .filter(|&&(xoff, yoff)| self.occupied(x + xoff, y + yoff, height))
+                    .count();
+                rule.removes(n as u8)
+            })
+            .count() as u32
+    }
+}
+
+pub fn count_free_file<P>(
+    filename: P,
+    rule: impl Into<Rule>,
+    neighborhood: Neighborhood,
+    boundary: Boundary,
+) -> Result<u32, Box<dyn Error>>
+where
+    P: AsRef<Path>,
+{
+    let rule = rule.into();
+    let filled = filled(boundary)?;
+    parse_file(filename, |lines| {
+        count_rows(lines, rule, neighborhood, filled)
+    })
+}
+
+// Parse errors are the ones FloorMap::try_from_lines gives, though the rows
+// before one have already been counted.
+pub fn count_free<I, S, T>(
+    line_iter: T,
+    rule: impl Into<Rule>,
+    neighborhood: Neighborhood,
+    boundary: Boundary,
+) -> Result<u32, StreamError>
+where
+    I: Iterator<Item = S>,
+    S: AsRef<[u8]>,
+    T: IntoIterator<IntoIter = I, Item = S>,
+{
+    let filled = filled(boundary)?;
+    Ok(count_rows(line_iter, rule.into(), neighborhood, filled)?)
+}
+
+fn count_rows<I, S, T>(
+    line_iter: T,
+    rule: Rule,
+    neighborhood: Neighborhood,
+    filled: bool,
+) -> Result<u32, MapParseError>
+where
+    I: Iterator<Item = S>,
+    S: AsRef<[u8]>,
+    T: IntoIterator<IntoIter = I, Item = S>,
+{
+    let reach = *neighborhood.row_offsets().end();
+    let mut window = Window {
+        rows: VecDeque::new(),
+        base: 0,
+        width: 0,
+        offsets: neighborhood.offsets().collect(),
+        filled,
+    };
+    let mut height = 0;
+    let mut freed = 0;
+    let mut line_iter = line_iter.into_iter().enumerate().peekable();
+    while let Some((lineno, line)) = line_iter.next() {
+        let line = line.as_ref();
+        // A trailing newline leaves one empty line at the end.
+        if line.is_empty() && height > 0 && line_iter.peek().is_none() {
+            break;
+        }
+        // Reuse the row about to drop out of the window.
+        let mut cells = match window.rows.len() > 2 * reach as usize {
+            true => {
+                window.base += 1;
+                window.rows.pop_front().unwrap()
+            }
+            false => Vec::new(),
+        };
+        parse_row(line, lineno, &mut cells)?;
+        if height == 0 {
+            window.width = cells.len() as i32;
+        } else if cells.len() as i32 != window.width {
+            return Err(MapParseError::MismatchedWidth {
+                line: lineno + 1,
+                width: cells.len(),
+                expected: window.width as usize,
+            });
+        }
+        window.rows.push_back(cells);
+        height += 1;
+        // The row `reach` above this one now has everything below it.
+        if height > reach {
+            freed += window.count_row(height - 1 - reach, None, rule);
+        }
+    }
+    if height == 0 {
+        return Err(MapParseError::Empty);
+    }
+    for y in (height - reach).max(0)..height {
+        freed += window.count_row(y, Some(height), rule);
+    }
+    Ok(freed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::FloorMap;
+    use super::super::neighborhood::Shape;
+    use super::*;
+
+    fn parse_err(e: StreamError) -> MapParseError {
+        match e {
+            StreamError::Parse(e) => e,
+            e => panic!("{}", e),
+        }
+    }
+
+    #[test]
+    fn test_stream_matches_count_free() {
+        let lines_of = |m: &FloorMap| {
+            let mut out = Vec::new();
+            m.write_grid(&mut out).unwrap();
+            out
+        };
+        let walled = FloorMap::new_from_lines([b"#@@.@@@".as_slice(), b"@#@@.@@", b"..@#@@@"]);
+        let maps = [
+            FloorMap::new_from_file("test.txt"),
+            FloorMap::random(70, 40, 0.6, 7),
+            FloorMap::random(5, 1, 0.8, 7),
+            walled,
+        ];
+        for m in &maps {
+            let bytes = lines_of(m);
+            for n in [
+                Neighborhood::moore(),
+                Neighborhood::new(Shape::VonNeumann, 2).unwrap(),
+                Neighborhood::new(Shape::Hex, 3).unwrap(),
+            ] {
+                for b in [Boundary::Empty, Boundary::Filled] {
+                    let loaded = m.clone().with_neighborhood(n).with_boundary(b);
+                    for rule in [Rule::Under(4), Rule::Over(3)] {
+                        let lines = bytes.split(|&v| v == b'\n');
+                        assert_eq!(
+                            count_free(lines, rule, n, b).map_err(parse_err),
+                            Ok(loaded.count_free(rule)),
+                            "{:?} {:?}",
+                            n,
+                            rule
+                        );
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            count_free_file("test.txt", 4, Neighborhood::moore(), Boundary::Empty).unwrap(),
+            13
+        );
+        for b in [Boundary::Wrap, Boundary::Mirror] {
+            let lines = lines_of(&maps[0]);
+            let res = count_free(lines.split(|&v| v == b'\n'), 4, Neighborhood::moore(), b);
+            assert!(matches!(res, Err(StreamError::Boundary(_))));
+            assert!(count_free_file("test.txt", 4, Neighborhood::moore(), b).is_err());
+        }
+    }
+
+    #[test]
+    fn test_stream_parse_errors() {
+        let count = |s: &[u8]| {
+            count_free(
+                s.split(|&v| v == b'\n'),
+                4,
+                Neighborhood::moore(),
+                Boundary::Empty,
+            )
+            .map_err(parse_err)
+        };
+        for bad in [b"@@\n@x".as_slice(), b"@@\n@@@", b"@@\n\n@@"] {
+            let expected = FloorMap::try_from_lines(bad.split(|&v| v == b'\n')).err();
+            assert_eq!(count(bad).err(), expected);
+            assert!(expected.is_some());
+        }
+        let none: [&[u8]; 0] = [];
+        assert_eq!(
+            count_free(none, 4, Neighborhood::moore(), Boundary::Empty).map_err(parse_err),
+            Err(MapParseError::Empty)
+        );
+    }
+}
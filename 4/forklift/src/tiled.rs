@@ -0,0 +1,287 @@
+// Tiled simultaneous exhaust for maps too big to stay in cache. The map is cut
+// into tiles, each a small FloorMap of its own holding its cells plus a ghost
+// border copied from the tiles around it (as wide as the neighborhood reaches),
+// so a pass over one tile runs the word kernel entirely inside it. Between
+// passes only the ghost borders are refreshed, and a tile whose cells and
+// ghosts both came through the last pass unchanged is skipped, since it would
+// free nothing again; late in an exhaust that's almost all of them.
+use super::{Boundary, Cell, FloorMap, Rule};
+use aoc_utils::BitGrid;
+use aoc_utils::bitgrid::WORD_BITS;
+use rayon::prelude::*;
+
+// Cells per side of a tile with its ghosts: 256 x 256 bits is 8 KiB of
+// pallets.
+const TILE: i32 = 256;
+
+struct Tile {
+    // Top-left of the interior on the map, and its size.
+    x0: i32,
+    y0: i32,
+    width: i32,
+    height: i32,
+    // Ghost border width.
+    ghost: i32,
+    // The interior and its ghost border; walls never change, so only the
+    // ghost pallets need refreshing.
+    local: FloorMap,
+    // Whether the next pass can free anything here.
+    active: bool,
+    // Whether the last pass freed anything here.
+    freed: bool,
+}
+
+impl Tile {
+    fn new(map: &FloorMap, (x0, y0): (i32, i32), (width, height): (i32, i32), ghost: i32) -> Tile {
+        let (lw, lh) = ((width + 2 * ghost) as usize, (height + 2 * ghost) as usize);
+        let mut grid = BitGrid::new(lw, lh);
+        let mut walls = map.walls.as_ref().map(|_| BitGrid::new(lw, lh));
+        for ly in 0..lh {
+            for lx in 0..lw {
+                let (x, y) = (x0 - ghost + lx as i32, y0 - ghost + ly as i32);
+                match map.cell(x, y) {
+                    Cell::Pallet => grid.set(lx, ly, true),
+                    // A Filled boundary puts walls in the ghosts of a map that
+                    // has none of its own.
+                    Cell::Wall => walls
+                        .get_or_insert_with(|| BitGrid::new(lw, lh))
+                        .set(lx, ly, true),
+                    Cell::Empty => {}
+                }
+            }
+        }
+        Tile {
+            x0,
+            y0,
+            width,
+            height,
+            ghost,
+            local: FloorMap {
+                walls,
+                ..FloorMap::from_grid(grid)
+            }
+            .with_neighborhood(map.neighborhood),
+            active: true,
+            freed: false,
+        }
+    }
+
+    fn is_interior(&self, lx: i32, ly: i32) -> bool {
+        let g = self.ghost;
+        lx >= g && ly >= g && lx < g + self.width && ly < g + self.height
+    }
+
+    // One simultaneous pass over the interior, returning the freed cells in
+    // map coordinates.
+    fn pass(&mut self, rule: Rule) -> Vec<(i32, i32)> {
+        let mask = self.local.free_mask(rule);
+        let stride = self.local.grid.stride();
+        let mut freed = Vec::new();
+        // Straight off the interior rows of the mask, already row-major.
+        for ly in self.ghost..self.ghost + self.height {
+            let row = &mask[ly as usize * stride..(ly as usize + 1) * stride];
+            for (i, &word) in row.iter().enumerate() {
+                let mut bits = word;
+                while bits != 0 {
+                    let lx = (i * WORD_BITS) as i32 + bits.trailing_zeros() as i32;
+                    bits &= bits - 1;
+                    if self.is_interior(lx, ly) {
+                        freed.push((lx, ly));
+                    }
+                }
+            }
+        }
+        self.local.clear_cells(&freed);
+        self.freed = !freed.is_empty();
+        self.active = self.freed;
+        let (dx, dy) = (self.x0 - self.ghost, self.y0 - self.ghost);
+        freed
+            .into_iter()
+            .map(|(lx, ly)| (lx + dx, ly + dy))
+            .collect()
+    }
+
+    // Copy the ghost pallets back in from the map, true if any changed.
+    fn refresh_ghosts(&mut self, map: &FloorMap) -> bool {
+        let (g, lw, lh) = (
+            self.ghost,
+            self.width + 2 * self.ghost,
+            self.height + 2 * self.ghost,
+        );
+        let (dx, dy) = (self.x0 - g, self.y0 - g);
+        let mut changed = false;
+        let mut refresh = |lx: i32, ly: i32| {
+            let val = map.map_val(lx + dx, ly + dy);
+            if self.local.grid.get(lx as isize, ly as isize) != val {
+                self.local.grid.set(lx as usize, ly as usize, val);
+                changed = true;
+            }
+        };
+        // The top and bottom strips, then the left and right ones between.
+        for ly in (0..g).chain(lh - g..lh) {
+            for lx in 0..lw {
+                refresh(lx, ly);
+            }
+        }
+        for ly in g..lh - g {
+            for lx in (0..g).chain(lw - g..lw) {
+                refresh(lx, ly);
+            }
+        }
+        changed
+    }
+}
+
+impl FloorMap {
+    // Tiles in row-major order, and how many make up each row of them. A
+    // tile is `size` cells tall and, so that its ghosts round it out to whole
+    // words, `size` less the ghosts wide, apart from the ones clipped by the
+    // right and bottom edges.
+    fn tiles(&self, size: i32) -> (Vec<Tile>, i32) {
+        let ghost = *self.neighborhood.row_offsets().end();
+        let across = (size - 2 * ghost).max(1);
+        let columns = (self.width() + across - 1) / across;
+        let corners: Vec<(i32, i32)> = (0..self.height())
+            .step_by(size as usize)
+            .flat_map(|y0| {
+                (0..self.width())
+                    .step_by(across as usize)
+                    .map(move |x0| (x0, y0))
+            })
+            .collect();
+        let tiles = corners
+            .into_par_iter()
+            .map(|(x0, y0)| {
+                let dims = (across.min(self.width() - x0), size.min(self.height() - y0));
+                Tile::new(self, (x0, y0), dims, ghost)
+            })
+            .collect();
+        (tiles, columns)
+    }
+
+    // A simultaneous exhaust like exhaust_parallel_with, run over tiles. Each
+    // pass's freed cells come back in row-major order.
+    pub fn exhaust_tiled_with<F>(&mut self, rule: impl Into<Rule>, on_pass: F) -> Vec<u32>
+    where
+        F: FnMut(&FloorMap, &[(i32, i32)]),
+    {
+        self.exhaust_tiles(rule.into(), TILE, on_pass)
+    }
+
+    pub fn exhaust_tiled(&mut self, rule: impl Into<Rule>) -> Vec<u32> {
+        self.exhaust_tiled_with(rule, |_, _| {})
+    }
+
+    fn exhaust_tiles<F>(&mut self, rule: Rule, size: i32, mut on_pass: F) -> Vec<u32>
+    where
+        F: FnMut(&FloorMap, &[(i32, i32)]),
+    {
+        let (mut tiles, columns) = self.tiles(size);
+        // Off the edges of an Empty or Filled map nothing changes, so a tile's
+        // ghosts can only change if one of the eight tiles around it freed
+        // something; the other boundaries can pull ghosts from anywhere.
+        let local_ghosts = matches!(self.boundary, Boundary::Empty | Boundary::Filled);
+        let mut passes = Vec::new();
+        loop {
+            let mut freed: Vec<(i32, i32)> = tiles
+                .par_iter_mut()
+                .map(|t| {
+                    if t.active {
+                        t.pass(rule)
+                    } else {
+                        t.freed = false;
+                        Vec::new()
+                    }
+                })
+                .flatten_iter()
+                .collect();
+            if freed.is_empty() {
+                break;
+            }
+            freed.sort_unstable_by_key(|&(x, y)| (y, x));
+            self.clear_cells(&freed);
+            self.record_pass(&freed);
+            // The exchange: every tile pulls its ghosts from the updated map,
+            // waking up if a neighbor freed something next to it.
+            let map = &*self;
+            let near: Vec<bool> = (0..tiles.len() as i32)
+                .map(|i| {
+                    let (tx, ty) = (i % columns, i / columns);
+                    !local_ghosts
+                        || (-1..=1).any(|dy| {
+                            (-1..=1).any(|dx| {
+                                let (nx, ny) = (tx + dx, ty + dy);
+                                nx >= 0
+                                    && nx < columns
+                                    && ny >= 0
+                                    && (nx + ny * columns) < tiles.len() as i32
+                                    && (dx, dy) != (0, 0)
+                                    && tiles[(nx + ny * columns) as usize].freed
+                            })
+                        })
+                })
+                .collect();
+            tiles
+                .par_iter_mut()
+                .zip(near)
+                .filter(|(_, near)| *near)
+                .for_each(|(t, _)| {
+                    if t.refresh_ghosts(map) {
+                        t.active = true;
+                    }
+                });
+            on_pass(self, &freed);
+            passes.push(freed.len() as u32);
+        }
+        passes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::neighborhood::{Neighborhood, Shape};
+    use super::super::{Boundary, UpdateMode};
+    use super::*;
+
+    #[test]
+    fn test_tiled_matches_parallel() {
+        let walled = FloorMap::new_from_lines([b"#@@.@@@".as_slice(), b"@#@@.@@", b"..@#@@@"]);
+        let neighborhoods = [
+            Neighborhood::moore(),
+            Neighborhood::new(Shape::VonNeumann, 2).unwrap(),
+            Neighborhood::new(Shape::Hex, 1).unwrap(),
+        ];
+        for n in neighborhoods {
+            for b in [
+                Boundary::Empty,
+                Boundary::Filled,
+                Boundary::Wrap,
+                Boundary::Mirror,
+            ] {
+                for map in [FloorMap::random(90, 70, 0.65, 3), walled.clone()] {
+                    let map = map.with_neighborhood(n).with_boundary(b);
+                    for rule in [Rule::Under(4), Rule::Over(5)] {
+                        let mut par = map.clone();
+                        let mut expected = Vec::new();
+                        let passes =
+                            par.exhaust_parallel_with(rule, |_, f| expected.push(f.to_vec()));
+                        // Small tiles so the map spans many of them.
+                        for size in [16, 25, TILE] {
+                            let mut tiled = map.clone();
+                            let mut freed = Vec::new();
+                            let tiled_passes =
+                                tiled.exhaust_tiles(rule, size, |_, f| freed.push(f.to_vec()));
+                            assert_eq!(tiled_passes, passes, "{:?} {:?} {}", n, rule, size);
+                            assert_eq!(freed, expected);
+                            assert_eq!(tiled.grid, par.grid);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut m = FloorMap::new_from_file("test.txt").with_update_mode(UpdateMode::Simultaneous);
+        let expected = m.clone().count_and_mark_exhaust_passes(4);
+        assert_eq!(m.exhaust_tiled(4), expected);
+    }
+}
@@ -0,0 +1,142 @@
+// Rotations, reflections and resizing of a whole map, e.g. for putting
+// generated maps into a canonical orientation before deduplicating them. Each
+// returns a new map with the same neighborhood, update mode and boundary.
+use super::FloorMap;
+use aoc_utils::BitGrid;
+
+// Copy every set bit of `bits` into a new width x height grid at `to(x, y)`.
+fn remap_bits<F>(bits: &BitGrid, width: usize, height: usize, to: F) -> BitGrid
+where
+    F: Fn(i32, i32) -> (i32, i32),
+{
+    let mut out = BitGrid::new(width, height);
+    for y in 0..bits.height() as i32 {
+        for x in 0..bits.width() as i32 {
+            if bits.get(x as isize, y as isize) {
+                let (nx, ny) = to(x, y);
+                out.set(nx as usize, ny as usize, true);
+            }
+        }
+    }
+    out
+}
+
+impl FloorMap {
+    fn remap<F>(&self, width: i32, height: i32, to: F) -> FloorMap
+    where
+        F: Fn(i32, i32) -> (i32, i32),
+    {
+        let (w, h) = (width as usize, height as usize);
+        FloorMap {
+            grid: remap_bits(&self.grid, w, h, &to),
+            walls: self
+                .walls
+                .as_ref()
+                .map(|walls| remap_bits(walls, w, h, &to)),
+            neighborhood: self.neighborhood,
+            update: self.update,
+            boundary: self.boundary,
+            // Recorded coordinates don't survive the move.
+            undo: None,
+        }
+    }
+
+    // A quarter turn clockwise: the left column becomes the top row.
+    pub fn rotate90(&self) -> FloorMap {
+        let h = self.height();
+        self.remap(h, self.width(), |x, y| (h - 1 - y, x))
+    }
+
+    // Mirror left to right.
+    pub fn flip_h(&self) -> FloorMap {
+        let w = self.width();
+        self.remap(w, self.height(), |x, y| (w - 1 - x, y))
+    }
+
+    // Shrink to the bounding box of the pallets and walls, dropping the empty
+    // rows and columns around them. A map with nothing on it trims to 0x0.
+    pub fn trim(&self) -> FloorMap {
+        let mut bounds: Option<(i32, i32, i32, i32)> = None;
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                if self.occupied_val(x, y) {
+                    let (x0, y0, x1, y1) = bounds.unwrap_or((x, y, x, y));
+                    bounds = Some((x0.min(x), y0.min(y), x1.max(x), y1.max(y)));
+                }
+            }
+        }
+        match bounds {
+            Some((x0, y0, x1, y1)) => self.remap(x1 - x0 + 1, y1 - y0 + 1, |x, y| (x - x0, y - y0)),
+            None => self.remap(0, 0, |x, y| (x, y)),
+        }
+    }
+
+    // Surround the map with n empty cells on every side.
+    pub fn pad(&self, n: usize) -> FloorMap {
+        let n = n as i32;
+        self.remap(self.width() + 2 * n, self.height() + 2 * n, |x, y| {
+            (x + n, y + n)
+        })
+    }
+
+    // Mirror top to bottom.
+    pub fn flip_v(&self) -> FloorMap {
+        let h = self.height();
+        self.remap(self.width(), h, |x, y| (x, h - 1 - y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(m: &FloorMap) -> Vec<String> {
+        (0..m.height())
+            .map(|y| {
+                (0..m.width())
+                    .map(|x| m.cell(x, y).glyph() as char)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_transforms() {
+        let m = FloorMap::new_from_lines([b"@@.".as_slice(), b"#.."]);
+        assert_eq!(rows(&m.rotate90()), vec!["#@", ".@", ".."]);
+        assert_eq!(rows(&m.flip_h()), vec![".@@", "..#"]);
+        assert_eq!(rows(&m.flip_v()), vec!["#..", "@@."]);
+        assert_eq!(m.rotate90().wall_count(), 1);
+
+        let r = FloorMap::random(70, 9, 0.4, 3);
+        let full_turn = r.rotate90().rotate90().rotate90().rotate90();
+        assert_eq!(full_turn.grid, r.grid);
+        assert_eq!(r.flip_h().flip_h().grid, r.grid);
+        assert_eq!(r.rotate90().rotate90().grid, r.flip_h().flip_v().grid);
+    }
+
+    #[test]
+    fn test_trim_pad() {
+        let m = FloorMap::new_from_lines([b".....".as_slice(), b"..@..", b".#...", b"....."]);
+        let t = m.trim();
+        assert_eq!(rows(&t), vec![".@", "#."]);
+        assert_eq!(rows(&t.pad(1)), vec!["....", "..@.", ".#..", "...."]);
+        assert_eq!(rows(&t.pad(1).trim()), rows(&t));
+        assert_eq!(t.pad(0).grid, t.grid);
+
+        let empty = FloorMap::new_from_lines([b"...".as_slice(), b"..."]).trim();
+        assert_eq!((empty.width(), empty.height()), (0, 0));
+        assert_eq!(empty.pad(2).width(), 4);
+    }
+
+    #[test]
+    fn test_exhaust_is_symmetric() {
+        // The Moore rule has every symmetry of the square and the exhausted
+        // map doesn't depend on removal order, so orientation can't matter.
+        let m = FloorMap::new_from_file("test.txt");
+        let total = m.clone().count_and_mark_exhaust(4);
+        for mut t in [m.rotate90(), m.flip_h(), m.flip_v()] {
+            assert_eq!(t.count_and_mark_exhaust(4), total);
+        }
+    }
+}
@@ -0,0 +1,320 @@
+// Interactive stepper for the exhaust: step passes forward and back, pan and
+// zoom around a large map, and hover a cell to see its live neighbor count.
+use super::{Cell, FloorMap, Rule};
+use ratatui::crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseEventKind,
+};
+use ratatui::crossterm::execute;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::Paragraph;
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::HashSet;
+use std::error::Error;
+use std::io;
+
+// Most map cells folded into one screen cell when zoomed out.
+const MAX_SCALE: i32 = 64;
+
+// Zoomed-out glyphs by how full the block is: none, some, half, most, all.
+const SHADES: [char; 5] = [' ', '░', '▒', '▓', '█'];
+
+pub struct Stepper {
+    map: FloorMap,
+    rule: Rule,
+    // Set once a pass frees nothing; cleared again by stepping back.
+    exhausted: bool,
+    // Map cell at the top-left of the view, and map cells per screen cell.
+    origin: (i32, i32),
+    scale: i32,
+    hover: Option<(i32, i32)>,
+    // Where the map was last drawn, for mapping mouse positions.
+    view: Rect,
+}
+
+impl Stepper {
+    pub fn new(map: FloorMap, rule: impl Into<Rule>) -> Stepper {
+        Stepper {
+            // The map's undo history holds every pass taken so far.
+            map: map.with_undo(),
+            rule: rule.into(),
+            exhausted: false,
+            origin: (0, 0),
+            scale: 1,
+            hover: None,
+            view: Rect::default(),
+        }
+    }
+
+    pub fn map(&self) -> &FloorMap {
+        &self.map
+    }
+
+    pub fn pass(&self) -> usize {
+        self.map.undo_depth()
+    }
+
+    // Run one pass; false once the map is exhausted.
+    pub fn step_forward(&mut self) -> bool {
+        if self.exhausted {
+            return false;
+        }
+        let freed = self.map.mark_free(self.rule);
+        if freed.is_empty() {
+            self.exhausted = true;
+            return false;
+        }
+        true
+    }
+
+    // Put back the cells the latest pass freed; false at the starting map.
+    pub fn step_back(&mut self) -> bool {
+        if self.map.undo_pass().is_none() {
+            return false;
+        }
+        self.exhausted = false;
+        true
+    }
+
+    // Move the view by whole screen cells, keeping its corner on the map.
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        let x = self.origin.0 + dx * self.scale;
+        let y = self.origin.1 + dy * self.scale;
+        self.origin = (
+            x.clamp(0, (self.map.width() - 1).max(0)),
+            y.clamp(0, (self.map.height() - 1).max(0)),
+        );
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.scale = (self.scale * 2).min(MAX_SCALE);
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.scale = (self.scale / 2).max(1);
+    }
+
+    // Map cell under a screen position inside the map view, taking the top
+    // left of the block when zoomed out.
+    pub fn cell_at(&self, col: u16, row: u16) -> Option<(i32, i32)> {
+        let view = self.view;
+        if col < view.x || row < view.y || col >= view.x + view.width || row >= view.y + view.height
+        {
+            return None;
+        }
+        let x = self.origin.0 + (col - view.x) as i32 * self.scale;
+        let y = self.origin.1 + (row - view.y) as i32 * self.scale;
+        (x < self.map.width() && y < self.map.height()).then_some((x, y))
+    }
+
+    // Pallets in the scale x scale block with (x, y) at its top left.
+    fn block_pallets(&self, x: i32, y: i32) -> (u32, u32) {
+        let x1 = (x + self.scale).min(self.map.width()) - 1;
+        let y1 = (y + self.scale).min(self.map.height()) - 1;
+        let mut pallets = 0;
+        for by in y..=y1 {
+            pallets += self
+                .map
+                .grid
+                .count_span(by as isize, x as isize, x1 as isize);
+        }
+        (pallets, ((x1 - x + 1) * (y1 - y + 1)) as u32)
+    }
+
+    fn view_lines(&self, width: u16, height: u16) -> Vec<Line<'static>> {
+        let pallet = Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD);
+        let freed_style = Style::default().fg(Color::Red).add_modifier(Modifier::BOLD);
+        let empty = Style::default().fg(Color::DarkGray);
+        let wall = Style::default().fg(Color::White);
+        let (x0, y0) = self.origin;
+        let x_end = x0 + width as i32 * self.scale;
+        let y_end = y0 + height as i32 * self.scale;
+        // Only the latest pass is highlighted, and only the part in view.
+        let just_freed: HashSet<(i32, i32)> = match (self.scale, self.map.last_pass()) {
+            (1, Some(freed)) => freed
+                .iter()
+                .copied()
+                .filter(|&(x, y)| x >= x0 && x < x_end && y >= y0 && y < y_end)
+                .collect(),
+            _ => HashSet::new(),
+        };
+
+        let mut lines = Vec::new();
+        for row in 0..height as i32 {
+            let y = y0 + row * self.scale;
+            if y >= self.map.height() {
+                break;
+            }
+            let mut spans = Vec::new();
+            for col in 0..width as i32 {
+                let x = x0 + col * self.scale;
+                if x >= self.map.width() {
+                    break;
+                }
+                let mut span = if self.scale == 1 {
+                    match self.map.cell(x, y) {
+                        Cell::Pallet => Span::styled("@", pallet),
+                        Cell::Wall => Span::styled("#", wall),
+                        Cell::Empty if just_freed.contains(&(x, y)) => {
+                            Span::styled("x", freed_style)
+                        }
+                        Cell::Empty => Span::styled(".", empty),
+                    }
+                } else {
+                    let (pallets, area) = self.block_pallets(x, y);
+                    let shade = match pallets {
+                        0 => 0,
+                        p if p == area => 4,
+                        p => 1 + (p * 3 / area) as usize,
+                    };
+                    Span::styled(SHADES[shade].to_string(), pallet)
+                };
+                if self.hover == Some((x, y)) {
+                    span = span.patch_style(Style::default().add_modifier(Modifier::REVERSED));
+                }
+                spans.push(span);
+            }
+            lines.push(Line::from(spans));
+        }
+        lines
+    }
+
+    fn status(&self) -> String {
+        let freed = self.map.last_pass().map_or(0, |f| f.len());
+        let mut status = format!(
+            " pass {}{} | freed {} | remaining {} | zoom 1:{}",
+            self.pass(),
+            if self.exhausted { " (exhausted)" } else { "" },
+            freed,
+            self.map.pallet_count(),
+            self.scale
+        );
+        if let Some((x, y)) = self.hover {
+            let kind = match self.map.cell(x, y) {
+                Cell::Pallet => "pallet",
+                Cell::Wall => "wall",
+                Cell::Empty => "empty",
+            };
+            status.push_str(&format!(
+                " | ({}, {}) {} neighbors {}",
+                x,
+                y,
+                kind,
+                self.map.count_neighbors(x, y)
+            ));
+        }
+        status.push_str(" | n/space step, b back, arrows pan, +/- zoom, q quit");
+        status
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [view, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        self.view = view;
+        let lines = self.view_lines(view.width, view.height);
+        frame.render_widget(Paragraph::new(lines), view);
+        let bar = Style::default().add_modifier(Modifier::REVERSED);
+        frame.render_widget(Paragraph::new(self.status()).style(bar), status);
+    }
+
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('n') | KeyCode::Char(' ') => {
+                        self.step_forward();
+                    }
+                    KeyCode::Char('b') | KeyCode::Backspace => {
+                        self.step_back();
+                    }
+                    KeyCode::Left | KeyCode::Char('h') => self.pan(-8, 0),
+                    KeyCode::Right | KeyCode::Char('l') => self.pan(8, 0),
+                    KeyCode::Up | KeyCode::Char('k') => self.pan(0, -4),
+                    KeyCode::Down | KeyCode::Char('j') => self.pan(0, 4),
+                    KeyCode::Char('+') | KeyCode::Char('=') => self.zoom_in(),
+                    KeyCode::Char('-') => self.zoom_out(),
+                    _ => {}
+                },
+                Event::Mouse(mouse) => match mouse.kind {
+                    MouseEventKind::Moved => self.hover = self.cell_at(mouse.column, mouse.row),
+                    MouseEventKind::ScrollUp => self.zoom_in(),
+                    MouseEventKind::ScrollDown => self.zoom_out(),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+pub fn run(map: FloorMap, rule: Rule) -> Result<(), Box<dyn Error>> {
+    let mut stepper = Stepper::new(map, rule);
+    execute!(io::stdout(), EnableMouseCapture)?;
+    let result = ratatui::run(|terminal| stepper.event_loop(terminal));
+    execute!(io::stdout(), DisableMouseCapture)?;
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_step_forward_and_back() {
+        let start = FloorMap::new_from_file("test.txt");
+        let mut s = Stepper::new(start.clone(), 4);
+        assert!(!s.step_back());
+        assert!(s.step_forward());
+        assert!(s.step_forward());
+        let two = s.map().grid.clone();
+        assert!(s.step_forward());
+        assert!(!s.step_forward());
+        assert_eq!(s.pass(), 3);
+        assert_eq!(s.map().pallet_count(), 71 - 43);
+        assert!(s.step_back());
+        assert_eq!(s.map().grid, two);
+        assert!(s.step_forward());
+        while s.step_back() {}
+        assert_eq!(s.map().grid, start.grid);
+    }
+
+    #[test]
+    fn test_view() {
+        let map = FloorMap::new_from_lines([b"@@..".as_slice(), b"@...", b"....", b"...@"]);
+        let mut s = Stepper::new(map, 4);
+        assert_eq!(text(&s.view_lines(3, 2)), vec!["@@.", "@.."]);
+        s.zoom_out();
+        assert_eq!(text(&s.view_lines(8, 8)), vec!["▓ ", " ░"]);
+        s.zoom_in();
+        s.pan(1, 1);
+        assert_eq!(text(&s.view_lines(8, 8)), vec!["...", "...", "..@"]);
+        s.pan(-5, -5);
+        assert_eq!(s.origin, (0, 0));
+
+        s.view = Rect::new(0, 0, 10, 5);
+        assert_eq!(s.cell_at(3, 3), Some((3, 3)));
+        assert_eq!(s.cell_at(4, 0), None);
+        s.zoom_out();
+        assert_eq!(s.cell_at(1, 1), Some((2, 2)));
+        assert_eq!(s.cell_at(1, 5), None);
+    }
+
+    #[test]
+    fn test_status() {
+        let mut s = Stepper::new(FloorMap::new_from_file("test.txt"), 4);
+        s.step_forward();
+        s.hover = Some((0, 0));
+        let status = s.status();
+        assert!(status.contains("pass 1 | freed 32 | remaining 39"));
+        assert!(status.contains("(0, 0) empty neighbors"));
+    }
+}
@@ -0,0 +1,102 @@
+// Opt-in undo history: once enabled, every pass that frees something records
+// its cells, so the map can be stepped back one pass at a time to exactly the
+// state before it, e.g. to retry the rest of a run with another threshold.
+use super::FloorMap;
+
+impl FloorMap {
+    // Start recording passes; a map that was already recording keeps what it
+    // has.
+    pub fn with_undo(mut self) -> Self {
+        self.undo.get_or_insert_with(Vec::new);
+        self
+    }
+
+    // Called by every pass with the cells it freed.
+    pub(crate) fn record_pass(&mut self, freed: &[(i32, i32)]) {
+        if let Some(undo) = &mut self.undo
+            && !freed.is_empty()
+        {
+            undo.push(freed.to_vec());
+        }
+    }
+
+    // Put back the pallets the latest recorded pass freed, returning them, or
+    // None with nothing left to undo (or recording off).
+    pub fn undo_pass(&mut self) -> Option<Vec<(i32, i32)>> {
+        let freed = self.undo.as_mut()?.pop()?;
+        for &(x, y) in &freed {
+            self.place_val(x, y);
+        }
+        Some(freed)
+    }
+
+    // How many passes undo_pass can still take back.
+    pub fn undo_depth(&self) -> usize {
+        self.undo.as_ref().map_or(0, |u| u.len())
+    }
+
+    // The cells the latest recorded pass freed.
+    pub fn last_pass(&self) -> Option<&[(i32, i32)]> {
+        self.undo.as_ref()?.last().map(|f| f.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::UpdateMode;
+    use super::*;
+
+    #[test]
+    fn test_undo_pass() {
+        let start = FloorMap::new_from_file("test.txt");
+        let mut m = start.clone().with_undo();
+        let passes = m.count_and_mark_exhaust_passes(4);
+        assert_eq!(m.undo_depth(), passes.len());
+        assert_eq!(m.last_pass().map(|f| f.len()), Some(2));
+        let mut restored = Vec::new();
+        while let Some(freed) = m.undo_pass() {
+            restored.push(freed.len() as u32);
+        }
+        restored.reverse();
+        assert_eq!(restored, passes);
+        assert_eq!(m.grid, start.grid);
+        assert_eq!(m.undo_pass(), None);
+
+        // Back up one pass, then carry on with a different threshold.
+        let mut m = start.clone().with_undo();
+        m.mark_free(4);
+        let after_one = m.grid.clone();
+        m.mark_free(4);
+        m.undo_pass();
+        assert_eq!(m.grid, after_one);
+        m.count_and_mark_exhaust(3);
+        let mut fresh = start.clone();
+        fresh.mark_free(4);
+        fresh.count_and_mark_exhaust(3);
+        assert_eq!(m.grid, fresh.grid);
+
+        // Every pass flavour records.
+        for mut m in [
+            start.clone().with_undo(),
+            start
+                .clone()
+                .with_undo()
+                .with_update_mode(UpdateMode::Simultaneous),
+        ] {
+            m.exhaust_incremental(4);
+            let depth = m.undo_depth();
+            m.exhaust_parallel(4);
+            assert_eq!(m.undo_depth(), depth);
+            while m.undo_pass().is_some() {}
+            assert_eq!(m.grid, start.grid);
+        }
+        let mut m = start.clone().with_undo();
+        m.exhaust_parallel(4);
+        assert!(m.undo_depth() > 0);
+
+        // Not recording is the default.
+        let mut m = start.clone();
+        m.mark_free(4);
+        assert_eq!((m.undo_pass(), m.undo_depth()), (None, 0));
+    }
+}
@@ -0,0 +1,67 @@
+// ANSI rendering of a FloorMap, highlighting the cells freed by the last pass.
+use super::{Cell, FloorMap};
+use aoc_utils::Grid;
+
+const PALLET: &str = "\x1b[1;33m@\x1b[0m";
+const FREED: &str = "\x1b[1;31mx\x1b[0m";
+const EMPTY: &str = "\x1b[2m.\x1b[0m";
+const WALL: &str = "\x1b[1;37m#\x1b[0m";
+
+pub fn render(map: &FloorMap, freed: &[(i32, i32)]) -> String {
+    let freed = freed.iter().map(|&at| (at, ()));
+    render_marked(map, freed, |cell, freed| {
+        Some(match cell {
+            Cell::Pallet => PALLET,
+            Cell::Wall => WALL,
+            Cell::Empty if freed.is_some() => FREED,
+            Cell::Empty => EMPTY,
+        })
+    })
+}
+
+// One line per row of the map, each cell drawn as whatever `draw` makes of
+// it and the mark `marked` gives it, if any; None draws the plain glyph.
+pub(crate) fn render_marked<T, M, F>(map: &FloorMap, marked: M, draw: F) -> String
+where
+    T: Clone,
+    M: IntoIterator<Item = ((i32, i32), T)>,
+    F: Fn(Cell, Option<T>) -> Option<&'static str>,
+{
+    let mut marks = Grid::new(map.grid.width(), map.grid.height(), None);
+    for ((x, y), mark) in marked {
+        marks[(x as usize, y as usize)] = Some(mark);
+    }
+    let mut out = String::new();
+    for y in 0..map.height() {
+        for x in 0..map.width() {
+            let cell = map.cell(x, y);
+            match draw(cell, marks[(x as usize, y as usize)].clone()) {
+                Some(glyph) => out.push_str(glyph),
+                None => out.push(cell.glyph() as char),
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut map = FloorMap::new_from_lines(b"@@.\n.@@".split(|&v| v == b'\n'));
+        let freed = map.mark_free(3);
+        assert_eq!(freed, vec![(0, 0), (1, 0), (1, 1), (2, 1)]);
+        assert_eq!(
+            render(&map, &freed[2..]),
+            format!("{EMPTY}{EMPTY}{EMPTY}\n{EMPTY}{FREED}{FREED}\n")
+        );
+
+        let map = FloorMap::new_from_lines(b"@.\n.@".split(|&v| v == b'\n'));
+        let out = render(&map, &[(1, 0)]);
+        let expected = format!("{PALLET}{FREED}\n{EMPTY}{PALLET}\n");
+        assert_eq!(out, expected);
+    }
+}
@@ -0,0 +1,79 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use foodb::interval::random_intervals;
+use foodb::interval::{IntervalSet, IntervalTree};
+use foodb::{bruteforce_interval, random_ids};
+use std::hint::black_box;
+
+// Counting fresh ids by scanning every unmerged interval against a binary
+// search over the normalized set, over its intervals or over flat arrays of
+// their ends, or an interval tree over the unmerged ones, across database
+// and query sizes. Listing every interval holding each id is timed for the
+// tree against the scan, since the merged set can't answer it.
+fn bench_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup");
+    group.sample_size(10);
+    for ints in [100, 1000, 10_000] {
+        // Lengths such that the intervals cover about half the ids.
+        let intervals = random_intervals(ints, 1 << 40, (1 << 40) / ints as u64, 42);
+        let set = IntervalSet::normalize(intervals.iter().cloned());
+        let tree = IntervalTree::new(&intervals);
+        for queries in [1000, 100_000] {
+            let ids = random_ids(&set, queries, 7);
+            // The scan is quadratic; skip the sizes that would take minutes.
+            if ints * queries <= 100_000_000 {
+                group.bench_function(format!("brute force {}x{}", ints, queries), |b| {
+                    b.iter(|| {
+                        black_box(&ids)
+                            .iter()
+                            .filter(|&&id| bruteforce_interval(id, &intervals))
+                            .count()
+                    })
+                });
+                group.bench_function(format!("scan listing {}x{}", ints, queries), |b| {
+                    b.iter(|| {
+                        black_box(&ids)
+                            .iter()
+                            .map(|&id| intervals.iter().filter(|i| i.contains(id)).count())
+                            .sum::<usize>()
+                    })
+                });
+            }
+            group.bench_function(format!("binary search {}x{}", ints, queries), |b| {
+                b.iter(|| {
+                    black_box(&ids)
+                        .iter()
+                        .filter(|&&id| set.contains(id))
+                        .count()
+                })
+            });
+            group.bench_function(format!("sweep batch {}x{}", ints, queries), |b| {
+                b.iter(|| {
+                    set.contains_batch(black_box(&ids))
+                        .into_iter()
+                        .filter(|&fresh| fresh)
+                        .count()
+                })
+            });
+            group.bench_function(format!("tree {}x{}", ints, queries), |b| {
+                b.iter(|| {
+                    black_box(&ids)
+                        .iter()
+                        .filter(|&&id| tree.contains(id))
+                        .count()
+                })
+            });
+            group.bench_function(format!("tree listing {}x{}", ints, queries), |b| {
+                b.iter(|| {
+                    black_box(&ids)
+                        .iter()
+                        .map(|&id| tree.containing(id).len())
+                        .sum::<usize>()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lookup);
+criterion_main!(benches);
@@ -0,0 +1,112 @@
+use criterion::{Criterion, criterion_group, criterion_main};
+use foodb::interval::random_intervals;
+use foodb::interval::{IntervalKind, IntervalSet};
+use foodb::{FoodbProblem, random_ids};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Counts live heap bytes and their high-water mark, so each parse's peak
+// memory can be reported next to Criterion's times.
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(live, Ordering::Relaxed);
+        // SAFETY: passed straight through.
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        // SAFETY: passed straight through.
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+// 10,000 intervals and then ids up to the size, in MiB, that
+// FOODB_PARSE_BENCH_MB asks for. The default keeps a run short; set it in
+// the thousands for the multi-GB case, where reading line by line runs out
+// of memory well before the map does.
+fn generate(path: &Path) -> usize {
+    let mb: usize = env::var("FOODB_PARSE_BENCH_MB")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(64);
+    let intervals = random_intervals(10_000, 1 << 40, 1 << 28, 42);
+    let set = IntervalSet::normalize(intervals.iter().cloned());
+    let mut out = BufWriter::new(File::create(path).unwrap());
+    for int in &intervals {
+        writeln!(out, "{}", int).unwrap();
+    }
+    writeln!(out).unwrap();
+    let mut written = 0;
+    let mut seed = 7;
+    while written < mb << 20 {
+        for id in random_ids(&set, 1 << 16, seed) {
+            let line = format!("{}\n", id);
+            written += line.len();
+            out.write_all(line.as_bytes()).unwrap();
+        }
+        seed += 1;
+    }
+    out.flush().unwrap();
+    written
+}
+
+// How new_from_file read inputs before mapping them: a Vec per line.
+fn parse_lines(path: &Path) -> FoodbProblem {
+    let lines: Vec<Vec<u8>> = BufReader::new(File::open(path).unwrap())
+        .split(b'\n')
+        .map(|l| l.unwrap())
+        .collect();
+    FoodbProblem::new_from_lines(lines, IntervalKind::Closed).unwrap()
+}
+
+fn parse_mapped(path: &Path) -> FoodbProblem {
+    FoodbProblem::new_from_file(path, IntervalKind::Closed).unwrap()
+}
+
+fn peak_of(parse: impl Fn() -> FoodbProblem) -> (usize, usize) {
+    PEAK.store(LIVE.load(Ordering::Relaxed), Ordering::Relaxed);
+    let before = LIVE.load(Ordering::Relaxed);
+    let fdb = parse();
+    (fdb.to_check.len(), PEAK.load(Ordering::Relaxed) - before)
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let path = env::temp_dir().join(format!("foodb-parse-bench-{}.txt", std::process::id()));
+    let bytes = generate(&path);
+    for (name, parse) in [
+        ("per-line Vecs", parse_lines as fn(&Path) -> FoodbProblem),
+        ("mapped", parse_mapped),
+    ] {
+        let (ids, peak) = peak_of(|| parse(&path));
+        println!(
+            "{}: {} ids from {} MiB, peak heap {} MiB",
+            name,
+            ids,
+            bytes >> 20,
+            peak >> 20
+        );
+    }
+    let mut group = c.benchmark_group("parse");
+    group.sample_size(10);
+    group.bench_function("per-line Vecs", |b| b.iter(|| parse_lines(&path)));
+    group.bench_function("mapped", |b| b.iter(|| parse_mapped(&path)));
+    group.finish();
+    fs::remove_file(&path).unwrap();
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+// The ways foodb can answer lookups, picked with --engine: binary searches
+// over the merged set, an interval tree over the intervals as they were
+// read, or a scan of them. All three answer the same questions; they differ
+// in what they build first and in which questions come cheap.
+use super::Int;
+use super::interval::{ClosedInt, IntervalSet, IntervalTree, OverlapCounter};
+use super::{bruteforce_interval, bruteforce_overlaps};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Engine {
+    Sorted,
+    Tree,
+    Brute,
+}
+
+impl Engine {
+    pub fn from_name(name: &str) -> Option<Engine> {
+        match name {
+            "sorted" => Some(Engine::Sorted),
+            "tree" => Some(Engine::Tree),
+            "brute" => Some(Engine::Brute),
+            _ => None,
+        }
+    }
+}
+
+pub enum Lookup<'a, N = u64> {
+    // Merging loses which intervals held an id, so listing them falls back
+    // to a scan.
+    Sorted {
+        set: IntervalSet<N>,
+        counter: OverlapCounter<N>,
+        ints: &'a [ClosedInt<N>],
+    },
+    Tree(IntervalTree<N>),
+    Brute(&'a [ClosedInt<N>]),
+}
+
+impl<'a, N: Int> Lookup<'a, N> {
+    pub fn new(engine: Engine, ints: &'a [ClosedInt<N>]) -> Self {
+        match engine {
+            Engine::Sorted => Lookup::Sorted {
+                set: IntervalSet::normalize(ints.iter().cloned()),
+                counter: OverlapCounter::new(ints),
+                ints,
+            },
+            Engine::Tree => Lookup::Tree(IntervalTree::new(ints)),
+            Engine::Brute => Lookup::Brute(ints),
+        }
+    }
+
+    // Whether any interval holds id.
+    pub fn contains(&self, id: N) -> bool {
+        match self {
+            Lookup::Sorted { set, .. } => set.contains(id),
+            Lookup::Tree(tree) => tree.contains(id),
+            Lookup::Brute(ints) => bruteforce_interval(id, ints),
+        }
+    }
+
+    // How many of the intervals hold id.
+    pub fn count(&self, id: N) -> usize {
+        match self {
+            Lookup::Sorted { counter, .. } => counter.count(id),
+            Lookup::Tree(tree) => tree.count(id),
+            Lookup::Brute(ints) => bruteforce_overlaps(id, ints),
+        }
+    }
+
+    // The indexes of the intervals holding id, in order.
+    pub fn containing(&self, id: N) -> Vec<usize> {
+        match self {
+            Lookup::Sorted { ints, .. } | Lookup::Brute(ints) => {
+                (0..ints.len()).filter(|&i| ints[i].contains(id)).collect()
+            }
+            Lookup::Tree(tree) => tree.containing(id),
+        }
+    }
+}
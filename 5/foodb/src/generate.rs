@@ -0,0 +1,155 @@
+// Random problems whose answers are known by construction, for checking the
+// solver against something other than itself. The covered ids are laid out
+// first as disjoint runs with gaps between them, and the intervals are
+// overlapping and touching pieces that cover exactly those runs, shuffled.
+// Each query id is put in a run or a gap on purpose, so the fresh count and
+// the coverage come from the layout, with no merging or lookups involved.
+use super::FoodbProblem;
+use super::interval::ClosedInt;
+use aoc_utils::Xorshift;
+use std::io::{self, Write};
+
+pub struct Generated {
+    pub problem: FoodbProblem,
+    // What normalizing the intervals has to give.
+    pub runs: Vec<ClosedInt>,
+    pub fresh: u64,
+    pub covered: u128,
+}
+
+// `runs` covered runs of up to `max_len` ids each, at least one and up to
+// `max_gap` ids apart, and `ids` query ids, about half of them fresh.
+pub fn generate(runs: usize, max_len: u64, max_gap: u64, ids: usize, seed: u64) -> Generated {
+    let mut rng = Xorshift::new(seed ^ 0x9e37_79b9_7f4a_7c15);
+    let mut next = || rng.next_u64();
+    let mut layout = Vec::with_capacity(runs);
+    let mut pos = next() % (max_gap + 1);
+    for _ in 0..runs {
+        let low = pos;
+        let high = low + next() % max_len.max(1);
+        layout.push(ClosedInt::new(low, high).unwrap());
+        pos = high + 2 + next() % max_gap.max(1);
+    }
+    let mut intervals = Vec::new();
+    for run in &layout {
+        // Cut the run into consecutive pieces, which touch, then stretch
+        // some of them over their neighbours without leaving the run.
+        let mut start = run.low();
+        while start <= run.high() {
+            let end = (start + next() % (run.length() as u64).div_ceil(3).max(1)).min(run.high());
+            let low = match next() % 3 {
+                0 => start - next() % (start - run.low() + 1),
+                _ => start,
+            };
+            let high = match next() % 3 {
+                0 => end + next() % (run.high() - end + 1),
+                _ => end,
+            };
+            intervals.push(ClosedInt::new(low, high).unwrap());
+            if next().is_multiple_of(8) {
+                intervals.push(ClosedInt::new(low, high).unwrap());
+            }
+            start = end + 1;
+        }
+    }
+    for i in (1..intervals.len()).rev() {
+        intervals.swap(i, next() as usize % (i + 1));
+    }
+    let mut to_check = Vec::with_capacity(ids);
+    let mut fresh = 0;
+    for _ in 0..ids {
+        if layout.is_empty() {
+            to_check.push(next() % 1000);
+            continue;
+        }
+        let at = next() as usize % layout.len();
+        let run = &layout[at];
+        let last = &layout[layout.len() - 1];
+        let floor = match at {
+            0 => 0,
+            _ => layout[at - 1].high() + 1,
+        };
+        // Half in a run, a quarter in the gap below one and the rest past
+        // the last, as are those for a first run with nothing below it.
+        let id = match next() % 4 {
+            0 | 1 => {
+                fresh += 1;
+                run.low() + next() % run.length() as u64
+            }
+            2 if floor < run.low() => floor + next() % (run.low() - floor),
+            _ => last.high() + 1 + next() % 1000,
+        };
+        to_check.push(id);
+    }
+    let covered = layout.iter().map(|r| r.length()).sum();
+    Generated {
+        problem: FoodbProblem {
+            intervals,
+            to_check,
+        },
+        runs: layout,
+        fresh,
+        covered,
+    }
+}
+
+// The problem in the input format, intervals, a blank line, then ids.
+pub fn write_input<W: Write>(out: &mut W, problem: &FoodbProblem) -> io::Result<()> {
+    for int in &problem.intervals {
+        writeln!(out, "{}", int)?;
+    }
+    writeln!(out)?;
+    for id in &problem.to_check {
+        writeln!(out, "{}", id)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Engine, Lookup};
+    use crate::interval::{IntervalKind, IntervalSet};
+    use crate::{bruteforce_interval, count_fresh, input_lines};
+
+    // The solver, from parsing the written input on, against the answers
+    // the generator built in, and the naive scan against both.
+    #[test]
+    fn test_generated_answers() {
+        let mut case = 0;
+        for seed in 1..=60u64 {
+            for (runs, max_len, max_gap, ids) in [
+                (0, 10, 10, 20),
+                (1, 1, 1, 20),
+                (5, 3, 1, 100),
+                (20, 50, 5, 300),
+                (100, 1 << 20, 1 << 30, 500),
+            ] {
+                case += 1;
+                let generated = generate(runs, max_len, max_gap, ids, seed * 1000 + case);
+                let mut text = Vec::new();
+                write_input(&mut text, &generated.problem).unwrap();
+                let fdb =
+                    FoodbProblem::<u64>::new_from_lines(input_lines(&text), IntervalKind::Closed)
+                        .unwrap();
+                assert_eq!(fdb.intervals.len(), generated.problem.intervals.len());
+                assert_eq!(fdb.to_check, generated.problem.to_check);
+
+                let set = IntervalSet::normalize(fdb.intervals.iter().cloned());
+                assert_eq!(set.intervals(), generated.runs, "case {}", case);
+                assert_eq!(set.coverage(), generated.covered, "case {}", case);
+                for engine in [Engine::Sorted, Engine::Tree, Engine::Brute] {
+                    let lookup = Lookup::new(engine, &fdb.intervals);
+                    let fresh = count_fresh(&lookup, &fdb.to_check, None);
+                    assert_eq!(fresh, generated.fresh, "case {} {:?}", case, engine);
+                }
+                let naive = fdb
+                    .to_check
+                    .iter()
+                    .filter(|&&id| bruteforce_interval(id, &fdb.intervals))
+                    .count();
+                assert_eq!(naive as u64, generated.fresh, "case {}", case);
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+// Intervals labeled `name: lo-hi`, for sorting ids into categories rather
+// than just counting them. Labels ride along through merging, so each merged
+// range knows every label that went into it, and a query reports the labels
+// of the intervals that hold it, which can be fewer than its range's.
+use super::interval::{ClosedInt, IntervalTree};
+use super::{Int, Labeled};
+
+// The merged ranges, as normalize would give them, each with the labels of
+// the intervals merged into it, sorted and without repeats.
+pub fn merge_labeled<N: Int>(ints: &[Labeled<N>]) -> Vec<(ClosedInt<N>, Vec<String>)> {
+    let mut sorted: Vec<&Labeled<N>> = ints.iter().collect();
+    sorted.sort_by(|a, b| a.1.cmp(&b.1));
+    let mut merged: Vec<(ClosedInt<N>, Vec<String>)> = Vec::new();
+    for (label, int) in sorted {
+        if let Some((last, labels)) = merged.last_mut()
+            && let Ok(joined) = last.merge(int, true)
+        {
+            *last = joined;
+            labels.extend(label.clone());
+            continue;
+        }
+        merged.push((int.clone(), label.iter().cloned().collect()));
+    }
+    for (_, labels) in &mut merged {
+        labels.sort_unstable();
+        labels.dedup();
+    }
+    merged
+}
+
+// Which labels hold an id, by interval tree over the unmerged intervals.
+pub struct LabelIndex<N = u64> {
+    tree: IntervalTree<N>,
+    labels: Vec<Option<String>>,
+}
+
+impl<N: Int> LabelIndex<N> {
+    pub fn new(ints: &[Labeled<N>]) -> Self {
+        LabelIndex {
+            tree: IntervalTree::new(ints.iter().map(|(_, int)| int)),
+            labels: ints.iter().map(|(label, _)| label.clone()).collect(),
+        }
+    }
+
+    // None when no interval holds id; otherwise the labels of those that
+    // do, sorted and without repeats, and empty if none of them has one.
+    pub fn labels_of(&self, id: N) -> Option<Vec<&str>> {
+        let holding = self.tree.containing(id);
+        if holding.is_empty() {
+            return None;
+        }
+        let mut labels: Vec<&str> = holding
+            .into_iter()
+            .filter_map(|i| self.labels[i].as_deref())
+            .collect();
+        labels.sort_unstable();
+        labels.dedup();
+        Some(labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FoodbProblem;
+    use crate::interval::{IntervalKind, IntervalSet};
+
+    #[test]
+    fn test_labels() {
+        let lines = b"fruit: 3-5, veg: 10-14
+dairy: 16-20
+veg: 12-18
+21-22
+fruit: 1-2
+
+1
+5
+11
+17";
+        let labeled = FoodbProblem::<u64>::labeled_from_lines(
+            lines.split(|&v| v == b'\n'),
+            IntervalKind::Closed,
+        )
+        .unwrap();
+        let fdb =
+            FoodbProblem::<u64>::new_from_lines(lines.split(|&v| v == b'\n'), IntervalKind::Closed)
+                .unwrap();
+        let unlabeled: Vec<_> = labeled.iter().map(|(_, int)| int.clone()).collect();
+        assert_eq!(unlabeled, fdb.intervals);
+
+        let merged = merge_labeled(&labeled);
+        let set = IntervalSet::normalize(fdb.intervals.clone());
+        let ranges: Vec<_> = merged.iter().map(|(int, _)| int.clone()).collect();
+        assert_eq!(ranges, set.intervals());
+        let labels: Vec<_> = merged.iter().map(|(_, labels)| labels.join(",")).collect();
+        assert_eq!(labels, ["fruit", "dairy,veg"]);
+
+        let index = LabelIndex::new(&labeled);
+        let found: Vec<_> = [1, 5, 8, 11, 17, 22]
+            .map(|id| index.labels_of(id).map(|l| l.join(",")))
+            .into_iter()
+            .collect();
+        assert_eq!(
+            found,
+            [
+                Some("fruit".to_string()),
+                Some("fruit".to_string()),
+                None,
+                Some("veg".to_string()),
+                Some("dairy,veg".to_string()),
+                Some(String::new())
+            ]
+        );
+
+        let bad =
+            FoodbProblem::<u64>::labeled_from_lines([b": 3-5".as_slice()], IntervalKind::Closed);
+        assert!(bad.is_err());
+        let bad = FoodbProblem::<u64>::new_from_lines([b"x: 3".as_slice()], IntervalKind::Closed);
+        assert!(bad.is_err());
+    }
+}
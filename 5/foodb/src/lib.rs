@@ -0,0 +1,873 @@
+use aoc_utils::Xorshift;
+use rayon::prelude::*;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::error::Error;
+use std::fmt;
+use std::io::BufRead;
+use std::iter::Peekable;
+use std::path::Path;
+
+pub mod engine;
+pub mod generate;
+pub mod labels;
+pub mod mapped;
+pub mod save;
+pub mod validate;
+pub mod viz;
+
+pub use aoc_utils::interval;
+use engine::Lookup;
+use interval::{ClosedInt, IntervalSet, InvalidClosedInt, Syntax};
+pub use interval::{InvalidAsciiInt, ascii_to_int, is_separator};
+use mapped::MappedFile;
+
+// What foodb's ids can be: any interval Int that saves, too.
+pub trait Int: interval::Int + Serialize + DeserializeOwned {}
+
+impl<N> Int for N where N: interval::Int + Serialize + DeserializeOwned {}
+
+pub fn bruteforce_interval<N: Int>(val: N, intervals: &[ClosedInt<N>]) -> bool {
+    for i in intervals {
+        if i.contains(val) {
+            return true;
+        }
+    }
+    false
+}
+
+pub fn bruteforce_overlaps<N: Int>(val: N, intervals: &[ClosedInt<N>]) -> usize {
+    intervals.iter().filter(|i| i.contains(val)).count()
+}
+
+// The lines of a whole input as slices of it, split the way BufRead::split
+// does: a final newline doesn't leave an empty line after it, and an empty
+// input has no lines.
+pub fn input_lines(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let body = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    (!bytes.is_empty())
+        .then(|| body.split(|&c| c == b'\n'))
+        .into_iter()
+        .flatten()
+}
+
+// The two parts of an input, with a blank line between them, or the lines
+// of an updates input, which has no parts.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Section {
+    Intervals,
+    Ids,
+    Updates,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Section::Intervals => write!(f, "interval"),
+            Section::Ids => write!(f, "id"),
+            Section::Updates => write!(f, "update"),
+        }
+    }
+}
+
+// Line numbers are 1-based.
+#[derive(Debug, PartialEq)]
+pub enum FoodbParseError {
+    Unparsable {
+        line: usize,
+        section: Section,
+        text: String,
+    },
+    // A blank line among the ids, which only the one at the end may be.
+    UnexpectedBlank {
+        line: usize,
+    },
+}
+
+impl fmt::Display for FoodbParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FoodbParseError::Unparsable {
+                line,
+                section,
+                text,
+            } => write!(
+                f,
+                "Line {}: couldn't parse {:?} as an {}",
+                line, text, section
+            ),
+            FoodbParseError::UnexpectedBlank { line } => {
+                write!(f, "Line {}: blank line in the middle of the ids", line)
+            }
+        }
+    }
+}
+
+impl Error for FoodbParseError {}
+
+// The comma-separated intervals on a line, as spreadsheets export them:
+// `3-5,10-14, 16-20`. Commas inside brackets, as in `[3, 7)`, belong to the
+// interval, and blank pieces are skipped.
+fn split_intervals(line: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut depth = 0;
+    line.split(move |&c| {
+        match c {
+            b'[' | b'(' => depth += 1,
+            b']' | b')' => depth -= 1,
+            _ => {}
+        }
+        c == b',' && depth <= 0
+    })
+    .filter(|piece| !piece.trim_ascii().is_empty())
+}
+
+// A piece's `name:` label, if it has one, and the interval after it.
+// Intervals have no colons of their own.
+fn split_label(piece: &[u8]) -> (Option<&[u8]>, &[u8]) {
+    match piece.iter().position(|&c| c == b':') {
+        Some(colon) => (Some(piece[..colon].trim_ascii()), &piece[colon + 1..]),
+        None => (None, piece),
+    }
+}
+
+// An interval and the 1-based input line it came from.
+pub type Numbered<N> = (usize, ClosedInt<N>);
+
+// An interval and its label, if its input gave it one.
+pub type Labeled<N> = (Option<String>, ClosedInt<N>);
+
+pub struct FoodbProblem<N = u64> {
+    pub intervals: Vec<ClosedInt<N>>,
+    pub to_check: Vec<N>,
+}
+
+impl<N: Int> FoodbProblem<N> {
+    // Saved problems (.json and .bin) load as they were saved.
+    pub fn new_from_file<P>(filename: P, syntax: impl Into<Syntax>) -> Result<Self, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        if save::SaveFormat::from_path(&filename).is_some() {
+            return Self::load(filename);
+        }
+        let map = MappedFile::open(filename)?;
+        Ok(Self::new_from_lines(input_lines(&map), syntax)?)
+    }
+
+    // The syntax's kind is how to read `lo-hi` intervals; bracketed ones
+    // say for themselves. Intervals that hold no ids are dropped, and so are
+    // `name:` labels, which labeled_intervals keeps.
+    pub fn new_from_lines<I, S, T>(
+        line_iter: T,
+        syntax: impl Into<Syntax>,
+    ) -> Result<Self, FoodbParseError>
+    where
+        I: Iterator<Item = S>,
+        S: AsRef<[u8]>,
+        T: IntoIterator<IntoIter = I, Item = S>,
+    {
+        let mut line_iter = line_iter.into_iter().enumerate().peekable();
+        let intervals = Self::parse_intervals(&mut line_iter, syntax.into())?;
+        let to_check = Self::parse_ids(&mut line_iter)?;
+        Ok(FoodbProblem {
+            intervals,
+            to_check,
+        })
+    }
+
+    pub fn sections_from_file<P>(
+        filename: P,
+        syntax: impl Into<Syntax>,
+    ) -> Result<Vec<Self>, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let map = MappedFile::open(filename)?;
+        Ok(Self::sections_from_lines(input_lines(&map), syntax)?)
+    }
+
+    // Several databases in one input, each an interval block and an id
+    // block like a whole input, with blank lines between the blocks. Runs
+    // of blank lines count as one, and line numbers in errors run on
+    // through the whole input.
+    pub fn sections_from_lines<I, S, T>(
+        line_iter: T,
+        syntax: impl Into<Syntax>,
+    ) -> Result<Vec<Self>, FoodbParseError>
+    where
+        I: Iterator<Item = S>,
+        S: AsRef<[u8]>,
+        T: IntoIterator<IntoIter = I, Item = S>,
+    {
+        let syntax = syntax.into();
+        let mut line_iter = line_iter.into_iter().enumerate().peekable();
+        let mut sections = Vec::new();
+        loop {
+            while line_iter.next_if(|(_, l)| l.as_ref().is_empty()).is_some() {}
+            if line_iter.peek().is_none() {
+                return Ok(sections);
+            }
+            let intervals = Self::parse_intervals(&mut line_iter, syntax)?;
+            while line_iter.next_if(|(_, l)| l.as_ref().is_empty()).is_some() {}
+            let (to_check, _) = Self::parse_id_block(&mut line_iter)?;
+            sections.push(FoodbProblem {
+                intervals,
+                to_check,
+            });
+        }
+    }
+
+    // The intervals of an input, as new_from_file reads them, each with
+    // its line, for pointing back at the input.
+    pub fn intervals_with_lines<P>(
+        filename: P,
+        syntax: impl Into<Syntax>,
+    ) -> Result<Vec<Numbered<N>>, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let map = MappedFile::open(filename)?;
+        let mut line_iter = input_lines(&map).enumerate().peekable();
+        Ok(Self::parse_numbered_intervals(
+            &mut line_iter,
+            syntax.into(),
+        )?)
+    }
+
+    // The intervals of an input with their labels, in the order
+    // new_from_file reads them.
+    pub fn labeled_intervals<P>(
+        filename: P,
+        syntax: impl Into<Syntax>,
+    ) -> Result<Vec<Labeled<N>>, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let map = MappedFile::open(filename)?;
+        Ok(Self::labeled_from_lines(input_lines(&map), syntax)?)
+    }
+
+    pub fn labeled_from_lines<I, S, T>(
+        line_iter: T,
+        syntax: impl Into<Syntax>,
+    ) -> Result<Vec<Labeled<N>>, FoodbParseError>
+    where
+        I: Iterator<Item = S>,
+        S: AsRef<[u8]>,
+        T: IntoIterator<IntoIter = I, Item = S>,
+    {
+        let mut line_iter = line_iter.into_iter().enumerate().peekable();
+        let mut ints = Vec::new();
+        Self::parse_interval_block(&mut line_iter, syntax.into(), |_, label, int| {
+            let label = label.map(|l| String::from_utf8_lossy(l).into_owned());
+            ints.push((label, int));
+        })?;
+        ints.sort_by(|a, b| a.1.cmp(&b.1));
+        Ok(ints)
+    }
+
+    // Just ids, one per line, as in a --queries file.
+    pub fn ids_from_lines<I, S, T>(line_iter: T) -> Result<Vec<N>, FoodbParseError>
+    where
+        I: Iterator<Item = S>,
+        S: AsRef<[u8]>,
+        T: IntoIterator<IntoIter = I, Item = S>,
+    {
+        Self::parse_ids(&mut line_iter.into_iter().enumerate().peekable())
+    }
+
+    // The intervals up to the next blank line, sorted.
+    fn parse_intervals<I, S>(
+        line_iter: &mut Peekable<I>,
+        syntax: Syntax,
+    ) -> Result<Vec<ClosedInt<N>>, FoodbParseError>
+    where
+        I: Iterator<Item = (usize, S)>,
+        S: AsRef<[u8]>,
+    {
+        let ints = Self::parse_numbered_intervals(line_iter, syntax)?;
+        Ok(ints.into_iter().map(|(_, int)| int).collect())
+    }
+
+    // The same, each with its line number, and those in line order where
+    // intervals tie.
+    fn parse_numbered_intervals<I, S>(
+        line_iter: &mut Peekable<I>,
+        syntax: Syntax,
+    ) -> Result<Vec<Numbered<N>>, FoodbParseError>
+    where
+        I: Iterator<Item = (usize, S)>,
+        S: AsRef<[u8]>,
+    {
+        let mut ints = Vec::<(usize, ClosedInt<N>)>::new();
+        Self::parse_interval_block(line_iter, syntax, |line, _, int| ints.push((line, int)))?;
+        ints.sort_by(|a, b| a.1.cmp(&b.1));
+        Ok(ints)
+    }
+
+    // Hands each interval up to the next blank line to `push` with its
+    // line and any label, in input order; labels are dropped unless asked
+    // for, so they cost nothing to the usual parse.
+    fn parse_interval_block<I, S>(
+        line_iter: &mut Peekable<I>,
+        syntax: Syntax,
+        mut push: impl FnMut(usize, Option<&[u8]>, ClosedInt<N>),
+    ) -> Result<(), FoodbParseError>
+    where
+        I: Iterator<Item = (usize, S)>,
+        S: AsRef<[u8]>,
+    {
+        for (lineno, line) in line_iter.by_ref() {
+            let line = line.as_ref();
+            if line.is_empty() {
+                break;
+            }
+            for piece in split_intervals(line) {
+                let (label, int) = split_label(piece);
+                let parsed = match (label, syntax.lenient) {
+                    (Some(b""), _) => Err(InvalidClosedInt),
+                    (_, true) => ClosedInt::parse_lenient(int, syntax.kind),
+                    (_, false) => ClosedInt::parse(int, syntax.kind),
+                };
+                match parsed {
+                    Ok(x) => x.into_iter().for_each(|int| push(lineno + 1, label, int)),
+                    Err(_) => {
+                        return Err(FoodbParseError::Unparsable {
+                            line: lineno + 1,
+                            section: Section::Intervals,
+                            text: String::from_utf8_lossy(piece.trim_ascii()).into_owned(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The ids through the end, where only a final blank line may be.
+    fn parse_ids<I, S>(line_iter: &mut Peekable<I>) -> Result<Vec<N>, FoodbParseError>
+    where
+        I: Iterator<Item = (usize, S)>,
+        S: AsRef<[u8]>,
+    {
+        match Self::parse_id_block(line_iter)? {
+            (_, Some(line)) if line_iter.peek().is_some() => {
+                Err(FoodbParseError::UnexpectedBlank { line })
+            }
+            (ids, _) => Ok(ids),
+        }
+    }
+
+    // The ids up to the next blank line, and that line's number if there
+    // was one.
+    fn parse_id_block<I, S>(
+        line_iter: &mut Peekable<I>,
+    ) -> Result<(Vec<N>, Option<usize>), FoodbParseError>
+    where
+        I: Iterator<Item = (usize, S)>,
+        S: AsRef<[u8]>,
+    {
+        let mut ids = Vec::<N>::new();
+        for (lineno, line) in line_iter.by_ref() {
+            let line = line.as_ref();
+            if line.is_empty() {
+                return Ok((ids, Some(lineno + 1)));
+            }
+            match ascii_to_int(line) {
+                Ok(x) => ids.push(x),
+                Err(_) => {
+                    return Err(FoodbParseError::Unparsable {
+                        line: lineno + 1,
+                        section: Section::Ids,
+                        text: String::from_utf8_lossy(line).into_owned(),
+                    });
+                }
+            }
+        }
+        Ok((ids, None))
+    }
+}
+
+// Queries per rayon task: enough that splitting costs little next to the
+// lookups.
+const QUERY_CHUNK: usize = 1 << 14;
+
+// How many of `ids` the lookup's intervals hold, counted over chunks on
+// rayon's pool. With `verify`, each is checked against a scan of those
+// unmerged intervals too.
+pub fn count_fresh<N: Int>(lookup: &Lookup<N>, ids: &[N], verify: Option<&[ClosedInt<N>]>) -> u64 {
+    ids.par_chunks(QUERY_CHUNK)
+        .map(|chunk| {
+            let mut fresh = 0;
+            for &c in chunk {
+                let hit = lookup.contains(c);
+                if let Some(ints) = verify
+                    && hit != bruteforce_interval(c, ints)
+                {
+                    panic!("{}", c);
+                }
+                fresh += hit as u64;
+            }
+            fresh
+        })
+        .sum()
+}
+
+// `n` ids spread evenly over the span of the set, from lowest bound to
+// highest, for benchmarks; empty if the set is.
+pub fn random_ids<N: Int>(set: &IntervalSet<N>, n: usize, seed: u64) -> Vec<N> {
+    let (Some(first), Some(last)) = (set.intervals().first(), set.intervals().last()) else {
+        return Vec::new();
+    };
+    let low = first.low();
+    let span = ClosedInt::new(low, last.high()).unwrap().length();
+    let mut rng = Xorshift::new(seed);
+    let mut next = || rng.next_u64();
+    // Added in two halves, each of which fits N even when the span doesn't
+    // fit its positive half.
+    (0..n)
+        .map(|_| {
+            let offset = ((next() as u128) << 64 | next() as u128) % span;
+            let half = offset / 2;
+            low + N::from(half).unwrap() + N::from(offset - half).unwrap()
+        })
+        .collect()
+}
+
+// Hand each id in `reader` to `check` as it's read, one line in memory at a
+// time, and return how many there were. Lines are as in ids_from_lines.
+pub fn stream_ids<N: Int, R: BufRead>(
+    mut reader: R,
+    mut check: impl FnMut(N),
+) -> Result<u64, Box<dyn Error>> {
+    let mut line = Vec::new();
+    let mut count = 0;
+    // A blank line that's fine only if nothing comes after it.
+    let mut blank = None;
+    for lineno in 1.. {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        if let Some(blank) = blank {
+            return Err(From::from(FoodbParseError::UnexpectedBlank { line: blank }));
+        }
+        if line.is_empty() {
+            blank = Some(lineno);
+            continue;
+        }
+        match ascii_to_int(&line) {
+            Ok(id) => check(id),
+            Err(_) => {
+                return Err(From::from(FoodbParseError::Unparsable {
+                    line: lineno,
+                    section: Section::Ids,
+                    text: String::from_utf8_lossy(&line).into_owned(),
+                }));
+            }
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+// Run an updates input against `set`, a line at a time: `+lo-hi` inserts an
+// interval, a bare id is handed to `answer` with whether the set holds it
+// right then, and `-lo-hi` removes an interval. A line that reads as an id
+// is one, so -5 asks about -5 rather than removing 5, which a remove can't
+// mean anyway without a separator. Blank lines are skipped. Returns how
+// many ids were asked about.
+pub fn stream_updates<N: Int, R: BufRead>(
+    mut reader: R,
+    syntax: impl Into<Syntax>,
+    set: &mut IntervalSet<N>,
+    mut answer: impl FnMut(N, bool),
+) -> Result<u64, Box<dyn Error>> {
+    let syntax = syntax.into();
+    let parse = |txt: &[u8]| match syntax.lenient {
+        true => ClosedInt::parse_lenient(txt, syntax.kind),
+        false => ClosedInt::parse(txt, syntax.kind),
+    };
+    let mut line = Vec::new();
+    let mut count = 0;
+    for lineno in 1.. {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let text = line.trim_ascii();
+        let done = if text.is_empty() {
+            Ok(())
+        } else if let Some(int) = text.strip_prefix(b"+") {
+            parse(int).map(|int| int.into_iter().for_each(|int| set.insert(int)))
+        } else if let Ok(id) = ascii_to_int(text) {
+            answer(id, set.contains(id));
+            count += 1;
+            Ok(())
+        } else if let Some(int) = text.strip_prefix(b"-") {
+            parse(int).map(|int| int.iter().for_each(|int| set.remove(int)))
+        } else {
+            Err(InvalidClosedInt)
+        };
+        if done.is_err() {
+            return Err(From::from(FoodbParseError::Unparsable {
+                line: lineno,
+                section: Section::Updates,
+                text: String::from_utf8_lossy(text).into_owned(),
+            }));
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::Engine;
+    use interval::{IntervalKind, random_intervals};
+
+    #[test]
+    fn test_comma_separated() {
+        let pieces: Vec<&[u8]> = split_intervals(b"3-5,10-14, 16-20 ,").collect();
+        assert_eq!(pieces, [b"3-5".as_slice(), b"10-14", b" 16-20 "]);
+        let pieces: Vec<&[u8]> = split_intervals(b"[3, 7), 9-9,(1,2]").collect();
+        assert_eq!(pieces, [b"[3, 7)".as_slice(), b" 9-9", b"(1,2]"]);
+
+        let lines = b"3-5,10-14, 16-20
+12-18
+
+11
+17";
+        let fdb =
+            FoodbProblem::<u64>::new_from_lines(lines.split(|&v| v == b'\n'), IntervalKind::Closed)
+                .unwrap();
+        assert_eq!(fdb.intervals.len(), 4);
+        // Both on the first line come back numbered with it.
+        let mut line_iter = lines.split(|&v| v == b'\n').enumerate().peekable();
+        let numbered = FoodbProblem::<u64>::parse_numbered_intervals(
+            &mut line_iter,
+            IntervalKind::Closed.into(),
+        )
+        .unwrap();
+        let numbers: Vec<usize> = numbered.iter().map(|&(line, _)| line).collect();
+        assert_eq!(numbers, [1, 1, 2, 1]);
+        assert_eq!(numbered[2].1, ClosedInt::new(12, 18).unwrap());
+        let set = IntervalSet::normalize(fdb.intervals);
+        assert_eq!(
+            set.intervals(),
+            &[
+                ClosedInt::new(3, 5).unwrap(),
+                ClosedInt::new(10, 20).unwrap()
+            ]
+        );
+        let bad =
+            FoodbProblem::<u64>::new_from_lines([b"3-5, 7-x".as_slice()], IntervalKind::Closed);
+        assert_eq!(
+            bad.err(),
+            Some(FoodbParseError::Unparsable {
+                line: 1,
+                section: Section::Intervals,
+                text: "7-x".to_string(),
+            })
+        );
+
+        // Copied from elsewhere, which only the lenient syntax reads.
+        let loose = "3 \u{2013} 5, 10..16\n16 - 20\n\n11\n".as_bytes();
+        let lenient = Syntax {
+            kind: IntervalKind::Closed,
+            lenient: true,
+        };
+        let fdb =
+            FoodbProblem::<u64>::new_from_lines(loose.split(|&v| v == b'\n'), lenient).unwrap();
+        let set = IntervalSet::normalize(fdb.intervals);
+        assert_eq!(
+            set.intervals(),
+            &[
+                ClosedInt::new(3, 5).unwrap(),
+                ClosedInt::new(10, 20).unwrap()
+            ]
+        );
+        let strict =
+            FoodbProblem::<u64>::new_from_lines(loose.split(|&v| v == b'\n'), IntervalKind::Closed);
+        assert!(strict.is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let load = |txt: &[u8]| {
+            FoodbProblem::<u64>::new_from_lines(txt.split(|&v| v == b'\n'), IntervalKind::Closed)
+                .err()
+        };
+        assert_eq!(
+            load(b"3-5\n10-x\n\n1\n"),
+            Some(FoodbParseError::Unparsable {
+                line: 2,
+                section: Section::Intervals,
+                text: "10-x".to_string(),
+            })
+        );
+        assert_eq!(
+            load(b"3-5\n\n1\n-2\n"),
+            Some(FoodbParseError::Unparsable {
+                line: 4,
+                section: Section::Ids,
+                text: "-2".to_string(),
+            })
+        );
+        let blank = load(b"3-5\n\n1\n\n2\n");
+        assert_eq!(blank, Some(FoodbParseError::UnexpectedBlank { line: 4 }));
+        assert_eq!(
+            load(b"5-3\n").unwrap().to_string(),
+            "Line 1: couldn't parse \"5-3\" as an interval"
+        );
+        assert_eq!(load(b"3-5\n\n1\n2\n"), None);
+    }
+
+    #[test]
+    fn test_ids_from_lines() {
+        let ids = FoodbProblem::<u64>::ids_from_lines(b"1\n5\n8\n".split(|&v| v == b'\n'));
+        assert_eq!(ids, Ok(vec![1, 5, 8]));
+        let none: [&[u8]; 0] = [];
+        assert_eq!(FoodbProblem::<u64>::ids_from_lines(none), Ok(vec![]));
+        assert_eq!(
+            FoodbProblem::<u64>::ids_from_lines(b"1\n\n8".split(|&v| v == b'\n')),
+            Err(FoodbParseError::UnexpectedBlank { line: 2 })
+        );
+    }
+
+    #[test]
+    fn test_sections() {
+        let sections = |txt: &[u8]| {
+            FoodbProblem::<u64>::sections_from_lines(
+                txt.split(|&v| v == b'\n'),
+                IntervalKind::Closed,
+            )
+        };
+        let txt = b"3-5\n10-14\n\n1\n5\n\n\n20-30\n\n25\n31\n\n7-7\n\n7\n";
+        let fdbs = sections(txt).unwrap();
+        let ends = |f: &FoodbProblem| (f.intervals.len(), f.to_check.clone());
+        let got: Vec<_> = fdbs.iter().map(ends).collect();
+        assert_eq!(got, [(2, vec![1, 5]), (1, vec![25, 31]), (1, vec![7])]);
+        // One section reads like new_from_lines, trailing newline or not.
+        let one = sections(b"3-5\n\n4\n").unwrap();
+        assert_eq!(one.len(), 1);
+        assert_eq!(one[0].to_check, [4]);
+        assert_eq!(sections(b"3-5\n\n4").unwrap()[0].to_check, [4]);
+        assert_eq!(sections(b"\n\n").unwrap().len(), 0);
+        assert_eq!(
+            sections(b"3-5\n\n4\n\n6-x\n").err(),
+            Some(FoodbParseError::Unparsable {
+                line: 5,
+                section: Section::Intervals,
+                text: "6-x".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_input_lines() {
+        for txt in [
+            b"".as_slice(),
+            b"\n",
+            b"\n\n",
+            b"3-5",
+            b"3-5\n",
+            b"3-5\n\n1\n\n",
+            b"3-5\n\n1",
+        ] {
+            let split: Vec<Vec<u8>> = BufRead::split(txt, b'\n').map(|l| l.unwrap()).collect();
+            let lines: Vec<&[u8]> = input_lines(txt).collect();
+            assert_eq!(lines, split, "{:?}", txt);
+        }
+    }
+
+    #[test]
+    fn test_stream_ids() {
+        let stream = |txt: &[u8]| {
+            let mut ids = Vec::new();
+            stream_ids(txt, |id: u64| ids.push(id)).map(|n| (n, ids))
+        };
+        assert_eq!(stream(b"1\n5\n8\n").unwrap(), (3, vec![1, 5, 8]));
+        assert_eq!(stream(b"1\n5\n8").unwrap(), (3, vec![1, 5, 8]));
+        assert_eq!(stream(b"1\n\n").unwrap(), (1, vec![1]));
+        assert_eq!(stream(b"").unwrap(), (0, vec![]));
+        let err = |txt: &[u8]| stream(txt).unwrap_err().to_string();
+        assert_eq!(
+            err(b"1\n\n8\n"),
+            FoodbParseError::UnexpectedBlank { line: 2 }.to_string()
+        );
+        assert_eq!(err(b"1\nx\n"), "Line 2: couldn't parse \"x\" as an id");
+    }
+
+    #[test]
+    fn test_stream_updates() {
+        let run = |txt: &[u8]| {
+            let mut set = IntervalSet::normalize([]);
+            let mut answers = Vec::new();
+            stream_updates(txt, IntervalKind::Closed, &mut set, |id: i64, fresh| {
+                answers.push((id, fresh))
+            })
+            .map(|n| (n, answers, set.intervals().to_vec()))
+        };
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let txt = b"5\n+3-5\n5\n+10-14\n+6-9\n\n-4-11\n5\n3\n-5\n12\n+[20, 25)\n24\n";
+        let (count, answers, ints) = run(txt).unwrap();
+        assert_eq!(count, 7);
+        let expected = [(5, false), (5, true), (5, false), (3, true), (-5, false)];
+        assert_eq!(answers[..5], expected);
+        assert_eq!(answers[5..], [(12, true), (24, true)]);
+        assert_eq!(ints, [int(3, 3), int(12, 14), int(20, 24)]);
+        let (_, answers, _) = run(b"+-10--3\n-5\n--8--6\n-7\n-3\n").unwrap();
+        assert_eq!(answers, [(-5, true), (-7, false), (-3, true)]);
+        let err = |txt: &[u8]| run(txt).unwrap_err().to_string();
+        assert_eq!(
+            err(b"+3-5\n+x\n"),
+            "Line 2: couldn't parse \"+x\" as an update"
+        );
+        assert_eq!(err(b"3-5\n"), "Line 1: couldn't parse \"3-5\" as an update");
+        assert_eq!(
+            err(b"+5-3\n"),
+            "Line 1: couldn't parse \"+5-3\" as an update"
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let lines = b"3-5
+10-14
+16-20
+12-18
+
+1
+5
+8
+11
+17
+32";
+        let fdb =
+            FoodbProblem::<u64>::new_from_lines(lines.split(|&v| v == b'\n'), IntervalKind::Closed)
+                .unwrap();
+        let mut res = 0;
+        for c in &fdb.to_check {
+            res += bruteforce_interval(*c, &fdb.intervals) as u64;
+        }
+        assert_eq!(res, 3);
+        let set = IntervalSet::normalize(fdb.intervals.clone());
+        let merged = set.intervals();
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0], ClosedInt::new(3, 5).unwrap());
+        assert_eq!(merged[1], ClosedInt::new(10, 20).unwrap());
+        let mut res = 0;
+        for c in &fdb.to_check {
+            res += bruteforce_interval(*c, merged) as u64;
+            assert_eq!(set.contains(*c), bruteforce_interval(*c, merged));
+        }
+        assert_eq!(res, 3);
+        let lookup = Lookup::new(Engine::Sorted, &fdb.intervals);
+        assert_eq!(count_fresh(&lookup, &fdb.to_check, Some(&fdb.intervals)), 3);
+
+        // The same numbers as half-open ranges, which then touch rather than
+        // overlap, and an empty one.
+        let lines = b"3-6
+[10, 15)
+16-21
+(11, 19]
+7-7
+
+5
+6
+15
+16
+20
+21";
+        let fdb =
+            FoodbProblem::new_from_lines(lines.split(|&v| v == b'\n'), IntervalKind::HalfOpen)
+                .unwrap();
+        assert_eq!(fdb.intervals.len(), 4);
+        let set = IntervalSet::normalize(fdb.intervals.clone());
+        assert_eq!(
+            set.intervals(),
+            &[
+                ClosedInt::new(3, 5).unwrap(),
+                ClosedInt::new(10, 20).unwrap()
+            ]
+        );
+        let fresh: Vec<_> = fdb.to_check.iter().map(|&c| set.contains(c)).collect();
+        assert_eq!(fresh, [true, false, true, true, true, false]);
+    }
+
+    // Differential tests over generated databases: every lookup has to agree
+    // with the brute-force scans. Seeded, so a failure names its case.
+    #[test]
+    fn test_lookups_agree() {
+        use interval::OverlapCounter;
+        let mut case = 0;
+        for seed in 1..=40u64 {
+            for (count, max_id, max_len) in [
+                (0, 100, 10),
+                (1, 100, 10),
+                (20, 100, 5),
+                (50, 100, 30),
+                (200, 1 << 40, 1 << 30),
+                (30, u64::MAX, u64::MAX / 8),
+            ] {
+                case += 1;
+                let ints = random_intervals(count, max_id, max_len, seed * 1000 + case);
+                let set = IntervalSet::normalize(ints.iter().cloned());
+                let counter = OverlapCounter::new(&ints);
+                let engines = [Engine::Sorted, Engine::Tree, Engine::Brute]
+                    .map(|engine| Lookup::new(engine, &ints));
+                // Generated ids, plus both ends of every interval and the ids
+                // just outside them, where off-by-ones would show.
+                let mut ids = random_ids(&set, 200, seed);
+                for i in &ints {
+                    ids.extend([i.low(), i.high()]);
+                    ids.extend(i.low().checked_sub(1));
+                    ids.extend(i.high().checked_add(1));
+                }
+                ids.extend([0, u64::MAX]);
+                for &id in &ids {
+                    let expected = bruteforce_interval(id, &ints);
+                    assert_eq!(
+                        set.contains(id),
+                        expected,
+                        "seed {} case {} id {}",
+                        seed,
+                        case,
+                        id
+                    );
+                    assert_eq!(
+                        counter.count(id),
+                        bruteforce_overlaps(id, &ints),
+                        "seed {} case {} id {}",
+                        seed,
+                        case,
+                        id
+                    );
+                    let holding: Vec<usize> =
+                        (0..ints.len()).filter(|&i| ints[i].contains(id)).collect();
+                    for lookup in &engines {
+                        assert_eq!(lookup.contains(id), expected, "case {} id {}", case, id);
+                        assert_eq!(lookup.count(id), holding.len(), "case {} id {}", case, id);
+                        assert_eq!(lookup.containing(id), holding, "case {} id {}", case, id);
+                    }
+                    let next = set.next_uncovered(id);
+                    if let Some(next) = next {
+                        assert!(next >= id && !bruteforce_interval(next, &ints));
+                    }
+                    assert_eq!(next == Some(id), !expected);
+                }
+                let fresh = ids
+                    .iter()
+                    .filter(|&&id| bruteforce_interval(id, &ints))
+                    .count();
+                for lookup in &engines {
+                    assert_eq!(count_fresh(lookup, &ids, Some(&ints)), fresh as u64);
+                }
+            }
+        }
+    }
+}
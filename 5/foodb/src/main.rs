@@ -1,351 +1,961 @@
-use log::info;
+use foodb::engine::{Engine, Lookup};
+use foodb::generate::{generate, write_input};
+use foodb::interval::{
+    ClosedInt, CoveredRanks, IntervalKind, IntervalSet, OverlapCounter, SortedIds, Syntax,
+    max_disjoint,
+};
+use foodb::labels::{LabelIndex, merge_labeled};
+use foodb::mapped::MappedFile;
+use foodb::save::{SaveFormat, saved_int};
+use foodb::validate::{Finding, drop_redundant, validate_file};
+use foodb::viz::{DEFAULT_VIZ_COLUMNS, write_viz};
+use foodb::{
+    FoodbProblem, Int, ascii_to_int, bruteforce_interval, bruteforce_overlaps, count_fresh,
+    is_separator, random_ids, stream_ids, stream_updates,
+};
 use simple_logger::SimpleLogger;
-use std::borrow::Cow;
 use std::env;
 use std::error::Error;
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::Path;
-
-#[derive(Debug, PartialEq)]
-struct InvalidAsciiU64;
-
-fn ascii_to_u64(bytes: &[u8]) -> Result<u64, InvalidAsciiU64> {
-    let mut res = 0;
-    for (i, c) in bytes.iter().enumerate() {
-        if *c < b'0' || *c > b'9' {
-            return Err(InvalidAsciiU64);
-        }
-        let val = (*c - b'0') as u64;
-        res += val * 10_u64.pow((bytes.len() - i - 1) as u32);
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::time::Instant;
+
+// Time lookups of `n` ids spread evenly over the set's span, on one thread
+// and then on the pool.
+fn bench_queries<N: Int>(set: &IntervalSet<N>, lookup: &Lookup<N>, n: usize) {
+    if set.intervals().is_empty() {
+        println!("no intervals to query");
+        return;
     }
-    Ok(res)
+    let ids = random_ids(set, n, 0x9e37_79b9_7f4a_7c15);
+    let report = |what: &str, fresh: u64, secs: f64| {
+        println!(
+            "{}: {} of {} fresh in {:.3}s, {:.0} ids/s",
+            what,
+            fresh,
+            n,
+            secs,
+            n as f64 / secs
+        );
+    };
+    let start = Instant::now();
+    let serial = ids.iter().filter(|&&c| lookup.contains(c)).count() as u64;
+    report("serial", serial, start.elapsed().as_secs_f64());
+    let start = Instant::now();
+    let par = count_fresh(lookup, &ids, None);
+    let what = format!("rayon, {} threads", rayon::current_num_threads());
+    report(&what, par, start.elapsed().as_secs_f64());
+    assert_eq!(serial, par);
 }
 
-mod interval {
-    use super::ascii_to_u64;
-    use std::cmp::Ordering;
-
-    #[derive(Debug, PartialEq)]
-    pub struct InvalidClosedInt;
+// The integer types the intervals and ids can be read as.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum IntType {
+    U64,
+    I64,
+    U128,
+    I128,
+}
 
-    #[derive(Debug, PartialEq)]
-    pub enum UnmergeOrder {
-        Before,
-        After,
+impl IntType {
+    fn from_name(name: &str) -> Option<IntType> {
+        match name {
+            "u64" => Some(IntType::U64),
+            "i64" => Some(IntType::I64),
+            "u128" => Some(IntType::U128),
+            "i128" => Some(IntType::I128),
+            _ => None,
+        }
     }
 
-    #[derive(Debug, PartialEq)]
-    pub struct UnmergableInts(UnmergeOrder);
-
-    #[derive(Debug, Eq, PartialEq, Clone)]
-    pub struct ClosedInt {
-        low: u64,
-        high: u64,
+    // The narrowest one that holds every number in `texts`: unsigned unless
+    // some number is negative, 64 bits unless some number needs more.
+    // Underscores between digits are read through, as --lenient does.
+    fn detect(texts: &[&[u8]]) -> IntType {
+        let (mut neg, mut over_u64, mut over_i64) = (false, false, false);
+        for text in texts {
+            Self::scan(text, &mut neg, &mut over_u64, &mut over_i64);
+        }
+        match (neg, over_u64, over_i64) {
+            (false, false, _) => IntType::U64,
+            (false, true, _) => IntType::U128,
+            (true, _, false) => IntType::I64,
+            (true, _, true) => IntType::I128,
+        }
     }
 
-    impl ClosedInt {
-        pub fn new(low: u64, high: u64) -> Result<Self, InvalidClosedInt> {
-            if low > high {
-                Err(InvalidClosedInt)
-            } else {
-                Ok(ClosedInt {
-                    low: low,
-                    high: high,
-                })
+    fn scan(text: &[u8], neg: &mut bool, over_u64: &mut bool, over_i64: &mut bool) {
+        let mut i = 0;
+        while i < text.len() {
+            if !text[i].is_ascii_digit() {
+                i += 1;
+                continue;
             }
-        }
-        pub fn from_str(txt: &[u8]) -> Result<Self, InvalidClosedInt> {
-            let mut idx = 0;
-            loop {
-                if idx >= txt.len() {
-                    return Err(InvalidClosedInt);
-                }
-                if txt[idx] == b'-' {
-                    break;
+            let start = i;
+            let mut digits = Vec::new();
+            while i < text.len() && text[i].is_ascii_digit() {
+                digits.push(text[i]);
+                i += 1;
+                if text.get(i) == Some(&b'_') && text.get(i + 1).is_some_and(u8::is_ascii_digit) {
+                    i += 1;
                 }
-                idx += 1;
             }
-            let low = match ascii_to_u64(&txt[0..idx]) {
-                Ok(low) => low,
-                Err(_) => {
-                    return Err(InvalidClosedInt);
-                }
-            };
-            let high = match ascii_to_u64(&txt[idx + 1..]) {
-                Ok(high) => high,
-                Err(_) => {
-                    return Err(InvalidClosedInt);
-                }
-            };
-            ClosedInt::new(low, high)
-        }
-
-        pub fn merge(&self, other: &Self) -> Result<ClosedInt, UnmergableInts> {
-            if self.high + 1 < other.low {
-                Err(UnmergableInts(UnmergeOrder::Before))
-            } else if other.high + 1 < self.low {
-                Err(UnmergableInts(UnmergeOrder::After))
+            let signed = start > 0 && text[start - 1] == b'-' && !is_separator(text, start - 1);
+            if signed {
+                *neg = true;
+                digits.insert(0, b'-');
+                *over_i64 |= ascii_to_int::<i64>(&digits).is_err();
             } else {
-                Ok(ClosedInt::new(
-                    std::cmp::min(self.low, other.low),
-                    std::cmp::max(self.high, other.high),
-                )
-                .unwrap())
+                *over_u64 |= ascii_to_int::<u64>(&digits).is_err();
+                *over_i64 |= ascii_to_int::<i64>(&digits).is_err();
             }
         }
-
-        pub fn length(&self) -> u64 {
-            self.high - self.low + 1
-        }
-        pub fn contains(&self, num: u64) -> bool {
-            num >= self.low && num <= self.high
-        }
     }
+}
 
-    impl Ord for ClosedInt {
-        fn cmp(&self, other: &Self) -> Ordering {
-            self.low.cmp(&other.low)
-        }
-    }
+// What --report can list.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Report {
+    // The query ids no interval holds, each after its index.
+    Uncovered,
+    // The runs of ids no interval holds, between the lowest bound and the
+    // highest.
+    Gaps,
+    // The normalized intervals themselves, bare `lo-hi` lines for diffing.
+    Merged,
+}
 
-    impl PartialOrd for ClosedInt {
-        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-            Some(self.cmp(&other))
-        }
-    }
+struct Options {
+    file: String,
+    verify: bool,
+    overlaps: bool,
+    max_overlap: bool,
+    // The most intervals that can be picked with no two overlapping.
+    max_disjoint: bool,
+    // For each id, every interval that holds it, with its line.
+    explain: bool,
+    // The merged ranges' labels, then each id's.
+    labels: bool,
+    // Chart coverage in this many columns.
+    viz: Option<usize>,
+    part: Option<u8>,
+    // Solve each interval and id block pair in the file on its own.
+    sections: bool,
+    // Run the file as adds, removes and queries, in order.
+    updates: bool,
+    // List duplicate, contained and reversed intervals instead of solving.
+    validate: bool,
+    // Drop duplicate and contained intervals before solving.
+    dedup: bool,
+    syntax: Syntax,
+    engine: Engine,
+    set_op: Option<(SetOp, String)>,
+    // Where the ids came from, for errors, and what was read.
+    queries: Option<(String, Vec<u8>)>,
+    // A --queries path (or -) to read as it's checked instead.
+    streamed: Option<String>,
+    // Time the lookup over this many generated ids instead.
+    bench: Option<usize>,
+    // Log to stderr after every this many ids checked.
+    progress_every: Option<usize>,
+    reports: Vec<Report>,
+    // For each interval, how many of the ids it holds.
+    ids_per_interval: bool,
+    // Print the first uncovered id at or above this one.
+    next_uncovered: Option<String>,
+    // List what's uncovered within this interval, and count both sides.
+    complement: Option<String>,
+    // Print the k-th smallest covered id, from 0, for each of these.
+    kth_covered: Vec<u128>,
+    // Where to save the parsed problem, and whether to save the intervals
+    // normalized.
+    save: Option<(String, bool)>,
+}
 
-    #[cfg(test)]
-    mod tests {
-        use super::*;
+enum SetOp {
+    Union,
+    Intersect,
+    // In the file's intervals but not the other file's.
+    Minus,
+}
 
-        #[test]
-        fn test_invalid() {
-            assert_eq!(ClosedInt::new(16, 15), Err(InvalidClosedInt));
+// `gen`: a random problem on stdout, and the answers it was built to have on
+// stderr, for checking a solver with.
+fn run_gen(args: impl Iterator<Item = String>) -> Result<(), Box<dyn Error>> {
+    let (mut runs, mut max_len, mut max_gap, mut ids, mut seed) = (1000, 1 << 20, 1 << 24, 1000, 1);
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        let slot = match arg.as_str() {
+            "--runs" => &mut runs,
+            "--max-len" => &mut max_len,
+            "--max-gap" => &mut max_gap,
+            "--ids" => &mut ids,
+            "--seed" => &mut seed,
+            _ => return Err(From::from(format!("Unexpected gen argument {}", arg))),
+        };
+        match args.next().and_then(|n| n.parse::<u64>().ok()) {
+            Some(n) => *slot = n,
+            None => return Err(From::from(format!("{} needs a count", arg))),
         }
+    }
+    let generated = generate(runs as usize, max_len, max_gap, ids as usize, seed);
+    let mut out = BufWriter::new(io::stdout().lock());
+    write_input(&mut out, &generated.problem)?;
+    out.flush()?;
+    eprintln!("fresh {} covered {}", generated.fresh, generated.covered);
+    Ok(())
+}
 
-        #[test]
-        fn test_single() {
-            if let Ok(x) = ClosedInt::new(15, 15) {
-                assert_eq!(x.low, 15);
-                assert_eq!(x.high, 15);
-            } else {
-                panic!("Invalid interval!");
+fn main() -> Result<(), Box<dyn Error>> {
+    SimpleLogger::new().init().unwrap();
+    let mut file = None;
+    // Check every lookup against a linear scan of the unmerged intervals.
+    let mut verify = false;
+    // For each id, how many of the file's intervals hold it.
+    let mut overlaps = false;
+    // The id the most intervals hold, and how many.
+    let mut max_overlap = false;
+    let mut max_disjoint = false;
+    let mut viz = false;
+    let mut explain = false;
+    let mut labels = false;
+    let mut viz_columns = DEFAULT_VIZ_COLUMNS;
+    // Print only one part's answer rather than the whole report.
+    let mut part = None;
+    // How to read `lo-hi` intervals.
+    let mut kind = IntervalKind::Closed;
+    // Also take spaced or en-dashed intervals, Rust ranges and 1_000
+    // underscores.
+    let mut lenient = false;
+    let mut sections = false;
+    let mut updates = false;
+    let mut validate = false;
+    let mut dedup = false;
+    // What answers the lookups.
+    let mut engine = Engine::Sorted;
+    // Combine the file's intervals with another file's and list the result.
+    let mut set_op = None;
+    // What to read the numbers as, or None to pick from what's in the files.
+    let mut int = None;
+    // Check the ids from this file, or stdin for -, rather than the ones
+    // after the intervals.
+    let mut queries = None;
+    // Check the --queries ids a line at a time without holding them.
+    let mut stream = false;
+    // Threads for the query loop, rather than one per core.
+    let mut threads = None;
+    let mut bench = None;
+    let mut progress_every = None;
+    // Print only these listings, in the order asked for.
+    let mut reports = Vec::new();
+    let mut next_uncovered = None;
+    let mut complement = None;
+    let mut kth_covered = Vec::new();
+    let mut ids_per_interval = false;
+    let mut save = None;
+    let mut args = env::args().skip(1).peekable();
+    if args.next_if_eq("gen").is_some() {
+        return run_gen(args);
+    }
+    // `union a b` and the like are --union and the rest spelled as
+    // subcommands.
+    let subcommand = match args.peek().map(String::as_str) {
+        Some("union") => Some(SetOp::Union),
+        Some("intersect") => Some(SetOp::Intersect),
+        Some("diff") => Some(SetOp::Minus),
+        _ => None,
+    };
+    if let Some(op) = subcommand {
+        let name = args.next().unwrap();
+        match (args.next(), args.next()) {
+            (Some(a), Some(b)) => {
+                file = Some(a);
+                set_op = Some((op, b));
             }
+            _ => return Err(From::from(format!("{} needs two interval files", name))),
         }
-
-        #[test]
-        fn test_u8() {
-            assert_eq!(ClosedInt::from_str(b"3-5"), ClosedInt::new(3, 5));
-        }
-
-        #[test]
-        fn test_contains() {
-            let closed = ClosedInt::new(10, 15).unwrap();
-            for i in 10..15 {
-                assert!(closed.contains(i));
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--verify" => verify = true,
+            // The older spelling of --report gaps.
+            "--gaps" => reports.push(Report::Gaps),
+            "--overlaps" => overlaps = true,
+            "--max-overlap" => max_overlap = true,
+            "--max-disjoint" => max_disjoint = true,
+            "--viz" => viz = true,
+            "--explain" => explain = true,
+            "--labels" => labels = true,
+            "--viz-columns" => match args.next().and_then(|n| n.parse::<usize>().ok()) {
+                Some(n) if n > 0 => viz_columns = n,
+                _ => return Err(From::from("--viz-columns needs a positive count")),
+            },
+            "--lenient" => lenient = true,
+            "--sections" => sections = true,
+            "--updates" => updates = true,
+            "--validate" => validate = true,
+            "--dedup" => dedup = true,
+            "--kind" => {
+                kind = match args.next().as_deref() {
+                    Some("closed") => IntervalKind::Closed,
+                    Some("half-open") => IntervalKind::HalfOpen,
+                    Some("open") => IntervalKind::Open,
+                    _ => return Err(From::from("--kind needs closed, half-open or open")),
+                };
             }
-            assert!(!closed.contains(9));
-            assert!(!closed.contains(16));
-        }
-
-        #[test]
-        fn test_merge() {
-            let a = ClosedInt::new(10, 15).unwrap();
-            let b = ClosedInt::new(20, 25).unwrap();
-            assert_eq!(a.merge(&b), Err(UnmergableInts(UnmergeOrder::Before)));
-            assert_eq!(b.merge(&a), Err(UnmergableInts(UnmergeOrder::After)));
-            let c = ClosedInt::new(24, 30).unwrap();
-            assert_eq!(b.merge(&c), Ok(ClosedInt::new(20, 30).unwrap()));
+            "--engine" => match args.next().as_deref().and_then(Engine::from_name) {
+                Some(e) => engine = e,
+                None => return Err(From::from("--engine needs sorted, tree or brute")),
+            },
+            "--part" => {
+                part = match args.next().as_deref() {
+                    Some("1") => Some(1),
+                    Some("2") => Some(2),
+                    _ => return Err(From::from("--part needs 1 or 2")),
+                };
+            }
+            "--union" | "--intersect" | "--minus" => {
+                if set_op.is_some() {
+                    return Err(From::from("Pick one of --union, --intersect and --minus"));
+                }
+                let op = match arg.as_str() {
+                    "--union" => SetOp::Union,
+                    "--intersect" => SetOp::Intersect,
+                    _ => SetOp::Minus,
+                };
+                match args.next() {
+                    Some(other) => set_op = Some((op, other)),
+                    None => return Err(From::from(format!("{} needs a second file", arg))),
+                }
+            }
+            "--queries" => match args.next() {
+                Some(path) => queries = Some(path),
+                None => return Err(From::from("--queries needs a file, or - for stdin")),
+            },
+            "--stream" => stream = true,
+            "--report" => match args.next().as_deref() {
+                Some("uncovered") => reports.push(Report::Uncovered),
+                Some("gaps") => reports.push(Report::Gaps),
+                Some("merged") => reports.push(Report::Merged),
+                _ => return Err(From::from("--report needs uncovered, gaps or merged")),
+            },
+            "--ids-per-interval" => ids_per_interval = true,
+            "--next-uncovered" => match args.next() {
+                Some(id) => next_uncovered = Some(id),
+                None => return Err(From::from("--next-uncovered needs an id")),
+            },
+            "--complement" => match args.next() {
+                Some(universe) => complement = Some(universe),
+                None => return Err(From::from("--complement needs an interval, e.g. 0-1000")),
+            },
+            "--kth-covered" => match args.next().and_then(|k| k.parse::<u128>().ok()) {
+                Some(k) => kth_covered.push(k),
+                None => return Err(From::from("--kth-covered needs a count from 0")),
+            },
+            "--threads" => match args.next().and_then(|n| n.parse::<usize>().ok()) {
+                Some(n) if n > 0 => threads = Some(n),
+                _ => return Err(From::from("--threads needs a positive count")),
+            },
+            "--progress-every" => match args.next().and_then(|n| n.parse::<usize>().ok()) {
+                Some(n) if n > 0 => progress_every = Some(n),
+                _ => return Err(From::from("--progress-every needs a positive count")),
+            },
+            "--bench" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => bench = Some(n),
+                None => return Err(From::from("--bench needs a query count")),
+            },
+            "--int" => {
+                int = match args.next().as_deref() {
+                    Some("auto") => None,
+                    Some(name) if IntType::from_name(name).is_some() => IntType::from_name(name),
+                    _ => return Err(From::from("--int needs u64, i64, u128, i128 or auto")),
+                };
+            }
+            "--save" | "--save-merged" => match args.next() {
+                Some(path) => save = Some((path, arg == "--save-merged")),
+                None => return Err(From::from(format!("{} needs a .json or .bin path", arg))),
+            },
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(From::from(format!("Unexpected argument {}", arg))),
         }
-
-        #[test]
-        fn test_ord() {
-            let closed0 = ClosedInt::new(10, 15).unwrap();
-            let closed0_copy = ClosedInt::new(10, 15).unwrap();
-            let closed1 = ClosedInt::new(20, 25).unwrap();
-            assert!(closed0 < closed1);
-            assert!(!(closed0 > closed1));
-            assert!(closed0 != closed1);
-            assert!(closed0 == closed0_copy);
+    }
+    let file = match file {
+        Some(file) => file,
+        None => {
+            return Err(From::from("Need a file argument!"));
         }
+    };
+    let streamed = match stream {
+        true => match queries.take() {
+            Some(path) => Some(path),
+            None => return Err(From::from("--stream needs --queries")),
+        },
+        false => None,
+    };
+    if streamed.is_some()
+        && (overlaps
+            || max_overlap
+            || max_disjoint
+            || viz
+            || explain
+            || labels
+            || complement.is_some()
+            || !kth_covered.is_empty()
+            || set_op.is_some()
+            || part == Some(2))
+    {
+        return Err(From::from(
+            "--stream only counts fresh ids; it can't go with --overlaps, --max-overlap, --max-disjoint, --viz, --explain, --labels, --complement, --kth-covered, set operations or --part 2",
+        ));
     }
-}
-
-use interval::ClosedInt;
-
-fn bruteforce_interval(val: u64, intervals: &[ClosedInt]) -> bool {
-    for i in intervals {
-        if i.contains(val) {
-            return true;
+    if sections
+        && (queries.is_some()
+            || streamed.is_some()
+            || set_op.is_some()
+            || save.is_some()
+            || bench.is_some())
+    {
+        return Err(From::from(
+            "--sections reports on each section; it can't go with --queries, --stream, set operations, --save or --bench",
+        ));
+    }
+    if updates
+        && (sections
+            || queries.is_some()
+            || streamed.is_some()
+            || set_op.is_some()
+            || save.is_some()
+            || bench.is_some())
+    {
+        return Err(From::from(
+            "--updates answers its own queries; it can't go with --sections, --queries, --stream, set operations, --save or --bench",
+        ));
+    }
+    if (sections || explain || labels || updates || validate)
+        && SaveFormat::from_path(&file).is_some()
+    {
+        return Err(From::from(
+            "--sections, --explain, --labels, --updates and --validate need a text input, not a save",
+        ));
+    }
+    // Dropping an interval another holds changes nothing about coverage,
+    // but does change how many hold an id.
+    if dedup
+        && (overlaps || max_overlap || max_disjoint || viz || explain || labels || ids_per_interval)
+    {
+        return Err(From::from(
+            "--dedup changes how many intervals hold an id; it can't go with --overlaps, --max-overlap, --max-disjoint, --viz, --explain, --labels or --ids-per-interval",
+        ));
+    }
+    // Read up front, since stdin can only be read once and detection needs
+    // to see the ids too. Streamed ids aren't seen, so --int has to cover
+    // them if the intervals' type doesn't.
+    let queries = match queries.as_deref() {
+        Some("-") => {
+            let mut text = Vec::new();
+            io::stdin().read_to_end(&mut text)?;
+            Some(("stdin".to_string(), text))
         }
+        Some(path) => Some((path.to_string(), fs::read(path)?)),
+        None => None,
+    };
+    let int = match int {
+        Some(int) => int,
+        // A save says what it holds.
+        None if SaveFormat::from_path(&file).is_some() => {
+            let name = saved_int(&file)?.unwrap();
+            IntType::from_name(&name).ok_or(format!("{}: can't read {} ids", file, name))?
+        }
+        // A pipe can only be read once, and detecting would use it up.
+        None if !fs::metadata(&file)?.is_file() => {
+            return Err(From::from(format!(
+                "{}: isn't a regular file, so its integer type can't be detected; give --int",
+                file
+            )));
+        }
+        None => {
+            let map = MappedFile::open(&file)?;
+            let other = match &set_op {
+                Some((_, other)) => Some(MappedFile::open(other)?),
+                None => None,
+            };
+            let mut texts: Vec<&[u8]> = vec![&map];
+            texts.extend(other.as_deref());
+            if let Some((_, ids)) = &queries {
+                texts.push(ids);
+            }
+            IntType::detect(&texts)
+        }
+    };
+    let opts = Options {
+        file,
+        verify,
+        overlaps,
+        max_overlap,
+        max_disjoint,
+        explain,
+        labels,
+        viz: viz.then_some(viz_columns),
+        part,
+        sections,
+        updates,
+        validate,
+        dedup,
+        syntax: Syntax { kind, lenient },
+        engine,
+        set_op,
+        queries,
+        streamed,
+        bench,
+        progress_every,
+        reports,
+        ids_per_interval,
+        next_uncovered,
+        complement,
+        kth_covered,
+        save,
+    };
+    if let Some(n) = threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(n)
+            .build_global()?;
+    }
+    match int {
+        IntType::U64 => run::<u64>(&opts),
+        IntType::I64 => run::<i64>(&opts),
+        IntType::U128 => run::<u128>(&opts),
+        IntType::I128 => run::<i128>(&opts),
     }
-    false
 }
 
-fn merge_intervals(buf: &mut [ClosedInt]) -> &[ClosedInt] {
-    buf.sort();
-    // a b c d e
-    // ab x c d e
-    let mut dst = 0;
-    let mut src = 1;
-    while src < buf.len() {
-        info!("{:?}, {:?}", buf[dst], buf[src]);
-
-        if let Ok(merged) = buf[dst].merge(&buf[src]) {
-            buf[dst] = merged;
-            info!("merged {:?}", buf[dst]);
-        } else {
-            dst += 1;
-            if src != dst {
-                buf[dst] = buf[src].clone();
-            }
-            info!("unmerged");
+// Each section's fresh ids and coverage, or only the part asked for, then
+// the sums.
+fn run_sections<N: Int>(opts: &Options) -> Result<(), Box<dyn Error>> {
+    let fdbs = FoodbProblem::<N>::sections_from_file(&opts.file, opts.syntax)
+        .map_err(|e| -> Box<dyn Error> { From::from(format!("{}: {}", opts.file, e)) })?;
+    let (mut fresh, mut covered) = (0, 0u128);
+    for (i, fdb) in fdbs.iter().enumerate() {
+        let merged = IntervalSet::normalize(fdb.intervals.iter().cloned());
+        let lookup = Lookup::new(opts.engine, &fdb.intervals);
+        let verify = opts.verify.then_some(fdb.intervals.as_slice());
+        let res = count_fresh(&lookup, &fdb.to_check, verify);
+        let coverage = merged.coverage();
+        match opts.part {
+            Some(1) => println!("section {} {}", i + 1, res),
+            Some(_) => println!("section {} {}", i + 1, coverage),
+            None => println!("section {} fresh {} covered {}", i + 1, res, coverage),
         }
-        src += 1;
+        fresh += res;
+        covered = covered.saturating_add(coverage);
+    }
+    match opts.part {
+        Some(1) => println!("total {}", fresh),
+        Some(_) => println!("total {}", covered),
+        None => println!("total fresh {} covered {}", fresh, covered),
     }
-    &buf[..dst + 1]
+    Ok(())
 }
 
-fn read_lines<P>(filename: P) -> io::Result<io::Split<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).split(b'\n'))
+// One --progress-every line: how far the check has got and what share of
+// the ids so far were fresh.
+fn log_progress(done: u64, total: Option<usize>, fresh: u64, start: Instant) {
+    let of = total.map(|t| format!(" of {}", t)).unwrap_or_default();
+    eprintln!(
+        "progress: {}{} ids checked, {} fresh ({:.1}%), {:.1}s",
+        done,
+        of,
+        fresh,
+        100.0 * fresh as f64 / done.max(1) as f64,
+        start.elapsed().as_secs_f64()
+    );
 }
 
-struct FoodbProblem {
-    intervals: Vec<ClosedInt>,
-    to_check: Vec<u64>,
+// Each query's answer as the updates above it left the set, then the
+// fresh count and what the set covers at the end.
+fn run_updates<N: Int>(opts: &Options) -> Result<(), Box<dyn Error>> {
+    let reader = io::BufReader::new(File::open(&opts.file)?);
+    let mut set = IntervalSet::<N>::normalize([]);
+    let mut fresh = 0;
+    let mut i = 0;
+    stream_updates(reader, opts.syntax, &mut set, |id, held| {
+        let state = if held { "fresh" } else { "spoiled" };
+        println!("{} {} {}", i, id, state);
+        fresh += held as u64;
+        i += 1;
+    })
+    .map_err(|e| -> Box<dyn Error> { From::from(format!("{}: {}", opts.file, e)) })?;
+    println!("fresh {}", fresh);
+    println!("covered {}", set.coverage());
+    Ok(())
 }
 
-impl FoodbProblem {
-    fn new_from_file<P>(filename: P) -> Self
-    where
-        P: AsRef<Path>,
-    {
-        let mut line_iter = read_lines(filename).unwrap().map(|res| res.unwrap());
-        return Self::new_from_lines(&mut line_iter);
+fn run<N: Int>(opts: &Options) -> Result<(), Box<dyn Error>> {
+    if opts.sections {
+        return run_sections::<N>(opts);
     }
-
-    fn new_from_lines<I, S, T>(line_iter: T) -> Self
-    where
-        I: Iterator<Item = S>,
-        S: AsRef<[u8]>,
-        T: IntoIterator<IntoIter = I, Item = S>,
-    {
-        let mut ints = Vec::<ClosedInt>::new();
-        let mut line_iter = line_iter.into_iter().peekable();
-
-        while let Some(line) = line_iter.next() {
-            if line.as_ref().len() == 0 {
-                break;
+    if opts.updates {
+        return run_updates::<N>(opts);
+    }
+    if opts.validate {
+        let findings = validate_file::<N, _>(&opts.file, opts.syntax)
+            .map_err(|e| -> Box<dyn Error> { From::from(format!("{}: {}", opts.file, e)) })?;
+        let mut counts = [0; 3];
+        for finding in &findings {
+            println!("{}", finding);
+            counts[match finding {
+                Finding::Duplicate { .. } => 0,
+                Finding::Contained { .. } => 1,
+                Finding::Reversed { .. } => 2,
+            }] += 1;
+        }
+        println!(
+            "duplicates {} contained {} reversed {}",
+            counts[0], counts[1], counts[2]
+        );
+        return Ok(());
+    }
+    let start = Instant::now();
+    let load = |file: &str| {
+        FoodbProblem::<N>::new_from_file(file, opts.syntax)
+            .map_err(|e| -> Box<dyn Error> { From::from(format!("{}: {}", file, e)) })
+    };
+    let mut fdb = load(&opts.file)?;
+    if opts.dedup {
+        let read = fdb.intervals.len();
+        fdb.intervals = drop_redundant(&fdb.intervals);
+        eprintln!("dedup: kept {} of {} intervals", fdb.intervals.len(), read);
+    }
+    if let Some((name, text)) = &opts.queries {
+        fdb.to_check = FoodbProblem::<N>::ids_from_lines(text.split(|&v| v == b'\n'))
+            .map_err(|e| -> Box<dyn Error> { From::from(format!("{}: {}", name, e)) })?;
+    }
+    let merged = IntervalSet::normalize(fdb.intervals.iter().cloned());
+    let lookup = Lookup::new(opts.engine, &fdb.intervals);
+    if opts.progress_every.is_some() {
+        eprintln!(
+            "progress: {} intervals ({} merged) and {} ids read, {:.1}s",
+            fdb.intervals.len(),
+            merged.intervals().len(),
+            fdb.to_check.len(),
+            start.elapsed().as_secs_f64()
+        );
+    }
+    if let Some((path, normalized)) = &opts.save {
+        let intervals = match normalized {
+            true => merged.intervals().to_vec(),
+            false => fdb.intervals.clone(),
+        };
+        let saved = FoodbProblem {
+            intervals,
+            to_check: fdb.to_check.clone(),
+        };
+        saved
+            .save(path)
+            .map_err(|e| -> Box<dyn Error> { From::from(format!("{}: {}", path, e)) })?;
+    }
+    if let Some(n) = opts.bench {
+        bench_queries(&merged, &lookup, n);
+        return Ok(());
+    }
+    if let Some(path) = &opts.streamed {
+        let reader: Box<dyn BufRead> = match path.as_str() {
+            "-" => Box::new(io::stdin().lock()),
+            _ => Box::new(io::BufReader::new(File::open(path)?)),
+        };
+        let start = Instant::now();
+        let mut res = 0;
+        let mut seen = 0;
+        let checked = stream_ids(reader, |c: N| {
+            let fresh = lookup.contains(c);
+            if opts.verify && fresh != bruteforce_interval(c, &fdb.intervals) {
+                panic!("{}", c);
             }
-            if let Ok(x) = ClosedInt::from_str(line.as_ref()) {
-                ints.push(x);
-            } else {
-                panic!(
-                    "Couldn't parse {} as interval",
-                    std::str::from_utf8(line.as_ref()).expect("not utf8")
-                );
+            res += fresh as u64;
+            seen += 1;
+            if let Some(every) = opts.progress_every
+                && seen % every as u64 == 0
+            {
+                log_progress(seen, None, res, start);
             }
+        })
+        .map_err(|e| -> Box<dyn Error> { From::from(format!("{}: {}", path, e)) })?;
+        let secs = start.elapsed().as_secs_f64();
+        eprintln!(
+            "checked {} ids in {:.3}s, {:.0} ids/s",
+            checked,
+            secs,
+            checked as f64 / secs
+        );
+        match opts.part {
+            Some(_) => println!("{}", res),
+            None => println!("sum {}", res),
         }
-
-        let mut ids = Vec::<u64>::new();
-
-        while let Some(line) = line_iter.next() {
-            if line.as_ref().len() == 0 {
-                if line_iter.peek().is_none() {
-                    break;
-                } else {
-                    panic!("Unexpected end of while parsing!");
-                }
+        return Ok(());
+    }
+    if let Some((op, other)) = &opts.set_op {
+        let other = IntervalSet::normalize(load(other)?.intervals);
+        let result = match op {
+            SetOp::Union => merged.union(&other),
+            SetOp::Intersect => merged.intersection(&other),
+            SetOp::Minus => merged.difference(&other),
+        };
+        for int in result.intervals() {
+            println!("{}", int);
+        }
+        println!("covered {}", result.coverage());
+        return Ok(());
+    }
+    // Lines about single ids lead with where the id was in the list read,
+    // from 0, to join them back to it by.
+    if opts.overlaps {
+        let mut total = 0;
+        for (i, &c) in fdb.to_check.iter().enumerate() {
+            let count = lookup.count(c);
+            if opts.verify && count != bruteforce_overlaps(c, &fdb.intervals) {
+                panic!("{}", c);
             }
-            if let Ok(x) = ascii_to_u64(line.as_ref()) {
-                ids.push(x);
+            println!("{} {} {}", i, c, count);
+            total += count;
+        }
+        println!("total {}", total);
+        return Ok(());
+    }
+    // Straight from the input, since the intervals it lines up with are the
+    // unmerged ones.
+    if opts.explain {
+        let numbered = FoodbProblem::<N>::intervals_with_lines(&opts.file, opts.syntax)?;
+        let mut fresh = 0;
+        for (i, &c) in fdb.to_check.iter().enumerate() {
+            let holding: Vec<String> = lookup
+                .containing(c)
+                .into_iter()
+                .map(|at| format!("{} (line {})", numbered[at].1, numbered[at].0))
+                .collect();
+            if holding.is_empty() {
+                println!("{} {} spoiled", i, c);
             } else {
-                panic!(
-                    "Couldn't parse {} as id",
-                    std::str::from_utf8(line.as_ref()).expect("not utf8")
-                );
+                println!("{} {} fresh: {}", i, c, holding.join(", "));
+                fresh += 1;
+            }
+            if opts.verify && holding.is_empty() == merged.contains(c) {
+                panic!("{}", c);
             }
         }
-
-        ints.sort();
-        FoodbProblem {
-            intervals: ints,
-            to_check: ids,
+        println!("fresh {}", fresh);
+        return Ok(());
+    }
+    if opts.labels {
+        let labeled = FoodbProblem::<N>::labeled_intervals(&opts.file, opts.syntax)?;
+        for (int, labels) in merge_labeled(&labeled) {
+            println!("range {}: {}", int, labels.join(", "));
+        }
+        let index = LabelIndex::new(&labeled);
+        let mut fresh = 0;
+        for (i, &c) in fdb.to_check.iter().enumerate() {
+            let found = index.labels_of(c);
+            fresh += found.is_some() as u64;
+            match found {
+                None => println!("{} {} spoiled", i, c),
+                Some(labels) if labels.is_empty() => println!("{} {} fresh", i, c),
+                Some(labels) => println!("{} {} fresh: {}", i, c, labels.join(", ")),
+            }
         }
+        println!("fresh {}", fresh);
+        return Ok(());
     }
-}
-
-fn main() -> Result<(), Box<dyn Error>> {
-    SimpleLogger::new().init().unwrap();
-    let mut args = env::args().skip(1).peekable();
-    let file = match args.next() {
-        Some(file) => file,
-        None => {
-            return Err(From::from("Need a file argument!"));
+    if let Some(columns) = opts.viz {
+        write_viz(
+            &mut io::stdout().lock(),
+            &fdb.intervals,
+            &fdb.to_check,
+            columns,
+        )?;
+        return Ok(());
+    }
+    if opts.max_overlap {
+        match OverlapCounter::new(&fdb.intervals).deepest() {
+            Some((num, depth)) => {
+                if opts.verify
+                    && (bruteforce_overlaps(num, &fdb.intervals) != depth
+                        || fdb
+                            .intervals
+                            .iter()
+                            .any(|i| bruteforce_overlaps(i.low(), &fdb.intervals) > depth))
+                {
+                    panic!("{}", num);
+                }
+                println!("{} {}", num, depth);
+            }
+            None => println!("none"),
         }
-    };
-    let mut fdb = FoodbProblem::new_from_file(file);
-    println!("pre-merge len {}", fdb.intervals.len());
-    let copy = fdb.intervals.clone();
-    let merged = merge_intervals(&mut fdb.intervals);
-    println!("post-merge len {}", merged.len());
-    let mut res = 0;
-    for c in fdb.to_check {
-        if bruteforce_interval(c, &merged) != bruteforce_interval(c, &copy) {
-            panic!("{}", c);
+        return Ok(());
+    }
+    if opts.max_disjoint {
+        let picked = max_disjoint(&fdb.intervals);
+        for int in &picked {
+            println!("{}", int);
         }
-        res += bruteforce_interval(c, &merged) as u64;
+        println!("count {}", picked.len());
+        return Ok(());
     }
+    // An id in several intervals counts toward each, in the total too.
+    if opts.ids_per_interval {
+        let ids = SortedIds::new(fdb.to_check.iter().copied());
+        let mut total = 0;
+        for int in &fdb.intervals {
+            let count = ids.count_in(int);
+            println!("{} {}", int, count);
+            total += count;
+        }
+        println!("total {}", total);
+        return Ok(());
+    }
+    if let Some(id) = &opts.next_uncovered {
+        let id: N = ascii_to_int(id.as_bytes())
+            .map_err(|_| format!("--next-uncovered: {} isn't a valid id", id))?;
+        match merged.next_uncovered(id) {
+            Some(next) => println!("{}", next),
+            None => println!("none"),
+        }
+        return Ok(());
+    }
+    if let Some(text) = &opts.complement {
+        let parsed = match opts.syntax.lenient {
+            true => ClosedInt::<N>::parse_lenient(text.as_bytes(), opts.syntax.kind),
+            false => ClosedInt::<N>::parse(text.as_bytes(), opts.syntax.kind),
+        };
+        let universe = match parsed {
+            Ok(Some(universe)) => universe,
+            _ => {
+                return Err(From::from(format!(
+                    "--complement: {} isn't an interval",
+                    text
+                )));
+            }
+        };
+        let rest = merged.complement(universe.low(), universe.high());
+        for int in rest.intervals() {
+            println!("{}", int);
+        }
+        println!("uncovered {}", rest.coverage());
+        println!("covered {}", universe.length() - rest.coverage());
+        return Ok(());
+    }
+    if !opts.kth_covered.is_empty() {
+        let ranks = CoveredRanks::new(&merged);
+        for &k in &opts.kth_covered {
+            match ranks.kth(k) {
+                Some(num) => println!("{} {}", k, num),
+                None => println!("{} none", k),
+            }
+        }
+        return Ok(());
+    }
+    if !opts.reports.is_empty() {
+        for report in &opts.reports {
+            match report {
+                Report::Uncovered => {
+                    for (i, c) in fdb.to_check.iter().enumerate() {
+                        if !lookup.contains(*c) {
+                            println!("uncovered {} {}", i, c);
+                        }
+                    }
+                }
+                Report::Gaps => {
+                    for gap in merged.gaps() {
+                        println!("gap {}", gap);
+                    }
+                }
+                Report::Merged => {
+                    for int in merged.intervals() {
+                        println!("{}", int);
+                    }
+                }
+            }
+        }
+        return Ok(());
+    }
+    // Part 2: every value covered by some interval, counted once.
+    if opts.part == Some(2) {
+        println!("{}", merged.coverage());
+        return Ok(());
+    }
+    if opts.part.is_none() {
+        println!("pre-merge len {}", fdb.intervals.len());
+        println!("post-merge len {}", merged.intervals().len());
+    }
+    let verify = opts.verify.then_some(fdb.intervals.as_slice());
+    let res = match opts.progress_every {
+        Some(every) => {
+            let mut res = 0;
+            let mut done = 0;
+            for chunk in fdb.to_check.chunks(every) {
+                res += count_fresh(&lookup, chunk, verify);
+                done += chunk.len() as u64;
+                log_progress(done, Some(fdb.to_check.len()), res, start);
+            }
+            res
+        }
+        None => count_fresh(&lookup, &fdb.to_check, verify),
+    };
 
-    println!("sum {}", res);
-
-    let mut range_count = 0;
-    for i in merged {
-        range_count += i.length();
+    if opts.part == Some(1) {
+        println!("{}", res);
+        return Ok(());
     }
-    println!("Range count {}", range_count);
+    println!("sum {}", res);
+    println!("Range count {}", merged.coverage());
     Ok(())
 }
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_ascii_to_u64() {
-        assert_eq!(ascii_to_u64(b"123123"), Ok(123123));
-    }
-
-    #[test]
-    fn test_load() {
-        let lines = b"3-5
-10-14
-16-20
-12-18
-
-1
-5
-8
-11
-17
-32";
-        let mut fdb = FoodbProblem::new_from_lines(lines.split(|&v| v == b'\n'));
-        let mut res = 0;
-        for c in &fdb.to_check {
-            res += bruteforce_interval(*c, &fdb.intervals) as u64;
-        }
-        assert_eq!(res, 3);
-        let merged = merge_intervals(&mut fdb.intervals);
-        assert_eq!(merged.len(), 2);
-        assert_eq!(merged[0], ClosedInt::new(3, 5).unwrap());
-        assert_eq!(merged[1], ClosedInt::new(10, 20).unwrap());
-        let mut res = 0;
-        for c in &fdb.to_check {
-            res += bruteforce_interval(*c, &merged) as u64;
-        }
-        assert_eq!(res, 3);
+    fn test_detect_int_type() {
+        assert_eq!(IntType::detect(&[b"3-5\n10-14\n\n1\n5\n"]), IntType::U64);
+        assert_eq!(IntType::detect(&[b"3-5\n\n-1\n"]), IntType::I64);
+        assert_eq!(IntType::detect(&[b"-5--3\n"]), IntType::I64);
+        let texts: [&[u8]; 2] = [b"3-5\n\n", b"-1\n"];
+        assert_eq!(IntType::detect(&texts), IntType::I64);
+        assert_eq!(IntType::detect(&[b"[-5, 3)\n"]), IntType::I64);
+        assert_eq!(
+            IntType::detect(&[b"0-18446744073709551615\n"]),
+            IntType::U64
+        );
+        assert_eq!(
+            IntType::detect(&[b"0-18446744073709551616\n"]),
+            IntType::U128
+        );
+        assert_eq!(
+            IntType::detect(&[b"-1-9223372036854775808\n"]),
+            IntType::I128
+        );
+        assert_eq!(
+            IntType::detect(&[b"-9223372036854775809-0\n"]),
+            IntType::I128
+        );
+        assert_eq!(
+            IntType::detect(&[b"0-18_446_744_073_709_551_616\n"]),
+            IntType::U128
+        );
+        assert_eq!(IntType::detect(&[b"0-1_000\n\n-1_0\n"]), IntType::I64);
+
+        let lines = b"-20--10
+-12-5
+
+-15
+0
+6";
+        let fdb =
+            FoodbProblem::<i64>::new_from_lines(lines.split(|&v| v == b'\n'), IntervalKind::Closed)
+                .unwrap();
+        let set = IntervalSet::normalize(fdb.intervals.clone());
+        assert_eq!(
+            set.intervals(),
+            &[foodb::interval::ClosedInt::new(-20, 5).unwrap()]
+        );
+        assert_eq!(fdb.to_check, [-15, 0, 6]);
     }
 }
@@ -0,0 +1,134 @@
+// Input files mapped read-only into memory, so a huge database parses
+// straight out of the page cache: the parsers take byte slices, and the
+// lines are slices of the map, with nothing copied or allocated per line.
+// Platforms without mmap read the whole file instead, and so do pipes and
+// anything else that isn't a regular file, which have no length to map.
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Deref;
+use std::path::Path;
+
+pub struct MappedFile(Contents);
+
+enum Contents {
+    #[cfg(unix)]
+    Mapped {
+        ptr: *const u8,
+        len: usize,
+    },
+    Read(Vec<u8>),
+}
+
+// The map is read-only and owned by this value alone.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    #[cfg(unix)]
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        let mut file = File::open(path)?;
+        let meta = file.metadata()?;
+        // mmap refuses empty maps.
+        if !meta.is_file() || meta.len() == 0 {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            return Ok(MappedFile(Contents::Read(bytes)));
+        }
+        let len = meta.len() as usize;
+        // SAFETY: a fresh private read-only map of the whole file, checked
+        // for failure below. The fd can close once it's mapped. Another
+        // process truncating the file under the map is the one hazard, as
+        // with any mmap; inputs aren't written while they're read.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the range was just mapped. The advice is only a hint, so
+        // its result doesn't matter.
+        unsafe {
+            libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+        }
+        Ok(MappedFile(Contents::Mapped {
+            ptr: ptr as *const u8,
+            len,
+        }))
+    }
+
+    #[cfg(not(unix))]
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        Ok(MappedFile(Contents::Read(bytes)))
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.0 {
+            // SAFETY: ptr is len mapped, readable bytes that live until
+            // drop.
+            #[cfg(unix)]
+            Contents::Mapped { ptr, len } => unsafe { std::slice::from_raw_parts(*ptr, *len) },
+            Contents::Read(bytes) => bytes,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if let Contents::Mapped { ptr, len } = self.0 {
+            // SAFETY: unmaps exactly what open mapped, once.
+            unsafe {
+                libc::munmap(ptr as *mut libc::c_void, len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_mapped_file() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("foodb-mapped-{}.txt", std::process::id()));
+        fs::write(&path, b"3-5\n10-14\n\n1\n").unwrap();
+        let map = MappedFile::open(&path).unwrap();
+        assert_eq!(&*map, b"3-5\n10-14\n\n1\n");
+        drop(map);
+        fs::write(&path, b"").unwrap();
+        assert!(MappedFile::open(&path).unwrap().is_empty());
+        fs::remove_file(&path).unwrap();
+        assert!(MappedFile::open(&path).is_err());
+
+        // A pipe has no length to map, so it's read instead.
+        #[cfg(target_os = "linux")]
+        {
+            let mut child = std::process::Command::new("printf")
+                .arg("3-5\\n\\n4\\n")
+                .stdout(std::process::Stdio::piped())
+                .spawn()
+                .unwrap();
+            let stdout = child.stdout.take().unwrap();
+            let path = format!("/proc/self/fd/{}", std::os::fd::AsRawFd::as_raw_fd(&stdout));
+            assert_eq!(&*MappedFile::open(&path).unwrap(), b"3-5\n\n4\n");
+            child.wait().unwrap();
+        }
+    }
+}
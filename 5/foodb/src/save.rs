@@ -0,0 +1,238 @@
+// Parsed problems saved whole, the intervals and the ids, so a database that
+// took a while to parse or normalize can be cached and shared. A .json path
+// gets JSON and a .bin path bincode; FoodbProblem::new_from_file reads either
+// back in place of the text format.
+use super::interval::ClosedInt;
+use super::{FoodbProblem, Int};
+use aoc_utils::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// Checked against the Header before the rest is read, so a save from an
+// older layout is refused rather than misread as this one.
+const SAVE_VERSION: u32 = 1;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SaveFormat {
+    Json,
+    Bincode,
+}
+
+impl SaveFormat {
+    // None for paths that hold the text format.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<SaveFormat> {
+        match path.as_ref().extension()?.to_str()? {
+            "json" => Some(SaveFormat::Json),
+            "bin" => Some(SaveFormat::Bincode),
+            _ => None,
+        }
+    }
+}
+
+// What comes first in every save, read on its own to learn the type.
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    // The integer type's name, e.g. "u64".
+    int: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Saved<N> {
+    version: u32,
+    int: String,
+    intervals: Vec<(N, N)>,
+    ids: Vec<N>,
+}
+
+fn config() -> impl bincode::config::Config {
+    // Ids and interval ends rarely need all of their type's bytes, least of
+    // all under --int u128 or i128, so varints keep .bin saves small.
+    bincode::config::standard()
+}
+
+fn read_saved<T, P>(path: P) -> Result<T, Box<dyn Error>>
+where
+    T: for<'de> Deserialize<'de>,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let mut input = BufReader::new(File::open(path)?);
+    Ok(match SaveFormat::from_path(path) {
+        Some(SaveFormat::Json) => serde_json::from_reader(input)?,
+        Some(SaveFormat::Bincode) => bincode::serde::decode_from_std_read(&mut input, config())?,
+        None => return Err(From::from("Saved problems need a .json or .bin path")),
+    })
+}
+
+// The name a save records its integer type by, as --int spells it: u64,
+// i64, u128 or i128. Built from the type's sign and width rather than
+// type_name, whose output can change between compilers.
+fn int_name<N: Int>() -> String {
+    let sign = match N::min_value() < N::zero() {
+        true => 'i',
+        false => 'u',
+    };
+    format!("{}{}", sign, std::mem::size_of::<N>() * 8)
+}
+
+// The integer type a save at `path` holds, or None if the path isn't one.
+pub fn saved_int<P: AsRef<Path>>(path: P) -> Result<Option<String>, Box<dyn Error>> {
+    if SaveFormat::from_path(&path).is_none() {
+        return Ok(None);
+    }
+    let header: Header = read_saved(path)?;
+    Ok(Some(header.int))
+}
+
+impl<N: Int> FoodbProblem<N> {
+    // A failed save leaves any earlier one at `path` intact.
+    pub fn save<P>(&self, path: P) -> Result<(), Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let format = SaveFormat::from_path(path).ok_or("Saves need a .json or .bin path")?;
+        let saved = Saved {
+            version: SAVE_VERSION,
+            int: int_name::<N>(),
+            intervals: self.intervals.iter().map(|i| (i.low(), i.high())).collect(),
+            ids: self.to_check.clone(),
+        };
+        write_atomic(path, |out| {
+            match format {
+                SaveFormat::Json => serde_json::to_writer(out, &saved)?,
+                SaveFormat::Bincode => {
+                    bincode::serde::encode_into_std_write(&saved, out, config())?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub fn load<P>(path: P) -> Result<Self, Box<dyn Error>>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let header: Header = read_saved(path)?;
+        if header.version != SAVE_VERSION {
+            return Err(From::from(format!(
+                "Save version {} is not supported (expected {})",
+                header.version, SAVE_VERSION
+            )));
+        }
+        let int = int_name::<N>();
+        if header.int != int {
+            return Err(From::from(format!(
+                "Saved with {} ids, not {}",
+                header.int, int
+            )));
+        }
+        let saved: Saved<N> = read_saved(path)?;
+        let mut intervals = Vec::with_capacity(saved.intervals.len());
+        for (low, high) in saved.intervals {
+            match ClosedInt::new(low, high) {
+                Ok(int) => intervals.push(int),
+                Err(_) => {
+                    return Err(From::from(format!(
+                        "Saved interval {}-{} is empty",
+                        low, high
+                    )));
+                }
+            }
+        }
+        intervals.sort();
+        Ok(FoodbProblem {
+            intervals,
+            to_check: saved.ids,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::interval::{IntervalKind, IntervalSet};
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_int_name() {
+        assert_eq!(int_name::<u64>(), "u64");
+        assert_eq!(int_name::<i64>(), "i64");
+        assert_eq!(int_name::<u128>(), "u128");
+        assert_eq!(int_name::<i128>(), "i128");
+    }
+
+    #[test]
+    fn test_save_load() {
+        let lines = b"3-5
+10-14
+16-20
+12-18
+
+1
+5
+17";
+        let fdb =
+            FoodbProblem::<u64>::new_from_lines(lines.split(|&v| v == b'\n'), IntervalKind::Closed)
+                .unwrap();
+        for name in ["foodb_save_test.json", "foodb_save_test.bin"] {
+            let path = env::temp_dir().join(name);
+            fdb.save(&path).unwrap();
+            assert_eq!(saved_int(&path).unwrap().as_deref(), Some("u64"));
+            let loaded = FoodbProblem::<u64>::load(&path).unwrap();
+            assert_eq!(loaded.intervals, fdb.intervals);
+            assert_eq!(loaded.to_check, fdb.to_check);
+            // new_from_file goes by the extension too.
+            let again = FoodbProblem::<u64>::new_from_file(&path, IntervalKind::Closed).unwrap();
+            assert_eq!(again.intervals, fdb.intervals);
+            assert!(FoodbProblem::<i64>::load(&path).is_err());
+        }
+
+        // A normalized database saves and loads like any other.
+        let signed =
+            FoodbProblem::<i64>::new_from_lines(lines.split(|&v| v == b'\n'), IntervalKind::Closed)
+                .unwrap();
+        let merged = FoodbProblem {
+            intervals: IntervalSet::normalize(signed.intervals)
+                .intervals()
+                .to_vec(),
+            to_check: vec![-3, 11],
+        };
+        let path = env::temp_dir().join("foodb_save_merged.json");
+        merged.save(&path).unwrap();
+        assert_eq!(saved_int(&path).unwrap().as_deref(), Some("i64"));
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains("\"intervals\":[[3,5],[10,20]]"), "{}", text);
+        assert_eq!(FoodbProblem::<i64>::load(&path).unwrap().to_check, [-3, 11]);
+    }
+
+    #[test]
+    fn test_load_bad_saves() {
+        let path = env::temp_dir().join("foodb_bad_save.json");
+        fs::write(
+            &path,
+            b"{\"version\":1,\"int\":\"u64\",\"intervals\":[[5,3]],\"ids\":[]}",
+        )
+        .unwrap();
+        assert!(FoodbProblem::<u64>::load(&path).is_err());
+        fs::write(
+            &path,
+            b"{\"version\":9,\"int\":\"u64\",\"intervals\":[],\"ids\":[]}",
+        )
+        .unwrap();
+        assert!(FoodbProblem::<u64>::load(&path).is_err());
+        fs::write(&path, b"not json").unwrap();
+        assert!(FoodbProblem::<u64>::load(&path).is_err());
+        let empty = FoodbProblem::<u64> {
+            intervals: vec![],
+            to_check: vec![],
+        };
+        assert!(empty.save(env::temp_dir().join("foodb_save.txt")).is_err());
+    }
+}
@@ -0,0 +1,307 @@
+// Checks on an input's intervals that parsing doesn't make: copies of an
+// interval, intervals some other one already holds, and `hi-lo` intervals
+// with their ends the wrong way round, which parsing only rejects. The
+// first two change no answer about which ids are fresh but make every scan
+// over the intervals slower, so drop_redundant can take them out first.
+use super::interval::{ClosedInt, Syntax};
+use super::{
+    FoodbParseError, Int, Section, ascii_to_int, input_lines, is_separator, split_intervals,
+    split_label,
+};
+use crate::mapped::MappedFile;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+// Line numbers are 1-based.
+#[derive(Debug, PartialEq)]
+pub enum Finding<N = u64> {
+    Duplicate {
+        line: usize,
+        int: ClosedInt<N>,
+        // Where its first copy is.
+        first: usize,
+    },
+    Contained {
+        line: usize,
+        int: ClosedInt<N>,
+        within: ClosedInt<N>,
+        within_line: usize,
+    },
+    Reversed {
+        line: usize,
+        text: String,
+    },
+}
+
+impl<N: Int> fmt::Display for Finding<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Finding::Duplicate { line, int, first } => {
+                write!(f, "Line {}: {} duplicates line {}", line, int, first)
+            }
+            Finding::Contained {
+                line,
+                int,
+                within,
+                within_line,
+            } => write!(
+                f,
+                "Line {}: {} is inside {} on line {}",
+                line, int, within, within_line
+            ),
+            Finding::Reversed { line, text } => {
+                write!(f, "Line {}: {:?} has its ends reversed", line, text)
+            }
+        }
+    }
+}
+
+// The intervals' indexes in order of low end, longest first, then input
+// order.
+fn by_low<N: Int>(ints: &[ClosedInt<N>]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..ints.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (x, y) = (&ints[a], &ints[b]);
+        x.low()
+            .cmp(&y.low())
+            .then(y.high().cmp(&x.high()))
+            .then(a.cmp(&b))
+    });
+    order
+}
+
+// For each interval, the one holding it that comes first in by_low's
+// order, or None if nothing else holds it. In that order, whatever holds
+// an interval comes before it, and the longest reach so far is the one to
+// check against.
+fn containers<N: Int>(ints: &[ClosedInt<N>]) -> Vec<Option<usize>> {
+    let mut within = vec![None; ints.len()];
+    let mut widest: Option<usize> = None;
+    for i in by_low(ints) {
+        match widest {
+            Some(w) if ints[w].high() >= ints[i].high() => within[i] = Some(w),
+            _ => widest = Some(i),
+        }
+    }
+    within
+}
+
+// For each interval, its first copy in input order, or None if it is the
+// first. by_low puts copies next to each other, the first one first.
+fn first_copies<N: Int>(ints: &[ClosedInt<N>]) -> Vec<Option<usize>> {
+    let mut copy_of = vec![None; ints.len()];
+    let mut first: Option<usize> = None;
+    for i in by_low(ints) {
+        match first {
+            Some(f) if ints[f] == ints[i] => copy_of[i] = Some(f),
+            _ => first = Some(i),
+        }
+    }
+    copy_of
+}
+
+// The intervals without copies of each other or any held by another, in
+// the order given. They cover exactly what all of them did.
+pub fn drop_redundant<N: Int>(ints: &[ClosedInt<N>]) -> Vec<ClosedInt<N>> {
+    containers(ints)
+        .into_iter()
+        .zip(ints)
+        .filter(|(within, _)| within.is_none())
+        .map(|(_, int)| int.clone())
+        .collect()
+}
+
+// Whether a piece is a lo-hi or bracketed interval whose low end is above
+// its high end.
+fn reversed<N: Int>(piece: &[u8]) -> bool {
+    let txt = piece.trim_ascii();
+    let ends = |low: &[u8], high: &[u8]| match (
+        ascii_to_int::<N>(low.trim_ascii()),
+        ascii_to_int::<N>(high.trim_ascii()),
+    ) {
+        (Ok(low), Ok(high)) => low > high,
+        _ => false,
+    };
+    match (txt.first(), txt.last()) {
+        (Some(b'[' | b'('), Some(b']' | b')')) => {
+            let body = &txt[1..txt.len() - 1];
+            match body.iter().position(|&c| c == b',') {
+                Some(sep) => ends(&body[..sep], &body[sep + 1..]),
+                None => false,
+            }
+        }
+        _ => (0..txt.len())
+            .filter(|&i| is_separator(txt, i))
+            .any(|sep| ends(&txt[..sep], &txt[sep + 1..])),
+    }
+}
+
+// What's wrong with an input's intervals, by line. Pieces that aren't
+// intervals at all, reversed or not, are still parse errors.
+pub fn validate_lines<N, I, S, T>(
+    line_iter: T,
+    syntax: impl Into<Syntax>,
+) -> Result<Vec<Finding<N>>, FoodbParseError>
+where
+    N: Int,
+    I: Iterator<Item = S>,
+    S: AsRef<[u8]>,
+    T: IntoIterator<IntoIter = I, Item = S>,
+{
+    let syntax = syntax.into();
+    // Each with the place of its piece in the input, to sort them back
+    // into input order by.
+    let mut findings = Vec::new();
+    let (mut places, mut lines, mut ints) = (Vec::new(), Vec::new(), Vec::new());
+    for (lineno, line) in line_iter.into_iter().enumerate() {
+        let line = line.as_ref();
+        if line.is_empty() {
+            break;
+        }
+        for piece in split_intervals(line) {
+            let place = places.len() + findings.len();
+            let (_, int) = split_label(piece);
+            let parsed = match syntax.lenient {
+                true => ClosedInt::<N>::parse_lenient(int, syntax.kind),
+                false => ClosedInt::<N>::parse(int, syntax.kind),
+            };
+            match parsed {
+                Ok(x) => {
+                    if let Some(int) = x {
+                        places.push(place);
+                        lines.push(lineno + 1);
+                        ints.push(int);
+                    }
+                }
+                Err(_) if reversed::<N>(int) => findings.push((
+                    place,
+                    Finding::Reversed {
+                        line: lineno + 1,
+                        text: String::from_utf8_lossy(piece.trim_ascii()).into_owned(),
+                    },
+                )),
+                Err(_) => {
+                    return Err(FoodbParseError::Unparsable {
+                        line: lineno + 1,
+                        section: Section::Intervals,
+                        text: String::from_utf8_lossy(piece.trim_ascii()).into_owned(),
+                    });
+                }
+            }
+        }
+    }
+    // A copy is a duplicate even when something wider holds it too.
+    let copies = first_copies(&ints);
+    for (i, within) in containers(&ints).into_iter().enumerate() {
+        let finding = match (copies[i], within) {
+            (Some(f), _) => Finding::Duplicate {
+                line: lines[i],
+                int: ints[i].clone(),
+                first: lines[f],
+            },
+            (None, Some(w)) => Finding::Contained {
+                line: lines[i],
+                int: ints[i].clone(),
+                within: ints[w].clone(),
+                within_line: lines[w],
+            },
+            (None, None) => continue,
+        };
+        findings.push((places[i], finding));
+    }
+    findings.sort_by_key(|&(place, _)| place);
+    Ok(findings.into_iter().map(|(_, f)| f).collect())
+}
+
+pub fn validate_file<N: Int, P: AsRef<Path>>(
+    filename: P,
+    syntax: impl Into<Syntax>,
+) -> Result<Vec<Finding<N>>, Box<dyn Error>> {
+    let map = MappedFile::open(filename)?;
+    Ok(validate_lines(input_lines(&map), syntax)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interval::{IntervalKind, IntervalSet};
+
+    #[test]
+    fn test_validate() {
+        let lines = b"3-5, 10-14
+12-13
+3-5
+20-16
+10-14, [9, 7]
+a: 3-5
+1-20
+
+1";
+        let findings: Vec<String> =
+            validate_lines::<u64, _, _, _>(lines.split(|&v| v == b'\n'), IntervalKind::Closed)
+                .unwrap()
+                .iter()
+                .map(|f| f.to_string())
+                .collect();
+        assert_eq!(
+            findings,
+            [
+                "Line 1: 3-5 is inside 1-20 on line 7",
+                "Line 1: 10-14 is inside 1-20 on line 7",
+                "Line 2: 12-13 is inside 1-20 on line 7",
+                "Line 3: 3-5 duplicates line 1",
+                "Line 4: \"20-16\" has its ends reversed",
+                "Line 5: 10-14 duplicates line 1",
+                "Line 5: \"[9, 7]\" has its ends reversed",
+                "Line 6: 3-5 duplicates line 1",
+            ]
+        );
+        let lines = b"3-5\n10-14\n12-13\n3-5\n10-14\n\n1";
+        let findings: Vec<String> =
+            validate_lines::<u64, _, _, _>(lines.split(|&v| v == b'\n'), IntervalKind::Closed)
+                .unwrap()
+                .iter()
+                .map(|f| f.to_string())
+                .collect();
+        assert_eq!(
+            findings,
+            [
+                "Line 3: 12-13 is inside 10-14 on line 2",
+                "Line 4: 3-5 duplicates line 1",
+                "Line 5: 10-14 duplicates line 2",
+            ]
+        );
+        let bad = validate_lines::<u64, _, _, _>([b"3-x".as_slice()], IntervalKind::Closed);
+        assert!(bad.is_err());
+        let signed = validate_lines::<i64, _, _, _>([b"-3--5".as_slice()], IntervalKind::Closed);
+        assert_eq!(signed.unwrap().len(), 1);
+
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let ints = [
+            int(3, 5),
+            int(1, 2),
+            int(3, 5),
+            int(4, 5),
+            int(0, 9),
+            int(12, 14),
+        ];
+        assert_eq!(drop_redundant(&ints), [int(0, 9), int(12, 14)]);
+        for seed in 1..20 {
+            let ints = crate::interval::random_intervals(200, 1000, 40, seed);
+            let kept = drop_redundant(&ints);
+            assert_eq!(
+                IntervalSet::normalize(kept.iter().cloned()),
+                IntervalSet::normalize(ints.iter().cloned())
+            );
+            for a in &kept {
+                assert_eq!(
+                    kept.iter()
+                        .filter(|b| b.contains(a.low()) && b.contains(a.high()))
+                        .count(),
+                    1
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// A text chart of the database: the numbers from the lowest interval or id
+// to the highest, in equal columns, with a bar for the most intervals
+// holding any number in each column, a row under it for how much of the
+// column is covered, and a row marking where the ids fall. Gaps show as
+// breaks in the coverage row and pile-ups as the tall bars.
+use super::Int;
+use super::interval::{ClosedInt, IntervalSet, OverlapCounter};
+use std::io::{self, Write};
+
+pub const DEFAULT_VIZ_COLUMNS: usize = 72;
+
+// Deeper pile-ups are scaled down to this many rows.
+const MAX_BAR_ROWS: usize = 8;
+
+#[derive(Debug, PartialEq)]
+pub struct Column<N = u64> {
+    pub span: ClosedInt<N>,
+    // The most intervals holding any one number in the span.
+    pub depth: usize,
+    // How many numbers in the span some interval holds.
+    pub covered: u128,
+    // Whether any ids fall in the span, and whether any of those are
+    // uncovered.
+    pub has_ids: bool,
+    pub has_uncovered: bool,
+}
+
+// `by` past `low`, which the caller keeps inside the type.
+fn offset<N: Int>(low: N, by: u128) -> N {
+    match low.to_i128().and_then(|l| l.checked_add_unsigned(by)) {
+        Some(v) => N::from(v).unwrap(),
+        // Only u128 sums get this far, and those aren't negative.
+        None => N::from(low.to_u128().unwrap() + by).unwrap(),
+    }
+}
+
+// At most `columns` columns, all but the last the same width, or none
+// with no intervals or ids.
+pub fn columns<N: Int>(intervals: &[ClosedInt<N>], ids: &[N], columns: usize) -> Vec<Column<N>> {
+    let ends = intervals.iter().map(|i| (i.low(), i.high()));
+    let ends = ends.chain(ids.iter().map(|&id| (id, id)));
+    let Some((low, high)) = ends.reduce(|(l, h), (il, ih)| (l.min(il), h.max(ih))) else {
+        return Vec::new();
+    };
+    let len = ClosedInt::new(low, high).unwrap().length();
+    let width = len.div_ceil(columns.max(1) as u128);
+    let set = IntervalSet::normalize(intervals.iter().cloned());
+    let counter = OverlapCounter::new(intervals);
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    (0..len.div_ceil(width))
+        .map(|i| {
+            let start = i * width;
+            let end = start.saturating_add(width).min(len) - 1;
+            let span = ClosedInt::new(offset(low, start), offset(low, end)).unwrap();
+            let ints = set.intervals();
+            let first = ints.partition_point(|i| i.high() < span.low());
+            let covered = ints[first..]
+                .iter()
+                .map_while(|i| i.intersect(&span))
+                .map(|i| i.length())
+                .sum();
+            let in_span = &ids[ids.partition_point(|&id| id < span.low())
+                ..ids.partition_point(|&id| id <= span.high())];
+            Column {
+                depth: counter.deepest_in(&span),
+                covered,
+                has_ids: !in_span.is_empty(),
+                has_uncovered: in_span.iter().any(|&id| !set.contains(id)),
+                span,
+            }
+        })
+        .collect()
+}
+
+pub fn write_viz<N: Int, W: Write>(
+    out: &mut W,
+    intervals: &[ClosedInt<N>],
+    ids: &[N],
+    columns: usize,
+) -> io::Result<()> {
+    let cols = self::columns(intervals, ids, columns);
+    let (Some(first), Some(last)) = (cols.first(), cols.last()) else {
+        return writeln!(out, "Nothing to chart");
+    };
+    let most = cols.iter().map(|c| c.depth).max().unwrap();
+    let rows = most.min(MAX_BAR_ROWS);
+    let margin = most.to_string().len();
+    for row in 0..rows {
+        // The rows split 1..=most evenly; only the deepest reach the top.
+        let at_least = (most * (rows - row)).div_ceil(rows);
+        let bar: String = cols
+            .iter()
+            .map(|c| if c.depth >= at_least { '#' } else { ' ' })
+            .collect();
+        writeln!(out, "{:>margin$} |{}", at_least, bar.trim_end())?;
+    }
+    let coverage: String = cols
+        .iter()
+        .map(|c| match c.covered {
+            0 => ' ',
+            n if n == c.span.length() => '=',
+            _ => '-',
+        })
+        .collect();
+    writeln!(out, "{:>margin$} +{}", 0, coverage.trim_end())?;
+    let marks: String = cols
+        .iter()
+        .map(|c| match (c.has_ids, c.has_uncovered) {
+            (_, true) => 'x',
+            (true, false) => '^',
+            _ => ' ',
+        })
+        .collect();
+    writeln!(out, "{:margin$}  {}", "", marks.trim_end())?;
+    let (low, high) = (first.span.low().to_string(), last.span.high().to_string());
+    let gap = cols.len().saturating_sub(low.len() + high.len()).max(1);
+    writeln!(out, "{:margin$}  {}{:gap$}{}", "", low, "", high)?;
+    writeln!(
+        out,
+        "{} per column; = covered, - partly, x uncovered ids, ^ fresh ids",
+        first.span.length()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_viz() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let intervals = [int(0, 9), int(5, 14), int(30, 39), int(8, 8)];
+        let cols = columns(&intervals, &[3, 20, 35], 8);
+        assert_eq!(cols.len(), 8);
+        assert_eq!(cols[0].span, int(0, 4));
+        assert_eq!(cols[7].span, int(35, 39));
+        let depths: Vec<usize> = cols.iter().map(|c| c.depth).collect();
+        assert_eq!(depths, [1, 3, 1, 0, 0, 0, 1, 1]);
+        let covered: Vec<u128> = cols.iter().map(|c| c.covered).collect();
+        assert_eq!(covered, [5, 5, 5, 0, 0, 0, 5, 5]);
+        assert!(cols[0].has_ids && !cols[0].has_uncovered);
+        assert!(cols[4].has_uncovered && !cols[5].has_ids);
+
+        let mut out = Vec::new();
+        write_viz(&mut out, &intervals, &[3, 20, 35], 8).unwrap();
+        let expected = "\
+3 | #
+2 | #
+1 |###   ##
+0 +===   ==
+   ^   x  ^
+   0     39
+5 per column; = covered, - partly, x uncovered ids, ^ fresh ids
+";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+        // Wider than the domain, so one number per column, and the ends of
+        // a signed one.
+        assert_eq!(columns(&[int(3, 5)], &[], 72).len(), 3);
+        let wide = columns(&[ClosedInt::new(i64::MIN, i64::MAX).unwrap()], &[-1], 4);
+        assert_eq!(wide[1].span, ClosedInt::new(i64::MIN / 2, -1).unwrap());
+        assert_eq!(wide[3].span.high(), i64::MAX);
+        assert!(
+            wide.iter()
+                .all(|c| c.depth == 1 && c.covered == c.span.length())
+        );
+        let mut out = Vec::new();
+        write_viz::<u64, _>(&mut out, &[], &[], 8).unwrap();
+        assert_eq!(out, b"Nothing to chart\n");
+    }
+}
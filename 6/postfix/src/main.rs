@@ -1,16 +1,21 @@
-use log::info;
+use aoc_utils::try_argmax;
 use simple_logger::SimpleLogger;
-use std::borrow::Cow;
 use std::env;
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
 
 #[derive(Debug, PartialEq)]
-struct InvalidAsciiU64;
+enum InvalidAsciiNum {
+    NoDigits,
+    // Past the limit it was read with.
+    TooLong,
+}
 
-fn ascii_to_u64(bytes: &[u8]) -> Result<(u64, usize), InvalidAsciiU64> {
+// The leading digits' value, if it's at most limit, and how many there are.
+fn ascii_to_num(bytes: &[u8], limit: u128) -> Result<(u128, usize), InvalidAsciiNum> {
     let mut end = 0;
     while end < bytes.len() {
         if bytes[end] < b'0' || bytes[end] > b'9' {
@@ -19,15 +24,19 @@ fn ascii_to_u64(bytes: &[u8]) -> Result<(u64, usize), InvalidAsciiU64> {
         end += 1;
     }
     let end = end;
-    let mut res = 0;
+    let mut res: u128 = 0;
     let mut idx = 0;
     while idx < end {
-        let val = (bytes[idx] - b'0') as u64;
-        res += val * 10_u64.pow((end - idx - 1) as u32);
+        let val = (bytes[idx] - b'0') as u128;
+        res = res
+            .checked_mul(10)
+            .and_then(|res| res.checked_add(val))
+            .filter(|&res| res <= limit)
+            .ok_or(InvalidAsciiNum::TooLong)?;
         idx += 1;
     }
     if idx == 0 {
-        Err(InvalidAsciiU64)
+        Err(InvalidAsciiNum::NoDigits)
     } else {
         Ok((res, idx))
     }
@@ -44,6 +53,142 @@ fn consume_space(bytes: &[u8]) -> usize {
 enum MathOp {
     Sum,
     Product,
+    Difference,
+    Quotient,
+    Min,
+    Max,
+    // The digits of each number after the last's: 12 | 34 is 1234.
+    Concat,
+}
+
+// Why a number couldn't be taken into its problem's answer.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ArithError {
+    DivideByZero,
+    // A difference below zero, which no answer here can hold.
+    Negative,
+    Overflow,
+}
+
+// What to do with an answer too big for a u64, picked with --overflow.
+// Answers are held as u128s whichever it is, and only `big` lets them
+// use the whole width; past that even `big` reports an overflow.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Overflow {
+    Checked,
+    Saturating,
+    Wrapping,
+    Big,
+}
+
+impl Overflow {
+    fn from_name(name: &str) -> Option<Overflow> {
+        match name {
+            "checked" => Some(Overflow::Checked),
+            "saturating" => Some(Overflow::Saturating),
+            "wrapping" => Some(Overflow::Wrapping),
+            "big" => Some(Overflow::Big),
+            _ => None,
+        }
+    }
+
+    // The largest answer there can be.
+    fn limit(self) -> u128 {
+        match self {
+            Overflow::Big => u128::MAX,
+            _ => u64::MAX as u128,
+        }
+    }
+}
+
+// Problems and their numbers count from 0, numbers in the order they're
+// taken: down the rows for part 1, right to left for part 2.
+#[derive(Debug, PartialEq)]
+struct SolveError {
+    problem: usize,
+    number: usize,
+    error: ArithError,
+}
+
+impl fmt::Display for ArithError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArithError::DivideByZero => write!(f, "divides by zero"),
+            ArithError::Negative => write!(f, "goes below zero"),
+            ArithError::Overflow => write!(f, "overflows"),
+        }
+    }
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Problem {} {} at number {}",
+            self.problem, self.error, self.number
+        )
+    }
+}
+
+impl Error for SolveError {}
+
+impl MathOp {
+    // What a problem's answer starts from, for the ops that have an
+    // identity. The rest start from the problem's first number, so
+    // `-` and `/` run top down: 10 - 3 - 2 is 5.
+    fn identity(&self, overflow: Overflow) -> Option<u128> {
+        match self {
+            MathOp::Sum | MathOp::Max => Some(0),
+            MathOp::Product => Some(1),
+            MathOp::Min => Some(overflow.limit()),
+            MathOp::Difference | MathOp::Quotient | MathOp::Concat => None,
+        }
+    }
+
+    // Division truncates, and by zero is an error rather than a panic. A
+    // result past the limit is an error too unless overflow says to
+    // saturate or wrap it.
+    fn apply(&self, acc: u128, val: u128, overflow: Overflow) -> Result<u128, ArithError> {
+        let exact = match self {
+            MathOp::Sum => acc.checked_add(val),
+            MathOp::Product => acc.checked_mul(val),
+            MathOp::Difference => Some(acc.checked_sub(val).ok_or(ArithError::Negative)?),
+            MathOp::Quotient => Some(acc.checked_div(val).ok_or(ArithError::DivideByZero)?),
+            MathOp::Min => Some(acc.min(val)),
+            MathOp::Max => Some(acc.max(val)),
+            MathOp::Concat => 10_u128
+                .checked_pow(val.checked_ilog10().unwrap_or(0) + 1)
+                .and_then(|shift| acc.checked_mul(shift))
+                .and_then(|shifted| shifted.checked_add(val)),
+        };
+        match (exact, overflow) {
+            (Some(res), _) if res <= overflow.limit() => Ok(res),
+            (_, Overflow::Saturating) => Ok(overflow.limit()),
+            (_, Overflow::Wrapping) => Ok(self.wrapping(acc as u64, val as u64) as u128),
+            _ => Err(ArithError::Overflow),
+        }
+    }
+
+    // The result in a u64 modulo 2^64, for the ops that can overflow.
+    fn wrapping(&self, acc: u64, val: u64) -> u64 {
+        match self {
+            MathOp::Sum => acc.wrapping_add(val),
+            MathOp::Product => acc.wrapping_mul(val),
+            MathOp::Concat => acc
+                .wrapping_mul(10_u64.wrapping_pow(val.checked_ilog10().unwrap_or(0) + 1))
+                .wrapping_add(val),
+            _ => unreachable!("only sums, products and concatenations overflow"),
+        }
+    }
+
+    // A problem's answer so far, None before its first number, with val
+    // taken in.
+    fn fold(&self, acc: Option<u128>, val: u128, overflow: Overflow) -> Result<u128, ArithError> {
+        match acc.or(self.identity(overflow)) {
+            Some(acc) => self.apply(acc, val, overflow),
+            None => Ok(val),
+        }
+    }
 }
 
 enum LineType {
@@ -52,21 +197,80 @@ enum LineType {
     Empty,
 }
 
+// By the line's first byte that isn't a space, so a line of nothing but
+// spaces is empty.
 fn classify_line_type(bytes: &[u8]) -> Result<LineType, UnknownLineType> {
     let idx = consume_space(bytes);
-    if bytes[idx] >= b'0' && bytes[idx] <= b'9' {
-        Ok(LineType::Numbers)
-    } else if let Ok(_) = get_op(bytes[idx]) {
-        Ok(LineType::Ops)
-    } else if bytes.len() - idx == 0 {
-        Ok(LineType::Empty)
-    } else {
-        Err(UnknownLineType)
+    match bytes.get(idx) {
+        None => Ok(LineType::Empty),
+        Some(c) if c.is_ascii_digit() => Ok(LineType::Numbers),
+        Some(&c) if get_op(c).is_ok() => Ok(LineType::Ops),
+        Some(_) => Err(UnknownLineType { column: idx }),
     }
 }
 
+// The offset of the byte the line starts with.
 #[derive(Debug, PartialEq)]
-struct UnknownLineType;
+struct UnknownLineType {
+    column: usize,
+}
+
+// What the worksheet parser came across instead of what it expected.
+#[derive(Debug, PartialEq)]
+enum Found {
+    Token(String),
+    EndOfLine,
+    EndOfInput,
+}
+
+// Why a worksheet couldn't be read. Lines and columns count from 1.
+#[derive(Debug, PartialEq)]
+struct MathParseError {
+    line: usize,
+    column: usize,
+    expected: &'static str,
+    found: Found,
+}
+
+impl MathParseError {
+    // At offset idx of a line, which is where a token starts or its end;
+    // the token runs up to the next space.
+    fn at(line: &[u8], lineno: usize, idx: usize, expected: &'static str) -> Self {
+        let rest = line.get(idx..).unwrap_or_default();
+        let token = rest.split(|&c| c == b' ').next().unwrap_or_default();
+        MathParseError {
+            line: lineno,
+            column: idx + 1,
+            expected,
+            found: match token.is_empty() {
+                true => Found::EndOfLine,
+                false => Found::Token(String::from_utf8_lossy(token).into_owned()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Found {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Found::Token(token) => write!(f, "{:?}", token),
+            Found::EndOfLine => write!(f, "end of line"),
+            Found::EndOfInput => write!(f, "end of input"),
+        }
+    }
+}
+
+impl fmt::Display for MathParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Line {}, column {}: expected {}, found {}",
+            self.line, self.column, self.expected, self.found
+        )
+    }
+}
+
+impl Error for MathParseError {}
 
 #[derive(Debug, PartialEq)]
 struct InvalidMathOp(u8);
@@ -75,10 +279,128 @@ fn get_op(c: u8) -> Result<MathOp, InvalidMathOp> {
     match c {
         b'+' => Ok(MathOp::Sum),
         b'*' => Ok(MathOp::Product),
+        b'-' => Ok(MathOp::Difference),
+        b'/' => Ok(MathOp::Quotient),
+        b'm' => Ok(MathOp::Min),
+        b'M' => Ok(MathOp::Max),
+        b'|' => Ok(MathOp::Concat),
         _ => Err(InvalidMathOp(c)),
     }
 }
 
+// Why a line of genuine postfix (`3 4 + 2 *`) didn't come to one number.
+// Lines count from 1.
+#[derive(Debug, PartialEq)]
+enum RpnError {
+    // An operator with fewer than two numbers under it.
+    Underflow { line: usize, token: String },
+    // More than one number left at the end, or none.
+    Leftover { line: usize, count: usize },
+    BadToken { line: usize, token: String },
+    Arith { line: usize, error: ArithError },
+}
+
+impl fmt::Display for RpnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RpnError::Underflow { line, token } => {
+                write!(f, "Line {}: {} needs two numbers", line, token)
+            }
+            RpnError::Leftover { line, count } => {
+                write!(f, "Line {}: {} numbers left, not 1", line, count)
+            }
+            RpnError::BadToken { line, token } => {
+                write!(
+                    f,
+                    "Line {}: {:?} is neither a number nor an operator",
+                    line, token
+                )
+            }
+            RpnError::Arith { line, error } => write!(f, "Line {}: {}", line, error),
+        }
+    }
+}
+
+impl Error for RpnError {}
+
+fn tokens(line: &[u8]) -> impl Iterator<Item = &[u8]> {
+    line.split(|c| c.is_ascii_whitespace())
+        .filter(|t| !t.is_empty())
+}
+
+// Whether a line is a postfix expression rather than a worksheet row: it
+// has numbers and an operator both, where worksheet rows are all one or
+// the other.
+fn is_rpn_line(line: &[u8]) -> bool {
+    let mut number = false;
+    let mut op = false;
+    for t in tokens(line) {
+        number |= t.iter().all(u8::is_ascii_digit);
+        op |= t.len() == 1 && get_op(t[0]).is_ok();
+    }
+    number && op
+}
+
+// One postfix expression, on an operand stack: numbers push, and each
+// operator pops b, then a, and pushes a op b, so `10 3 -` is 7.
+fn eval_rpn(line: &[u8], lineno: usize, overflow: Overflow) -> Result<u128, RpnError> {
+    let mut stack = Vec::<u128>::new();
+    for t in tokens(line) {
+        let token = || String::from_utf8_lossy(t).into_owned();
+        if let Ok((num, len)) = ascii_to_num(t, overflow.limit())
+            && len == t.len()
+        {
+            stack.push(num);
+            continue;
+        }
+        let op = match t {
+            [c] => get_op(*c).map_err(|_| RpnError::BadToken {
+                line: lineno,
+                token: token(),
+            })?,
+            _ => {
+                return Err(RpnError::BadToken {
+                    line: lineno,
+                    token: token(),
+                });
+            }
+        };
+        let (Some(b), Some(a)) = (stack.pop(), stack.pop()) else {
+            return Err(RpnError::Underflow {
+                line: lineno,
+                token: token(),
+            });
+        };
+        let res = op.apply(a, b, overflow).map_err(|error| RpnError::Arith {
+            line: lineno,
+            error,
+        })?;
+        stack.push(res);
+    }
+    match stack[..] {
+        [res] => Ok(res),
+        _ => Err(RpnError::Leftover {
+            line: lineno,
+            count: stack.len(),
+        }),
+    }
+}
+
+// Every non-blank line's value, in order.
+fn solve_rpn<I, S, T>(line_iter: T, overflow: Overflow) -> Result<Vec<u128>, RpnError>
+where
+    I: Iterator<Item = S>,
+    S: AsRef<[u8]>,
+    T: IntoIterator<IntoIter = I, Item = S>,
+{
+    line_iter
+        .into_iter()
+        .enumerate()
+        .filter(|(_, line)| tokens(line.as_ref()).next().is_some())
+        .map(|(i, line)| eval_rpn(line.as_ref(), i + 1, overflow))
+        .collect()
+}
+
 fn read_lines<P>(filename: P) -> io::Result<io::Split<io::BufReader<File>>>
 where
     P: AsRef<Path>,
@@ -90,181 +412,585 @@ where
 struct MathProblems {
     height: u32,
     width: u32,
-    numbers: Vec<u64>,
+    numbers: Vec<u128>,
     operators: Vec<MathOp>,
+    // The number lines as they were read, and the column each operator sat
+    // in, for reading the worksheet by columns of digits instead.
+    rows: Vec<Vec<u8>>,
+    op_columns: Vec<usize>,
 }
 
 impl MathProblems {
-    fn new_from_file<P>(filename: P) -> Self
-    where
-        P: AsRef<Path>,
-    {
-        let mut line_iter = read_lines(filename).unwrap().map(|res| res.unwrap());
-        return Self::new_from_lines(&mut line_iter);
-    }
-
-    fn new_from_lines<I, S, T>(line_iter: T) -> Self
+    // Rows of numbers, all as many as the first, then a row of as many
+    // operators, and after that nothing but blank lines. Numbers can be as
+    // big as overflow's limit.
+    fn new_from_lines<I, S, T>(line_iter: T, overflow: Overflow) -> Result<Self, MathParseError>
     where
         I: Iterator<Item = S>,
         S: AsRef<[u8]>,
         T: IntoIterator<IntoIter = I, Item = S>,
     {
-        let mut numbers = Vec::<u64>::new();
-        let mut nlines = 0;
+        let mut numbers = Vec::<u128>::new();
+        let mut rows = Vec::<Vec<u8>>::new();
 
-        let mut process_num_line = |line: &[u8], expected_width: Option<u32>| -> u32 {
+        let mut process_num_line = |line: &[u8], lineno: usize, expected_width: Option<u32>| {
             let mut w = 0;
-            let mut idx = 0;
+            let mut idx = consume_space(line);
             while idx < line.len() {
-                idx += consume_space(&line[idx..]);
-                if let Ok((num, offs)) = ascii_to_u64(line[idx..].as_ref()) {
-                    numbers.push(num);
-                    //                    info!("Got Num: {} {} -> {}", num, idx, offs);
-                    idx += offs;
-                } else {
-                    //                  info!("giving up on: {}", line[idx]);
-                    break;
+                if expected_width == Some(w) {
+                    return Err(MathParseError::at(line, lineno, idx, "end of line"));
                 }
-
-                w += 1;
-                if let Some(ewidth) = expected_width {
-                    if w > ewidth {
-                        panic!("Line exceeded expected width {}", ewidth);
+                let (num, offs) = match ascii_to_num(&line[idx..], overflow.limit()) {
+                    Ok(parsed) => parsed,
+                    Err(InvalidAsciiNum::NoDigits) => {
+                        return Err(MathParseError::at(line, lineno, idx, "a number"));
                     }
+                    Err(InvalidAsciiNum::TooLong) => {
+                        let expected = match overflow {
+                            Overflow::Big => "a number that fits in a u128",
+                            _ => "a number that fits in a u64",
+                        };
+                        return Err(MathParseError::at(line, lineno, idx, expected));
+                    }
+                };
+                numbers.push(num);
+                idx += offs;
+                idx += consume_space(&line[idx..]);
+                w += 1;
+            }
+            match expected_width {
+                Some(ewidth) if w < ewidth => {
+                    Err(MathParseError::at(line, lineno, idx, "a number"))
                 }
+                _ => Ok(w),
             }
-            w
         };
 
         let mut ops = Vec::<MathOp>::new();
+        let mut op_columns = Vec::<usize>::new();
 
-        let mut process_op_line = |line: &[u8], expected_width: Option<u32>| -> u32 {
+        let mut process_op_line = |line: &[u8], lineno: usize, width: u32| {
             let mut w = 0;
-            let mut idx = 0;
-            loop {
-                idx += consume_space(&line[idx..]);
-                if idx < line.len() {
-                    if let Ok(op) = get_op(line[idx]) {
-                        ops.push(op);
-                        idx += 1;
-                    } else {
-                        break;
-                    }
-                } else {
-                    break;
+            let mut idx = consume_space(line);
+            while idx < line.len() {
+                if w == width {
+                    return Err(MathParseError::at(line, lineno, idx, "end of line"));
                 }
-
+                let Ok(op) = get_op(line[idx]) else {
+                    return Err(MathParseError::at(line, lineno, idx, "an operator"));
+                };
+                ops.push(op);
+                op_columns.push(idx);
+                idx += 1;
+                idx += consume_space(&line[idx..]);
                 w += 1;
-                if let Some(ewidth) = expected_width {
-                    if w > ewidth {
-                        panic!("Line exceeded expected width {}", ewidth);
-                    }
-                }
             }
-            w
+            match w < width {
+                true => Err(MathParseError::at(line, lineno, idx, "an operator")),
+                false => Ok(()),
+            }
         };
 
-        let mut line_iter = line_iter.into_iter().peekable();
+        let mut line_iter = line_iter.into_iter().enumerate();
 
-        let first = match line_iter.next() {
-            Some(f) => f,
-            None => panic!("No lines to read!"),
+        let Some((_, first)) = line_iter.next() else {
+            return Err(MathParseError {
+                line: 1,
+                column: 1,
+                expected: "a number",
+                found: Found::EndOfInput,
+            });
         };
-        let width = match classify_line_type(first.as_ref()) {
-            Ok(LineType::Numbers) => process_num_line(first.as_ref(), None),
-            Ok(LineType::Ops) => {
-                panic!("Got an ops line as first line!");
+        let first = first.as_ref();
+        let width = match classify_line_type(first) {
+            Ok(LineType::Numbers) => {
+                rows.push(first.to_vec());
+                process_num_line(first, 1, None)?
             }
-            Ok(LineType::Empty) => {
-                panic!("Got an empty line as first line!");
-            }
-            Err(UnknownLineType) => {
-                panic!("Unknown line type!");
+            _ => {
+                return Err(MathParseError::at(
+                    first,
+                    1,
+                    consume_space(first),
+                    "a number",
+                ));
             }
         };
-        nlines += 1;
-
-        while let Some(line) = line_iter.next() {
-            if let Ok(line_type) = classify_line_type(line.as_ref()) {
-                match line_type {
-                    LineType::Numbers => {
-                        let w = process_num_line(line.as_ref(), Some(width));
-                        if w != width {
-                            panic!("Mismatched line width {}, expected {}", w, width);
-                        }
-                    }
-                    LineType::Ops => {
-                        let w = process_op_line(line.as_ref(), Some(width));
-                        if w != width {
-                            panic!("Mismatched line width {}, expected {}", w, width);
-                        }
-                    }
-                    LineType::Empty => {
-                        assert!(line_iter.peek().is_none() && ops.len() == width as usize);
-                        break;
-                    }
-                }
 
-                nlines += 1;
-            } else {
-                panic!("Unknown line type!");
+        let mut got_ops = false;
+        for (i, line) in line_iter {
+            let (line, lineno) = (line.as_ref(), i + 1);
+            let expected = match got_ops {
+                true => "end of input",
+                false => "a number or an operator",
+            };
+            match classify_line_type(line) {
+                Ok(LineType::Empty) if got_ops => {}
+                Ok(_) if got_ops => {
+                    return Err(MathParseError::at(
+                        line,
+                        lineno,
+                        consume_space(line),
+                        expected,
+                    ));
+                }
+                Ok(LineType::Numbers) => {
+                    rows.push(line.to_vec());
+                    process_num_line(line, lineno, Some(width))?;
+                }
+                Ok(LineType::Ops) => {
+                    process_op_line(line, lineno, width)?;
+                    got_ops = true;
+                }
+                Ok(LineType::Empty) => {
+                    return Err(MathParseError::at(
+                        line,
+                        lineno,
+                        consume_space(line),
+                        expected,
+                    ));
+                }
+                Err(UnknownLineType { column }) => {
+                    return Err(MathParseError::at(line, lineno, column, expected));
+                }
             }
         }
-        assert!(ops.len() == width as usize);
+        if !got_ops {
+            return Err(MathParseError {
+                line: rows.len() + 1,
+                column: 1,
+                expected: "an operator",
+                found: Found::EndOfInput,
+            });
+        }
 
-        Self {
-            height: nlines - 1,
-            width: width,
-            numbers: numbers,
+        Ok(Self {
+            height: rows.len() as u32,
+            width,
+            numbers,
             operators: ops,
-        }
+            rows,
+            op_columns,
+        })
     }
 
-    fn solve(&self) -> Vec<u64> {
-        let mut res: Vec<u64> = vec![0_u64; self.width as usize];
-        for p in 0..(self.width as usize) {
-            match self.operators[p] {
-                MathOp::Sum => {
-                    res[p] = 0;
-                }
-                MathOp::Product => {
-                    res[p] = 1;
+    // Part 2: each problem's numbers run down its columns, the top digit
+    // most significant, read right to left. A problem starts at its
+    // operator's column and runs up to the next one; the blank column
+    // between problems, and any short line's missing tail, add no digits.
+    // A column's digits are taken in as concatenations, so a number too
+    // long for the limit overflows the way a product would.
+    fn solve_columns(&self, overflow: Overflow) -> Result<Vec<u128>, SolveError> {
+        let line_len = self.rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        let mut res = Vec::with_capacity(self.width as usize);
+        for (p, op) in self.operators.iter().enumerate() {
+            let start = self.op_columns[p];
+            let end = self.op_columns.get(p + 1).copied().unwrap_or(line_len);
+            let mut acc = None;
+            let mut n = 0;
+            for col in (start..end).rev() {
+                let mut digits = self
+                    .rows
+                    .iter()
+                    .filter_map(|r| r.get(col).filter(|c| c.is_ascii_digit()))
+                    .peekable();
+                if digits.peek().is_none() {
+                    continue;
                 }
+                let err = |error| SolveError {
+                    problem: p,
+                    number: n,
+                    error,
+                };
+                let val = digits.try_fold(None, |num, &d| {
+                    MathOp::Concat
+                        .fold(num, (d - b'0') as u128, overflow)
+                        .map(Some)
+                });
+                let val = val.map_err(err)?.unwrap_or(0);
+                let next = op.fold(acc, val, overflow).map_err(err)?;
+                acc = Some(next);
+                n += 1;
             }
+            res.push(acc.or(op.identity(overflow)).unwrap_or(0));
         }
+        Ok(res)
+    }
+
+    // A problem is a column of the worksheet and its numbers are the rows,
+    // so an overflow's problem and number say where it happened.
+    fn solve(&self, overflow: Overflow) -> Result<Vec<u128>, SolveError> {
+        let mut res: Vec<Option<u128>> = vec![None; self.width as usize];
         assert!(res.len() == self.width as usize);
         for h in 0..(self.height as usize) {
-            for p in 0..(self.width as usize) {
+            for (p, op) in self.operators.iter().enumerate() {
                 let val = self.numbers[h * (self.width as usize) + p];
-                match self.operators[p] {
-                    MathOp::Sum => {
-                        res[p] += val;
-                    }
-                    MathOp::Product => {
-                        res[p] *= val;
-                    }
-                }
+                let next = op.fold(res[p], val, overflow).map_err(|error| SolveError {
+                    problem: p,
+                    number: h,
+                    error,
+                })?;
+                res[p] = Some(next);
             }
         }
-        res
+        Ok(res
+            .into_iter()
+            .zip(&self.operators)
+            .map(|(r, op)| r.or(op.identity(overflow)).unwrap_or(0))
+            .collect())
     }
 }
 fn main() -> Result<(), Box<dyn Error>> {
     SimpleLogger::new().init().unwrap();
     let mut args = env::args().skip(1).peekable();
-    let file = match args.next() {
+    let mut file = None;
+    let mut part = 1;
+    // Read the file as postfix expressions a line each, rather than as a
+    // worksheet; picked from the first line when not given.
+    let mut rpn = false;
+    let mut overflow = Overflow::Checked;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--part" => {
+                part = match args.next().as_deref() {
+                    Some("1") => 1,
+                    Some("2") => 2,
+                    _ => return Err(From::from("--part needs 1 or 2")),
+                };
+            }
+            "--rpn" => rpn = true,
+            "--overflow" => {
+                overflow = args
+                    .next()
+                    .as_deref()
+                    .and_then(Overflow::from_name)
+                    .ok_or("--overflow needs checked, saturating, wrapping or big")?;
+            }
+            _ if file.is_none() => file = Some(arg),
+            _ => return Err(From::from(format!("Unexpected argument {}", arg))),
+        }
+    }
+    let file = match file {
         Some(file) => file,
         None => {
             return Err(From::from("Need a file argument!"));
         }
     };
-    let mathops = MathProblems::new_from_file(file);
-    println!("width: {} height: {}", mathops.width, mathops.height);
-    let v = mathops.solve();
+    // Read once, for both picking the mode and solving.
+    let lines = read_lines(&file)
+        .and_then(|lines| lines.collect::<io::Result<Vec<_>>>())
+        .map_err(|e| format!("{}: {}", file, e))?;
+    rpn |= lines
+        .iter()
+        .find(|line| tokens(line).next().is_some())
+        .is_some_and(|line| is_rpn_line(line));
+    let v = if rpn {
+        solve_rpn(&lines, overflow).map_err(|e| format!("{}: {}", file, e))?
+    } else {
+        let mathops = MathProblems::new_from_lines(&lines, overflow)
+            .map_err(|e| format!("{}: {}", file, e))?;
+        println!("width: {} height: {}", mathops.width, mathops.height);
+        match part {
+            1 => mathops.solve(overflow),
+            _ => mathops.solve_columns(overflow),
+        }
+        .map_err(|e| format!("{}: {}", file, e))?
+    };
     for (i, p) in v.iter().enumerate() {
         println!("{}: {}", i, p);
     }
 
-    println!("Sum: {}", v.into_iter().sum::<u64>());
+    if let Some(i) = try_argmax(&v) {
+        println!("Largest: {}: {}", i, v[i]);
+    }
+
+    let sum = v
+        .into_iter()
+        .try_fold(0, |acc, p| MathOp::Sum.apply(acc, p, overflow))
+        .map_err(|e| format!("Sum {}", e))?;
+    println!("Sum: {}", sum);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &[u8] = b"123 328  51 64 
+ 45 64  387 23 
+  6 98  215 314
+*   +   *   +  ";
+
+    #[test]
+    fn test_sample() {
+        let mathops =
+            MathProblems::new_from_lines(SAMPLE.split(|&c| c == b'\n'), Overflow::Checked).unwrap();
+        assert_eq!(
+            mathops.solve(Overflow::Checked).unwrap(),
+            [33210, 490, 4243455, 401]
+        );
+        assert_eq!(
+            mathops
+                .solve(Overflow::Checked)
+                .unwrap()
+                .iter()
+                .sum::<u128>(),
+            4277556
+        );
+        assert_eq!(
+            mathops.solve_columns(Overflow::Checked).unwrap(),
+            [8544, 625, 3253600, 1058]
+        );
+        assert_eq!(
+            mathops
+                .solve_columns(Overflow::Checked)
+                .unwrap()
+                .iter()
+                .sum::<u128>(),
+            3263827
+        );
+    }
+
+    #[test]
+    fn test_rpn() {
+        assert_eq!(eval_rpn(b"3 4 + 2 *", 1, Overflow::Checked), Ok(14));
+        assert_eq!(eval_rpn(b"  10 3 -  2 / ", 1, Overflow::Checked), Ok(3));
+        assert_eq!(
+            eval_rpn(b"12 34 | 5 M 1000 m", 1, Overflow::Checked),
+            Ok(1000)
+        );
+        assert_eq!(eval_rpn(b"7", 1, Overflow::Checked), Ok(7));
+        assert_eq!(
+            solve_rpn(
+                b"3 4 +\n\n5 1 2 + 4 * + 3 -".split(|&c| c == b'\n'),
+                Overflow::Checked
+            ),
+            Ok(vec![7, 14])
+        );
+        let underflow = |token: &str| RpnError::Underflow {
+            line: 2,
+            token: token.to_string(),
+        };
+        assert_eq!(eval_rpn(b"3 +", 2, Overflow::Checked), Err(underflow("+")));
+        assert_eq!(
+            eval_rpn(b"3 4", 2, Overflow::Checked),
+            Err(RpnError::Leftover { line: 2, count: 2 })
+        );
+        assert_eq!(
+            eval_rpn(b"3 4x +", 2, Overflow::Checked),
+            Err(RpnError::BadToken {
+                line: 2,
+                token: "4x".to_string()
+            })
+        );
+        assert_eq!(
+            eval_rpn(b"123456789012345678901 2 +", 2, Overflow::Checked),
+            Err(RpnError::BadToken {
+                line: 2,
+                token: "123456789012345678901".to_string()
+            })
+        );
+        assert_eq!(
+            eval_rpn(b"3 0 /", 2, Overflow::Checked),
+            Err(RpnError::Arith {
+                line: 2,
+                error: ArithError::DivideByZero
+            })
+        );
+        assert!(is_rpn_line(b"3 4 + 2 *"));
+        assert!(!is_rpn_line(b"123 328  51 64 "));
+        assert!(!is_rpn_line(b"*   +   *   +  "));
+    }
+
+    #[test]
+    fn test_ops() {
+        let lines = b"100 7 12 5  9 12 3
+ 20 2  3 8  0  0 4
+  3 1  5 2 10 34 0
+-   / m  M  |  |  +";
+        let mathops =
+            MathProblems::new_from_lines(lines.split(|&c| c == b'\n'), Overflow::Checked).unwrap();
+        assert_eq!(
+            mathops.solve(Overflow::Checked).unwrap(),
+            [77, 3, 3, 8, 9010, 12034, 7]
+        );
+        let solve = |txt: &[u8]| {
+            MathProblems::new_from_lines(txt.split(|&c| c == b'\n'), Overflow::Checked)
+                .unwrap()
+                .solve(Overflow::Checked)
+        };
+        let err = |problem, number, error| {
+            Err(SolveError {
+                problem,
+                number,
+                error,
+            })
+        };
+        assert_eq!(solve(b"4 5\n6 0\n+ /"), err(1, 1, ArithError::DivideByZero));
+        assert_eq!(solve(b"4 5\n2 6\n+ -"), err(1, 1, ArithError::Negative));
+        assert_eq!(
+            solve(b"9\n0\n/").unwrap_err().to_string(),
+            "Problem 0 divides by zero at number 1"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let parse = |txt: &[u8]| {
+            MathProblems::new_from_lines(txt.split(|&c| c == b'\n'), Overflow::Checked)
+                .err()
+                .map(|e| e.to_string())
+        };
+        let expect = [
+            (
+                b"".as_slice(),
+                "Line 1, column 1: expected a number, found end of line",
+            ),
+            (b"  * +", "Line 1, column 3: expected a number, found \"*\""),
+            (
+                b"123456789012345678901 2\n+ *",
+                "Line 1, column 1: expected a number that fits in a u64, found \"123456789012345678901\"",
+            ),
+            (
+                b"1 2\n3 4 5\n+ *",
+                "Line 2, column 5: expected end of line, found \"5\"",
+            ),
+            (
+                b"1 2\n3   \n+ *",
+                "Line 2, column 5: expected a number, found end of line",
+            ),
+            (
+                b"1 2\n3x 4\n+ *",
+                "Line 2, column 2: expected a number, found \"x\"",
+            ),
+            (
+                b"1 2\n+ ",
+                "Line 2, column 3: expected an operator, found end of line",
+            ),
+            (
+                b"1 2\n+ *x",
+                "Line 2, column 4: expected end of line, found \"x\"",
+            ),
+            (
+                b"1 2\n+ x",
+                "Line 2, column 3: expected an operator, found \"x\"",
+            ),
+            (
+                b"1 2\n   \n+ *",
+                "Line 2, column 4: expected a number or an operator, found end of line",
+            ),
+            (
+                b"1 2\n ?3 4",
+                "Line 2, column 2: expected a number or an operator, found \"?3\"",
+            ),
+            (
+                b"1 2\n3 4",
+                "Line 3, column 1: expected an operator, found end of input",
+            ),
+            (
+                b"1 2\n+ *\n\n5 6",
+                "Line 4, column 1: expected end of input, found \"5\"",
+            ),
+        ];
+        for (txt, msg) in expect {
+            assert_eq!(parse(txt).as_deref(), Some(msg), "{:?}", txt);
+        }
+        assert_eq!(
+            MathProblems::new_from_lines(Vec::<&[u8]>::new(), Overflow::Checked).err(),
+            Some(MathParseError {
+                line: 1,
+                column: 1,
+                expected: "a number",
+                found: Found::EndOfInput
+            })
+        );
+        assert!(parse(b"1 2\n3 4\n+ *\n\n").is_none());
+        assert!(matches!(classify_line_type(b"    "), Ok(LineType::Empty)));
+        assert!(matches!(classify_line_type(b""), Ok(LineType::Empty)));
+        assert_eq!(
+            classify_line_type(b"  x").err(),
+            Some(UnknownLineType { column: 2 })
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let txt = b"4294967296 2\n4294967296 3\n*          *";
+        let mathops =
+            MathProblems::new_from_lines(txt.split(|&c| c == b'\n'), Overflow::Checked).unwrap();
+        assert_eq!(
+            mathops.solve(Overflow::Checked),
+            Err(SolveError {
+                problem: 0,
+                number: 1,
+                error: ArithError::Overflow
+            })
+        );
+        assert_eq!(
+            mathops.solve(Overflow::Saturating).unwrap(),
+            [u64::MAX as u128, 6]
+        );
+        assert_eq!(mathops.solve(Overflow::Wrapping).unwrap(), [0, 6]);
+        assert_eq!(mathops.solve(Overflow::Big).unwrap(), [1 << 64, 6]);
+
+        let concat = |overflow| MathOp::Concat.apply(1844674407370955161, 6, overflow);
+        assert_eq!(concat(Overflow::Checked), Err(ArithError::Overflow));
+        assert_eq!(concat(Overflow::Saturating), Ok(u64::MAX as u128));
+        assert_eq!(concat(Overflow::Wrapping), Ok(0));
+        assert_eq!(concat(Overflow::Big), Ok(18446744073709551616));
+        assert_eq!(
+            MathOp::Product.apply(u128::MAX, 2, Overflow::Big),
+            Err(ArithError::Overflow)
+        );
+
+        // Twenty rows of 9s make one number too long for a u64.
+        let mut tall = "9\n".repeat(20);
+        tall.push('+');
+        let mathops =
+            MathProblems::new_from_lines(tall.as_bytes().split(|&c| c == b'\n'), Overflow::Checked)
+                .unwrap();
+        assert_eq!(
+            mathops
+                .solve_columns(Overflow::Checked)
+                .unwrap_err()
+                .to_string(),
+            "Problem 0 overflows at number 0"
+        );
+        assert_eq!(
+            mathops.solve_columns(Overflow::Big).unwrap(),
+            [99999999999999999999]
+        );
+        assert_eq!(mathops.solve(Overflow::Checked).unwrap(), [180]);
+
+        let line = b"4294967296 4294967296 * 1 +";
+        assert_eq!(
+            eval_rpn(line, 3, Overflow::Checked),
+            Err(RpnError::Arith {
+                line: 3,
+                error: ArithError::Overflow
+            })
+        );
+        assert_eq!(eval_rpn(line, 3, Overflow::Wrapping), Ok(1));
+        assert_eq!(eval_rpn(line, 3, Overflow::Big), Ok((1 << 64) + 1));
+
+        // Numbers past a u64 only parse when answers can outgrow one.
+        let wide = b"123456789012345678901 2\n                    3 3\n*                     *";
+        let parse = |overflow| MathProblems::new_from_lines(wide.split(|&c| c == b'\n'), overflow);
+        assert_eq!(
+            parse(Overflow::Checked).err().map(|e| e.expected),
+            Some("a number that fits in a u64")
+        );
+        assert_eq!(
+            parse(Overflow::Big).unwrap().solve(Overflow::Big).unwrap(),
+            [370370367037037036703, 6]
+        );
+        let line = b"123456789012345678901 2 +";
+        assert!(matches!(
+            eval_rpn(line, 1, Overflow::Checked),
+            Err(RpnError::BadToken { .. })
+        ));
+        assert_eq!(eval_rpn(line, 1, Overflow::Big), Ok(123456789012345678903));
+        assert_eq!(Overflow::from_name("big"), Some(Overflow::Big));
+        assert_eq!(Overflow::from_name("bignum"), None);
+    }
+}
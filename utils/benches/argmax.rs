@@ -0,0 +1,28 @@
+use aoc_utils::{Xorshift, argmax, argmax_u8};
+use criterion::{Criterion, criterion_group, criterion_main};
+use std::hint::black_box;
+
+// Cheap deterministic filler so runs are comparable without a rand dependency.
+fn digits(n: usize) -> Vec<u8> {
+    let mut rng = Xorshift::new(0x2545_f491);
+    (0..n).map(|_| (rng.next_u64() % 9 + 1) as u8).collect()
+}
+
+fn bench_argmax(c: &mut Criterion) {
+    for n in [100, 10_000, 1_000_000] {
+        let v = digits(n);
+        c.bench_function(&format!("argmax u8 {}", n), |b| {
+            b.iter(|| argmax(black_box(&v)))
+        });
+        c.bench_function(&format!("argmax_u8 {}", n), |b| {
+            b.iter(|| argmax_u8(black_box(&v)))
+        });
+        let f: Vec<f64> = v.iter().map(|d| *d as f64).collect();
+        c.bench_function(&format!("argmax f64 {}", n), |b| {
+            b.iter(|| argmax(black_box(&f)))
+        });
+    }
+}
+
+criterion_group!(benches, bench_argmax);
+criterion_main!(benches);
@@ -0,0 +1,52 @@
+// Saving files so a crash or a failed write part way through never leaves a
+// truncated file where an earlier good one was.
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// Hand `write` a buffered writer onto `path` with .tmp on the end, then
+// rename that over `path` once everything is flushed. If `write` or the flush
+// fails, the temporary file is removed and `path` is left as it was.
+pub fn write_atomic<P, F>(path: P, write: F) -> Result<(), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), Box<dyn Error>>,
+{
+    let path = path.as_ref();
+    let mut tmp = OsString::from(path.as_os_str());
+    tmp.push(".tmp");
+    let mut out = BufWriter::new(File::create(&tmp)?);
+    let written = write(&mut out).and_then(|()| Ok(out.flush()?));
+    drop(out);
+    if let Err(e) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn test_write_atomic() {
+        let path = env::temp_dir().join("aoc_utils_atomic_test.txt");
+        let mut tmp = OsString::from(path.as_os_str());
+        tmp.push(".tmp");
+        write_atomic(&path, |out| Ok(out.write_all(b"first")?)).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+        // A failed write keeps the old file and cleans up after itself.
+        let failed = write_atomic(&path, |out| {
+            out.write_all(b"second")?;
+            Err(From::from("no room"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+        assert!(!Path::new(&tmp).exists());
+    }
+}
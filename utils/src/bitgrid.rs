@@ -0,0 +1,269 @@
+// Row-major 2D grid of bits packed into u64 words. Each row starts on a word
+// boundary and its padding bits stay zero, so rows can be popcounted whole.
+use super::grid::GridSizeMismatch;
+
+pub const WORD_BITS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitGrid {
+    width: usize,
+    height: usize,
+    stride: usize,
+    words: Vec<u64>,
+}
+
+impl BitGrid {
+    pub fn new(width: usize, height: usize) -> Self {
+        let stride = width.div_ceil(WORD_BITS);
+        BitGrid {
+            width,
+            height,
+            stride,
+            words: vec![0; stride * height],
+        }
+    }
+
+    pub fn from_vec(
+        width: usize,
+        height: usize,
+        cells: Vec<bool>,
+    ) -> Result<Self, GridSizeMismatch> {
+        if cells.len() != width * height {
+            return Err(GridSizeMismatch {
+                expected: width * height,
+                found: cells.len(),
+            });
+        }
+        let mut g = BitGrid::new(width, 0);
+        for row in cells.chunks(width.max(1)) {
+            g.push_row(row);
+        }
+        g.height = height;
+        g.words.resize(g.stride * height, 0);
+        Ok(g)
+    }
+
+    // Rebuild from the packed words of another grid of the same shape, e.g.
+    // one read back from disk. Padding bits past the width are cleared.
+    pub fn from_words(
+        width: usize,
+        height: usize,
+        mut words: Vec<u64>,
+    ) -> Result<Self, GridSizeMismatch> {
+        let stride = width.div_ceil(WORD_BITS);
+        if words.len() != stride * height {
+            return Err(GridSizeMismatch {
+                expected: stride * height,
+                found: words.len(),
+            });
+        }
+        if !width.is_multiple_of(WORD_BITS) {
+            let mask = u64::MAX >> (WORD_BITS - width % WORD_BITS);
+            for row in words.chunks_mut(stride) {
+                row[stride - 1] &= mask;
+            }
+        }
+        Ok(BitGrid {
+            width,
+            height,
+            stride,
+            words,
+        })
+    }
+
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Words per row.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn in_bounds(&self, x: isize, y: isize) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    // Out-of-bounds cells read as unset.
+    pub fn get(&self, x: isize, y: isize) -> bool {
+        if !self.in_bounds(x, y) {
+            return false;
+        }
+        let (x, y) = (x as usize, y as usize);
+        self.words[y * self.stride + x / WORD_BITS] >> (x % WORD_BITS) & 1 == 1
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, val: bool) {
+        if x >= self.width || y >= self.height {
+            panic!(
+                "({}, {}) out of bounds for {}x{} grid",
+                x, y, self.width, self.height
+            );
+        }
+        let word = &mut self.words[y * self.stride + x / WORD_BITS];
+        let bit = 1u64 << (x % WORD_BITS);
+        if val {
+            *word |= bit;
+        } else {
+            *word &= !bit;
+        }
+    }
+
+    // Set or clear row y from x0 to x1 inclusive, a word at a time.
+    pub fn fill_span(&mut self, y: usize, x0: usize, x1: usize, val: bool) {
+        if x0 > x1 || x1 >= self.width || y >= self.height {
+            panic!(
+                "Span {}..={} of row {} out of bounds for {}x{} grid",
+                x0, x1, y, self.width, self.height
+            );
+        }
+        let (first, last) = (x0 / WORD_BITS, x1 / WORD_BITS);
+        let start = y * self.stride;
+        for w in first..=last {
+            let lo = if w == first { x0 % WORD_BITS } else { 0 };
+            let hi = if w == last {
+                x1 % WORD_BITS
+            } else {
+                WORD_BITS - 1
+            };
+            let mask = (u64::MAX >> (WORD_BITS - 1 - hi)) & (u64::MAX << lo);
+            if val {
+                self.words[start + w] |= mask;
+            } else {
+                self.words[start + w] &= !mask;
+            }
+        }
+    }
+
+    // Append a row at the bottom; it must be exactly `width` cells.
+    pub fn push_row(&mut self, row: &[bool]) {
+        if row.len() != self.width {
+            panic!(
+                "Row of {} cells pushed onto a grid {} wide",
+                row.len(),
+                self.width
+            );
+        }
+        let start = self.words.len();
+        self.words.resize(start + self.stride, 0);
+        for (x, _) in row.iter().enumerate().filter(|(_, v)| **v) {
+            self.words[start + x / WORD_BITS] |= 1 << (x % WORD_BITS);
+        }
+        self.height += 1;
+    }
+
+    pub fn row_words(&self, y: usize) -> &[u64] {
+        let start = y * self.stride;
+        &self.words[start..start + self.stride]
+    }
+
+    pub fn count_ones(&self) -> u64 {
+        self.words.iter().map(|w| w.count_ones() as u64).sum()
+    }
+
+    // Set bits in row y between x0 and x1 inclusive, clipped to the grid.
+    pub fn count_span(&self, y: isize, x0: isize, x1: isize) -> u32 {
+        if y < 0 || y as usize >= self.height {
+            return 0;
+        }
+        let x0 = x0.max(0) as usize;
+        let x1 = x1.min(self.width as isize - 1);
+        if x1 < x0 as isize {
+            return 0;
+        }
+        let x1 = x1 as usize;
+        let (first, last) = (x0 / WORD_BITS, x1 / WORD_BITS);
+        let row = self.row_words(y as usize);
+        let mut sum = 0;
+        for (w, word) in row.iter().enumerate().take(last + 1).skip(first) {
+            let lo = if w == first { x0 % WORD_BITS } else { 0 };
+            let hi = if w == last {
+                x1 % WORD_BITS
+            } else {
+                WORD_BITS - 1
+            };
+            let mask = (u64::MAX >> (WORD_BITS - 1 - hi)) & (u64::MAX << lo);
+            sum += (word & mask).count_ones();
+        }
+        sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_set() {
+        let mut g = BitGrid::new(70, 3);
+        assert_eq!(g.stride(), 2);
+        g.set(0, 0, true);
+        g.set(69, 2, true);
+        g.set(64, 1, true);
+        assert!(g.get(0, 0));
+        assert!(g.get(69, 2));
+        assert!(g.get(64, 1));
+        assert!(!g.get(63, 1));
+        assert!(!g.get(-1, 0));
+        assert!(!g.get(70, 2));
+        assert_eq!(g.count_ones(), 3);
+        g.set(64, 1, false);
+        assert!(!g.get(64, 1));
+        assert_eq!(g.count_ones(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_set_out_of_bounds() {
+        BitGrid::new(2, 2).set(2, 0, true);
+    }
+
+    #[test]
+    fn test_from_vec() {
+        let g = BitGrid::from_vec(3, 2, vec![true, false, true, false, true, true]).unwrap();
+        assert_eq!(g.row_words(0), &[0b101]);
+        assert_eq!(g.row_words(1), &[0b110]);
+        assert_eq!(
+            BitGrid::from_vec(2, 2, vec![true]),
+            Err(GridSizeMismatch {
+                expected: 4,
+                found: 1
+            })
+        );
+        assert_eq!(BitGrid::from_vec(0, 0, vec![]).unwrap().height(), 0);
+
+        let copy = BitGrid::from_words(3, 2, g.words().to_vec()).unwrap();
+        assert_eq!(copy, g);
+        let padded = BitGrid::from_words(3, 2, vec![u64::MAX, 0b10]).unwrap();
+        assert_eq!(padded.count_ones(), 4);
+        assert!(BitGrid::from_words(3, 2, vec![0]).is_err());
+    }
+
+    #[test]
+    fn test_count_span() {
+        let row: Vec<bool> = (0..130).map(|x| x % 3 == 0).collect();
+        let mut g = BitGrid::new(130, 0);
+        g.push_row(&row);
+        for (x0, x1) in [(0, 2), (62, 66), (-3, 1), (120, 140), (0, 129), (5, 4)] {
+            let expected = (x0.max(0)..=x1.min(129)).filter(|x| x % 3 == 0).count();
+            assert_eq!(g.count_span(0, x0, x1), expected as u32, "{}..={}", x0, x1);
+        }
+        assert_eq!(g.count_span(-1, 0, 10), 0);
+
+        let mut f = BitGrid::new(130, 1);
+        f.fill_span(0, 60, 129, true);
+        assert_eq!(f.count_ones(), 70);
+        f.fill_span(0, 62, 66, false);
+        assert_eq!(f.count_span(0, 0, 129), 65);
+        assert!(f.get(61, 0) && !f.get(62, 0) && !f.get(66, 0) && f.get(67, 0));
+        assert_eq!(g.count_span(1, 0, 10), 0);
+    }
+}
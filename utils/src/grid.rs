@@ -0,0 +1,194 @@
+// Row-major 2D grid over a flat Vec, shared by the grid-shaped puzzles.
+use std::error::Error;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+#[derive(Debug, PartialEq)]
+pub struct GridSizeMismatch {
+    pub expected: usize,
+    pub found: usize,
+}
+
+impl fmt::Display for GridSizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Grid needs {} cells, but {} were given",
+            self.expected, self.found
+        )
+    }
+}
+
+impl Error for GridSizeMismatch {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn from_vec(width: usize, height: usize, cells: Vec<T>) -> Result<Self, GridSizeMismatch> {
+        if cells.len() != width * height {
+            return Err(GridSizeMismatch {
+                expected: width * height,
+                found: cells.len(),
+            });
+        }
+        Ok(Grid {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // Signed so callers can probe neighbors at -1 without casting games.
+    pub fn in_bounds(&self, x: isize, y: isize) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    pub fn index_of(&self, x: usize, y: usize) -> usize {
+        if x >= self.width || y >= self.height {
+            panic!(
+                "({}, {}) out of bounds for {}x{} grid",
+                x, y, self.width, self.height
+            );
+        }
+        y * self.width + x
+    }
+
+    pub fn get(&self, x: isize, y: isize) -> Option<&T> {
+        if self.in_bounds(x, y) {
+            Some(&self.cells[y as usize * self.width + x as usize])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, x: isize, y: isize) -> Option<&mut T> {
+        if self.in_bounds(x, y) {
+            Some(&mut self.cells[y as usize * self.width + x as usize])
+        } else {
+            None
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        let start = self.index_of(0, y);
+        &self.cells[start..start + self.width]
+    }
+
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+
+    pub fn cells_mut(&mut self) -> &mut [T] {
+        &mut self.cells
+    }
+
+    // Every (x, y) in row-major order.
+    pub fn coords(&self) -> impl Iterator<Item = (usize, usize)> + use<T> {
+        let width = self.width;
+        (0..self.height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+
+    pub fn map<U, F>(&self, f: F) -> Grid<U>
+    where
+        F: FnMut(&T) -> U,
+    {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        &self.cells[self.index_of(x, y)]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        let i = self.index_of(x, y);
+        &mut self.cells[i]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_vec() {
+        let g = Grid::from_vec(3, 2, vec![1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(g.width(), 3);
+        assert_eq!(g.height(), 2);
+        assert_eq!(g[(0, 1)], 4);
+        assert_eq!(g.row(1), &[4, 5, 6]);
+        assert_eq!(
+            Grid::from_vec(3, 3, vec![1, 2]),
+            Err(GridSizeMismatch {
+                expected: 9,
+                found: 2
+            })
+        );
+        let boxed: Box<dyn Error> = Grid::from_vec(3, 3, vec![1, 2]).unwrap_err().into();
+        assert_eq!(boxed.to_string(), "Grid needs 9 cells, but 2 were given");
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut g = Grid::new(2, 3, false);
+        *g.get_mut(1, 2).unwrap() = true;
+        assert_eq!(g.get(1, 2), Some(&true));
+        assert_eq!(g.get(-1, 0), None);
+        assert_eq!(g.get(0, -1), None);
+        assert_eq!(g.get(2, 0), None);
+        assert_eq!(g.get(0, 3), None);
+        assert!(g.get_mut(5, 5).is_none());
+        assert!(g.in_bounds(0, 0));
+        assert!(!g.in_bounds(2, 2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_out_of_bounds() {
+        let g = Grid::new(2, 2, 0);
+        let _ = g[(2, 0)];
+    }
+
+    #[test]
+    fn test_coords_and_map() {
+        let g = Grid::from_vec(2, 2, vec![1, 2, 3, 4]).unwrap();
+        assert_eq!(
+            g.coords().collect::<Vec<_>>(),
+            vec![(0, 0), (1, 0), (0, 1), (1, 1)]
+        );
+        let doubled = g.map(|v| v * 2);
+        assert_eq!(doubled.cells(), &[2, 4, 6, 8]);
+    }
+}
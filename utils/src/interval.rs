@@ -0,0 +1,1489 @@
+//! Closed intervals of integers and normalized sets of them, generic over
+//! the integer type: parsing `lo-hi` and bracketed intervals, merging,
+//! lookups, overlap counts, an interval tree and set algebra.
+use super::rng::Xorshift;
+use log::info;
+use num_traits::PrimInt;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::ControlFlow;
+
+/// What interval ends can be: any primitive integer.
+pub trait Int: PrimInt + fmt::Display + fmt::Debug + Send + Sync {}
+
+impl<N> Int for N where N: PrimInt + fmt::Display + fmt::Debug + Send + Sync {}
+
+/// Not a number, or one outside the type.
+#[derive(Debug, PartialEq)]
+pub struct InvalidAsciiInt;
+
+/// Decimal digits, after a - for a negative number of a signed type.
+pub fn ascii_to_int<N: PrimInt>(bytes: &[u8]) -> Result<N, InvalidAsciiInt> {
+    let (neg, digits) = match bytes.split_first() {
+        Some((b'-', digits)) => (true, digits),
+        _ => (false, bytes),
+    };
+    if digits.is_empty() || (neg && N::min_value() == N::zero()) {
+        return Err(InvalidAsciiInt);
+    }
+    let ten = N::from(10).unwrap();
+    let mut res = N::zero();
+    for c in digits {
+        if *c < b'0' || *c > b'9' {
+            return Err(InvalidAsciiInt);
+        }
+        let val = N::from(*c - b'0').unwrap();
+        // Negatives build downward, since N::min_value() has no positive
+        // counterpart.
+        let next = res.checked_mul(&ten);
+        res = match neg {
+            true => next.and_then(|r| r.checked_sub(&val)),
+            false => next.and_then(|r| r.checked_add(&val)),
+        }
+        .ok_or(InvalidAsciiInt)?;
+    }
+    Ok(res)
+}
+
+/// Whether the - at txt[idx] separates the ends of a lo-hi range, which is
+/// when it follows a digit; anywhere else it's a sign.
+pub fn is_separator(txt: &[u8], idx: usize) -> bool {
+    txt[idx] == b'-' && idx > 0 && txt[idx - 1].is_ascii_digit()
+}
+
+/// Not an interval, or one whose low end is above its high end.
+#[derive(Debug, PartialEq)]
+pub struct InvalidClosedInt;
+
+/// Which side of the other an interval that can't merge with it is on.
+#[derive(Debug, PartialEq)]
+pub enum UnmergeOrder {
+    Before,
+    After,
+}
+
+/// Why ClosedInt::merge failed: the two have numbers between them.
+#[derive(Debug, PartialEq)]
+pub struct UnmergableInts(pub UnmergeOrder);
+
+/// Which ends of a `lo-hi` interval are included.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum IntervalKind {
+    // [lo, hi]
+    Closed,
+    // [lo, hi)
+    HalfOpen,
+    // (lo, hi)
+    Open,
+}
+
+impl IntervalKind {
+    // Whether the low and high ends are left out.
+    fn open_ends(self) -> (bool, bool) {
+        match self {
+            IntervalKind::Closed => (false, false),
+            IntervalKind::HalfOpen => (false, true),
+            IntervalKind::Open => (true, true),
+        }
+    }
+}
+
+/// How to read the intervals in a database: `kind` for `lo-hi`, and
+/// whether to take the looser forms parse_lenient does too.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Syntax {
+    pub kind: IntervalKind,
+    pub lenient: bool,
+}
+
+impl From<IntervalKind> for Syntax {
+    fn from(kind: IntervalKind) -> Self {
+        Syntax {
+            kind,
+            lenient: false,
+        }
+    }
+}
+
+/// The integers from low through high, both included.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ClosedInt<N = u64> {
+    low: N,
+    high: N,
+}
+
+impl<N: Int> ClosedInt<N> {
+    /// low-high, or an error if low is above high.
+    pub fn new(low: N, high: N) -> Result<Self, InvalidClosedInt> {
+        if low > high {
+            Err(InvalidClosedInt)
+        } else {
+            Ok(ClosedInt { low, high })
+        }
+    }
+
+    /// A `lo-hi` interval, both ends in and either end possibly negative.
+    // Bytes rather than a str, so not FromStr.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(txt: &[u8]) -> Result<Self, InvalidClosedInt> {
+        // The first - that ends a run of digits, so -5--3 splits after
+        // -5 and leaves the high end its sign.
+        let mut idx = 1;
+        loop {
+            if idx >= txt.len() {
+                return Err(InvalidClosedInt);
+            }
+            if is_separator(txt, idx) {
+                break;
+            }
+            idx += 1;
+        }
+        let low = match ascii_to_int(&txt[0..idx]) {
+            Ok(low) => low,
+            Err(_) => {
+                return Err(InvalidClosedInt);
+            }
+        };
+        let high = match ascii_to_int(&txt[idx + 1..]) {
+            Ok(high) => high,
+            Err(_) => {
+                return Err(InvalidClosedInt);
+            }
+        };
+        ClosedInt::new(low, high)
+    }
+
+    /// An interval written with either syntax: `lo-hi`, whose ends are
+    /// inclusive or not as `kind` says, or with brackets marking each end,
+    /// as in `[lo, hi)`. The ends are whole numbers, so every kind has a
+    /// closed form, e.g. [3, 7) is 3-6 and (3, 7) is 4-6; that's what this
+    /// returns, so merging and lookups are the same for every kind. An
+    /// interval that holds no number, like [5, 5), gives None.
+    pub fn parse(txt: &[u8], kind: IntervalKind) -> Result<Option<Self>, InvalidClosedInt> {
+        let txt = txt.trim_ascii();
+        let (open_low, open_high, low, high) = match (txt.first(), txt.last()) {
+            (Some(&l @ (b'[' | b'(')), Some(&h @ (b']' | b')'))) => {
+                let body = &txt[1..txt.len() - 1];
+                let (low, high) = match body.iter().position(|&c| c == b',') {
+                    Some(sep) => (&body[..sep], &body[sep + 1..]),
+                    None => return Err(InvalidClosedInt),
+                };
+                let low = ascii_to_int::<N>(low.trim_ascii()).map_err(|_| InvalidClosedInt)?;
+                let high = ascii_to_int::<N>(high.trim_ascii()).map_err(|_| InvalidClosedInt)?;
+                if low > high {
+                    return Err(InvalidClosedInt);
+                }
+                (l == b'(', h == b')', low, high)
+            }
+            _ => {
+                let (open_low, open_high) = kind.open_ends();
+                let int = ClosedInt::from_str(txt)?;
+                (open_low, open_high, int.low, int.high)
+            }
+        };
+        let low = match open_low {
+            true => low.checked_add(&N::one()),
+            false => Some(low),
+        };
+        let high = match open_high {
+            true => high.checked_sub(&N::one()),
+            false => Some(high),
+        };
+        Ok(match (low, high) {
+            (Some(low), Some(high)) if low <= high => Some(ClosedInt { low, high }),
+            _ => None,
+        })
+    }
+
+    /// Parse, also taking what inputs copied from elsewhere tend to hold:
+    /// spaces around the -, an en dash in place of it, and Rust ranges,
+    /// where `lo..hi` leaves hi out whatever `kind` says and `lo..=hi`
+    /// keeps it. Underscores between digits, as in `1_000_000`, are
+    /// dropped.
+    pub fn parse_lenient(txt: &[u8], kind: IntervalKind) -> Result<Option<Self>, InvalidClosedInt> {
+        let digit = |i: Option<&u8>| i.is_some_and(u8::is_ascii_digit);
+        let txt: Vec<u8> = (0..txt.len())
+            .filter(|&i| {
+                txt[i] != b'_' || !(i > 0 && digit(txt.get(i - 1)) && digit(txt.get(i + 1)))
+            })
+            .map(|i| txt[i])
+            .collect();
+        let txt = txt.trim_ascii();
+        let ends = |sep: usize, len: usize| -> Result<(N, N), InvalidClosedInt> {
+            let low = ascii_to_int::<N>(txt[..sep].trim_ascii()).map_err(|_| InvalidClosedInt)?;
+            let high =
+                ascii_to_int::<N>(txt[sep + len..].trim_ascii()).map_err(|_| InvalidClosedInt)?;
+            match low <= high {
+                true => Ok((low, high)),
+                false => Err(InvalidClosedInt),
+            }
+        };
+        if let Some(sep) = txt.windows(2).position(|w| w == b"..") {
+            return match txt.get(sep + 2) {
+                Some(b'=') => ends(sep, 3).map(|(low, high)| Some(ClosedInt { low, high })),
+                _ => ends(sep, 2).map(|(low, high)| {
+                    high.checked_sub(&N::one())
+                        .filter(|&high| low <= high)
+                        .map(|high| ClosedInt { low, high })
+                }),
+            };
+        }
+        let dashed = String::from_utf8_lossy(txt).replace('\u{2013}', "-");
+        let dashed = dashed.as_bytes();
+        // Whitespace next to a - is dropped, other whitespace left to fail.
+        let solid = |c: &&u8| !c.is_ascii_whitespace();
+        let squeezed: Vec<u8> = (0..dashed.len())
+            .filter(|&i| {
+                !dashed[i].is_ascii_whitespace()
+                    || dashed[..i].iter().rev().find(solid) != Some(&b'-')
+                        && dashed[i..].iter().find(solid) != Some(&b'-')
+            })
+            .map(|i| dashed[i])
+            .collect();
+        Self::parse(&squeezed, kind)
+    }
+
+    /// Whether some number is in both.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        self.low <= other.high && other.low <= self.high
+    }
+
+    /// Whether one ends right before the other starts, e.g. 3-5 and 6-9.
+    pub fn adjacent(&self, other: &Self) -> bool {
+        self.high.checked_add(&N::one()) == Some(other.low)
+            || other.high.checked_add(&N::one()) == Some(self.low)
+    }
+
+    /// The numbers in both, if there are any.
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        ClosedInt::new(
+            std::cmp::max(self.low, other.low),
+            std::cmp::min(self.high, other.high),
+        )
+        .ok()
+    }
+
+    /// The smallest interval holding both, when that holds nothing else:
+    /// when they overlap or, if `adjacent` is set, touch. Otherwise the
+    /// error says which side of other self lies on.
+    pub fn merge(&self, other: &Self, adjacent: bool) -> Result<Self, UnmergableInts> {
+        if !(self.overlaps(other) || adjacent && self.adjacent(other)) {
+            let order = match self.high < other.low {
+                true => UnmergeOrder::Before,
+                false => UnmergeOrder::After,
+            };
+            return Err(UnmergableInts(order));
+        }
+        Ok(ClosedInt::new(
+            std::cmp::min(self.low, other.low),
+            std::cmp::max(self.high, other.high),
+        )
+        .unwrap())
+    }
+
+    /// u128, since 0-18446744073709551615 holds one more value than a u64
+    /// can count. The whole u128 or i128 range holds one more than a u128
+    /// can, and saturates.
+    pub fn length(&self) -> u128 {
+        self.checked_length().unwrap_or(u128::MAX)
+    }
+
+    /// None only for the whole u128 or i128 range.
+    pub fn checked_length(&self) -> Option<u128> {
+        let span = match (self.low.to_i128(), self.high.to_i128()) {
+            (Some(low), Some(high)) => high.abs_diff(low),
+            // Only u128 ends don't fit an i128, and those aren't negative.
+            _ => self.high.to_u128().unwrap() - self.low.to_u128().unwrap(),
+        };
+        span.checked_add(1)
+    }
+
+    pub fn contains(&self, num: N) -> bool {
+        num >= self.low && num <= self.high
+    }
+
+    pub fn low(&self) -> N {
+        self.low
+    }
+
+    pub fn high(&self) -> N {
+        self.high
+    }
+}
+
+impl<N: Int> Ord for ClosedInt<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.low.cmp(&other.low)
+    }
+}
+
+impl<N: Int> PartialOrd for ClosedInt<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N: Int> fmt::Display for ClosedInt<N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.low, self.high)
+    }
+}
+
+/// Sorted, disjoint intervals with at least one missing number between
+/// any two of them; sets are made by normalize and changed by insert and
+/// remove, which all keep them in that form.
+#[derive(Debug, PartialEq, Clone)]
+pub struct IntervalSet<N = u64> {
+    ints: Vec<ClosedInt<N>>,
+}
+
+impl<N: Int> IntervalSet<N> {
+    /// Sort, then merge each interval into the one before it while they
+    /// overlap or touch.
+    pub fn normalize(ints: impl IntoIterator<Item = ClosedInt<N>>) -> Self {
+        let mut buf: Vec<ClosedInt<N>> = ints.into_iter().collect();
+        buf.sort();
+        let mut ints: Vec<ClosedInt<N>> = Vec::with_capacity(buf.len());
+        for int in buf {
+            if let Some(last) = ints.last_mut() {
+                info!("{:?}, {:?}", last, int);
+                if let Ok(merged) = last.merge(&int, true) {
+                    info!("merged {:?}", merged);
+                    *last = merged;
+                    continue;
+                }
+                info!("unmerged");
+            }
+            ints.push(int);
+        }
+        IntervalSet { ints }
+    }
+
+    /// In order, lowest first.
+    pub fn intervals(&self) -> &[ClosedInt<N>] {
+        &self.ints
+    }
+
+    /// Add one interval, merging it with every interval it overlaps or
+    /// touches. Those form a run, found by two binary searches, and are
+    /// replaced by the merged interval in place.
+    pub fn insert(&mut self, int: ClosedInt<N>) {
+        // The first interval ending at or after the number just below int,
+        // and the first starting past the number just after it.
+        let start = self
+            .ints
+            .partition_point(|i| i.high.checked_add(&N::one()).is_some_and(|h| h < int.low));
+        let end = self.ints[start..]
+            .partition_point(|i| int.high.checked_add(&N::one()).is_none_or(|h| i.low <= h))
+            + start;
+        let merged = self.ints[start..end]
+            .iter()
+            .fold(int, |m, i| m.merge(i, true).unwrap());
+        self.ints.splice(start..end, [merged]);
+    }
+
+    /// Take out every number in int. The intervals it overlaps form a run;
+    /// what's left of the first below int and of the last above it stays.
+    pub fn remove(&mut self, int: &ClosedInt<N>) {
+        let start = self.ints.partition_point(|i| i.high < int.low);
+        let end = self.ints[start..].partition_point(|i| i.low <= int.high) + start;
+        if start == end {
+            return;
+        }
+        let (first, last) = (&self.ints[start], &self.ints[end - 1]);
+        let below = (first.low < int.low).then(|| ClosedInt {
+            low: first.low,
+            high: int.low - N::one(),
+        });
+        let above = (last.high > int.high).then(|| ClosedInt {
+            low: int.high + N::one(),
+            high: last.high,
+        });
+        self.ints.splice(start..end, below.into_iter().chain(above));
+    }
+
+    /// Binary search for the last interval starting at or below `num`,
+    /// the only one that can hold it, and then it does if it hasn't ended.
+    pub fn contains(&self, num: N) -> bool {
+        let after = self.ints.partition_point(|i| i.low <= num);
+        after > 0 && num <= self.ints[after - 1].high
+    }
+
+    /// contains for each of nums, in their order, by one sweep: the
+    /// queries are sorted, then walked up alongside the intervals, so past
+    /// the sort each interval and each query is looked at once. foodb's
+    /// lookup bench compares it with a search per query.
+    pub fn contains_batch(&self, nums: &[N]) -> Vec<bool> {
+        let mut order: Vec<usize> = (0..nums.len()).collect();
+        order.sort_unstable_by_key(|&i| nums[i]);
+        let mut res = vec![false; nums.len()];
+        let mut ints = self.ints.iter().peekable();
+        for i in order {
+            let num = nums[i];
+            while ints.next_if(|int| int.high < num).is_some() {}
+            res[i] = ints.peek().is_some_and(|int| int.low <= num);
+        }
+        res
+    }
+
+    /// The smallest number at or above `num` that no interval holds, or
+    /// None when the set runs from there to the top of the type.
+    pub fn next_uncovered(&self, num: N) -> Option<N> {
+        let after = self.ints.partition_point(|i| i.low <= num);
+        match after.checked_sub(1).map(|i| &self.ints[i]) {
+            // Normalized, so the number after an interval is never in
+            // the next.
+            Some(int) if int.contains(num) => int.high.checked_add(&N::one()),
+            _ => Some(num),
+        }
+    }
+
+    /// How many numbers the set covers. Any set of 64-bit ints fits, up to
+    /// the 2^64 of the whole range; only 128-bit sets saturate.
+    pub fn coverage(&self) -> u128 {
+        self.checked_coverage().unwrap_or(u128::MAX)
+    }
+
+    /// None when more numbers are covered than a u128 counts.
+    pub fn checked_coverage(&self) -> Option<u128> {
+        self.ints
+            .iter()
+            .try_fold(0u128, |sum, i| sum.checked_add(i.checked_length()?))
+    }
+
+    /// The k-th smallest number the set holds, counting from 0, or None
+    /// when it holds no more than k. One query's worth of CoveredRanks.
+    pub fn kth_covered(&self, k: u128) -> Option<N> {
+        CoveredRanks::new(self).kth(k)
+    }
+
+    /// The runs of missing numbers between the first interval and the
+    /// last.
+    pub fn gaps(&self) -> Vec<ClosedInt<N>> {
+        self.ints
+            .windows(2)
+            .map(|w| ClosedInt::new(w[0].high + N::one(), w[1].low - N::one()).unwrap())
+            .collect()
+    }
+
+    /// The values either covers.
+    pub fn union(&self, other: &Self) -> Self {
+        IntervalSet::normalize(self.ints.iter().chain(&other.ints).cloned())
+    }
+
+    /// Both sides are sorted and disjoint, so a single walk over the two
+    /// finds every overlap, and the results come out normalized.
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut ints = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.ints.len() && j < other.ints.len() {
+            let (a, b) = (&self.ints[i], &other.ints[j]);
+            ints.extend(a.intersect(b));
+            if a.high < b.high {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet { ints }
+    }
+
+    /// The numbers from low through high that no interval holds: the gaps
+    /// within that universe, including any before the first interval or
+    /// after the last. Empty when low is above high.
+    pub fn complement(&self, low: N, high: N) -> Self {
+        match ClosedInt::new(low, high) {
+            Ok(universe) => IntervalSet {
+                ints: vec![universe],
+            }
+            .difference(self),
+            Err(_) => IntervalSet { ints: Vec::new() },
+        }
+    }
+
+    /// The values in self that other doesn't cover.
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut ints = Vec::new();
+        let mut j = 0;
+        for a in &self.ints {
+            while j < other.ints.len() && other.ints[j].high < a.low {
+                j += 1;
+            }
+            // The start of what's left of a, if anything is.
+            let mut low = Some(a.low);
+            let mut k = j;
+            while let Some(l) = low {
+                let Some(b) = other.ints.get(k).filter(|b| b.overlaps(a)) else {
+                    break;
+                };
+                if b.low > l {
+                    ints.push(ClosedInt {
+                        low: l,
+                        high: b.low - N::one(),
+                    });
+                }
+                low = b.high.checked_add(&N::one()).filter(|&l| l <= a.high);
+                k += 1;
+            }
+            if let Some(low) = low {
+                ints.push(ClosedInt { low, high: a.high });
+            }
+        }
+        IntervalSet { ints }
+    }
+}
+
+/// As many of a list of intervals as can be picked with no two sharing a
+/// number, in order: greedily by earliest end, taking each that starts
+/// past the last one taken. Ending first leaves the most room after, so no
+/// larger pick exists. Touching intervals, like 3-5 and 6-9, don't share.
+pub fn max_disjoint<'a, N: Int + 'a>(
+    ints: impl IntoIterator<Item = &'a ClosedInt<N>>,
+) -> Vec<ClosedInt<N>> {
+    let mut by_end: Vec<&ClosedInt<N>> = ints.into_iter().collect();
+    by_end.sort_by_key(|i| i.high);
+    let mut picked: Vec<ClosedInt<N>> = Vec::new();
+    for int in by_end {
+        if picked.last().is_none_or(|last| last.high < int.low) {
+            picked.push(int.clone());
+        }
+    }
+    picked
+}
+
+/// How many of a list of intervals, overlaps and all, contain a number.
+/// Those starting at or below it, less those that ended before it: two
+/// binary searches over the sorted ends.
+pub struct OverlapCounter<N = u64> {
+    lows: Vec<N>,
+    highs: Vec<N>,
+}
+
+impl<N: Int> OverlapCounter<N> {
+    pub fn new<'a>(ints: impl IntoIterator<Item = &'a ClosedInt<N>>) -> Self
+    where
+        N: 'a,
+    {
+        let (mut lows, mut highs): (Vec<N>, Vec<N>) =
+            ints.into_iter().map(|i| (i.low, i.high)).unzip();
+        lows.sort_unstable();
+        highs.sort_unstable();
+        OverlapCounter { lows, highs }
+    }
+
+    /// How many of the intervals hold num.
+    pub fn count(&self, num: N) -> usize {
+        self.lows.partition_point(|&l| l <= num) - self.highs.partition_point(|&h| h < num)
+    }
+
+    /// The most intervals holding any one number in int. Past int.low the
+    /// depth only rises where an interval starts, so those are the only
+    /// numbers to check.
+    pub fn deepest_in(&self, int: &ClosedInt<N>) -> usize {
+        let start = self.lows.partition_point(|&l| l <= int.low);
+        let end = self.lows.partition_point(|&l| l <= int.high);
+        self.lows[start..end]
+            .iter()
+            .map(|&l| self.count(l))
+            .fold(self.count(int.low), std::cmp::max)
+    }
+
+    /// The smallest number held by the most intervals, and how many hold
+    /// it, or None with no intervals. The depth only rises where one
+    /// starts, so a sweep over the starts in order, dropping the intervals
+    /// that ended before each, sees every peak.
+    pub fn deepest(&self) -> Option<(N, usize)> {
+        let mut best: Option<(N, usize)> = None;
+        let mut ended = 0;
+        for (i, &low) in self.lows.iter().enumerate() {
+            while self.highs[ended] < low {
+                ended += 1;
+            }
+            // Starts at the same number count in full at the last of them.
+            let depth = i + 1 - ended;
+            if best.is_none_or(|(_, d)| depth > d) {
+                best = Some((low, depth));
+            }
+        }
+        best
+    }
+}
+
+/// The reverse of OverlapCounter: how many of a list of ids, repeats and
+/// all, an interval holds, by binary searches for its ends.
+pub struct SortedIds<N = u64> {
+    ids: Vec<N>,
+}
+
+impl<N: Int> SortedIds<N> {
+    pub fn new(ids: impl IntoIterator<Item = N>) -> Self {
+        let mut ids: Vec<N> = ids.into_iter().collect();
+        ids.sort_unstable();
+        SortedIds { ids }
+    }
+
+    /// How many of the ids int holds.
+    pub fn count_in(&self, int: &ClosedInt<N>) -> usize {
+        self.ids.partition_point(|&id| id <= int.high)
+            - self.ids.partition_point(|&id| id < int.low)
+    }
+}
+
+/// A set's intervals with how many numbers come before each, so the k-th
+/// smallest covered number is a binary search over those prefix sums away.
+/// Sums saturate like coverage, which only the whole 128-bit ranges reach.
+pub struct CoveredRanks<N = u64> {
+    ints: Vec<ClosedInt<N>>,
+    before: Vec<u128>,
+}
+
+impl<N: Int> CoveredRanks<N> {
+    pub fn new(set: &IntervalSet<N>) -> Self {
+        let mut sum = 0u128;
+        let before = set
+            .ints
+            .iter()
+            .map(|i| {
+                let b = sum;
+                sum = sum.saturating_add(i.length());
+                b
+            })
+            .collect();
+        CoveredRanks {
+            ints: set.ints.clone(),
+            before,
+        }
+    }
+
+    /// The k-th smallest number held, counting from 0: the last interval
+    /// with at most k numbers before it, if it's long enough, k less those
+    /// past its low end.
+    pub fn kth(&self, k: u128) -> Option<N> {
+        let at = self.before.partition_point(|&b| b <= k).checked_sub(1)?;
+        let int = &self.ints[at];
+        let mut offset = k - self.before[at];
+        if offset >= int.length() {
+            return None;
+        }
+        // A signed type's lengths don't all fit it, so the offset goes on
+        // in up to two steps of at most N's max.
+        let mut num = int.low;
+        while offset > 0 {
+            let step = N::from(offset).unwrap_or_else(N::max_value);
+            num = num + step;
+            offset -= step.to_u128().unwrap();
+        }
+        Some(num)
+    }
+}
+
+/// A list of intervals, overlaps and all, that finds every one holding a
+/// number without merging them first. The intervals are sorted by low end
+/// and read as an implicit balanced tree, the middle of each range its
+/// root, with each node keeping the highest end in its subtree. A search
+/// drops subtrees that end before the number and, past a node starting
+/// after it, everything to its right: O(log n) per interval found.
+pub struct IntervalTree<N = u64> {
+    ints: Vec<ClosedInt<N>>,
+    // Where each interval was in the list it came from.
+    order: Vec<usize>,
+    // The highest end in the subtree rooted at each node.
+    max_high: Vec<N>,
+}
+
+impl<N: Int> IntervalTree<N> {
+    pub fn new<'a>(ints: impl IntoIterator<Item = &'a ClosedInt<N>>) -> Self
+    where
+        N: 'a,
+    {
+        let mut numbered: Vec<(usize, ClosedInt<N>)> =
+            ints.into_iter().cloned().enumerate().collect();
+        numbered.sort_unstable_by(|(a, i), (b, j)| i.low.cmp(&j.low).then(a.cmp(b)));
+        let (order, ints): (Vec<usize>, Vec<ClosedInt<N>>) = numbered.into_iter().unzip();
+        let mut max_high: Vec<N> = ints.iter().map(|i| i.high).collect();
+        let mut tree = IntervalTree {
+            ints,
+            order,
+            max_high: Vec::new(),
+        };
+        tree.build(0, tree.ints.len(), &mut max_high);
+        tree.max_high = max_high;
+        tree
+    }
+
+    // Fills in max_high for the subtree over lo..hi and returns its root's.
+    fn build(&self, lo: usize, hi: usize, max_high: &mut [N]) -> Option<N> {
+        if lo >= hi {
+            return None;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let left = self.build(lo, mid, max_high);
+        let right = self.build(mid + 1, hi, max_high);
+        let high = [left, right]
+            .into_iter()
+            .flatten()
+            .fold(max_high[mid], std::cmp::max);
+        max_high[mid] = high;
+        Some(high)
+    }
+
+    // Hands every interval in lo..hi that holds num to hit, in order of low
+    // end, until hit breaks.
+    fn stab(
+        &self,
+        lo: usize,
+        hi: usize,
+        num: N,
+        hit: &mut impl FnMut(usize) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        if lo >= hi {
+            return ControlFlow::Continue(());
+        }
+        let mid = lo + (hi - lo) / 2;
+        if self.max_high[mid] < num {
+            return ControlFlow::Continue(());
+        }
+        self.stab(lo, mid, num, hit)?;
+        if self.ints[mid].low > num {
+            return ControlFlow::Continue(());
+        }
+        if self.ints[mid].high >= num {
+            hit(mid)?;
+        }
+        self.stab(mid + 1, hi, num, hit)
+    }
+
+    /// Whether any of the intervals holds num, stopping at the first.
+    pub fn contains(&self, num: N) -> bool {
+        self.stab(0, self.ints.len(), num, &mut |_| ControlFlow::Break(()))
+            .is_break()
+    }
+
+    /// How many of the intervals hold num.
+    pub fn count(&self, num: N) -> usize {
+        let mut count = 0;
+        let _ = self.stab(0, self.ints.len(), num, &mut |_| {
+            count += 1;
+            ControlFlow::Continue(())
+        });
+        count
+    }
+
+    /// Where each interval holding num was in the list the tree was built
+    /// from, in that list's order.
+    pub fn containing(&self, num: N) -> Vec<usize> {
+        let mut found = Vec::new();
+        let _ = self.stab(0, self.ints.len(), num, &mut |i| {
+            found.push(self.order[i]);
+            ControlFlow::Continue(())
+        });
+        found.sort_unstable();
+        found
+    }
+}
+
+/// `count` intervals of up to `max_len` numbers each, starting anywhere
+/// below `max_id`, from a Xorshift seeded with `seed`, for tests and
+/// benchmarks. Overlaps are likely once the lengths add up to more than
+/// max_id.
+pub fn random_intervals(count: usize, max_id: u64, max_len: u64, seed: u64) -> Vec<ClosedInt> {
+    let mut rng = Xorshift::new(seed);
+    let mut next = || rng.next_u64();
+    (0..count)
+        .map(|_| {
+            let low = next() % max_id;
+            ClosedInt::new(low, low.saturating_add(next() % max_len.max(1))).unwrap()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Overlapping, touching and lone intervals, out of order.
+    fn sample_raw() -> [ClosedInt; 6] {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        [
+            int(22, 22),
+            int(10, 14),
+            int(3, 5),
+            int(16, 20),
+            int(12, 18),
+            int(6, 6),
+        ]
+    }
+
+    // Both ends of the type, one of them held twice.
+    fn sample_ends() -> [ClosedInt; 2] {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        [int(0, u64::MAX), int(u64::MAX, u64::MAX)]
+    }
+
+    #[test]
+    fn test_ascii_to_u64() {
+        assert_eq!(ascii_to_int::<u64>(b"123123"), Ok(123123));
+    }
+
+    #[test]
+    fn test_ascii_to_int() {
+        assert_eq!(ascii_to_int::<i64>(b"-123"), Ok(-123));
+        assert_eq!(ascii_to_int::<i64>(b"-9223372036854775808"), Ok(i64::MIN));
+        assert_eq!(
+            ascii_to_int::<i64>(b"9223372036854775808"),
+            Err(InvalidAsciiInt)
+        );
+        assert_eq!(
+            ascii_to_int::<u64>(b"18446744073709551616"),
+            Err(InvalidAsciiInt)
+        );
+        assert_eq!(ascii_to_int::<u128>(b"18446744073709551616"), Ok(1 << 64));
+        assert_eq!(ascii_to_int::<u64>(b"-1"), Err(InvalidAsciiInt));
+        assert_eq!(ascii_to_int::<u64>(b""), Err(InvalidAsciiInt));
+        assert_eq!(ascii_to_int::<i64>(b"-"), Err(InvalidAsciiInt));
+        assert_eq!(ascii_to_int::<u64>(b"1x"), Err(InvalidAsciiInt));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(ClosedInt::new(16, 15), Err(InvalidClosedInt));
+    }
+
+    #[test]
+    fn test_single() {
+        if let Ok(x) = ClosedInt::new(15, 15) {
+            assert_eq!(x.low, 15);
+            assert_eq!(x.high, 15);
+        } else {
+            panic!("Invalid interval!");
+        }
+    }
+
+    #[test]
+    fn test_u8() {
+        assert_eq!(ClosedInt::from_str(b"3-5"), ClosedInt::new(3, 5));
+    }
+
+    #[test]
+    fn test_parse_kinds() {
+        let parse = |txt: &[u8], kind| ClosedInt::parse(txt, kind);
+        let int = |low, high| Ok(Some(ClosedInt::new(low, high).unwrap()));
+        assert_eq!(parse(b"3-7", IntervalKind::Closed), int(3, 7));
+        assert_eq!(parse(b"3-7", IntervalKind::HalfOpen), int(3, 6));
+        assert_eq!(parse(b"3-7", IntervalKind::Open), int(4, 6));
+        // Brackets win over the kind.
+        for kind in [
+            IntervalKind::Closed,
+            IntervalKind::HalfOpen,
+            IntervalKind::Open,
+        ] {
+            assert_eq!(parse(b"[3,7]", kind), int(3, 7));
+            assert_eq!(parse(b"[3, 7)", kind), int(3, 6));
+            assert_eq!(parse(b"(3, 7]", kind), int(4, 7));
+            assert_eq!(parse(b" (3 , 7) ", kind), int(4, 6));
+        }
+        assert_eq!(parse(b"5-5", IntervalKind::Closed), int(5, 5));
+        assert_eq!(parse(b"5-5", IntervalKind::HalfOpen), Ok(None));
+        assert_eq!(parse(b"(5, 6)", IntervalKind::Closed), Ok(None));
+        assert_eq!(parse(b"(5, 6]", IntervalKind::Closed), int(6, 6));
+        assert_eq!(parse(b"[0, 0)", IntervalKind::Closed), Ok(None));
+        let max = u64::MAX.to_string();
+        let top = format!("({}, {}]", max, max);
+        assert_eq!(parse(top.as_bytes(), IntervalKind::Closed), Ok(None));
+        let all = format!("[0, {}]", max);
+        assert_eq!(
+            parse(all.as_bytes(), IntervalKind::Closed),
+            int(0, u64::MAX)
+        );
+
+        assert_eq!(parse(b"7-3", IntervalKind::HalfOpen), Err(InvalidClosedInt));
+        assert_eq!(
+            parse(b"[7, 3)", IntervalKind::Closed),
+            Err(InvalidClosedInt)
+        );
+        assert_eq!(parse(b"[3-7)", IntervalKind::Closed), Err(InvalidClosedInt));
+        assert_eq!(parse(b"[3, 7", IntervalKind::Closed), Err(InvalidClosedInt));
+        assert_eq!(
+            parse(b"{3, 7}", IntervalKind::Closed),
+            Err(InvalidClosedInt)
+        );
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        let int = |low, high| Ok(Some(ClosedInt::new(low, high).unwrap()));
+        let lenient =
+            |txt: &str| ClosedInt::<i64>::parse_lenient(txt.as_bytes(), IntervalKind::Closed);
+        assert_eq!(lenient("  3-5 "), int(3, 5));
+        assert_eq!(lenient("3 - 5"), int(3, 5));
+        assert_eq!(lenient("3\t-  5"), int(3, 5));
+        assert_eq!(lenient("3\u{2013}5"), int(3, 5));
+        assert_eq!(lenient("3 \u{2013} 5"), int(3, 5));
+        assert_eq!(lenient("-5 - -3"), int(-5, -3));
+        assert_eq!(lenient("- 5 - 3"), int(-5, 3));
+        assert_eq!(lenient("3..7"), int(3, 6));
+        assert_eq!(lenient("3..=7"), int(3, 7));
+        assert_eq!(lenient(" -7 ..= -3 "), int(-7, -3));
+        assert_eq!(lenient("5..5"), Ok(None));
+        assert_eq!(lenient("[3 , 7)"), int(3, 6));
+        assert_eq!(lenient("1_000_000-2_000_000"), int(1_000_000, 2_000_000));
+        assert_eq!(lenient("-1_0 - 2_0"), int(-10, 20));
+        assert_eq!(lenient("1_0..=2_0"), int(10, 20));
+        assert_eq!(lenient("[1_000, 2_000)"), int(1000, 1999));
+        // Rust ranges say for themselves which ends are in.
+        assert_eq!(
+            ClosedInt::<u64>::parse_lenient(b"3..7", IntervalKind::Open),
+            Ok(Some(ClosedInt::new(3, 6).unwrap()))
+        );
+        assert_eq!(
+            ClosedInt::<u64>::parse_lenient(b"3 - 7", IntervalKind::Open),
+            Ok(Some(ClosedInt::new(4, 6).unwrap()))
+        );
+        for bad in [
+            "1 2-5",
+            "3..",
+            "..3",
+            "7..=3",
+            "3...5",
+            "3..=5..7",
+            "3 5",
+            "3\u{2014}5",
+            "_1-5",
+            "1_-5",
+            "1-5_",
+            "1__0-5",
+        ] {
+            assert_eq!(lenient(bad), Err(InvalidClosedInt), "{}", bad);
+        }
+        // The strict parse takes none of these.
+        for loose in ["3 - 5", "3\u{2013}5", "3..7", "3..=7", "1_000-2_000"] {
+            assert_eq!(
+                ClosedInt::<i64>::parse(loose.as_bytes(), IntervalKind::Closed),
+                Err(InvalidClosedInt),
+                "{}",
+                loose
+            );
+        }
+    }
+
+    #[test]
+    fn test_contains() {
+        let closed = ClosedInt::new(10, 15).unwrap();
+        for i in 10..15 {
+            assert!(closed.contains(i));
+        }
+        assert!(!closed.contains(9));
+        assert!(!closed.contains(16));
+    }
+
+    #[test]
+    fn test_merge() {
+        let a = ClosedInt::new(10, 15).unwrap();
+        let b = ClosedInt::new(20, 25).unwrap();
+        assert_eq!(a.merge(&b, true), Err(UnmergableInts(UnmergeOrder::Before)));
+        assert_eq!(b.merge(&a, true), Err(UnmergableInts(UnmergeOrder::After)));
+        let c = ClosedInt::new(24, 30).unwrap();
+        assert_eq!(b.merge(&c, true), Ok(ClosedInt::new(20, 30).unwrap()));
+    }
+
+    // Every pair of intervals in 0..10, checked against the sets of
+    // numbers they hold, plus the ends of the u64 range.
+    #[test]
+    fn test_merge_exhaustive() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let mut ints = Vec::new();
+        for low in 0..10 {
+            for high in low..10 {
+                ints.push(int(low, high));
+            }
+        }
+        let mask = |i: &ClosedInt| (i.low..=i.high).fold(0u32, |m, n| m | 1 << n);
+        for a in &ints {
+            for b in &ints {
+                let (ma, mb) = (mask(a), mask(b));
+                let overlaps = ma & mb != 0;
+                // Contiguous union, but no shared number.
+                let union = ma | mb;
+                let contiguous = (union >> union.trailing_zeros()).count_ones()
+                    == (union >> union.trailing_zeros()).trailing_ones();
+                let adjacent = contiguous && !overlaps;
+                assert_eq!(a.overlaps(b), overlaps, "{} {}", a, b);
+                assert_eq!(a.adjacent(b), adjacent, "{} {}", a, b);
+                assert_eq!(
+                    a.intersect(b).map(|i| mask(&i)),
+                    Some(ma & mb).filter(|&m| m != 0)
+                );
+                for touching in [false, true] {
+                    let merged = a.merge(b, touching);
+                    if overlaps || touching && adjacent {
+                        assert_eq!(merged.as_ref().map(mask), Ok(union), "{} {}", a, b);
+                        assert_eq!(b.merge(a, touching), merged);
+                    } else {
+                        let order = match a.low < b.low {
+                            true => UnmergeOrder::Before,
+                            false => UnmergeOrder::After,
+                        };
+                        assert_eq!(merged, Err(UnmergableInts(order)), "{} {}", a, b);
+                    }
+                }
+            }
+        }
+
+        let top = int(u64::MAX - 3, u64::MAX);
+        let below = int(5, u64::MAX - 4);
+        assert!(top.adjacent(&below) && below.adjacent(&top) && !top.overlaps(&below));
+        assert_eq!(top.merge(&below, true), Ok(int(5, u64::MAX)));
+        assert_eq!(
+            top.merge(&below, false),
+            Err(UnmergableInts(UnmergeOrder::After))
+        );
+        let zero = int(0, 0);
+        assert!(!top.adjacent(&zero) && !zero.adjacent(&top));
+        assert_eq!(zero.intersect(&top), None);
+        assert_eq!(int(0, u64::MAX).intersect(&top), Some(top.clone()));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let raw = sample_raw();
+        let set = IntervalSet::normalize(raw.clone());
+        // 3-5 and 6-6 touch, so they merge too.
+        assert_eq!(set.intervals(), &[int(3, 6), int(10, 20), int(22, 22)]);
+        for num in 0..30 {
+            let expected = raw.iter().any(|i| i.contains(num));
+            assert_eq!(set.contains(num), expected, "{}", num);
+        }
+        assert_eq!(set.coverage(), 4 + 11 + 1);
+        assert_eq!(set.gaps(), vec![int(7, 9), int(21, 21)]);
+        assert_eq!(set.intervals()[1].to_string(), "10-20");
+
+        let empty = IntervalSet::normalize([]);
+        assert!(empty.intervals().is_empty() && !empty.contains(4));
+        assert!(empty.gaps().is_empty());
+        let all = IntervalSet::normalize([int(5, u64::MAX), int(0, 4)]);
+        assert_eq!(all.intervals(), &[int(0, u64::MAX)]);
+        assert!(all.contains(u64::MAX));
+        assert_eq!(all.coverage(), u64::MAX as u128 + 1);
+    }
+
+    #[test]
+    fn test_next_uncovered() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let set = IntervalSet::normalize(sample_raw());
+        for num in 0..30 {
+            let expected = (num..).find(|&n| !set.contains(n));
+            assert_eq!(set.next_uncovered(num), expected, "{}", num);
+        }
+        let all = IntervalSet::normalize([int(5, u64::MAX), int(0, 4)]);
+        assert_eq!(all.next_uncovered(0), None);
+        let empty = IntervalSet::normalize([]);
+        assert_eq!(empty.next_uncovered(u64::MAX), Some(u64::MAX));
+        let top = IntervalSet::normalize([int(10, 20), int(30, u64::MAX)]);
+        assert_eq!(top.next_uncovered(12), Some(21));
+        assert_eq!(top.next_uncovered(25), Some(25));
+        assert_eq!(top.next_uncovered(30), None);
+    }
+
+    #[test]
+    fn test_overlap_counter() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let (raw, ends) = (sample_raw(), sample_ends());
+        let counter = OverlapCounter::new(&raw);
+        for num in 0..30 {
+            let expected = raw.iter().filter(|i| i.contains(num)).count();
+            assert_eq!(counter.count(num), expected, "{}", num);
+        }
+        assert_eq!(counter.count(13), 2);
+        let counter = OverlapCounter::new(&ends);
+        assert_eq!((counter.count(0), counter.count(u64::MAX)), (1, 2));
+        assert_eq!(OverlapCounter::new(&[]).count(7), 0);
+        assert_eq!(OverlapCounter::new(&raw).deepest(), Some((12, 2)));
+        assert_eq!(OverlapCounter::new(&ends).deepest(), Some((u64::MAX, 2)));
+        assert_eq!(OverlapCounter::<u64>::new(&[]).deepest(), None);
+        let counter = OverlapCounter::new(&raw);
+        for (low, high) in [(0, 2), (0, 30), (13, 15), (15, 16), (19, 22), (6, 6)] {
+            let expected = (low..=high).map(|n| counter.count(n)).max().unwrap();
+            assert_eq!(counter.deepest_in(&int(low, high)), expected);
+        }
+        let stacked = [int(5, 9), int(1, 5), int(5, 5), int(3, 4)];
+        assert_eq!(OverlapCounter::new(&stacked).deepest(), Some((5, 3)));
+        for seed in 1..20 {
+            let ints = random_intervals(50, 500, 60, seed);
+            let (num, depth) = OverlapCounter::new(&ints).deepest().unwrap();
+            let count = |n| ints.iter().filter(|i| i.contains(n)).count();
+            let most = (0..600).map(count).max().unwrap();
+            assert_eq!(depth, most);
+            assert_eq!(Some(num), (0..600).find(|&n| count(n) == most));
+        }
+    }
+
+    #[test]
+    fn test_sorted_ids() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let raw = sample_raw();
+        let ids = SortedIds::new([17, 1, 5, 11, 8, 32, 5, 20, 10]);
+        for i in &raw {
+            let expected = ids.ids.iter().filter(|&&id| i.contains(id)).count();
+            assert_eq!(ids.count_in(i), expected, "{}", i);
+        }
+        assert_eq!(ids.count_in(&int(3, 5)), 2);
+        assert_eq!(ids.count_in(&int(0, u64::MAX)), 9);
+        assert_eq!(ids.count_in(&int(33, u64::MAX)), 0);
+    }
+
+    #[test]
+    fn test_interval_tree() {
+        let (raw, ends) = (sample_raw(), sample_ends());
+        let tree = IntervalTree::new(&raw);
+        for num in 0..30 {
+            let expected: Vec<usize> = (0..raw.len()).filter(|&i| raw[i].contains(num)).collect();
+            assert_eq!(tree.containing(num), expected, "{}", num);
+            assert_eq!(tree.count(num), expected.len(), "{}", num);
+            assert_eq!(tree.contains(num), !expected.is_empty(), "{}", num);
+        }
+        let tree = IntervalTree::new(&ends);
+        assert_eq!(tree.containing(u64::MAX), [0, 1]);
+        assert_eq!(tree.containing(0), [0]);
+        assert!(!IntervalTree::new(&[]).contains(7));
+        for seed in 1..20 {
+            let ints = random_intervals(50, 500, 60, seed);
+            let tree = IntervalTree::new(&ints);
+            for num in 0..600 {
+                let expected: Vec<usize> =
+                    (0..ints.len()).filter(|&i| ints[i].contains(num)).collect();
+                assert_eq!(tree.containing(num), expected, "seed {} num {}", seed, num);
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_disjoint() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let raw = [int(3, 5), int(10, 14), int(16, 20), int(12, 18), int(6, 9)];
+        assert_eq!(
+            max_disjoint(&raw),
+            [int(3, 5), int(6, 9), int(10, 14), int(16, 20)]
+        );
+        let long = [
+            int(0, 100),
+            int(1, 2),
+            int(2, 3),
+            int(3, 4),
+            int(u64::MAX, u64::MAX),
+        ];
+        assert_eq!(
+            max_disjoint(&long),
+            [int(1, 2), int(3, 4), int(u64::MAX, u64::MAX)]
+        );
+        assert!(max_disjoint::<u64>(&[]).is_empty());
+        // Against every subset, for the size of the largest disjoint one.
+        for seed in 1..40 {
+            let ints = random_intervals(10, 60, 15, seed);
+            let best = (0u32..1 << ints.len())
+                .filter(|mask| {
+                    let chosen: Vec<_> = (0..ints.len()).filter(|i| mask >> i & 1 == 1).collect();
+                    chosen.iter().all(|&a| {
+                        chosen
+                            .iter()
+                            .all(|&b| a == b || !ints[a].overlaps(&ints[b]))
+                    })
+                })
+                .map(|mask| mask.count_ones() as usize)
+                .max()
+                .unwrap();
+            let picked = max_disjoint(&ints);
+            assert_eq!(picked.len(), best, "seed {}", seed);
+            assert!(picked.windows(2).all(|w| w[0].high < w[1].low));
+            assert!(picked.iter().all(|p| ints.contains(p)));
+        }
+    }
+
+    #[test]
+    fn test_kth_covered() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let set = IntervalSet::normalize([int(3, 5), int(10, 14), int(16, 20), int(12, 18)]);
+        let covered: Vec<u64> = (0..30).filter(|&n| set.contains(n)).collect();
+        for (k, &num) in covered.iter().enumerate() {
+            assert_eq!(set.kth_covered(k as u128), Some(num), "{}", k);
+        }
+        assert_eq!(set.kth_covered(covered.len() as u128), None);
+        assert_eq!(set.kth_covered(u128::MAX), None);
+        assert_eq!(IntervalSet::<u64>::normalize([]).kth_covered(0), None);
+        for seed in 1..20 {
+            let set = IntervalSet::normalize(random_intervals(30, 500, 40, seed));
+            let ranks = CoveredRanks::new(&set);
+            let mut covered = (0..600).filter(|&n| set.contains(n));
+            for k in 0..set.coverage() {
+                assert_eq!(ranks.kth(k), covered.next(), "seed {} k {}", seed, k);
+            }
+            assert_eq!(ranks.kth(set.coverage()), None);
+        }
+
+        let all = IntervalSet::normalize([int(0, u64::MAX)]);
+        assert_eq!(all.kth_covered(u64::MAX as u128), Some(u64::MAX));
+        assert_eq!(all.kth_covered(u64::MAX as u128 + 1), None);
+        let all = IntervalSet::normalize([ClosedInt::new(i8::MIN, i8::MAX).unwrap()]);
+        assert_eq!(all.kth_covered(0), Some(i8::MIN));
+        assert_eq!(all.kth_covered(128), Some(0));
+        assert_eq!(all.kth_covered(255), Some(i8::MAX));
+        let all = IntervalSet::normalize([ClosedInt::new(i128::MIN, i128::MAX).unwrap()]);
+        assert_eq!(all.kth_covered(u128::MAX - 1), Some(i128::MAX - 1));
+    }
+
+    #[test]
+    fn test_coverage_extremes() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let top = u64::MAX;
+        let all = IntervalSet::normalize([int(0, top)]);
+        assert_eq!(all.coverage(), 1 << 64);
+        assert_eq!(all.checked_coverage(), Some(1 << 64));
+        // Both ends of the domain, and pieces that together make all of it.
+        let ends = IntervalSet::normalize([int(0, 0), int(top - 2, top)]);
+        assert_eq!(ends.coverage(), 4);
+        let halves = IntervalSet::normalize([int(0, top / 2 - 1), int(top / 2 + 1, top)]);
+        assert_eq!(halves.coverage(), (1 << 64) - 1);
+        let near_top = IntervalSet::normalize([int(top - 10, top - 6), int(top - 4, top)]);
+        assert_eq!(near_top.coverage(), 10);
+        assert_eq!(int(top, top).length(), 1);
+        assert_eq!(int(0, top - 1).length(), top as u128);
+        let ones = IntervalSet::normalize((0..64).map(|b| int(top >> b & !1, top >> b)));
+        assert_eq!(
+            ones.coverage(),
+            ones.intervals().iter().map(|i| i.length()).sum::<u128>()
+        );
+
+        // Past what a u128 counts, checked gives up and coverage saturates.
+        let wint = |low, high| ClosedInt::new(low, high).unwrap();
+        let wide = IntervalSet::normalize([ClosedInt::new(1u128, u128::MAX).unwrap()]);
+        assert_eq!(wide.checked_coverage(), Some(u128::MAX));
+        let wide = IntervalSet::normalize([ClosedInt::new(0u128, u128::MAX).unwrap()]);
+        assert_eq!(wide.checked_coverage(), None);
+        let signed = IntervalSet::normalize([wint(i128::MIN, -1), wint(1, i128::MAX)]);
+        assert_eq!(signed.checked_coverage(), Some(u128::MAX));
+        let whole = IntervalSet::normalize([wint(i128::MIN, i128::MAX)]);
+        assert_eq!(whole.checked_coverage(), None);
+        assert_eq!(whole.coverage(), u128::MAX);
+        assert_eq!(wint(i128::MIN, i128::MAX).checked_length(), None);
+    }
+
+    #[test]
+    fn test_contains_boundaries() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let set = IntervalSet::normalize([int(10, 20), int(22, 22), int(30, 40)]);
+        for (num, expected) in [
+            // Below everything.
+            (0, false),
+            (9, false),
+            // Each end of each interval, and the ones just outside.
+            (10, true),
+            (20, true),
+            (21, false),
+            (22, true),
+            (23, false),
+            (29, false),
+            (30, true),
+            (40, true),
+            // Above everything.
+            (41, false),
+            (u64::MAX, false),
+        ] {
+            assert_eq!(set.contains(num), expected, "{}", num);
+        }
+        let ends = IntervalSet::normalize([int(0, 0), int(u64::MAX, u64::MAX)]);
+        assert!(ends.contains(0) && ends.contains(u64::MAX));
+        assert!(!ends.contains(1) && !ends.contains(u64::MAX - 1));
+        let one = IntervalSet::normalize([int(5, 5)]);
+        assert!(!one.contains(4) && one.contains(5) && !one.contains(6));
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        // Sets of 0..64 as bitmasks, where the operations are plain bit
+        // operations to check against.
+        let from_mask = |mask: u64| {
+            IntervalSet::normalize((0..64).filter(|b| mask >> b & 1 == 1).map(|b| int(b, b)))
+        };
+        let masks = [
+            0,
+            u64::MAX,
+            0x0ff0_0ff0_f0f0_1234,
+            0xf00f_f00f_0f0f_4321,
+            0x8000_0000_0000_0001,
+            0x7fff_ffff_ffff_fffe,
+            0x00ff_ff00_0000_ffff,
+        ];
+        for &a in &masks {
+            for &b in &masks {
+                let (sa, sb) = (from_mask(a), from_mask(b));
+                assert_eq!(sa.union(&sb), from_mask(a | b));
+                assert_eq!(sa.intersection(&sb), from_mask(a & b));
+                assert_eq!(sa.difference(&sb), from_mask(a & !b));
+            }
+        }
+
+        let all = IntervalSet::normalize([int(0, u64::MAX)]);
+        let some = IntervalSet::normalize([int(0, 9), int(100, 199), int(u64::MAX - 5, u64::MAX)]);
+        let rest = all.difference(&some);
+        assert_eq!(rest.intervals(), &[int(10, 99), int(200, u64::MAX - 6)]);
+        assert_eq!(rest.union(&some), all);
+        assert_eq!(all.intersection(&some), some);
+        assert!(some.difference(&all).intervals().is_empty());
+        assert_eq!(rest.coverage() + some.coverage(), all.coverage());
+
+        let set = IntervalSet::normalize([int(10, 19), int(30, 39)]);
+        assert_eq!(
+            set.complement(0, 50).intervals(),
+            &[int(0, 9), int(20, 29), int(40, 50)]
+        );
+        assert_eq!(set.complement(12, 35).intervals(), &[int(20, 29)]);
+        assert!(set.complement(30, 35).intervals().is_empty());
+        assert!(set.complement(9, 0).intervals().is_empty());
+        assert_eq!(set.complement(0, u64::MAX), all.difference(&set));
+        assert_eq!(some.complement(0, u64::MAX), rest);
+        for &a in &masks {
+            let sa = from_mask(a);
+            assert_eq!(sa.complement(0, 63), from_mask(!a));
+            assert_eq!(sa.complement(8, 15), from_mask(!a & 0xff00));
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let mut set = IntervalSet::normalize([]);
+        for i in [int(10, 14), int(20, 24), int(30, 34)] {
+            set.insert(i);
+        }
+        assert_eq!(set.intervals(), &[int(10, 14), int(20, 24), int(30, 34)]);
+        // Touching on one side, then bridging two.
+        set.insert(int(15, 16));
+        assert_eq!(set.intervals(), &[int(10, 16), int(20, 24), int(30, 34)]);
+        set.insert(int(17, 19));
+        assert_eq!(set.intervals(), &[int(10, 24), int(30, 34)]);
+        set.insert(int(0, 8));
+        set.insert(int(12, 12));
+        assert_eq!(set.intervals(), &[int(0, 8), int(10, 24), int(30, 34)]);
+        set.insert(int(5, 40));
+        assert_eq!(set.intervals(), &[int(0, 40)]);
+        set.insert(int(u64::MAX - 1, u64::MAX));
+        set.insert(int(41, u64::MAX - 2));
+        assert_eq!(set.intervals(), &[int(0, u64::MAX)]);
+
+        // In any order, inserts build what normalize does.
+        let ints = random_intervals(300, 5000, 40, 11);
+        let mut set = IntervalSet::normalize([]);
+        for i in &ints {
+            set.insert(i.clone());
+        }
+        assert_eq!(set, IntervalSet::normalize(ints));
+    }
+
+    #[test]
+    fn test_contains_batch() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let set = IntervalSet::normalize([int(3, 5), int(10, 20), int(u64::MAX, u64::MAX)]);
+        let nums = [20, 0, 5, 21, 3, u64::MAX, 9, 10, 5, u64::MAX - 1];
+        let expected: Vec<bool> = nums.iter().map(|&n| set.contains(n)).collect();
+        assert_eq!(set.contains_batch(&nums), expected);
+        assert!(set.contains_batch(&[]).is_empty());
+        assert_eq!(
+            IntervalSet::normalize([]).contains_batch(&[1, 2]),
+            [false, false]
+        );
+        for seed in 1..10 {
+            let set = IntervalSet::normalize(random_intervals(100, 10_000, 80, seed));
+            let nums: Vec<u64> = random_intervals(1000, 11_000, 1, seed + 100)
+                .iter()
+                .map(|i| i.low())
+                .collect();
+            let expected: Vec<bool> = nums.iter().map(|&n| set.contains(n)).collect();
+            assert_eq!(set.contains_batch(&nums), expected);
+        }
+    }
+
+    #[test]
+    fn test_remove() {
+        let int = |low, high| ClosedInt::new(low, high).unwrap();
+        let mut set = IntervalSet::normalize([int(0, 9), int(20, 29), int(40, 49)]);
+        // A hole in the middle of one, then a bite off each end of two.
+        set.remove(&int(4, 5));
+        assert_eq!(
+            set.intervals(),
+            &[int(0, 3), int(6, 9), int(20, 29), int(40, 49)]
+        );
+        set.remove(&int(25, 44));
+        assert_eq!(
+            set.intervals(),
+            &[int(0, 3), int(6, 9), int(20, 24), int(45, 49)]
+        );
+        // Nothing there, and whole intervals.
+        set.remove(&int(10, 19));
+        set.remove(&int(0, 3));
+        set.remove(&int(45, 49));
+        assert_eq!(set.intervals(), &[int(6, 9), int(20, 24)]);
+        set.remove(&int(0, u64::MAX));
+        assert!(set.intervals().is_empty());
+        let mut all = IntervalSet::normalize([int(0, u64::MAX)]);
+        all.remove(&int(0, 0));
+        all.remove(&int(u64::MAX, u64::MAX));
+        assert_eq!(all.intervals(), &[int(1, u64::MAX - 1)]);
+
+        // Against difference, one removed interval at a time.
+        let base = IntervalSet::normalize(random_intervals(200, 5000, 60, 5));
+        let holes = random_intervals(100, 5000, 30, 6);
+        let mut set = base.clone();
+        for h in &holes {
+            set.remove(h);
+        }
+        assert_eq!(set, base.difference(&IntervalSet::normalize(holes)));
+    }
+
+    #[test]
+    fn test_wide_and_signed() {
+        let int = |low: i64, high: i64| ClosedInt::new(low, high).unwrap();
+        assert_eq!(ClosedInt::from_str(b"-5--3"), Ok(int(-5, -3)));
+        assert_eq!(ClosedInt::from_str(b"-5-3"), Ok(int(-5, 3)));
+        assert_eq!(
+            ClosedInt::<i64>::parse(b"(-5, 3)", IntervalKind::Closed),
+            Ok(Some(int(-4, 2)))
+        );
+        assert_eq!(ClosedInt::<u64>::from_str(b"-5-3"), Err(InvalidClosedInt));
+        assert_eq!(ClosedInt::from_str(b"-5-10"), Ok(int(-5, 10)));
+        assert_eq!(ClosedInt::from_str(b"-10--5"), Ok(int(-10, -5)));
+        assert_eq!(ClosedInt::from_str(b"0--0"), Ok(int(0, 0)));
+        assert_eq!(ClosedInt::<i64>::from_str(b"5--10"), Err(InvalidClosedInt));
+        assert_eq!(ClosedInt::<i64>::from_str(b"--5-10"), Err(InvalidClosedInt));
+        assert_eq!(ClosedInt::<i64>::from_str(b"-5"), Err(InvalidClosedInt));
+        assert_eq!(ClosedInt::<i64>::from_str(b"-5-"), Err(InvalidClosedInt));
+        let set = IntervalSet::normalize([int(-10, -1), int(0, 4), int(i64::MIN, -20)]);
+        assert_eq!(set.intervals(), &[int(i64::MIN, -20), int(-10, 4)]);
+        assert!(set.contains(-5) && !set.contains(-15) && set.contains(i64::MIN));
+        assert_eq!(set.gaps(), vec![int(-19, -11)]);
+        assert_eq!(set.coverage(), (i64::MAX as u128 - 18) + 15);
+        assert_eq!(int(i64::MIN, i64::MAX).length(), u64::MAX as u128 + 1);
+        assert_eq!(OverlapCounter::new(&[int(-3, 3), int(-1, 1)]).count(0), 2);
+
+        let big = ClosedInt::<u128>::from_str(b"18446744073709551616-18446744073709551620");
+        assert_eq!(big.map(|i| i.length()), Ok(5));
+        assert_eq!(ClosedInt::new(0, u128::MAX).unwrap().length(), u128::MAX);
+        assert_eq!(
+            ClosedInt::new(i128::MIN, i128::MAX).unwrap().length(),
+            u128::MAX
+        );
+    }
+
+    #[test]
+    // Spelled out to exercise each comparison operator.
+    #[allow(clippy::nonminimal_bool)]
+    fn test_ord() {
+        let closed0 = ClosedInt::new(10, 15).unwrap();
+        let closed0_copy = ClosedInt::new(10, 15).unwrap();
+        let closed1 = ClosedInt::new(20, 25).unwrap();
+        assert!(closed0 < closed1);
+        assert!(!(closed0 > closed1));
+        assert!(closed0 != closed1);
+        assert!(closed0 == closed0_copy);
+    }
+}
@@ -0,0 +1,129 @@
+// Helpers shared between the day crates.
+
+pub mod atomic;
+pub mod bitgrid;
+pub mod grid;
+pub mod interval;
+pub mod rng;
+pub use atomic::write_atomic;
+pub use bitgrid::BitGrid;
+pub use grid::Grid;
+pub use interval::{ClosedInt, IntervalSet};
+pub use rng::Xorshift;
+
+// Index of the first largest element, or None for an empty slice. Elements
+// that don't compare (e.g. NaN) never replace the current pick.
+pub fn try_argmax<T: PartialOrd>(slice: &[T]) -> Option<usize> {
+    if slice.is_empty() {
+        return None;
+    }
+
+    let mut argmax = 0;
+    for (i, v) in slice.iter().enumerate() {
+        if *v > slice[argmax] {
+            argmax = i;
+        }
+    }
+    Some(argmax)
+}
+
+// Index of the first largest element; panics on an empty slice.
+pub fn argmax<T: PartialOrd>(slice: &[T]) -> usize {
+    match try_argmax(slice) {
+        Some(i) => i,
+        None => panic!("Empty slice"),
+    }
+}
+
+// Same answer as argmax for byte slices, but scans 16 lanes at a time:
+// one pass to find the max value, a second for its first position.
+#[cfg(target_arch = "x86_64")]
+pub fn argmax_u8(slice: &[u8]) -> usize {
+    use std::arch::x86_64::*;
+
+    if slice.is_empty() {
+        panic!("Empty slice");
+    }
+    let chunks = slice.chunks_exact(16);
+    let tail = chunks.remainder();
+
+    // SAFETY: sse2 is part of the x86_64 baseline and loadu has no alignment
+    // requirement; every load reads exactly the 16 bytes of its chunk.
+    let max = unsafe {
+        let mut acc = _mm_setzero_si128();
+        for c in chunks.clone() {
+            acc = _mm_max_epu8(acc, _mm_loadu_si128(c.as_ptr() as *const __m128i));
+        }
+        let mut lanes = [0_u8; 16];
+        _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, acc);
+        lanes.into_iter().chain(tail.iter().copied()).max().unwrap()
+    };
+
+    // SAFETY: as above.
+    unsafe {
+        let needle = _mm_set1_epi8(max as i8);
+        for (i, c) in chunks.enumerate() {
+            let eq = _mm_cmpeq_epi8(_mm_loadu_si128(c.as_ptr() as *const __m128i), needle);
+            let mask = _mm_movemask_epi8(eq);
+            if mask != 0 {
+                return i * 16 + mask.trailing_zeros() as usize;
+            }
+        }
+    }
+    let base = slice.len() - tail.len();
+    base + tail.iter().position(|v| *v == max).unwrap()
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn argmax_u8(slice: &[u8]) -> usize {
+    argmax(slice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_argmax() {
+        let v = [1, 3, 3, 9, 2, 4, 1, 6].to_vec();
+        assert_eq!(argmax(&v), 3);
+        assert_eq!(argmax(&v[..3]), 1);
+        assert_eq!(4 + argmax(&v[4..]), 7);
+    }
+
+    #[test]
+    fn test_try_argmax() {
+        let empty: [u8; 0] = [];
+        assert_eq!(try_argmax(&empty), None);
+        assert_eq!(try_argmax(&[5_u64]), Some(0));
+        assert_eq!(try_argmax(&[1.5, f64::NAN, 2.5, 0.0]), Some(2));
+        assert_eq!(try_argmax(&["b", "c", "a", "c"]), Some(1));
+    }
+
+    #[test]
+    fn test_argmax_u8() {
+        let v = [1, 3, 3, 9, 2, 4, 1, 6].to_vec();
+        assert_eq!(argmax_u8(&v), 3);
+        assert_eq!(argmax_u8(&v[..3]), 1);
+
+        // Exercise full chunks, the tail, and ties across chunk boundaries.
+        let mut long: Vec<u8> = (0..100).map(|i| (i * 7 % 13) as u8).collect();
+        assert_eq!(argmax_u8(&long), argmax(&long));
+        long[97] = 200;
+        assert_eq!(argmax_u8(&long), 97);
+        long[20] = 200;
+        assert_eq!(argmax_u8(&long), 20);
+        long[15] = 255;
+        assert_eq!(argmax_u8(&long), 15);
+        for n in 0..long.len() {
+            assert_eq!(argmax_u8(&long[n..]), argmax(&long[n..]));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_argmax_empty() {
+        let empty: [u8; 0] = [];
+        argmax(&empty);
+    }
+}
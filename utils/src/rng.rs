@@ -0,0 +1,43 @@
+// A small seeded PRNG, so the day crates can make random maps, intervals and
+// samples for tests and benchmarks without a rand dependency.
+
+// xorshift64*: tiny, fast and deterministic for a seed; nowhere near good
+// enough for anything that needs real randomness.
+#[derive(Debug, Clone)]
+pub struct Xorshift(u64);
+
+impl Xorshift {
+    // The state must never be zero, so the low bit is always set; seeds that
+    // only differ there give the same sequence.
+    pub fn new(seed: u64) -> Self {
+        Xorshift(seed | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xorshift() {
+        let take = |seed| {
+            let mut rng = Xorshift::new(seed);
+            (0..100).map(|_| rng.next_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(take(7), take(7));
+        assert_ne!(take(7), take(9));
+        // A zero seed doesn't get stuck at zero.
+        assert!(take(0).iter().all(|&v| v != 0));
+        let mut sorted = take(3);
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 100);
+    }
+}